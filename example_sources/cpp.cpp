@@ -0,0 +1,37 @@
+#include <iostream>
+#include <string>
+#include <vector>
+
+// Top-level function
+void greet() {
+    std::cout << "Hello from top-level function!" << std::endl;
+}
+
+// A class with a method named in the same way as the top-level function
+class Greeter {
+public:
+    Greeter(std::string name) : name_(name) {}
+
+    void greet();
+
+private:
+    std::string name_;
+};
+
+// Method defined out of the class
+void Greeter::greet() {
+    std::vector<std::string> words;
+    for (const std::string word : {"Hello", "from", name_.c_str()}) {
+        words.push_back(word);
+    }
+    for (const std::string word : words) {
+        std::cout << word << " ";
+    }
+    std::cout << std::endl;
+}
+
+int main() {
+    greet();
+    Greeter greeter("Bob");
+    greeter.greet();
+}
@@ -0,0 +1,33 @@
+package com.example;
+
+import java.util.ArrayList;
+import java.util.List;
+
+// A class with a constructor and a method
+public class Greeter {
+    private final String name;
+
+    public Greeter(String name) {
+        this.name = name;
+    }
+
+    public void greet() {
+        System.out.println("Hello from " + name + ", inside the Greeter class!");
+    }
+
+    // A nested class with a method named in the same way as the one of the outer class
+    static class Loud {
+        void greet() {
+            List<String> words = new ArrayList<>();
+            for (String word : "hello from the nested class".split(" ")) {
+                words.add(word.toUpperCase());
+            }
+            System.out.println(String.join(" ", words));
+        }
+    }
+
+    public static void main(String[] args) {
+        new Greeter("Bob").greet();
+        new Loud().greet();
+    }
+}
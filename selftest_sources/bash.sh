@@ -0,0 +1,13 @@
+#!/usr/bin/env bash
+
+checkout() {
+    local total=0
+    for price in "$@"; do
+        total=$((total + price))
+    done
+    echo "$total"
+}
+
+test_checkout_is_free() {
+    [ "$(checkout)" = "0" ]
+}
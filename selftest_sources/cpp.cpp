@@ -0,0 +1,21 @@
+#include <gtest/gtest.h>
+
+class Cart {
+public:
+    Cart() : total_(0) {}
+
+    int total() const { return total_; }
+
+    void add(int price) { total_ += price; }
+
+private:
+    int total_;
+};
+
+int checkout(const Cart& cart) {
+    return cart.total();
+}
+
+TEST(Cart, CheckoutIsFree) {
+    EXPECT_EQ(0, checkout(Cart()));
+}
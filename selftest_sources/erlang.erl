@@ -0,0 +1,7 @@
+-module(cart).
+-export([checkout/1]).
+
+checkout([]) -> 0;
+checkout([Price | Prices]) -> Price + checkout(Prices).
+
+checkout_is_free_test() -> 0 = checkout([]).
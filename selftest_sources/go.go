@@ -0,0 +1,29 @@
+package cart
+
+import "testing"
+
+type Cart struct {
+	total int
+}
+
+func NewCart() *Cart {
+	return &Cart{}
+}
+
+func (c *Cart) Total() int {
+	return c.total
+}
+
+func (c *Cart) Add(price int) {
+	c.total += price
+}
+
+func Checkout(c *Cart) int {
+	return c.Total()
+}
+
+func TestCheckout(t *testing.T) {
+	if Checkout(NewCart()) != 0 {
+		t.Fail()
+	}
+}
@@ -0,0 +1,28 @@
+package com.example;
+
+import org.junit.jupiter.api.Test;
+
+public class Cart {
+    private int total;
+
+    public Cart() {
+        this.total = 0;
+    }
+
+    public int getTotal() {
+        return total;
+    }
+
+    public void add(int price) {
+        total += price;
+    }
+
+    public static int checkout(Cart cart) {
+        return cart.getTotal();
+    }
+
+    @Test
+    void checkoutIsFree() {
+        assert checkout(new Cart()) == 0;
+    }
+}
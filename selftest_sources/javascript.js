@@ -0,0 +1,21 @@
+class Cart {
+    constructor() {
+        this.total = 0;
+    }
+
+    get count() {
+        return this.total;
+    }
+
+    add(price) {
+        this.total += price;
+    }
+}
+
+function checkout(cart) {
+    return cart.count;
+}
+
+describe("checkout", () => {
+    const isFree = () => expect(checkout(new Cart())).toBe(0);
+});
@@ -0,0 +1,22 @@
+class Cart extends Component {
+    constructor(props) {
+        super(props);
+        this.state = { items: [] };
+    }
+
+    get count() {
+        return this.state.items.length;
+    }
+
+    render() {
+        return <ul>{this.state.items.map((item) => <li key={item.id}>{item.name}</li>)}</ul>;
+    }
+}
+
+function Checkout({ cart }) {
+    return <button disabled={cart.count === 0}>Checkout</button>;
+}
+
+describe("Checkout", () => {
+    const rendersButton = () => expect(render(<Checkout cart={new Cart()} />)).toBeTruthy();
+});
@@ -0,0 +1,26 @@
+struct Cart {
+    total: u64,
+}
+
+impl Cart {
+    fn new() -> Self {
+        Cart { total: 0 }
+    }
+
+    fn total(&self) -> u64 {
+        self.total
+    }
+
+    fn add(&mut self, price: u64) {
+        self.total += price;
+    }
+}
+
+fn checkout(cart: &Cart) -> u64 {
+    cart.total()
+}
+
+#[test]
+fn checkout_is_free() {
+    assert_eq!(0, checkout(&Cart::new()));
+}
@@ -0,0 +1,19 @@
+package com.example
+
+import org.junit.Test
+
+class Cart(private var sum: Int) {
+  def this() = this(0)
+
+  def total: Int = sum
+
+  def add(price: Int): Unit = sum += price
+}
+
+object Cart {
+  def checkout(cart: Cart): Int = cart.total
+}
+
+class CartTest {
+  @Test def checkoutIsFree(): Unit = assert(Cart.checkout(new Cart()) == 0)
+}
@@ -0,0 +1,26 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+contract Cart {
+    uint256 public total;
+
+    constructor() {
+        total = 0;
+    }
+
+    function add(uint256 price) external {
+        total += price;
+    }
+}
+
+library Prices {
+    function checkout(Cart cart) internal view returns (uint256) {
+        return cart.total();
+    }
+}
+
+contract CartTest {
+    function testCheckoutIsFree() public {
+        assert(Prices.checkout(new Cart()) == 0);
+    }
+}
@@ -0,0 +1,3 @@
+CREATE FUNCTION shop.checkout(prices ARRAY<INT64>) RETURNS INT64 AS (
+  (SELECT IFNULL(SUM(price), 0) FROM UNNEST(prices) AS price)
+);
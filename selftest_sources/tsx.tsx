@@ -0,0 +1,22 @@
+class Cart extends Component<{}, { items: Item[] }> {
+    constructor(props: {}) {
+        super(props);
+        this.state = { items: [] };
+    }
+
+    get count(): number {
+        return this.state.items.length;
+    }
+
+    render(): JSX.Element {
+        return <ul>{this.state.items.map((item) => <li key={item.id}>{item.name}</li>)}</ul>;
+    }
+}
+
+function Checkout({ cart }: { cart: Cart }): JSX.Element {
+    return <button disabled={cart.count === 0}>Checkout</button>;
+}
+
+describe("Checkout", () => {
+    const rendersButton = (): void => expect(render(<Checkout cart={new Cart()} />)).toBeTruthy();
+});
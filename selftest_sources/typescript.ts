@@ -0,0 +1,23 @@
+class Cart {
+    private total: number;
+
+    constructor() {
+        this.total = 0;
+    }
+
+    get count(): number {
+        return this.total;
+    }
+
+    add(price: number): void {
+        this.total += price;
+    }
+}
+
+function checkout(cart: Cart): number {
+    return cart.count;
+}
+
+describe("checkout", () => {
+    const isFree = (): void => expect(checkout(new Cart())).toBe(0);
+});
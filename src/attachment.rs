@@ -0,0 +1,80 @@
+use std::fs::read;
+use std::path::Path;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The image types models accept, by extension
+const IMAGE_MEDIA_TYPES: &[(&str, &str)] = &[
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+];
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let triple = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |triple, (index, byte)| {
+                triple | (*byte as u32) << (16 - 8 * index)
+            });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (triple >> (18 - 6 * index)) & 0b11_1111;
+                encoded.push(BASE64_ALPHABET[sextet as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+/// The image as a `data:` URL, which is how images are put in prompts
+pub fn image_data_url(path: &Path) -> Result<String, String> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let Some((_, media_type)) = IMAGE_MEDIA_TYPES
+        .iter()
+        .find(|(image_extension, _)| *image_extension == extension)
+    else {
+        return Err(format!(
+            "{} is not a png, jpeg, gif or webp image",
+            path.display()
+        ));
+    };
+    let image = read(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+
+    Ok(format!("data:{};base64,{}", media_type, base64(&image)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::attachment::{base64, image_data_url};
+
+    #[test]
+    fn data_urls() {
+        assert_eq!("", base64(b""));
+        assert_eq!("Zg==", base64(b"f"));
+        assert_eq!("Zm8=", base64(b"fo"));
+        assert_eq!("Zm9v", base64(b"foo"));
+        assert_eq!("Zm9vYmFy/w==", base64(b"foobar\xff"));
+
+        let path = std::env::temp_dir().join("senior-attachment-test.PNG");
+        std::fs::write(&path, b"\x89PNG").unwrap();
+        assert_eq!(
+            "data:image/png;base64,iVBORw==",
+            image_data_url(&path).unwrap()
+        );
+        assert!(image_data_url(Path::new("flamegraph.svg")).is_err());
+    }
+}
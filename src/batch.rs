@@ -0,0 +1,379 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use ignore::WalkBuilder;
+
+use tree_sitter::{Node, Range, Tree};
+
+use crate::complexity::over_budget;
+use crate::config::ComplexityConfig;
+use crate::coverage::same_file;
+use crate::diagnostics::Diagnostic;
+use crate::git;
+use crate::supported_languages::supported_language::{
+    detect_language, is_test_file, matches_glob, FunctionKind, SupportedLanguage,
+};
+
+/// Narrows down which functions `--all` goes through.
+pub struct BatchFilters {
+    pub kind: Option<FunctionKind>,
+    pub skip_tests: bool,
+    pub only_tests: bool,
+    pub min_lines: Option<usize>,
+    pub max_lines: Option<usize>,
+    pub skip_generated: bool,
+    /// Only the functions over this complexity budget
+    pub over_budget: Option<ComplexityConfig>,
+    /// Only the functions with one of these diagnostics in them
+    pub diagnosed: Option<Arc<Vec<Diagnostic>>>,
+}
+
+/// Every function `--all` goes through without flags, which leaves out generated code
+impl Default for BatchFilters {
+    fn default() -> Self {
+        Self {
+            kind: None,
+            skip_tests: false,
+            only_tests: false,
+            min_lines: None,
+            max_lines: None,
+            skip_generated: true,
+            over_budget: None,
+            diagnosed: None,
+        }
+    }
+}
+
+/// File names of generated code
+const GENERATED_FILE_GLOBS: [&str; 6] = [
+    "*.pb.go",
+    "*_gen.go",
+    "*_generated.rs",
+    "*.generated.ts",
+    "*.generated.js",
+    "*.min.js",
+];
+
+/// Markers that generators leave in the first comments of the files they write
+const GENERATED_CODE_MARKERS: [&str; 4] = [
+    "Code generated by",
+    "Code generated from",
+    "@generated",
+    "DO NOT EDIT",
+];
+
+/// Whether the file was written by a tool, like protoc or bindgen, rather than by hand
+pub fn is_generated_file(file_path: &str, source_file: &str) -> bool {
+    GENERATED_FILE_GLOBS
+        .iter()
+        .any(|glob| matches_glob(&glob::Pattern::new(glob).unwrap(), file_path))
+        || source_file.lines().take(10).any(|line| {
+            GENERATED_CODE_MARKERS
+                .iter()
+                .any(|marker| line.contains(marker))
+        })
+}
+
+impl BatchFilters {
+    /// The ranges of the functions of a parsed file that should be optimised, in source order.
+    /// Functions nested inside other functions are left to their outermost function.
+    pub fn find_targets(
+        &self,
+        supported_language: &dyn SupportedLanguage,
+        file_path: &str,
+        source_file: &str,
+        tree: &Tree,
+    ) -> Vec<Range> {
+        let in_test_file = is_test_file(file_path);
+        if self.skip_tests && in_test_file {
+            return vec![];
+        }
+        if self.skip_generated && is_generated_file(file_path, source_file) {
+            return vec![];
+        }
+
+        let mut outermost_functions: Vec<Node> = vec![];
+        for function_node in supported_language.find_all_functions(tree) {
+            let is_nested = outermost_functions
+                .last()
+                .is_some_and(|outer| function_node.end_byte() <= outer.end_byte());
+            if !is_nested {
+                outermost_functions.push(function_node);
+            }
+        }
+
+        outermost_functions
+            .into_iter()
+            .filter(|function_node| {
+                supported_language.is_of_kind(source_file, *function_node, &self.kind)
+            })
+            .filter(|function_node| {
+                if !self.skip_tests && !self.only_tests {
+                    return true;
+                }
+                let is_test = in_test_file
+                    || supported_language.function_kind(source_file, *function_node)
+                        == FunctionKind::Test;
+
+                is_test == self.only_tests
+            })
+            .filter(|function_node| {
+                let lines =
+                    function_node.end_position().row - function_node.start_position().row + 1;
+
+                self.min_lines.is_none_or(|min_lines| lines >= min_lines)
+                    && self.max_lines.is_none_or(|max_lines| lines <= max_lines)
+            })
+            .filter(|function_node| {
+                self.over_budget
+                    .is_none_or(|limits| !over_budget(&limits, *function_node).is_empty())
+            })
+            .filter(|function_node| {
+                self.diagnosed.as_ref().is_none_or(|diagnostics| {
+                    diagnostics.iter().any(|diagnostic| {
+                        function_node.start_position().row < diagnostic.start_line
+                            && diagnostic.end_line <= function_node.end_position().row + 1
+                            && same_file(&diagnostic.file, file_path)
+                    })
+                })
+            })
+            .map(|function_node| function_node.range())
+            .collect()
+    }
+}
+
+/// The path itself if it is a file, otherwise every supported source file under it, sorted. Files
+/// matched by `.gitignore`, `.ignore` and similar files are left out, unless `no_ignore` is set.
+pub fn source_files(path: &Path, no_ignore: bool) -> Vec<String> {
+    if !path.is_dir() {
+        return vec![path.to_string_lossy().to_string()];
+    }
+
+    let mut files: Vec<String> = WalkBuilder::new(path)
+        .git_ignore(!no_ignore)
+        .git_global(!no_ignore)
+        .git_exclude(!no_ignore)
+        .ignore(!no_ignore)
+        .parents(!no_ignore)
+        .require_git(false)
+        .build()
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_file())
+        })
+        .map(|entry| entry.path().to_string_lossy().to_string())
+        .filter(|file_path| detect_language(file_path).is_ok())
+        .collect();
+    files.sort();
+
+    files
+}
+
+/// Like `source_files`, but only keeps the files with uncommitted changes if `changed` is set
+pub fn batch_files(path: &Path, changed: bool, no_ignore: bool) -> Result<Vec<String>, String> {
+    let files = source_files(path, no_ignore);
+    if !changed {
+        return Ok(files);
+    }
+    let changed_files = git::changed_files()?;
+
+    Ok(files
+        .into_iter()
+        .filter(|file_path| {
+            Path::new(file_path)
+                .canonicalize()
+                .is_ok_and(|file_path| changed_files.contains(&file_path))
+        })
+        .collect())
+}
+
+/// The answers of the model for the functions a batch run went through, by their language and
+/// code. Vendored and copied code would otherwise be sent to the model once for every copy, so the
+/// copies reuse the answer for the first one instead, and each copy is still checked and confirmed
+/// on its own.
+#[derive(Default)]
+pub struct IdenticalFunctions {
+    answers: HashMap<(String, String), (String, String)>,
+    reused: usize,
+}
+
+impl IdenticalFunctions {
+    /// Where the first function with the same code was, and the answer for it, counting it as
+    /// reused
+    pub fn first_of(&mut self, language: &str, code: &str) -> Option<(String, String)> {
+        let first = self
+            .answers
+            .get(&(language.to_string(), code.to_string()))
+            .cloned()?;
+        self.reused += 1;
+
+        Some(first)
+    }
+    /// Only answers are recorded, so that the copies of a function the model could not be asked
+    /// about are tried again
+    pub fn record(&mut self, language: &str, code: &str, location: &str, answer: &str) {
+        self.answers
+            .entry((language.to_string(), code.to_string()))
+            .or_insert_with(|| (location.to_string(), answer.to_string()));
+    }
+    /// How many functions reused the answer for an identical one
+    pub fn reused(&self) -> usize {
+        self.reused
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Range;
+
+    use std::fs::{create_dir_all, write};
+
+    use crate::batch::{is_generated_file, source_files, BatchFilters, IdenticalFunctions};
+    use crate::helpers::tree_sitter::parse_source_with_language;
+    use crate::supported_languages::supported_language::detect_language;
+
+    const RUST_SOURCE: &str = r#"fn greet() {
+    let shout = || println!("HELLO");
+    shout();
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn greets() {
+        super::greet();
+    }
+}"#;
+
+    fn targets(file_path: &str, filters: BatchFilters) -> Vec<usize> {
+        let tree = parse_source_with_language(RUST_SOURCE, tree_sitter_rust::language());
+        let ra = detect_language(file_path).unwrap();
+
+        filters
+            .find_targets(ra.as_ref(), file_path, RUST_SOURCE, &tree)
+            .iter()
+            .map(|range: &Range| range.start_point.row)
+            .collect()
+    }
+
+    #[test]
+    fn all_functions() {
+        let filters = BatchFilters::default();
+
+        assert_eq!(vec![0, 8], targets("src/lib.rs", filters))
+    }
+
+    #[test]
+    fn skip_tests() {
+        let filters = BatchFilters {
+            skip_tests: true,
+            ..BatchFilters::default()
+        };
+
+        assert_eq!(vec![0], targets("src/lib.rs", filters))
+    }
+
+    #[test]
+    fn only_tests() {
+        let filters = BatchFilters {
+            only_tests: true,
+            ..BatchFilters::default()
+        };
+
+        assert_eq!(vec![8], targets("src/lib.rs", filters))
+    }
+
+    #[test]
+    fn skip_test_files() {
+        let filters = BatchFilters {
+            skip_tests: true,
+            ..BatchFilters::default()
+        };
+
+        assert!(targets("tests/greet.rs", filters).is_empty())
+    }
+
+    #[test]
+    fn line_thresholds() {
+        let filters = BatchFilters {
+            min_lines: Some(4),
+            ..BatchFilters::default()
+        };
+        assert_eq!(vec![0], targets("src/lib.rs", filters));
+
+        let filters = BatchFilters {
+            max_lines: Some(3),
+            ..BatchFilters::default()
+        };
+        assert_eq!(vec![8], targets("src/lib.rs", filters));
+    }
+
+    #[test]
+    fn generated_files() {
+        assert!(is_generated_file("api/greeter.pb.go", "package api"));
+        assert!(is_generated_file("src/bindings_generated.rs", ""));
+        assert!(is_generated_file(
+            "api/greeter.go",
+            "// Code generated by protoc-gen-go. DO NOT EDIT.\npackage api"
+        ));
+        assert!(is_generated_file(
+            "src/schema.rs",
+            "// @generated\nfn x() {}"
+        ));
+        assert!(!is_generated_file("src/lib.rs", RUST_SOURCE));
+
+        let filters = BatchFilters::default();
+        assert!(targets("src/greet_generated.rs", filters).is_empty());
+
+        let filters = BatchFilters {
+            skip_generated: false,
+            ..BatchFilters::default()
+        };
+        assert_eq!(vec![0, 8], targets("src/greet_generated.rs", filters));
+    }
+
+    #[test]
+    fn ignored_files() {
+        let root = std::env::temp_dir().join("senior-ignore-test");
+        create_dir_all(root.join("node_modules/left-pad")).unwrap();
+        create_dir_all(root.join("src")).unwrap();
+        write(root.join(".gitignore"), "node_modules/\n").unwrap();
+        write(root.join("node_modules/left-pad/index.js"), "").unwrap();
+        write(root.join("src/index.js"), "").unwrap();
+        write(root.join("README.md"), "").unwrap();
+
+        let file_names = |no_ignore| -> Vec<String> {
+            source_files(&root, no_ignore)
+                .iter()
+                .map(|file_path| file_path.replace(&*root.to_string_lossy(), ""))
+                .collect()
+        };
+        assert_eq!(vec!["/src/index.js"], file_names(false));
+        assert_eq!(
+            vec!["/node_modules/left-pad/index.js", "/src/index.js"],
+            file_names(true)
+        );
+    }
+
+    #[test]
+    fn identical_functions() {
+        let mut identical_functions = IdenticalFunctions::default();
+        let answer = "```rust\nfn greet() {}\n```";
+        identical_functions.record("rust", "fn greet() { () }", "a.rs:1", answer);
+        identical_functions.record("rust", "fn greet() { () }", "b.rs:1", "OPTIMAL");
+
+        assert_eq!(
+            Some(("a.rs:1".to_string(), answer.to_string())),
+            identical_functions.first_of("rust", "fn greet() { () }")
+        );
+        assert_eq!(None, identical_functions.first_of("rust", "fn shout() {}"));
+        assert_eq!(
+            None,
+            identical_functions.first_of("go", "fn greet() { () }")
+        );
+        assert_eq!(1, identical_functions.reused());
+    }
+}
@@ -0,0 +1,199 @@
+use crate::diagnostics::Diagnostic;
+use crate::sandbox::{describe_where, sandboxed_command, Sandbox};
+use crate::supported_languages::supported_language::detect_language;
+
+/// The system prompt of `senior fix`, which fixes the errors the build reported in a function
+pub const FIX_SYSTEM_PROMPT: &str = r#"You are a senior software engineer. Your task is to fix the build errors of the code you are given.
+
+Strictly adhere to the following instructions:
+1. Only change what it takes to fix the errors, and keep what the code does.
+2. Do not change the type signature, unless an error is about it.
+3. Respond only with code, and no commentary or explanations.
+"#;
+
+/// Runs the build command in a shell, in the sandbox if there is one, and returns whether it
+/// succeeded, along with what it wrote to stdout and stderr
+pub fn run_build(command: &str, sandbox: Option<&Sandbox>) -> Result<(bool, String), String> {
+    let words = ["sh", "-c", command].map(str::to_string);
+    let output = sandboxed_command(&words, sandbox)?.output().map_err(|e| {
+        format!(
+            "failed to run `{}`{}: {}",
+            command,
+            describe_where(sandbox),
+            e
+        )
+    })?;
+
+    Ok((
+        output.status.success(),
+        format!(
+            "{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        ),
+    ))
+}
+
+// A location like `src/main.rs:12:5` or `main.go:12`, in a file of a supported language
+fn location(text: &str) -> Option<(String, usize)> {
+    let mut parts = text.trim().split(':');
+    let file = parts.next()?;
+    let line = parts.next()?.trim().parse().ok()?;
+    detect_language(file).ok()?;
+
+    Some((file.trim_start_matches("./").to_string(), line))
+}
+
+// Splits lines like `main.go:12:5: undefined: total`, `Cart.java:12: error: ...` or, for tsc in
+// pretty mode, `src/cart.ts:12:5 - error TS2322: ...` into their location and their message
+fn split_location(line: &str) -> Option<((String, usize), &str)> {
+    if let Some((head, message)) = line.split_once(" - ") {
+        if let Some(location) = location(head) {
+            return Some((location, message));
+        }
+    }
+    let mut parts = line.splitn(3, ':');
+    let (file, line_number, rest) = (parts.next()?, parts.next()?, parts.next()?);
+    let location = location(&format!("{}:{}", file, line_number))?;
+    // The column is optional
+    let message = match rest.split_once(':') {
+        Some((column, message)) if column.trim().parse::<usize>().is_ok() => message,
+        _ => rest,
+    };
+
+    Some((location, message))
+}
+
+fn diagnostic(file: String, line: usize, message: &str) -> Diagnostic {
+    let message = message.trim().trim_start_matches("error:").trim();
+    // Like `error TS2322: Type 'string' is not assignable...`
+    let (rule, message) = match message
+        .strip_prefix("error ")
+        .and_then(|rest| rest.split_once(": "))
+    {
+        Some((rule, message)) => (Some(rule.to_string()), message),
+        None => (None, message),
+    };
+
+    Diagnostic {
+        file,
+        start_line: line,
+        end_line: line,
+        rule,
+        message: message.to_string(),
+    }
+}
+
+/// The errors of the output of a build, in the formats of rustc, the Go compiler, tsc, javac and
+/// gcc or clang. Warnings and notes are left out.
+pub fn parse_build_errors(output: &str) -> Vec<Diagnostic> {
+    let mut errors = vec![];
+    // rustc gives the message first, and its location on a later line
+    let mut rust_error: Option<(Option<String>, String)> = None;
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("warning") {
+            rust_error = None;
+            continue;
+        }
+        if let Some(message) = trimmed.strip_prefix("error") {
+            rust_error = message.split_once(": ").map(|(code, message)| {
+                let code = code.trim_start_matches('[').trim_end_matches(']');
+                (
+                    (!code.is_empty()).then(|| code.to_string()),
+                    message.to_string(),
+                )
+            });
+            continue;
+        }
+        if let Some(rest) = trimmed.strip_prefix("--> ") {
+            if let (Some((code, message)), Some((file, line))) = (rust_error.take(), location(rest))
+            {
+                errors.push(Diagnostic {
+                    file,
+                    start_line: line,
+                    end_line: line,
+                    rule: code,
+                    message,
+                });
+            }
+            continue;
+        }
+
+        // tsc, as in `src/cart.ts(12,5): error TS2322: ...`
+        if let Some((head, message)) = trimmed.split_once("): ") {
+            if let Some((file, position)) = head.split_once('(') {
+                if let Some((file, line)) =
+                    location(&format!("{}:{}", file, position.replace(',', ":")))
+                {
+                    if message.starts_with("error") {
+                        errors.push(diagnostic(file, line, message));
+                    }
+                    continue;
+                }
+            }
+        }
+        let Some(((file, line), message)) = split_location(trimmed) else {
+            continue;
+        };
+        let message = message.trim();
+        if message.starts_with("warning") || message.starts_with("note") {
+            continue;
+        }
+        errors.push(diagnostic(file, line, message));
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::build_errors::parse_build_errors;
+
+    #[test]
+    fn errors_of_each_compiler() {
+        let rustc = r#"   Compiling shop v0.1.0
+error[E0308]: mismatched types
+  --> src/cart.rs:12:5
+   |
+12 |     total
+   |     ^^^^^ expected `u32`, found `String`
+warning: unused variable: `x`
+  --> src/cart.rs:3:9
+"#;
+        let errors = parse_build_errors(rustc);
+        assert_eq!(1, errors.len());
+        assert_eq!(
+            ("src/cart.rs", 12, Some("E0308"), "mismatched types"),
+            (
+                errors[0].file.as_str(),
+                errors[0].start_line,
+                errors[0].rule.as_deref(),
+                errors[0].message.as_str()
+            )
+        );
+
+        let go = "# example.com/shop\n./cart.go:7:9: undefined: price\n";
+        let errors = parse_build_errors(go);
+        assert_eq!(
+            ("cart.go", 7, "undefined: price"),
+            (
+                errors[0].file.as_str(),
+                errors[0].start_line,
+                errors[0].message.as_str()
+            )
+        );
+
+        let tsc = "src/cart.ts(4,3): error TS2322: Type 'string' is not assignable to type 'number'.\nsrc/cart.ts:9:1 - error TS2304: Cannot find name 'total'.\n";
+        let errors = parse_build_errors(tsc);
+        assert_eq!(2, errors.len());
+        assert_eq!(Some("TS2322"), errors[0].rule.as_deref());
+        assert_eq!(9, errors[1].start_line);
+        assert_eq!("Cannot find name 'total'.", errors[1].message);
+
+        let javac = "src/main/java/Cart.java:12: error: cannot find symbol\ncart.cpp:3:10: warning: unused variable 'x'\n";
+        let errors = parse_build_errors(javac);
+        assert_eq!(1, errors.len());
+        assert_eq!("cannot find symbol", errors[0].message);
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use sha2::{Digest, Sha256};
+
+use crate::history::HistoryStore;
+use crate::openai::Prompt;
+
+/// The answers of previous runs, keyed by the fingerprint of what was asked. Lets repeated
+/// `--all` runs skip functions that did not change since the last time.
+#[derive(Default)]
+pub struct VerdictCache {
+    verdicts: HashMap<String, String>,
+}
+
+impl VerdictCache {
+    /// Loads the cache from the store, starting from scratch if it cannot be read
+    pub fn load(history: &dyn HistoryStore) -> Self {
+        Self {
+            verdicts: history.verdicts(),
+        }
+    }
+
+    pub fn save(&self, history: &dyn HistoryStore) -> Result<(), String> {
+        history.save_verdicts(&self.verdicts)
+    }
+
+    pub fn get(&self, fingerprint: &str) -> Option<&str> {
+        self.verdicts.get(fingerprint).map(String::as_str)
+    }
+
+    pub fn record(&mut self, fingerprint: String, suggestion: String) {
+        self.verdicts.insert(fingerprint, suggestion);
+    }
+}
+
+/// Hashes everything that influences the answer: the model and the prompt, which holds both
+/// the code and the settings.
+pub fn fingerprint(model: &str, prompt: &Prompt) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(model.as_bytes());
+    hasher.update([0]);
+    hasher.update(prompt.system.as_bytes());
+    hasher.update([0]);
+    hasher.update(prompt.user.as_bytes());
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::{fingerprint, VerdictCache};
+    use crate::history::JsonlHistory;
+    use crate::openai::Prompt;
+
+    fn prompt(system: &str, user: &str) -> Prompt {
+        Prompt {
+            system: system.to_string(),
+            user: user.to_string(),
+        }
+    }
+
+    #[test]
+    fn fingerprints_depend_on_model_and_prompt() {
+        let original = fingerprint("gpt-3.5-turbo", &prompt("optimise", "fn greet() {}"));
+
+        assert_eq!(
+            original,
+            fingerprint("gpt-3.5-turbo", &prompt("optimise", "fn greet() {}"))
+        );
+        assert_ne!(
+            original,
+            fingerprint("gpt-4", &prompt("optimise", "fn greet() {}"))
+        );
+        assert_ne!(
+            original,
+            fingerprint("gpt-3.5-turbo", &prompt("optimise", "fn greet() { }"))
+        );
+        assert_ne!(
+            original,
+            fingerprint("gpt-3.5-turbo", &prompt("optimise!", "fn greet() {}"))
+        );
+    }
+
+    #[test]
+    fn save_and_load() {
+        let history = JsonlHistory::new(std::env::temp_dir().join("senior-cache-test"));
+        let mut cache = VerdictCache::default();
+        cache.record("abc".to_string(), "OPTIMAL".to_string());
+        cache.save(&history).unwrap();
+
+        assert_eq!(Some("OPTIMAL"), VerdictCache::load(&history).get("abc"));
+        assert_eq!(None, VerdictCache::load(&history).get("def"));
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+use tree_sitter::{Node, Tree};
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::batch::source_files;
+use crate::context::{callee_name, contains, name_of, signature};
+use crate::helpers::tree_sitter::{node_value, parse_with_thread_parser};
+use crate::openai::Prompt;
+use crate::supported_languages::supported_language::{detect_language, SupportedLanguage};
+
+/// A function calling the function whose signature changed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Caller {
+    pub file: String,
+    pub name: Option<String>,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    /// Counted from one
+    pub line: usize,
+    pub code: String,
+}
+
+/// The new code of a caller, made to call the function with its new signature
+pub struct CallerUpdate {
+    pub caller: Caller,
+    pub code: String,
+}
+
+/// The signature of the first function of the code, with whitespace collapsed so that formatting
+/// does not count as a change
+fn normalized_signature(code: &str, language: tree_sitter::Language) -> Option<String> {
+    let tree = parse_with_thread_parser(code, language)?;
+
+    Some(
+        signature(code, tree.root_node())
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" "),
+    )
+}
+
+/// Whether the suggestion changes how the function is called, and its signatures before and after
+pub fn signature_change(
+    language: tree_sitter::Language,
+    original: &str,
+    suggestion: &str,
+) -> Option<(String, String)> {
+    let before = normalized_signature(original, language)?;
+    let after = normalized_signature(suggestion, language)?;
+
+    (before != after).then_some((before, after))
+}
+
+/// Whether the node calls a function of that name. Rust macros, like `println!`, hold tokens rather
+/// than expressions, where a call is a name followed by parentheses.
+fn calls(source_file: &str, node: Node, function_name: &str) -> bool {
+    match node.kind() {
+        "call_expression" | "method_invocation" => {
+            callee_name(source_file, node) == Some(function_name)
+        }
+        "identifier" => {
+            node.parent()
+                .is_some_and(|parent| parent.kind() == "token_tree")
+                && node_value(source_file, node) == function_name
+                && node.next_sibling().is_some_and(|arguments| {
+                    arguments.kind() == "token_tree"
+                        && node_value(source_file, arguments).starts_with('(')
+                })
+        }
+        _ => false,
+    }
+}
+
+/// The functions of the file calling a function of that name, each once, in source order
+pub fn callers_in<'a>(
+    supported_language: &dyn SupportedLanguage,
+    source_file: &str,
+    tree: &'a Tree,
+    function_name: &str,
+) -> Vec<Node<'a>> {
+    let functions = supported_language.find_all_functions(tree);
+    let mut callers: Vec<Node> = vec![];
+    for call in traverse(tree.walk(), Order::Pre) {
+        if !calls(source_file, call, function_name) {
+            continue;
+        }
+        // The innermost function holding the call is the one to update
+        let Some(caller) = functions
+            .iter()
+            .filter(|function| contains(**function, call))
+            .min_by_key(|function| function.byte_range().len())
+        else {
+            continue;
+        };
+        if !callers.contains(caller) {
+            callers.push(*caller);
+        }
+    }
+
+    callers
+}
+
+/// Every function under the root, in files of the same language, that calls a function of that
+/// name, bar the function itself
+pub fn find_callers(
+    root: &Path,
+    language: &str,
+    function_file: &str,
+    function_name: &str,
+) -> Vec<Caller> {
+    let function_file = Path::new(function_file).canonicalize().ok();
+    let mut callers = vec![];
+    for file_path in source_files(root, false) {
+        let Ok(supported_language) = detect_language(&file_path) else {
+            continue;
+        };
+        if supported_language.to_string() != language {
+            continue;
+        }
+        let Ok(source_file) = read_to_string(&file_path) else {
+            continue;
+        };
+        let Some(tree) = parse_with_thread_parser(&source_file, supported_language.language())
+        else {
+            continue;
+        };
+        let is_function_file = Path::new(&file_path).canonicalize().ok() == function_file;
+        for caller in callers_in(
+            supported_language.as_ref(),
+            &source_file,
+            &tree,
+            function_name,
+        ) {
+            let name = name_of(&source_file, caller).map(str::to_string);
+            // Recursive calls were already updated by the suggestion
+            if is_function_file && name.as_deref() == Some(function_name) {
+                continue;
+            }
+            callers.push(Caller {
+                file: file_path.clone(),
+                name,
+                start_byte: caller.start_byte(),
+                end_byte: caller.end_byte(),
+                line: caller.start_position().row + 1,
+                code: node_value(&source_file, caller).to_string(),
+            });
+        }
+    }
+
+    callers
+}
+
+pub fn draft_caller_update_instructions(
+    language: &str,
+    function_name: &str,
+    (before, after): &(String, String),
+    caller: &str,
+) -> Prompt {
+    Prompt {
+        system: format!(
+            r#"You are a senior {} engineer updating the callers of a function whose signature changed.
+
+The signature of {} changed from:
+{}
+to:
+{}
+
+Strictly adhere to the following instructions:
+1. Update the calls to {} in the code you are given to the new signature, keeping what they do.
+2. Change nothing else.
+3. If the code does not call this {}, respond with it unchanged.
+4. Respond only with the whole updated code, and no commentary or explanations."#,
+            language, function_name, before, after, function_name, function_name
+        ),
+        user: caller.to_string(),
+    }
+}
+
+/// Applies the updates, and returns the files that changed. Nothing is written if any caller
+/// changed since it was read.
+pub fn apply_caller_updates(updates: &[CallerUpdate]) -> Result<Vec<String>, String> {
+    let mut updates_by_file: HashMap<&str, Vec<&CallerUpdate>> = HashMap::new();
+    for update in updates {
+        updates_by_file
+            .entry(&update.caller.file)
+            .or_default()
+            .push(update);
+    }
+
+    let mut updated_files: Vec<(&str, String)> = vec![];
+    for (file, mut updates) in updates_by_file {
+        let mut source_file =
+            read_to_string(file).map_err(|e| format!("failed to read {}: {}", file, e))?;
+        // From the end, so that the offsets of the callers before stay right
+        updates.sort_by_key(|update| std::cmp::Reverse(update.caller.start_byte));
+        for update in updates {
+            let range = update.caller.start_byte..update.caller.end_byte;
+            if source_file.get(range.clone()) != Some(update.caller.code.as_str()) {
+                return Err(format!("{} changed since its callers were read", file));
+            }
+            source_file.replace_range(range, &update.code);
+        }
+        updated_files.push((file, source_file));
+    }
+    updated_files.sort();
+    for (file, source_file) in &updated_files {
+        write(file, source_file).map_err(|e| format!("failed to write {}: {}", file, e))?;
+    }
+
+    Ok(updated_files
+        .into_iter()
+        .map(|(file, _)| file.to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::callers::{callers_in, signature_change};
+    use crate::helpers::tree_sitter::{node_value, parse_source_with_language};
+    use crate::supported_languages::supported_language::detect_language;
+
+    const SOURCE: &str = r#"fn total(prices: &[u32]) -> u32 {
+    prices.iter().sum()
+}
+
+fn checkout(cart: &Cart) -> u32 {
+    let subtotal = total(&cart.prices);
+    subtotal + total(&cart.fees)
+}
+
+fn report(cart: &Cart) {
+    println!("{}", total(&cart.prices));
+}
+
+impl Cart {
+    fn refund(&self) -> u32 {
+        self.total(&self.prices)
+    }
+
+    fn empty(&self) -> bool {
+        self.prices.is_empty()
+    }
+}"#;
+
+    #[test]
+    fn callers() {
+        let tree = parse_source_with_language(SOURCE, tree_sitter_rust::language());
+        let callers: Vec<&str> = callers_in(
+            detect_language("lib.rs").unwrap().as_ref(),
+            SOURCE,
+            &tree,
+            "total",
+        )
+        .into_iter()
+        .map(|caller| node_value(SOURCE, caller).lines().next().unwrap())
+        .collect();
+
+        assert_eq!(
+            vec![
+                "fn checkout(cart: &Cart) -> u32 {",
+                "fn report(cart: &Cart) {",
+                "fn refund(&self) -> u32 {"
+            ],
+            callers
+        );
+    }
+
+    #[test]
+    fn signature_changes() {
+        let language = tree_sitter_rust::language();
+        let original = "fn total(prices: &[u32]) -> u32 {\n    prices.iter().sum()\n}";
+
+        assert_eq!(
+            None,
+            signature_change(
+                language,
+                original,
+                "fn total(prices:  &[u32])\n    -> u32 {\n    prices.iter().copied().sum()\n}"
+            )
+        );
+        assert_eq!(
+            Some((
+                "fn total(prices: &[u32]) -> u32".to_string(),
+                "fn total(prices: &[u64]) -> u64".to_string()
+            )),
+            signature_change(
+                language,
+                original,
+                "fn total(prices: &[u64]) -> u64 { prices.iter().sum() }"
+            )
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt::{Display, Formatter};
+
+/// What a model supports. Features that need a capability are left out for models without it,
+/// rather than failing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    pub streaming: bool,
+    /// Answers that are any JSON object
+    pub json_mode: bool,
+    /// Answers that are JSON following a schema
+    pub structured_outputs: bool,
+    /// How many tokens the prompt and the answer may take up together
+    pub max_context_tokens: usize,
+    /// Images in the prompt
+    pub vision: bool,
+    /// The provider caches prompt prefixes
+    pub prompt_caching: bool,
+    /// The model reasons before answering, which takes longer and can be given more or less effort
+    pub reasoning: bool,
+}
+
+/// Models are matched by the longest prefix of their name, so that `gpt-4o-mini` is a `gpt-4o`
+/// and dated snapshots, like `gpt-4o-2024-08-06`, are the model they snapshot
+const KNOWN_CAPABILITIES: &[(&str, Capabilities)] = &[
+    (
+        "gpt-3.5-turbo",
+        Capabilities {
+            streaming: true,
+            json_mode: true,
+            structured_outputs: false,
+            max_context_tokens: 16_385,
+            vision: false,
+            prompt_caching: false,
+            reasoning: false,
+        },
+    ),
+    (
+        "gpt-4",
+        Capabilities {
+            streaming: true,
+            json_mode: false,
+            structured_outputs: false,
+            max_context_tokens: 8_192,
+            vision: false,
+            prompt_caching: false,
+            reasoning: false,
+        },
+    ),
+    (
+        "gpt-4-turbo",
+        Capabilities {
+            streaming: true,
+            json_mode: true,
+            structured_outputs: false,
+            max_context_tokens: 128_000,
+            vision: true,
+            prompt_caching: false,
+            reasoning: false,
+        },
+    ),
+    (
+        "gpt-4o",
+        Capabilities {
+            streaming: true,
+            json_mode: true,
+            structured_outputs: true,
+            max_context_tokens: 128_000,
+            vision: true,
+            prompt_caching: true,
+            reasoning: false,
+        },
+    ),
+    (
+        "gpt-4.1",
+        Capabilities {
+            streaming: true,
+            json_mode: true,
+            structured_outputs: true,
+            max_context_tokens: 1_047_576,
+            vision: true,
+            prompt_caching: true,
+            reasoning: false,
+        },
+    ),
+    (
+        "gpt-5",
+        Capabilities {
+            streaming: true,
+            json_mode: true,
+            structured_outputs: true,
+            max_context_tokens: 400_000,
+            vision: true,
+            prompt_caching: true,
+            reasoning: true,
+        },
+    ),
+    (
+        "o1",
+        Capabilities {
+            streaming: false,
+            json_mode: true,
+            structured_outputs: true,
+            max_context_tokens: 200_000,
+            vision: true,
+            prompt_caching: true,
+            reasoning: true,
+        },
+    ),
+    (
+        "o3",
+        Capabilities {
+            streaming: true,
+            json_mode: true,
+            structured_outputs: true,
+            max_context_tokens: 200_000,
+            vision: true,
+            prompt_caching: true,
+            reasoning: true,
+        },
+    ),
+    (
+        "o4",
+        Capabilities {
+            streaming: true,
+            json_mode: true,
+            structured_outputs: true,
+            max_context_tokens: 200_000,
+            vision: true,
+            prompt_caching: true,
+            reasoning: true,
+        },
+    ),
+];
+
+/// What is assumed of models that are not known: plain text answers in a small context
+const UNKNOWN_CAPABILITIES: Capabilities = Capabilities {
+    streaming: true,
+    json_mode: false,
+    structured_outputs: false,
+    max_context_tokens: 8_192,
+    vision: false,
+    prompt_caching: false,
+    reasoning: false,
+};
+
+/// The capabilities of the model, or conservative ones if it is not known
+pub fn capabilities(model: &str) -> Capabilities {
+    KNOWN_CAPABILITIES
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or(UNKNOWN_CAPABILITIES, |(_, capabilities)| *capabilities)
+}
+
+/// Whether the model is one of the known ones, rather than assumed to have the fewest capabilities
+pub fn is_known_model(model: &str) -> bool {
+    KNOWN_CAPABILITIES
+        .iter()
+        .any(|(prefix, _)| model.starts_with(prefix))
+}
+
+impl Display for Capabilities {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+        writeln!(f, "streaming: {}", yes_no(self.streaming))?;
+        writeln!(f, "json mode: {}", yes_no(self.json_mode))?;
+        writeln!(f, "structured outputs: {}", yes_no(self.structured_outputs))?;
+        writeln!(f, "max context tokens: {}", self.max_context_tokens)?;
+        writeln!(f, "vision: {}", yes_no(self.vision))?;
+        writeln!(f, "prompt caching: {}", yes_no(self.prompt_caching))?;
+        write!(f, "reasoning: {}", yes_no(self.reasoning))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::capabilities::{capabilities, is_known_model, UNKNOWN_CAPABILITIES};
+
+    #[test]
+    fn model_capabilities() {
+        assert!(capabilities("gpt-4o-mini").structured_outputs);
+        assert!(capabilities("gpt-4o-2024-08-06").vision);
+        assert!(!capabilities("gpt-3.5-turbo").structured_outputs);
+        assert_eq!(128_000, capabilities("gpt-4-turbo").max_context_tokens);
+        assert_eq!(8_192, capabilities("gpt-4-0613").max_context_tokens);
+        assert!(!capabilities("o1-preview").streaming);
+        assert!(capabilities("o3-mini").reasoning && !capabilities("gpt-4.1").reasoning);
+
+        assert_eq!(UNKNOWN_CAPABILITIES, capabilities("llama-3"));
+        assert!(!is_known_model("llama-3"));
+        assert!(is_known_model("o3-mini"));
+    }
+}
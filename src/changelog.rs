@@ -0,0 +1,162 @@
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::Path;
+
+use crate::openai::Prompt;
+
+/// How `--changelog` writes the applied suggestions down
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ChangelogStyle {
+    /// Under the `### Changed` section of `## [Unreleased]`, creating them if needed
+    #[value(name = "keepachangelog")]
+    KeepAChangelog,
+    /// As a list appended to a fragment file, like `changelog.d/+senior.changed.md`
+    Towncrier,
+}
+
+pub fn draft_changelog_entry_instructions(original: &str, suggestion: &str) -> Prompt {
+    Prompt {
+        system: r#"Describe the change to the code you are given in a single sentence for a changelog, focusing on its
+effect on performance. Respond only with the sentence."#
+            .to_string(),
+        user: format!(
+            "The following code:\n{}\n\nwas changed into:\n{}",
+            original, suggestion
+        ),
+    }
+}
+
+/// Adds the entries to the `### Changed` list of the `## [Unreleased]` section
+fn add_to_keepachangelog(changelog: &str, entries: &[String]) -> String {
+    let items: Vec<String> = entries.iter().map(|entry| format!("- {}", entry)).collect();
+    let mut lines: Vec<String> = changelog.lines().map(str::to_string).collect();
+
+    let Some(unreleased) = lines
+        .iter()
+        .position(|line| line.trim().eq_ignore_ascii_case("## [unreleased]"))
+    else {
+        if lines.last().is_some_and(|line| !line.trim().is_empty()) {
+            lines.push("".to_string());
+        }
+        lines.extend(["## [Unreleased]", "", "### Changed", ""].map(str::to_string));
+        lines.extend(items);
+
+        return lines.join("\n") + "\n";
+    };
+    let section_end = (unreleased + 1..lines.len())
+        .find(|&index| lines[index].starts_with("## "))
+        .unwrap_or(lines.len());
+    let changed = (unreleased + 1..section_end).find(|&index| lines[index].trim() == "### Changed");
+
+    let (insert_at, new_lines) = match changed {
+        Some(changed) => {
+            let subsection_end = (changed + 1..section_end)
+                .find(|&index| lines[index].starts_with("### "))
+                .unwrap_or(section_end);
+            let last_item = (changed + 1..subsection_end)
+                .rev()
+                .find(|&index| lines[index].starts_with("- ") || lines[index].starts_with("  "));
+            match last_item {
+                Some(last_item) => (last_item + 1, items),
+                None => (changed + 1, [vec!["".to_string()], items].concat()),
+            }
+        }
+        None => (
+            unreleased + 1,
+            [
+                vec!["".to_string(), "### Changed".to_string(), "".to_string()],
+                items,
+            ]
+            .concat(),
+        ),
+    };
+    lines.splice(insert_at..insert_at, new_lines);
+
+    lines.join("\n") + "\n"
+}
+
+/// Writes the entries down in the given style, creating the file if it does not exist
+pub fn write_changelog(
+    path: &Path,
+    style: ChangelogStyle,
+    entries: &[String],
+) -> Result<(), String> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let changelog = read_to_string(path).unwrap_or_default();
+    let updated = match style {
+        ChangelogStyle::KeepAChangelog => add_to_keepachangelog(&changelog, entries),
+        ChangelogStyle::Towncrier => {
+            let items: String = entries
+                .iter()
+                .map(|entry| format!("- {}\n", entry))
+                .collect();
+            format!("{}{}", changelog, items)
+        }
+    };
+
+    write(path, updated).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::changelog::add_to_keepachangelog;
+
+    #[test]
+    fn new_unreleased_section() {
+        assert_eq!(
+            "# Changelog\n\n## [Unreleased]\n\n### Changed\n\n- Faster greetings\n",
+            add_to_keepachangelog("# Changelog\n", &["Faster greetings".to_string()])
+        );
+    }
+
+    #[test]
+    fn existing_changed_list() {
+        let changelog = r#"# Changelog
+
+## [Unreleased]
+
+### Added
+
+- Greetings
+
+### Changed
+
+- Louder greetings
+
+## [0.1.0]
+"#;
+
+        assert_eq!(
+            r#"# Changelog
+
+## [Unreleased]
+
+### Added
+
+- Greetings
+
+### Changed
+
+- Louder greetings
+- Faster greetings
+
+## [0.1.0]
+"#,
+            add_to_keepachangelog(changelog, &["Faster greetings".to_string()])
+        );
+    }
+
+    #[test]
+    fn unreleased_section_without_changed_list() {
+        let changelog = "## [Unreleased]\n\n## [0.1.0]\n";
+
+        assert_eq!(
+            "## [Unreleased]\n\n### Changed\n\n- Faster greetings\n\n## [0.1.0]\n",
+            add_to_keepachangelog(changelog, &["Faster greetings".to_string()])
+        );
+    }
+}
@@ -0,0 +1,209 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+use tree_sitter::Language;
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::helpers::tree_sitter::parse_with_thread_parser;
+use crate::supported_languages::supported_language::SupportedLanguage;
+
+/// The source, with its comments and string literals blanked out, so that symbols are only found
+/// in code
+fn code_only(source: &str, language: Language) -> String {
+    let Some(tree) = parse_with_thread_parser(source, language) else {
+        return source.to_string();
+    };
+    let mut code = source.as_bytes().to_vec();
+    for node in traverse(tree.walk(), Order::Pre) {
+        if node.kind().contains("comment") || node.kind().contains("string") {
+            for byte in &mut code[node.start_byte()..node.end_byte()] {
+                if *byte != b'\n' {
+                    *byte = b' ';
+                }
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&code).to_string()
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// How many times the symbol appears in the code. A symbol starting or ending like an identifier,
+/// like `unsafe`, must not be part of a longer identifier, like `unsafe_cell`.
+fn count_symbol(code: &str, symbol: &str) -> usize {
+    code.match_indices(symbol)
+        .filter(|(start, _)| {
+            let before = code[..*start].chars().next_back();
+            let after = code[start + symbol.len()..].chars().next();
+            let glued_before =
+                symbol.starts_with(is_identifier_char) && before.is_some_and(is_identifier_char);
+            let glued_after =
+                symbol.ends_with(is_identifier_char) && after.is_some_and(is_identifier_char);
+
+            !glued_before && !glued_after
+        })
+        .count()
+}
+
+/// The forbidden symbols the suggestion uses more often than the original code did
+pub fn introduced_symbols<'a>(
+    language: Language,
+    original: &str,
+    suggestion: &str,
+    forbidden_symbols: &'a [String],
+) -> Vec<&'a str> {
+    let original = code_only(original, language);
+    let suggestion = code_only(suggestion, language);
+
+    forbidden_symbols
+        .iter()
+        .filter(|symbol| count_symbol(&suggestion, symbol) > count_symbol(&original, symbol))
+        .map(String::as_str)
+        .collect()
+}
+
+fn count_unsafe_blocks(source: &str, language: Language) -> usize {
+    parse_with_thread_parser(source, language).map_or(0, |tree| {
+        traverse(tree.walk(), Order::Pre)
+            .filter(|node| node.kind() == "unsafe_block")
+            .count()
+    })
+}
+
+/// Whether the suggestion has more `unsafe` blocks than the original code. Only Rust has them.
+pub fn introduces_unsafe_blocks(language: Language, original: &str, suggestion: &str) -> bool {
+    count_unsafe_blocks(suggestion, language) > count_unsafe_blocks(original, language)
+}
+
+/// The contents of the nearest manifest holding the file
+fn find_manifest(file_path: &Path, manifest_name: &str) -> Option<String> {
+    let file_path = file_path
+        .canonicalize()
+        .unwrap_or_else(|_| file_path.to_path_buf());
+
+    file_path
+        .ancestors()
+        .skip(1)
+        .find_map(|directory| read_to_string(directory.join(manifest_name)).ok())
+}
+
+/// The packages the suggestion refers to that the file neither imports nor uses, and that its
+/// manifest does not declare
+pub fn introduced_dependencies(
+    supported_language: &dyn SupportedLanguage,
+    file_path: &Path,
+    source_file: &str,
+    suggestion: &str,
+) -> Vec<String> {
+    let language = supported_language.language();
+    let Some(suggestion_tree) = parse_with_thread_parser(suggestion, language) else {
+        return vec![];
+    };
+    let mut known_packages = supported_language
+        .manifest_name()
+        .and_then(|manifest_name| find_manifest(file_path, manifest_name))
+        .map(|manifest| supported_language.declared_packages(&manifest))
+        .unwrap_or_default();
+    if let Some(tree) = parse_with_thread_parser(source_file, language) {
+        known_packages.extend(supported_language.referenced_packages(source_file, &tree));
+    }
+    let source_code = code_only(source_file, language);
+
+    supported_language
+        .referenced_packages(suggestion, &suggestion_tree)
+        .into_iter()
+        .filter(|package| {
+            !known_packages.contains(package) && count_symbol(&source_code, package) == 0
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+
+    use crate::checks::{introduced_dependencies, introduced_symbols, introduces_unsafe_blocks};
+    use crate::supported_languages::supported_language::detect_language;
+
+    #[test]
+    fn forbidden_symbols() {
+        let forbidden_symbols = vec![
+            "unsafe".to_string(),
+            "reflect.".to_string(),
+            "eval(".to_string(),
+        ];
+
+        let original = "func kind(v any) string {\n\treturn \"unknown\"\n}\n";
+        let suggestion = "func kind(v any) string {\n\t// no eval( here\n\treturn reflect.TypeOf(v).String()\n}\n";
+        assert_eq!(
+            vec!["reflect."],
+            introduced_symbols(
+                tree_sitter_go::language(),
+                original,
+                suggestion,
+                &forbidden_symbols
+            )
+        );
+
+        let original = "fn get(v: &[u8]) -> u8 {\n    unsafe { *v.get_unchecked(0) }\n}\n";
+        let suggestion =
+            "fn get(v: &[u8]) -> u8 {\n    let unsafe_free = \"unsafe\";\n    unsafe { *v.get_unchecked(0) }\n}\n";
+        assert!(introduced_symbols(
+            tree_sitter_rust::language(),
+            original,
+            suggestion,
+            &forbidden_symbols
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn unsafe_blocks() {
+        let language = tree_sitter_rust::language();
+        let original = "fn first(v: &[u8]) -> u8 {\n    // not unsafe { at all }\n    v[0]\n}\n";
+        let suggestion = "fn first(v: &[u8]) -> u8 {\n    unsafe { *v.get_unchecked(0) }\n}\n";
+
+        assert!(introduces_unsafe_blocks(language, original, suggestion));
+        assert!(!introduces_unsafe_blocks(language, suggestion, suggestion));
+    }
+
+    #[test]
+    fn new_dependencies() {
+        let root =
+            std::env::temp_dir().join(format!("senior-dependencies-test-{}", std::process::id()));
+        create_dir_all(root.join("src")).unwrap();
+        write(
+            root.join("Cargo.toml"),
+            "[package]\nname = \"greeter\"\n\n[dependencies]\ntokio-util = \"0.7\"\n",
+        )
+        .unwrap();
+        let file_path = root.join("src/lib.rs");
+
+        let source_file = "use serde::Serialize;\n\nfn greet() {}\n";
+        let suggestion = r#"fn greet() {
+    let names: Vec<String> = Vec::new();
+    names.par_iter().for_each(|name| std::mem::drop(name));
+    let _ = (serde::de::IgnoredAny, tokio_util::sync::CancellationToken::new());
+    itertools::repeat_n(0, 2).for_each(drop);
+    rayon::spawn(|| {});
+    use crate::utils;
+    utils::trim(names);
+    mod helpers {}
+    helpers::run();
+}"#;
+        assert_eq!(
+            vec!["itertools", "rayon"],
+            introduced_dependencies(
+                detect_language("src/lib.rs").unwrap().as_ref(),
+                &file_path,
+                source_file,
+                suggestion
+            )
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,71 @@
+use std::io::IsTerminal;
+
+use clap::ValueEnum;
+use colored::Color;
+
+pub const NO_COLOR_ENV_VAR_KEY: &str = "NO_COLOR";
+
+/// Whether to color the output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+    /// Color the output when it goes to a terminal, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn should_color(self, no_color: Option<&str>) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            // Any value but an empty one asks for no color, see https://no-color.org
+            ColorChoice::Auto => {
+                no_color.is_none_or(str::is_empty) && std::io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+/// The colors of removed, added and moved code in diffs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Palette {
+    #[default]
+    RedGreen,
+    /// Yellow, blue and magenta, which stay apart for most kinds of color blindness
+    HighContrast,
+}
+
+impl Palette {
+    pub fn removed(self) -> Color {
+        match self {
+            Palette::RedGreen => Color::Red,
+            Palette::HighContrast => Color::BrightYellow,
+        }
+    }
+    pub fn added(self) -> Color {
+        match self {
+            Palette::RedGreen => Color::Green,
+            Palette::HighContrast => Color::BrightBlue,
+        }
+    }
+    pub fn moved(self) -> Color {
+        match self {
+            Palette::RedGreen => Color::Cyan,
+            Palette::HighContrast => Color::BrightMagenta,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::ColorChoice;
+
+    #[test]
+    fn color_choice() {
+        assert!(ColorChoice::Always.should_color(Some("1")));
+        assert!(!ColorChoice::Never.should_color(None));
+        assert!(!ColorChoice::Auto.should_color(Some("1")));
+    }
+}
@@ -0,0 +1,231 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+use colored::*;
+use tree_sitter::Node;
+
+use crate::batch::{batch_files, BatchFilters};
+use crate::config::ComplexityConfig;
+use crate::context::name_of;
+use crate::helpers::tree_sitter::parse_with_thread_parser;
+use crate::supported_languages::supported_language::detect_language;
+
+/// Conditionals and loops, each of which nests what it holds one level deeper
+const NESTING_KINDS: &[&str] = &[
+    "if_expression",
+    "if_statement",
+    "match_expression",
+    "switch_statement",
+    "expression_switch_statement",
+    "type_switch_statement",
+    "select_statement",
+    "for_expression",
+    "while_expression",
+    "loop_expression",
+    "for_statement",
+    "for_in_statement",
+    "enhanced_for_statement",
+    "for_range_loop",
+    "while_statement",
+    "do_statement",
+    "try_statement",
+];
+
+// Whether the node is the `if` of an `else if`, which is no deeper than the `if` before it
+fn is_else_if(node: Node) -> bool {
+    node.parent().is_some_and(|parent| {
+        parent.kind() == "else_clause"
+            || (NESTING_KINDS.contains(&parent.kind())
+                && parent.child_by_field_name("alternative") == Some(node))
+    })
+}
+
+/// How deep conditionals and loops nest in the node, counting `else if` chains as one level
+pub fn nesting_depth(node: Node) -> usize {
+    let mut cursor = node.walk();
+    let deepest_child = node
+        .children(&mut cursor)
+        .map(nesting_depth)
+        .max()
+        .unwrap_or_default();
+    let nests = NESTING_KINDS.contains(&node.kind()) && !is_else_if(node);
+
+    deepest_child + usize::from(nests)
+}
+
+/// How the function goes over the budget, if it does
+pub fn over_budget(limits: &ComplexityConfig, function_node: Node) -> Vec<String> {
+    let mut violations = vec![];
+    let lines = function_node.end_position().row - function_node.start_position().row + 1;
+    if let Some(max_function_lines) = limits.max_function_lines.filter(|max| lines > *max) {
+        violations.push(format!(
+            "{} lines, over the {} allowed",
+            lines, max_function_lines
+        ));
+    }
+    let depth = nesting_depth(function_node);
+    if let Some(max_nesting) = limits.max_nesting.filter(|max| depth > *max) {
+        violations.push(format!(
+            "nested {} levels deep, over the {} allowed",
+            depth, max_nesting
+        ));
+    }
+
+    violations
+}
+
+/// The system prompt of `senior fix-complexity`, which splits functions over the budget
+pub fn refactor_system_prompt(limits: &ComplexityConfig) -> String {
+    let mut budget = vec![];
+    if let Some(max_function_lines) = limits.max_function_lines {
+        budget.push(format!("takes up more than {} lines", max_function_lines));
+    }
+    if let Some(max_nesting) = limits.max_nesting {
+        budget.push(format!(
+            "nests conditionals and loops more than {} levels deep",
+            max_nesting
+        ));
+    }
+
+    format!(
+        r#"You are a senior software engineer. Your task is to refactor the code you are given, which is too complex.
+
+Strictly adhere to the following instructions:
+1. Do not change the type signature, nor what the code does.
+2. Split the code into smaller functions, placed right after it, so that none of them {}.
+3. Prefer early returns to nesting, and give the new functions names that say what they do.
+4. Respond only with code, and no commentary or explanations.
+"#,
+        budget.join(" or ")
+    )
+}
+
+/// Reports the functions over the complexity budget without asking the model anything, and
+/// returns how many there are
+pub fn run_complexity_check(
+    path: &Path,
+    changed: bool,
+    no_ignore: bool,
+    limits: &ComplexityConfig,
+) -> Result<usize, String> {
+    if limits.max_function_lines.is_none() && limits.max_nesting.is_none() {
+        return Err(
+            "no complexity budget, set `max_function_lines` or `max_nesting` in the [complexity] section of .senior.toml"
+                .to_string(),
+        );
+    }
+    let filters = BatchFilters {
+        kind: None,
+        skip_tests: false,
+        only_tests: false,
+        min_lines: None,
+        max_lines: None,
+        skip_generated: true,
+        over_budget: None,
+        diagnosed: None,
+    };
+
+    let mut violators = 0;
+    for file_path in batch_files(path, changed, no_ignore)? {
+        let supported_language = detect_language(&file_path)?;
+        let Ok(source_file) = read_to_string(&file_path) else {
+            eprintln!("{}", format!("{}: failed to read it", file_path).yellow());
+            continue;
+        };
+        let Some(tree) = parse_with_thread_parser(&source_file, supported_language.language())
+        else {
+            eprintln!("{}", format!("{}: failed to parse it", file_path).yellow());
+            continue;
+        };
+
+        for target in
+            filters.find_targets(supported_language.as_ref(), &file_path, &source_file, &tree)
+        {
+            let function_node = tree
+                .root_node()
+                .descendant_for_byte_range(target.start_byte, target.end_byte)
+                .unwrap();
+            let violations = over_budget(limits, function_node);
+            if violations.is_empty() {
+                continue;
+            }
+            violators += 1;
+            println!(
+                "{} {}: {}",
+                format!("{}:{}", file_path, target.start_point.row + 1).bold(),
+                name_of(&source_file, function_node).unwrap_or("(anonymous)"),
+                violations.join(", ").red()
+            );
+        }
+    }
+    if violators == 0 {
+        println!(
+            "{}",
+            "every function is within the complexity budget.".green()
+        );
+    }
+
+    Ok(violators)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::complexity::{nesting_depth, over_budget};
+    use crate::config::ComplexityConfig;
+    use crate::helpers::tree_sitter::parse_source_with_language;
+
+    #[test]
+    fn rust_nesting() {
+        let source = r#"fn classify(values: &[i32]) -> Vec<&'static str> {
+    let mut labels = vec![];
+    for value in values {
+        if *value < 0 {
+            labels.push("negative");
+        } else if *value == 0 {
+            labels.push("zero");
+        } else {
+            match value % 2 {
+                0 => labels.push("even"),
+                _ => labels.push("odd"),
+            }
+        }
+    }
+    labels
+}"#;
+        let tree = parse_source_with_language(source, tree_sitter_rust::language());
+        let function_node = tree.root_node().named_child(0).unwrap();
+
+        assert_eq!(3, nesting_depth(function_node));
+        let limits = ComplexityConfig {
+            max_function_lines: Some(10),
+            max_nesting: Some(2),
+        };
+        assert_eq!(
+            vec![
+                "16 lines, over the 10 allowed",
+                "nested 3 levels deep, over the 2 allowed"
+            ],
+            over_budget(&limits, function_node)
+        );
+        assert!(over_budget(&ComplexityConfig::default(), function_node).is_empty());
+    }
+
+    #[test]
+    fn go_nesting() {
+        let source = r#"func find(grid [][]int, target int) bool {
+	for _, row := range grid {
+		for _, cell := range row {
+			if cell == target {
+				return true
+			} else if cell > target {
+				break
+			}
+		}
+	}
+	return false
+}"#;
+        let tree = parse_source_with_language(source, tree_sitter_go::language());
+
+        assert_eq!(3, nesting_depth(tree.root_node()));
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use serde::Deserialize;
+
+pub const CONFIG_FILE_PATH: &str = ".senior.toml";
+pub const CONSTRAINTS_FILE_PATH: &str = ".senior/constraints.md";
+/// Where the policy of the organisation is read from, unless the env var points elsewhere
+pub const POLICY_FILE_ENV_VAR_KEY: &str = "SENIOR_POLICY_FILE";
+const DEFAULT_POLICY_FILE_PATH: &str = "/etc/senior/policy.toml";
+
+/// Settings read from `.senior.toml`. Every section and field is optional.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Symbols, like `unsafe` or `eval(`, that suggestions may not introduce
+    pub forbidden_symbols: Vec<String>,
+    /// Replace the advice given to the model for a language, keyed by language, like `rust`. An
+    /// empty string leaves it out.
+    pub instructions: HashMap<String, String>,
+    /// Commands run after every applied suggestion, like `cargo fmt`. `{file}`, `{function}` and
+    /// `{language}` in them are replaced by those of the suggestion.
+    pub post_apply: Vec<String>,
+    /// Replace the comment `--provenance-comment` leaves after applied suggestions, keyed by
+    /// language. `{model}` and `{date}` in them are replaced by those of the suggestion.
+    pub provenance_comment: HashMap<String, String>,
+    pub policy: Option<PolicyConfig>,
+    pub nightly: NightlyConfig,
+    pub complexity: ComplexityConfig,
+}
+
+/// The complexity budget of every function, checked by `senior check`
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ComplexityConfig {
+    /// How many lines a function may take up
+    pub max_function_lines: Option<usize>,
+    /// How deep conditionals and loops may nest in a function
+    pub max_nesting: Option<usize>,
+}
+
+/// What an organisation requires of every run, set in `.senior.toml` or in the policy file
+#[derive(Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicyConfig {
+    /// Every suggestion is reviewed by a person: `--skip-prompt` and `--auto-apply` are refused,
+    /// and who applied or rejected each suggestion is recorded in the history, along with when
+    pub compliance: bool,
+    /// Only honoured in the policy file. Repositories cannot override a locked policy.
+    pub locked: bool,
+}
+
+/// The policy file of the organisation holds a `[policy]` section alone
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct PolicyFile {
+    policy: PolicyConfig,
+}
+
+/// Settings of `senior nightly`.
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct NightlyConfig {
+    /// File or directory to go through
+    pub path: String,
+    pub model: Option<String>,
+    pub extra_context: Option<String>,
+    pub skip_tests: bool,
+    pub min_lines: Option<usize>,
+    pub max_lines: Option<usize>,
+    /// Also go through the files matched by `.gitignore`, `.ignore` and similar files
+    pub no_ignore: bool,
+    /// Also go through generated files, which are skipped by default
+    pub include_generated: bool,
+    /// Where the markdown report is written to
+    pub report: String,
+    /// Slack incoming webhook that receives a summary of the report
+    pub slack_webhook: Option<String>,
+}
+
+impl Default for NightlyConfig {
+    fn default() -> Self {
+        Self {
+            path: ".".to_string(),
+            model: None,
+            extra_context: None,
+            skip_tests: true,
+            min_lines: None,
+            max_lines: None,
+            no_ignore: false,
+            include_generated: false,
+            report: ".senior/nightly-report.md".to_string(),
+            slack_webhook: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let contents = read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+
+        toml::from_str(&contents).map_err(|e| format!("failed to parse {}: {}", path.display(), e))
+    }
+}
+
+/// The policy of the organisation, from the file `SENIOR_POLICY_FILE` points to, or from
+/// `/etc/senior/policy.toml`. Without one there is none.
+pub fn load_org_policy() -> Result<Option<PolicyConfig>, String> {
+    let path = std::env::var(POLICY_FILE_ENV_VAR_KEY)
+        .unwrap_or_else(|_| DEFAULT_POLICY_FILE_PATH.to_string());
+    if !Path::new(&path).exists() {
+        return Ok(None);
+    }
+    let contents = read_to_string(&path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+    let policy_file: PolicyFile =
+        toml::from_str(&contents).map_err(|e| format!("failed to parse {}: {}", path, e))?;
+
+    Ok(Some(policy_file.policy))
+}
+
+/// The policy in force: the one of the organisation if it is locked, otherwise the one of the
+/// repository, if it has one
+pub fn effective_policy(
+    org_policy: Option<PolicyConfig>,
+    repo_policy: Option<PolicyConfig>,
+) -> PolicyConfig {
+    match (org_policy, repo_policy) {
+        (Some(org_policy), _) if org_policy.locked => org_policy,
+        (_, Some(repo_policy)) => repo_policy,
+        (org_policy, None) => org_policy.unwrap_or_default(),
+    }
+}
+
+/// The hard constraints of the repository, like "must stay no_std", that every suggestion must
+/// respect. Without a constraints file there are none.
+pub fn load_constraints(path: &Path) -> String {
+    match read_to_string(path) {
+        Ok(constraints) if !constraints.trim().is_empty() => format!(
+            "These constraints of the project must hold, whatever the cost:\n{}",
+            constraints.trim()
+        ),
+        _ => "".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::{effective_policy, load_constraints, Config, PolicyConfig};
+
+    #[test]
+    fn defaults() {
+        let config: Config = toml::from_str("").unwrap();
+
+        assert_eq!(".", config.nightly.path);
+        assert!(config.nightly.skip_tests);
+        assert_eq!(None, config.nightly.slack_webhook);
+        assert!(config.forbidden_symbols.is_empty());
+        assert!(config.post_apply.is_empty());
+    }
+
+    #[test]
+    fn nightly_section() {
+        let config: Config = toml::from_str(
+            r#"[nightly]
+path = "src"
+min_lines = 5
+slack_webhook = "https://hooks.slack.com/services/T000/B000/XXXX"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!("src", config.nightly.path);
+        assert_eq!(Some(5), config.nightly.min_lines);
+        assert!(config.nightly.slack_webhook.is_some());
+    }
+
+    #[test]
+    fn instructions_section() {
+        let config: Config =
+            toml::from_str("[instructions]\nrust = \"Avoid recursion.\"\ngo = \"\"\n").unwrap();
+
+        assert_eq!("Avoid recursion.", config.instructions["rust"]);
+        assert_eq!("", config.instructions["go"]);
+    }
+
+    #[test]
+    fn complexity_section() {
+        let config: Config =
+            toml::from_str("[complexity]\nmax_function_lines = 80\nmax_nesting = 4\n").unwrap();
+
+        assert_eq!(Some(80), config.complexity.max_function_lines);
+        assert_eq!(Some(4), config.complexity.max_nesting);
+        assert_eq!(None, Config::default().complexity.max_nesting);
+    }
+
+    #[test]
+    fn policies() {
+        let config: Config = toml::from_str("[policy]\ncompliance = false\n").unwrap();
+        let org_policy = PolicyConfig {
+            compliance: true,
+            locked: false,
+        };
+
+        assert_eq!(
+            config.policy,
+            Some(effective_policy(Some(org_policy), config.policy))
+        );
+        assert!(effective_policy(Some(org_policy), None).compliance);
+        let locked = PolicyConfig {
+            locked: true,
+            ..org_policy
+        };
+        assert!(effective_policy(Some(locked), config.policy).compliance);
+        assert!(!effective_policy(None, None).compliance);
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        assert!(toml::from_str::<Config>("[nightly]\npaht = \"src\"\n").is_err());
+    }
+
+    #[test]
+    fn constraints() {
+        let path = std::env::temp_dir().join("senior-constraints-test.md");
+        std::fs::write(
+            &path,
+            "- we target wasm32, no threads\n- must stay no_std\n",
+        )
+        .unwrap();
+
+        assert!(load_constraints(&path)
+            .ends_with(":\n- we target wasm32, no threads\n- must stay no_std"));
+        std::fs::write(&path, "\n").unwrap();
+        assert_eq!("", load_constraints(&path));
+        assert_eq!("", load_constraints(&path.with_extension("missing")));
+    }
+}
@@ -0,0 +1,419 @@
+use std::collections::HashSet;
+
+use tree_sitter::{Node, Tree};
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::helpers::tree_sitter::node_value;
+use crate::supported_languages::supported_language::SupportedLanguage;
+
+/// How many tokens the function and its context may take up in the prompt, unless told otherwise
+pub const DEFAULT_TOKEN_BUDGET: usize = 4096;
+
+const BODY_KINDS: &[&str] = &[
+    "block",
+    "statement_block",
+    // Java
+    "constructor_body",
+    // C++
+    "compound_statement",
+];
+
+/// The calls of functions, which Java calls invocations of methods
+const CALL_KINDS: &[&str] = &["call_expression", "method_invocation"];
+
+const TYPE_DEFINITION_KINDS: &[&str] = &[
+    // Rust
+    "struct_item",
+    "enum_item",
+    "union_item",
+    "type_item",
+    "trait_item",
+    // Go
+    "type_spec",
+    // Javascript, Typescript and Java
+    "class_declaration",
+    "interface_declaration",
+    "type_alias_declaration",
+    "enum_declaration",
+    // Java
+    "record_declaration",
+    // C++
+    "class_specifier",
+    "struct_specifier",
+    "union_specifier",
+    "enum_specifier",
+];
+
+pub const IMPORT_KINDS: &[&str] = &[
+    // Rust
+    "use_declaration",
+    "extern_crate_declaration",
+    // Go and Java
+    "import_declaration",
+    // Javascript and Typescript
+    "import_statement",
+    // C++
+    "preproc_include",
+    "using_declaration",
+];
+
+/// A rough count of the tokens of the text, as models average about four characters per token
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+/// The name of a function or type definition, which for javascript variables and object keys lies
+/// in the declarator or in the key, and for C++ functions deep in their declarator
+pub fn name_of<'a>(source_file: &'a str, node: Node<'a>) -> Option<&'a str> {
+    let name_node = node
+        .child_by_field_name("name")
+        .or_else(|| node.child_by_field_name("key"))
+        .or_else(|| match node.kind() {
+            "function_definition" => declared_name(node),
+            // Solidity names these by their keyword
+            "constructor_definition" | "fallback_receive_definition" => node.child(0),
+            _ => node.named_child(0)?.child_by_field_name("name"),
+        })?;
+
+    Some(node_value(source_file, name_node))
+}
+
+/// The name a C++ function is declared by, under the pointers, references and scopes of its
+/// declarator, like `bar` in `int *Foo::bar(int x)`
+pub fn declared_name(function_node: Node) -> Option<Node> {
+    let mut declarator = function_node.child_by_field_name("declarator")?;
+    loop {
+        declarator = match declarator.kind() {
+            "qualified_identifier" | "template_function" => {
+                declarator.child_by_field_name("name")?
+            }
+            "reference_declarator" => declarator.named_child(0)?,
+            _ => match declarator.child_by_field_name("declarator") {
+                Some(inner) => inner,
+                None => return Some(declarator),
+            },
+        };
+    }
+}
+
+/// The block holding the statements of the function, which for javascript variables lies in their
+/// value. Functions whose body is a single expression have none.
+pub fn function_body(function_node: Node) -> Option<Node> {
+    function_node.parent()?;
+
+    traverse(function_node.walk(), Order::Pre)
+        .find_map(|node| node.child_by_field_name("body"))
+        .filter(|body| BODY_KINDS.contains(&body.kind()))
+}
+
+/// Everything up to the body of the function, or its first line if it has none
+pub fn signature<'a>(source_file: &'a str, function_node: Node<'a>) -> &'a str {
+    let body = traverse(function_node.walk(), Order::Pre)
+        .skip(1)
+        .find(|node| BODY_KINDS.contains(&node.kind()));
+    let end_byte = match body {
+        Some(body) => body.start_byte(),
+        None => node_value(source_file, function_node)
+            .find('\n')
+            .map_or(function_node.end_byte(), |end_of_line| {
+                function_node.start_byte() + end_of_line
+            }),
+    };
+
+    source_file[function_node.start_byte()..end_byte].trim_end()
+}
+
+/// The name of the function a call expression calls
+pub fn callee_name<'a>(source_file: &'a str, call: Node<'a>) -> Option<&'a str> {
+    if call.kind() == "method_invocation" {
+        return Some(node_value(source_file, call.child_by_field_name("name")?));
+    }
+    let function = call.child_by_field_name("function")?;
+    // Only the last element of paths like `self.parse` or `lexer::tokenize` names it
+    let callee = ["name", "field", "property"]
+        .iter()
+        .find_map(|field| function.child_by_field_name(field))
+        .unwrap_or(function);
+
+    Some(node_value(source_file, callee))
+}
+
+pub fn contains(outer: Node, inner: Node) -> bool {
+    outer.start_byte() <= inner.start_byte() && inner.end_byte() <= outer.end_byte()
+}
+
+/// What a section of the context is, from the highest priority to the lowest
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SectionKind {
+    Signature,
+    TypeDefinition,
+    Import,
+}
+
+impl SectionKind {
+    fn describe(&self) -> &'static str {
+        match self {
+            SectionKind::Signature => "signature of a called function",
+            SectionKind::TypeDefinition => "type definition",
+            SectionKind::Import => "import",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContextSection {
+    pub kind: SectionKind,
+    pub code: String,
+}
+
+/// The context packed for the function, along with what did not fit in the budget
+#[derive(Default, Debug, PartialEq)]
+pub struct PackedContext {
+    pub packed: String,
+    /// The sections left out, by priority
+    pub dropped: Vec<ContextSection>,
+    /// How many tokens the function and the packed sections take up
+    pub tokens: usize,
+}
+
+impl PackedContext {
+    /// What was left out to fit in the budget, a line per section, or nothing if all of it fit
+    pub fn render_overflow(&self, token_budget: usize) -> Option<String> {
+        if self.dropped.is_empty() {
+            return None;
+        }
+        let mut overflow = format!(
+            "the context was trimmed to fit in {} tokens, leaving out:",
+            token_budget
+        );
+        for section in &self.dropped {
+            overflow.push_str(&format!(
+                "\n  {}: {}",
+                section.kind.describe(),
+                section.code.lines().next().unwrap_or_default().trim()
+            ));
+        }
+        overflow.push_str(&format!(
+            "\nthe function and its context take up about {} tokens.",
+            self.tokens
+        ));
+
+        Some(overflow)
+    }
+}
+
+/// The parts of the file that help understand the function, by priority: the signatures of the
+/// functions it calls, the definitions of the types it names, and then the imports
+fn context_sections(
+    supported_language: &dyn SupportedLanguage,
+    source_file: &str,
+    tree: &Tree,
+    function_node: Node,
+) -> Vec<ContextSection> {
+    let mut callees: HashSet<&str> = HashSet::new();
+    let mut names: HashSet<&str> = HashSet::new();
+    for node in traverse(function_node.walk(), Order::Pre) {
+        if CALL_KINDS.contains(&node.kind()) {
+            if let Some(callee) = callee_name(source_file, node) {
+                callees.insert(callee);
+            }
+        } else if node.child_count() == 0 && node.kind().ends_with("identifier") {
+            names.insert(node_value(source_file, node));
+        }
+    }
+
+    let signatures = supported_language
+        .find_all_functions(tree)
+        .into_iter()
+        .filter(|callee| !contains(*callee, function_node) && !contains(function_node, *callee))
+        .filter(|callee| name_of(source_file, *callee).is_some_and(|name| callees.contains(name)))
+        .map(|callee| ContextSection {
+            kind: SectionKind::Signature,
+            code: signature(source_file, callee).to_string(),
+        });
+    let type_definitions = traverse(tree.walk(), Order::Pre)
+        .filter(|node| TYPE_DEFINITION_KINDS.contains(&node.kind()))
+        .filter(|definition| !contains(*definition, function_node))
+        .filter(|definition| {
+            name_of(source_file, *definition).is_some_and(|name| names.contains(name))
+        })
+        .map(|definition| {
+            // Go names the type in a spec, whose declaration holds the `type` keyword
+            let definition = match definition.parent() {
+                Some(parent) if parent.kind() == "type_declaration" => parent,
+                _ => definition,
+            };
+            ContextSection {
+                kind: SectionKind::TypeDefinition,
+                code: node_value(source_file, definition).to_string(),
+            }
+        });
+    let imports = traverse(tree.walk(), Order::Pre)
+        .filter(|node| IMPORT_KINDS.contains(&node.kind()))
+        .map(|import| ContextSection {
+            kind: SectionKind::Import,
+            code: node_value(source_file, import).to_string(),
+        });
+
+    let mut sections: Vec<ContextSection> = vec![];
+    for section in signatures.chain(type_definitions).chain(imports) {
+        if !sections.iter().any(|packed| packed.code == section.code) {
+            sections.push(section);
+        }
+    }
+
+    sections
+}
+
+/// Packs as much of the context of the function as fits in the budget, along with the function.
+/// Sections are added by priority, so the lowest priority ones are the first left out.
+pub fn pack_context(
+    supported_language: &dyn SupportedLanguage,
+    source_file: &str,
+    tree: &Tree,
+    function_node: Node,
+    token_budget: usize,
+) -> PackedContext {
+    if function_node == tree.root_node() {
+        return PackedContext::default();
+    }
+
+    let mut tokens = estimate_tokens(node_value(source_file, function_node));
+    let mut packed_sections = vec![];
+    let mut dropped = vec![];
+    for section in context_sections(supported_language, source_file, tree, function_node) {
+        let section_tokens = estimate_tokens(&section.code);
+        // Once a section is left out, so are all the lower priority ones
+        if !dropped.is_empty() || tokens + section_tokens > token_budget {
+            dropped.push(section);
+            continue;
+        }
+        tokens += section_tokens;
+        packed_sections.push(section.code);
+    }
+
+    PackedContext {
+        packed: packed_sections.join("\n\n"),
+        dropped,
+        tokens,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::context::{estimate_tokens, function_body, pack_context, SectionKind};
+    use crate::helpers::tree_sitter::parse_source_with_language;
+    use crate::supported_languages::supported_language::detect_language;
+
+    const RUST_SOURCE: &str = r#"use std::collections::HashMap;
+
+struct Counts {
+    words: HashMap<String, usize>,
+}
+
+fn tokenize(text: &str) -> Vec<&str> {
+    text.split(' ').collect()
+}
+
+fn unrelated() {}
+
+fn count(text: &str) -> Counts {
+    let mut counts = Counts { words: HashMap::new() };
+    for word in tokenize(text) {
+        *counts.words.entry(word.to_string()).or_default() += 1;
+    }
+    counts
+}
+"#;
+
+    #[test]
+    fn context_by_priority() {
+        let supported_language = detect_language("src/lib.rs").unwrap();
+        let tree = parse_source_with_language(RUST_SOURCE, tree_sitter_rust::language());
+        let function_node = supported_language
+            .find_correct_node(RUST_SOURCE, &tree, &None, &Some("count".to_string()), &None)
+            .unwrap();
+        let pack = |token_budget| {
+            pack_context(
+                supported_language.as_ref(),
+                RUST_SOURCE,
+                &tree,
+                function_node,
+                token_budget,
+            )
+            .packed
+        };
+
+        assert_eq!(
+            "fn tokenize(text: &str) -> Vec<&str>\n\nstruct Counts {\n    words: HashMap<String, usize>,\n}\n\nuse std::collections::HashMap;",
+            pack(4096)
+        );
+        let function_tokens = estimate_tokens(&RUST_SOURCE[function_node.byte_range()]);
+        assert_eq!(
+            "fn tokenize(text: &str) -> Vec<&str>",
+            pack(function_tokens + 10)
+        );
+        assert_eq!("", pack(0));
+        assert_eq!(
+            "",
+            pack_context(
+                supported_language.as_ref(),
+                RUST_SOURCE,
+                &tree,
+                tree.root_node(),
+                4096
+            )
+            .packed
+        );
+
+        let trimmed = pack_context(
+            supported_language.as_ref(),
+            RUST_SOURCE,
+            &tree,
+            function_node,
+            function_tokens + 10,
+        );
+        assert_eq!(
+            vec![SectionKind::TypeDefinition, SectionKind::Import],
+            trimmed
+                .dropped
+                .iter()
+                .map(|section| section.kind)
+                .collect::<Vec<SectionKind>>()
+        );
+        assert_eq!(
+            format!(
+                "the context was trimmed to fit in {} tokens, leaving out:\n  type definition: struct Counts {{\n  import: use std::collections::HashMap;\nthe function and its context take up about {} tokens.",
+                function_tokens + 10,
+                trimmed.tokens
+            ),
+            trimmed.render_overflow(function_tokens + 10).unwrap()
+        );
+        assert_eq!(
+            None,
+            pack_context(
+                supported_language.as_ref(),
+                RUST_SOURCE,
+                &tree,
+                function_node,
+                4096
+            )
+            .render_overflow(4096)
+        );
+    }
+
+    #[test]
+    fn function_bodies() {
+        let source = "const greet = (name) => {\n    return `hello ${name}`;\n};\nconst shout = (name) => name.toUpperCase();\n";
+        let tree = parse_source_with_language(source, tree_sitter_javascript::language());
+        let root_node = tree.root_node();
+        let body = function_body(root_node.named_child(0).unwrap()).unwrap();
+
+        assert_eq!(
+            "{\n    return `hello ${name}`;\n}",
+            &source[body.byte_range()]
+        );
+        assert_eq!(None, function_body(root_node.named_child(1).unwrap()));
+        assert_eq!(None, function_body(root_node));
+    }
+}
@@ -0,0 +1,85 @@
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::{Path, PathBuf};
+
+use crate::openai::{Message, Prompt};
+
+/// The messages exchanged about one target over several invocations, so that the model remembers
+/// earlier constraints and rejected attempts
+pub struct Conversation {
+    path: PathBuf,
+    pub messages: Vec<Message>,
+}
+
+impl Conversation {
+    /// Loads the conversation stored at the path, starting a new one if there is none
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let messages = match read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| format!("failed to parse {}: {}", path.display(), e))?,
+            Err(_) => vec![],
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            messages,
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let contents = serde_json::to_string_pretty(&self.messages).map_err(|e| e.to_string())?;
+
+        write(&self.path, contents).map_err(|e| e.to_string())
+    }
+
+    /// Adds the prompt of a new invocation. Only new conversations get its system prompt, ongoing
+    /// ones just get the new request.
+    pub fn ask(&mut self, prompt: &Prompt) {
+        if self.messages.is_empty() {
+            self.messages.push(Message::new("system", &prompt.system));
+        }
+        self.messages.push(Message::new("user", &prompt.user));
+    }
+
+    pub fn answer(&mut self, answer: &str) {
+        self.messages.push(Message::new("assistant", answer));
+    }
+
+    /// Tells the model what became of its last answer
+    pub fn reply(&mut self, reply: &str) {
+        self.messages.push(Message::new("user", reply));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::conversation::Conversation;
+    use crate::openai::Prompt;
+
+    #[test]
+    fn continues_where_it_left_off() {
+        let path = std::env::temp_dir().join("senior-conversation-test/greet.json");
+        let _ = std::fs::remove_file(&path);
+
+        let prompt = Prompt {
+            system: "optimise".to_string(),
+            user: "fn greet() {}".to_string(),
+        };
+        let mut conversation = Conversation::load(&path).unwrap();
+        conversation.ask(&prompt);
+        conversation.answer("fn greet() { }");
+        conversation.reply("I rejected this suggestion.");
+        conversation.save().unwrap();
+
+        let mut conversation = Conversation::load(&path).unwrap();
+        conversation.ask(&prompt);
+        let roles: Vec<&str> = conversation
+            .messages
+            .iter()
+            .map(|message| message.role.as_str())
+            .collect();
+        assert_eq!(vec!["system", "user", "assistant", "user", "user"], roles);
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::{Component, Path};
+
+/// How many times each line of each file of a coverage report ran, by the path the report gives
+#[derive(Debug, Default)]
+pub struct Coverage {
+    files: HashMap<String, HashMap<usize, usize>>,
+}
+
+impl Coverage {
+    /// Reads an lcov report, like `lcov.info`, or a Go cover profile, like `coverage.out`, told
+    /// apart by the `mode:` line Go profiles start with
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let report = read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+
+        if report.starts_with("mode:") {
+            Self::parse_go_profile(&report)
+        } else {
+            Self::parse_lcov(&report)
+        }
+        .map_err(|e| format!("failed to parse {}: {}", path.display(), e))
+    }
+
+    fn parse_lcov(report: &str) -> Result<Self, String> {
+        let mut coverage = Self::default();
+        let mut current_file = None;
+        for line in report.lines().map(str::trim) {
+            if let Some(file) = line.strip_prefix("SF:") {
+                current_file = Some(file.to_string());
+            } else if let Some(hits) = line.strip_prefix("DA:") {
+                let file = current_file.as_ref().ok_or("DA: before any SF:")?;
+                let malformed = || format!("malformed line `{}`", line);
+                let mut fields = hits.split(',');
+                let (Some(line_number), Some(hits)) = (fields.next(), fields.next()) else {
+                    return Err(malformed());
+                };
+                let line_number: usize = line_number.parse().map_err(|_| malformed())?;
+                let hits: usize = hits.parse().map_err(|_| malformed())?;
+                *coverage
+                    .files
+                    .entry(file.clone())
+                    .or_default()
+                    .entry(line_number)
+                    .or_default() += hits;
+            } else if line == "end_of_record" {
+                current_file = None;
+            }
+        }
+
+        Ok(coverage)
+    }
+
+    // Blocks look like `example.com/shop/cart.go:12.34,15.2 3 1`: the file, where the block starts
+    // and ends, how many statements it holds and how many times it ran
+    fn parse_go_profile(report: &str) -> Result<Self, String> {
+        let mut coverage = Self::default();
+        for block in report
+            .lines()
+            .skip(1)
+            .filter(|line| !line.trim().is_empty())
+        {
+            let malformed = || format!("malformed block `{}`", block);
+            let (file, rest) = block.rsplit_once(':').ok_or_else(malformed)?;
+            let mut fields = rest.split_whitespace();
+            let (Some(range), Some(_), Some(count)) = (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(malformed());
+            };
+            let (start, end) = range.split_once(',').ok_or_else(malformed)?;
+            let line_of = |position: &str| -> Result<usize, String> {
+                position
+                    .split('.')
+                    .next()
+                    .and_then(|line| line.parse().ok())
+                    .ok_or_else(malformed)
+            };
+            let count: usize = count.parse().map_err(|_| malformed())?;
+            let lines = coverage.files.entry(file.to_string()).or_default();
+            for line in line_of(start)?..=line_of(end)? {
+                *lines.entry(line).or_default() += count;
+            }
+        }
+
+        Ok(coverage)
+    }
+
+    /// The lines of the file in the report, where its path may be relative to another directory,
+    /// or a Go import path
+    fn lines_of(&self, file_path: &str) -> Option<&HashMap<usize, usize>> {
+        self.files
+            .iter()
+            .find(|(report_path, _)| same_file(report_path, file_path))
+            .map(|(_, lines)| lines)
+    }
+
+    /// Whether tests ran any line of the file between those two, both counted from 1. Files the
+    /// report does not mention were not run at all.
+    pub fn is_covered(&self, file_path: &str, start_line: usize, end_line: usize) -> bool {
+        self.lines_of(file_path).is_some_and(|lines| {
+            (start_line..=end_line).any(|line| lines.get(&line).is_some_and(|hits| *hits > 0))
+        })
+    }
+}
+
+/// Whether a path given by a tool report, which may be absolute, relative to another directory or
+/// a Go import path, is the file
+pub fn same_file(report_path: &str, file_path: &str) -> bool {
+    let report_components = components(report_path);
+    let file_components = components(file_path);
+
+    report_components.ends_with(&file_components) || file_components.ends_with(&report_components)
+}
+
+// Reports written on Windows separate the components with backslashes, which are only separators
+// there
+fn components(path: &str) -> Vec<String> {
+    Path::new(&path.replace('\\', "/"))
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::coverage::{same_file, Coverage};
+
+    #[test]
+    fn lcov_reports() {
+        let coverage = Coverage::parse_lcov(
+            "TN:\nSF:/home/ci/shop/src/cart.rs\nDA:1,0\nDA:2,0\nDA:5,3\nDA:6,3\nend_of_record\n",
+        )
+        .unwrap();
+
+        assert!(!coverage.is_covered("src/cart.rs", 1, 3));
+        assert!(coverage.is_covered("src/cart.rs", 4, 6));
+        assert!(coverage.is_covered("./src/cart.rs", 6, 9));
+        assert!(!coverage.is_covered("src/prices.rs", 1, 100));
+        assert!(Coverage::parse_lcov("DA:1,1\n").is_err());
+        assert!(same_file("C:\\ci\\shop\\src\\cart.rs", "./src/cart.rs"));
+    }
+
+    #[test]
+    fn go_profiles() {
+        let coverage = Coverage::parse_go_profile(
+            "mode: set\nexample.com/shop/cart/cart.go:3.30,5.2 1 1\nexample.com/shop/cart/cart.go:7.25,9.2 1 0\n",
+        )
+        .unwrap();
+
+        assert!(coverage.is_covered("cart/cart.go", 3, 5));
+        assert!(!coverage.is_covered("cart/cart.go", 7, 9));
+        assert!(Coverage::parse_go_profile("mode: set\ncart.go 1 1\n").is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::coverage::same_file;
+
+/// What the model is told about the diagnostics of the code
+pub const DIAGNOSTICS_HEADING: &str = "These were reported about the code, fix them too:";
+
+/// A lint reported by clippy, ESLint or golangci-lint, or an error reported by a build
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub file: String,
+    /// The lines it spans, counted from 1
+    pub start_line: usize,
+    pub end_line: usize,
+    pub rule: Option<String>,
+    pub message: String,
+}
+
+fn as_line(value: &Value) -> Option<usize> {
+    value.as_u64().map(|line| line as usize)
+}
+
+fn as_string(value: &Value) -> Option<String> {
+    value.as_str().map(str::to_string)
+}
+
+// `cargo clippy --message-format=json` writes a JSON object per line, the lints being the compiler
+// messages, located by their primary span
+fn parse_clippy(output: &str) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|message| message["reason"] == "compiler-message")
+        .filter_map(|message| {
+            let message = &message["message"];
+            let span = message["spans"]
+                .as_array()?
+                .iter()
+                .find(|span| span["is_primary"] == true)?;
+
+            Some(Diagnostic {
+                file: as_string(&span["file_name"])?,
+                start_line: as_line(&span["line_start"])?,
+                end_line: as_line(&span["line_end"])?,
+                rule: as_string(&message["code"]["code"]),
+                message: as_string(&message["message"])?,
+            })
+        })
+        .collect()
+}
+
+// `eslint -f json` writes an array of files, each with its messages
+fn parse_eslint(files: &Value) -> Vec<Diagnostic> {
+    files
+        .as_array()
+        .into_iter()
+        .flatten()
+        .flat_map(|file| {
+            let file_path = as_string(&file["filePath"]).unwrap_or_default();
+            file["messages"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(move |message| {
+                    let start_line = as_line(&message["line"])?;
+                    Some(Diagnostic {
+                        file: file_path.clone(),
+                        start_line,
+                        end_line: as_line(&message["endLine"]).unwrap_or(start_line),
+                        rule: as_string(&message["ruleId"]),
+                        message: as_string(&message["message"])?,
+                    })
+                })
+        })
+        .collect()
+}
+
+// `golangci-lint run --out-format json` writes an object whose `Issues` are the lints
+fn parse_golangci(report: &Value) -> Vec<Diagnostic> {
+    report["Issues"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|issue| {
+            let line = as_line(&issue["Pos"]["Line"])?;
+            Some(Diagnostic {
+                file: as_string(&issue["Pos"]["Filename"])?,
+                start_line: line,
+                end_line: line,
+                rule: as_string(&issue["FromLinter"]),
+                message: as_string(&issue["Text"])?,
+            })
+        })
+        .collect()
+}
+
+/// The diagnostics of a clippy, ESLint or golangci-lint JSON output, told apart by their shape
+pub fn load_diagnostics(path: &Path) -> Result<Vec<Diagnostic>, String> {
+    let output =
+        read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+
+    Ok(match serde_json::from_str::<Value>(&output) {
+        Ok(files) if files.is_array() => parse_eslint(&files),
+        Ok(report) if report.get("Issues").is_some() => parse_golangci(&report),
+        _ => parse_clippy(&output),
+    })
+}
+
+/// The diagnostics of the file between those lines, as a markdown list that quotes the line each
+/// starts on, since the model does not see line numbers
+pub fn render_diagnostics(
+    diagnostics: &[Diagnostic],
+    file_path: &str,
+    source_file: &str,
+    start_line: usize,
+    end_line: usize,
+) -> String {
+    diagnostics
+        .iter()
+        .filter(|diagnostic| {
+            start_line <= diagnostic.start_line
+                && diagnostic.end_line <= end_line
+                && same_file(&diagnostic.file, file_path)
+        })
+        .map(|diagnostic| {
+            let line = source_file
+                .lines()
+                .nth(diagnostic.start_line - 1)
+                .unwrap_or_default()
+                .trim();
+            let rule = diagnostic
+                .rule
+                .as_ref()
+                .map_or("".to_string(), |rule| format!(" ({})", rule));
+            format!("- `{}`: {}{}", line, diagnostic.message, rule)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::diagnostics::{parse_clippy, parse_eslint, parse_golangci, render_diagnostics};
+
+    #[test]
+    fn diagnostics_of_each_linter() {
+        let clippy = [
+            json!({"reason": "compiler-artifact"}).to_string(),
+            json!({"reason": "compiler-message", "message": {
+                "message": "redundant clone",
+                "code": {"code": "clippy::redundant_clone"},
+                "spans": [{"file_name": "src/cart.rs", "line_start": 3, "line_end": 3, "is_primary": true}]
+            }})
+            .to_string(),
+        ]
+        .join("\n");
+        let diagnostics = parse_clippy(&clippy);
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(
+            Some("clippy::redundant_clone"),
+            diagnostics[0].rule.as_deref()
+        );
+
+        let eslint = json!([{"filePath": "/home/ci/shop/src/cart.js", "messages": [
+            {"ruleId": "no-unused-vars", "message": "'x' is unused.", "line": 2, "endLine": 2}
+        ]}]);
+        assert_eq!(2, parse_eslint(&eslint)[0].start_line);
+
+        let golangci = json!({"Issues": [
+            {"FromLinter": "ineffassign", "Text": "ineffectual assignment to err", "Pos": {"Filename": "cart/cart.go", "Line": 7}}
+        ]});
+        assert_eq!("cart/cart.go", parse_golangci(&golangci)[0].file);
+
+        let source = "fn total(items: &[Item]) -> u32 {\n    let x = 1;\n    let items = items.clone();\n    0\n}\n";
+        assert_eq!(
+            "- `let items = items.clone();`: redundant clone (clippy::redundant_clone)",
+            render_diagnostics(&diagnostics, "src/cart.rs", source, 1, 5)
+        );
+        assert_eq!(
+            "",
+            render_diagnostics(&diagnostics, "src/prices.rs", source, 1, 5)
+        );
+    }
+}
@@ -0,0 +1,326 @@
+use std::ops::Range;
+
+use clap::ValueEnum;
+use colored::*;
+use tree_sitter::Language;
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::color::Palette;
+use crate::helpers::tree_sitter::parse_with_thread_parser;
+use crate::history::line_diff;
+
+/// How suggestions are shown against the original code
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DiffMode {
+    /// The lines removed and added
+    Text,
+    /// The tokens removed and added, regardless of formatting, telling apart statements that only
+    /// moved
+    Structural,
+}
+
+// The statements of blocks, and the items of files, are what can be moved around as a whole
+const STATEMENT_PARENT_KINDS: &[&str] = &["block", "statement_block", "source_file", "program"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Change {
+    Unchanged,
+    /// Part of a statement found elsewhere in the other code
+    Moved,
+    /// Only found in one of the two
+    Novel,
+}
+
+/// The leaves of the tree, with comments and strings kept whole
+struct Tokens<'a> {
+    texts: Vec<&'a str>,
+    ranges: Vec<Range<usize>>,
+    /// The tokens of every statement, outermost first
+    statements: Vec<Range<usize>>,
+}
+
+fn is_atomic(kind: &str) -> bool {
+    kind.contains("comment") || kind.contains("string")
+}
+
+fn tokenize(source: &str, language: Language) -> Option<Tokens<'_>> {
+    let tree = parse_with_thread_parser(source, language)?;
+    let mut ranges: Vec<Range<usize>> = vec![];
+    let mut statement_ranges = vec![];
+    for node in traverse(tree.walk(), Order::Pre) {
+        let is_within_token = ranges
+            .last()
+            .is_some_and(|last| node.start_byte() < last.end);
+        if node.is_named()
+            && !node.kind().contains("comment")
+            && node
+                .parent()
+                .is_some_and(|parent| STATEMENT_PARENT_KINDS.contains(&parent.kind()))
+        {
+            statement_ranges.push(node.byte_range());
+        }
+        if !is_within_token
+            && node.start_byte() < node.end_byte()
+            && (node.child_count() == 0 || is_atomic(node.kind()))
+        {
+            ranges.push(node.byte_range());
+        }
+    }
+
+    let statements = statement_ranges
+        .into_iter()
+        .map(|statement| {
+            let first = ranges.partition_point(|token| token.start < statement.start);
+            let end = ranges.partition_point(|token| token.end <= statement.end);
+            first..end.max(first)
+        })
+        .filter(|statement| !statement.is_empty())
+        .collect();
+
+    Some(Tokens {
+        texts: ranges.iter().map(|range| &source[range.clone()]).collect(),
+        ranges,
+        statements,
+    })
+}
+
+/// The tokens of both the longest common subsequence leaves unchanged
+fn common_tokens(original: &[&str], suggestion: &[&str]) -> Vec<(usize, usize)> {
+    let mut common = vec![vec![0u32; suggestion.len() + 1]; original.len() + 1];
+    for i in (0..original.len()).rev() {
+        for j in (0..suggestion.len()).rev() {
+            common[i][j] = if original[i] == suggestion[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut pairs = vec![];
+    while i < original.len() && j < suggestion.len() {
+        if original[i] == suggestion[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    pairs
+}
+
+fn changes(original: &Tokens, suggestion: &Tokens) -> (Vec<Change>, Vec<Change>) {
+    let mut original_changes = vec![Change::Novel; original.texts.len()];
+    let mut suggestion_changes = vec![Change::Novel; suggestion.texts.len()];
+    // Tokens shared by both ends are matched right away, which keeps the table small
+    let prefix = original
+        .texts
+        .iter()
+        .zip(&suggestion.texts)
+        .take_while(|(original, suggestion)| original == suggestion)
+        .count();
+    let suffix = original.texts[prefix..]
+        .iter()
+        .rev()
+        .zip(suggestion.texts[prefix..].iter().rev())
+        .take_while(|(original, suggestion)| original == suggestion)
+        .count();
+    original_changes[..prefix].fill(Change::Unchanged);
+    suggestion_changes[..prefix].fill(Change::Unchanged);
+    let original_end = original.texts.len() - suffix;
+    let suggestion_end = suggestion.texts.len() - suffix;
+    original_changes[original_end..].fill(Change::Unchanged);
+    suggestion_changes[suggestion_end..].fill(Change::Unchanged);
+    for (i, j) in common_tokens(
+        &original.texts[prefix..original_end],
+        &suggestion.texts[prefix..suggestion_end],
+    ) {
+        original_changes[prefix + i] = Change::Unchanged;
+        suggestion_changes[prefix + j] = Change::Unchanged;
+    }
+
+    // A statement that is not where it was reads as removed and added again, unless told apart
+    let mut moved_from = vec![false; original.statements.len()];
+    for statement in &suggestion.statements {
+        if !suggestion_changes[statement.clone()].contains(&Change::Novel) {
+            continue;
+        }
+        let moved = original
+            .statements
+            .iter()
+            .enumerate()
+            .find(|(index, other)| {
+                !moved_from[*index]
+                    && original_changes[(*other).clone()].contains(&Change::Novel)
+                    && original.texts[(*other).clone()] == suggestion.texts[statement.clone()]
+            });
+        if let Some((index, other)) = moved {
+            moved_from[index] = true;
+            original_changes[other.clone()].fill(Change::Moved);
+            suggestion_changes[statement.clone()].fill(Change::Moved);
+        }
+    }
+
+    (original_changes, suggestion_changes)
+}
+
+/// The lines of the source holding changes, or every line, marked in the gutter by what changed
+fn render_lines(
+    source: &str,
+    tokens: &Tokens,
+    changes: &[Change],
+    only_changed_lines: bool,
+    novel_mark: &str,
+    novel_color: Color,
+    moved_color: Color,
+) -> Vec<String> {
+    let mut byte_changes = vec![Change::Unchanged; source.len()];
+    for (range, change) in tokens.ranges.iter().zip(changes) {
+        byte_changes[range.clone()].fill(*change);
+    }
+
+    let mut lines = vec![];
+    let mut line_start = 0;
+    for (line_index, line) in source.split('\n').enumerate() {
+        let line_changes = &byte_changes[line_start..line_start + line.len()];
+        line_start += line.len() + 1;
+        let mark = if line_changes.contains(&Change::Novel) {
+            novel_mark
+        } else if line_changes.contains(&Change::Moved) {
+            "~"
+        } else if only_changed_lines {
+            continue;
+        } else {
+            " "
+        };
+
+        let mut rendered_line = String::new();
+        let mut segment_start = 0;
+        for end in 1..=line.len() {
+            if end < line.len()
+                && (line_changes[end] == line_changes[segment_start] || !line.is_char_boundary(end))
+            {
+                continue;
+            }
+            let segment = &line[segment_start..end];
+            let segment = match line_changes[segment_start] {
+                Change::Unchanged => segment.normal(),
+                Change::Moved => segment.color(moved_color),
+                Change::Novel => segment.color(novel_color).bold(),
+            };
+            rendered_line.push_str(&segment.to_string());
+            segment_start = end;
+        }
+        lines.push(format!(
+            "{} {} {}",
+            format!("{:>4}", line_index + 1).dimmed(),
+            mark,
+            rendered_line
+        ));
+    }
+
+    lines
+}
+
+/// The suggestion, with the tokens it adds highlighted, followed by the lines of the original code
+/// holding the tokens it removes. Statements that only moved are highlighted apart, and changes
+/// to formatting alone are not shown. Nothing comes out if either cannot be parsed.
+pub fn render_structural_diff(
+    language: Language,
+    original: &str,
+    suggestion: &str,
+    palette: Palette,
+) -> Option<String> {
+    let original_tokens = tokenize(original, language)?;
+    let suggestion_tokens = tokenize(suggestion, language)?;
+    let (original_changes, suggestion_changes) = changes(&original_tokens, &suggestion_tokens);
+
+    let mut lines = render_lines(
+        suggestion,
+        &suggestion_tokens,
+        &suggestion_changes,
+        false,
+        "+",
+        palette.added(),
+        palette.moved(),
+    );
+    let removed_lines = render_lines(
+        original,
+        &original_tokens,
+        &original_changes,
+        true,
+        "-",
+        palette.removed(),
+        palette.moved(),
+    );
+    if !removed_lines.is_empty() {
+        lines.push("removed from the original:".dimmed().to_string());
+        lines.extend(removed_lines);
+    }
+
+    Some(lines.join("\n"))
+}
+
+/// The lines removed from the original code, and the ones added by the suggestion, in the colors of
+/// the palette
+pub fn render_text_diff(original: &str, suggestion: &str, palette: Palette) -> String {
+    line_diff(original.trim(), suggestion.trim())
+        .lines()
+        .map(|line| match line.chars().next() {
+            Some('-') => line.color(palette.removed()).to_string(),
+            Some('+') => line.color(palette.added()).to_string(),
+            _ => line.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diff::{changes, tokenize, Change};
+
+    fn changed_tokens(original: &str, suggestion: &str) -> (Vec<String>, Vec<String>) {
+        let language = tree_sitter_rust::language();
+        let original = tokenize(original, language).unwrap();
+        let suggestion = tokenize(suggestion, language).unwrap();
+        let (original_changes, suggestion_changes) = changes(&original, &suggestion);
+        let describe = |texts: &[&str], changes: &[Change]| {
+            texts
+                .iter()
+                .zip(changes)
+                .filter(|(_, change)| **change != Change::Unchanged)
+                .map(|(text, change)| format!("{:?} {}", change, text))
+                .collect()
+        };
+
+        (
+            describe(&original.texts, &original_changes),
+            describe(&suggestion.texts, &suggestion_changes),
+        )
+    }
+
+    #[test]
+    fn structural_changes() {
+        let original = "fn f() {\n    let a = 1;\n    let b = 2;\n    a + b\n}";
+
+        let reformatted = "fn f() { let a = 1; let b = 2;\n    a + b }";
+        let (removed, added) = changed_tokens(original, reformatted);
+        assert!(removed.is_empty() && added.is_empty());
+
+        let reordered = "fn f() {\n    let b = 2;\n    let a = 1;\n    a + b\n}";
+        let (removed, added) = changed_tokens(original, reordered);
+        assert!(removed.iter().all(|token| token.starts_with("Moved")));
+        assert!(added.iter().all(|token| token.starts_with("Moved")));
+        assert!(!added.is_empty() && !added.contains(&"Moved +".to_string()));
+
+        let changed = "fn f() {\n    let a = 1;\n    let b = 3;\n    // the sum\n    a + b\n}";
+        let (removed, added) = changed_tokens(original, changed);
+        assert_eq!(vec!["Novel 2"], removed);
+        assert_eq!(vec!["Novel 3", "Novel // the sum"], added);
+    }
+}
@@ -0,0 +1,266 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use colored::*;
+use tree_sitter::Parser;
+
+use crate::config::Config;
+use crate::hooks::expand_post_apply_command;
+use crate::openai::{list_models, OPENAI_API_KEY_ENV_VAR_KEY};
+use crate::supported_languages::supported_language::{analyser, unknown_node_kinds, LANGUAGES};
+
+/// What a check of `senior doctor` found
+#[derive(Debug, PartialEq)]
+enum Finding {
+    Healthy(String),
+    /// What is wrong, and how to fix it
+    Unhealthy(String, String),
+}
+
+struct Check {
+    name: String,
+    finding: Finding,
+}
+
+impl Check {
+    fn healthy(name: &str, detail: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            finding: Finding::Healthy(detail.to_string()),
+        }
+    }
+    fn unhealthy(name: &str, problem: &str, fix: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            finding: Finding::Unhealthy(problem.to_string(), fix.to_string()),
+        }
+    }
+}
+
+/// Where the program is run from, looked up in `PATH` unless it is a path already
+fn find_on_path(program: &str) -> Option<PathBuf> {
+    if program.contains(std::path::MAIN_SEPARATOR) || program.contains('/') {
+        return Some(PathBuf::from(program)).filter(|path| path.is_file());
+    }
+    let executable_names = if cfg!(windows) {
+        vec![program.to_string(), format!("{}.exe", program)]
+    } else {
+        vec![program.to_string()]
+    };
+    env::split_paths(&env::var_os("PATH")?).find_map(|directory| {
+        executable_names
+            .iter()
+            .map(|name| directory.join(name))
+            .find(|path| path.is_file())
+    })
+}
+
+async fn check_api_key(secret: Option<&str>, model: &str) -> Vec<Check> {
+    let Some(secret) = secret.filter(|secret| !secret.trim().is_empty()) else {
+        return vec![Check::unhealthy(
+            "api key",
+            &format!("the {} env var is not present", OPENAI_API_KEY_ENV_VAR_KEY),
+            &format!(
+                "create a key at https://platform.openai.com/api-keys and `export {}=<key>`",
+                OPENAI_API_KEY_ENV_VAR_KEY
+            ),
+        )];
+    };
+
+    match list_models(secret).await {
+        Ok(models) => {
+            let model_check = if models.iter().any(|available| available == model) {
+                Check::healthy("model", &format!("{} is available", model))
+            } else {
+                Check::unhealthy(
+                    "model",
+                    &format!("{} is not amongst the models the key may use", model),
+                    "pick another one with `-m`, or see `senior capabilities` for the known ones",
+                )
+            };
+            vec![Check::healthy("api key", "OpenAI accepted it"), model_check]
+        }
+        Err(e) if e.contains("401") => vec![Check::unhealthy(
+            "api key",
+            "OpenAI rejected it",
+            &format!(
+                "check {} for typos, or create a new key at https://platform.openai.com/api-keys",
+                OPENAI_API_KEY_ENV_VAR_KEY
+            ),
+        )],
+        Err(e) => vec![Check::unhealthy(
+            "api key",
+            &format!("could not reach OpenAI: {}", e),
+            "check your network connection and proxy settings",
+        )],
+    }
+}
+
+/// Whether the grammar of every language loads in the tree-sitter senior was built with
+fn check_grammars() -> Vec<Check> {
+    LANGUAGES
+        .iter()
+        .map(|language| {
+            let name = format!("{} grammar", language);
+            let supported_language = analyser(language);
+            let grammar = supported_language.language();
+            let unknown = unknown_node_kinds(supported_language.as_ref());
+            let mut parser = Parser::new();
+            match parser.set_language(grammar) {
+                Ok(_) if !unknown.is_empty() => Check::unhealthy(
+                    &name,
+                    &format!("has no `{}`", unknown.join("`, `")),
+                    "update senior, the grammar changed node kinds it relies on",
+                ),
+                Ok(_) if parser.parse("", None).is_some() => {
+                    Check::healthy(&name, &format!("version {}", grammar.version()))
+                }
+                Ok(_) => Check::unhealthy(
+                    &name,
+                    "fails to parse",
+                    "rebuild senior, the grammar may be corrupt",
+                ),
+                Err(e) => Check::unhealthy(
+                    &name,
+                    &e.to_string(),
+                    &format!(
+                    "rebuild senior against a grammar generated for tree-sitter versions {} to {}",
+                    tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION,
+                    tree_sitter::LANGUAGE_VERSION
+                ),
+                ),
+            }
+        })
+        .collect()
+}
+
+/// Parses the configuration file, if there is one
+fn check_config(path: &Path) -> (Check, Option<Config>) {
+    if !path.exists() {
+        return (
+            Check::healthy(
+                "config",
+                &format!("no {}, using the defaults", path.display()),
+            ),
+            None,
+        );
+    }
+
+    match Config::load(path) {
+        Ok(config) => (
+            Check::healthy("config", &format!("{} is valid", path.display())),
+            Some(config),
+        ),
+        Err(e) => (
+            Check::unhealthy(
+                "config",
+                &e,
+                &format!(
+                    "fix {} or move it away; every section and field is optional",
+                    path.display()
+                ),
+            ),
+            None,
+        ),
+    }
+}
+
+/// Whether git, and the programs of the `post_apply` commands, like formatters and test runners,
+/// can be run
+fn check_commands(config: Option<&Config>) -> Vec<Check> {
+    let mut checks = vec![match find_on_path("git") {
+        Some(path) => Check::healthy("git", &path.display().to_string()),
+        None => Check::unhealthy(
+            "git",
+            "not found in PATH",
+            "install git, which --git-commit, review and nightly need",
+        ),
+    }];
+    for command in config.map_or(&[][..], |config| &config.post_apply) {
+        let words = expand_post_apply_command(command, &[]);
+        let Some(program) = words.first() else {
+            continue;
+        };
+        checks.push(match find_on_path(program) {
+            Some(path) => Check::healthy(
+                &format!("post_apply `{}`", command),
+                &path.display().to_string(),
+            ),
+            None => Check::unhealthy(
+                &format!("post_apply `{}`", command),
+                &format!("{} is not found in PATH", program),
+                &format!("install {} or remove the command from post_apply", program),
+            ),
+        });
+    }
+
+    checks
+}
+
+/// Validates the environment senior runs in: the API key and model, the grammars, the
+/// configuration and the commands it runs. Prints how to fix every problem found, and returns
+/// whether there were none.
+pub async fn run_doctor(config_path: &Path, model: &str, secret: Option<&str>) -> bool {
+    let (config_check, config) = check_config(config_path);
+    let mut checks = check_api_key(secret, model).await;
+    checks.extend(check_grammars());
+    checks.push(config_check);
+    checks.extend(check_commands(config.as_ref()));
+
+    let mut healthy = true;
+    for check in &checks {
+        match &check.finding {
+            Finding::Healthy(detail) => {
+                println!(
+                    "{} {}: {}",
+                    "ok".green(),
+                    check.name.bold(),
+                    detail.dimmed()
+                )
+            }
+            Finding::Unhealthy(problem, fix) => {
+                healthy = false;
+                println!("{} {}: {}", "!!".red(), check.name.bold(), problem);
+                println!("   {} {}", "fix:".yellow(), fix);
+            }
+        }
+    }
+
+    healthy
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use crate::doctor::{check_commands, check_config, check_grammars, Finding};
+
+    #[test]
+    fn checks() {
+        assert!(check_grammars()
+            .iter()
+            .all(|check| matches!(check.finding, Finding::Healthy(_))));
+
+        let directory = std::env::temp_dir().join("senior-doctor-test");
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join(".senior.toml");
+        write(
+            &path,
+            "post_apply = [\"senior-missing-formatter {file}\"]\n",
+        )
+        .unwrap();
+        let (check, config) = check_config(&path);
+        assert!(matches!(check.finding, Finding::Healthy(_)));
+        let commands = check_commands(config.as_ref());
+        assert!(matches!(
+            &commands.last().unwrap().finding,
+            Finding::Unhealthy(problem, _) if problem.contains("senior-missing-formatter")
+        ));
+
+        write(&path, "post_aply = []\n").unwrap();
+        assert!(matches!(
+            check_config(&path).0.finding,
+            Finding::Unhealthy(..)
+        ));
+    }
+}
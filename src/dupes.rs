@@ -0,0 +1,288 @@
+use std::collections::HashSet;
+use std::fs::read_to_string;
+use std::path::Path;
+
+use colored::*;
+use tree_sitter::Node;
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::batch::{source_files, BatchFilters};
+use crate::embeddings::{cosine_similarity, EmbeddingIndex};
+use crate::helpers::tree_sitter::{node_value, parse_with_thread_parser};
+use crate::history::repository_history;
+use crate::openai::{complete, Prompt};
+use crate::supported_languages::supported_language::detect_language;
+
+/// How many tokens make up a shingle, the unit functions are compared by
+const SHINGLE_LENGTH: usize = 3;
+
+/// The settings of `senior dupes`
+pub struct DupesSettings<'a> {
+    pub no_ignore: bool,
+    pub min_lines: usize,
+    /// How alike two functions must be, from 0 to 1, to be reported
+    pub threshold: f32,
+    /// Also compare the embeddings of the functions, with this API key
+    pub embed_with: Option<&'a str>,
+    /// Ask for a unified implementation of every pair, with this model and API key
+    pub unify_with: Option<(&'a str, &'a str)>,
+}
+
+struct Function {
+    location: String,
+    language: String,
+    code: String,
+    shingles: HashSet<Vec<String>>,
+}
+
+/// The tokens of the function, with identifiers and literals replaced by placeholders and
+/// comments left out, so that renamed copies look the same
+fn normalized_tokens(source_file: &str, function_node: Node) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut within: Option<usize> = None;
+    for node in traverse(function_node.walk(), Order::Pre) {
+        if within.is_some_and(|end| node.start_byte() < end) {
+            continue;
+        }
+        within = None;
+        let kind = node.kind();
+        if kind.contains("comment") {
+            within = Some(node.end_byte());
+        } else if kind.contains("string") {
+            within = Some(node.end_byte());
+            tokens.push("$string".to_string());
+        } else if node.child_count() == 0 {
+            tokens.push(if kind.contains("identifier") {
+                "$identifier".to_string()
+            } else if kind.contains("literal") || kind.contains("number") {
+                "$literal".to_string()
+            } else {
+                node_value(source_file, node).to_string()
+            });
+        }
+    }
+
+    tokens
+}
+
+fn shingles(tokens: &[String]) -> HashSet<Vec<String>> {
+    tokens
+        .windows(SHINGLE_LENGTH.min(tokens.len()).max(1))
+        .map(<[String]>::to_vec)
+        .collect()
+}
+
+/// The Jaccard similarity of the shingles of both functions
+fn structural_similarity(a: &HashSet<Vec<String>>, b: &HashSet<Vec<String>>) -> f32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+
+    a.intersection(b).count() as f32 / union as f32
+}
+
+fn collect_functions(path: &Path, settings: &DupesSettings) -> Vec<Function> {
+    let filters = BatchFilters {
+        kind: None,
+        skip_tests: true,
+        only_tests: false,
+        min_lines: Some(settings.min_lines),
+        max_lines: None,
+        skip_generated: true,
+        over_budget: None,
+        diagnosed: None,
+    };
+    let mut functions = vec![];
+    for file_path in source_files(path, settings.no_ignore) {
+        let Ok(supported_language) = detect_language(&file_path) else {
+            continue;
+        };
+        let Ok(source_file) = read_to_string(&file_path) else {
+            eprintln!("{}", format!("failed to read {}", file_path).yellow());
+            continue;
+        };
+        let Some(tree) = parse_with_thread_parser(&source_file, supported_language.language())
+        else {
+            continue;
+        };
+        for target in
+            filters.find_targets(supported_language.as_ref(), &file_path, &source_file, &tree)
+        {
+            let Some(function_node) = tree
+                .root_node()
+                .descendant_for_byte_range(target.start_byte, target.end_byte)
+            else {
+                continue;
+            };
+            functions.push(Function {
+                location: format!("{}:{}", file_path, target.start_point.row + 1),
+                language: supported_language.to_string(),
+                code: node_value(&source_file, function_node).to_string(),
+                shingles: shingles(&normalized_tokens(&source_file, function_node)),
+            });
+        }
+    }
+
+    functions
+}
+
+/// The pairs of functions of the same language that are at least `threshold` alike, most alike
+/// first. The similarity of a pair is the highest of its structural one and, if given, the one of
+/// its embeddings.
+fn find_duplicates(
+    functions: &[Function],
+    embeddings: Option<&EmbeddingIndex>,
+    threshold: f32,
+) -> Vec<(usize, usize, f32)> {
+    let mut duplicates = vec![];
+    for (i, first) in functions.iter().enumerate() {
+        for (j, second) in functions.iter().enumerate().skip(i + 1) {
+            if first.language != second.language {
+                continue;
+            }
+            let embedding_similarity = embeddings
+                .and_then(|index| {
+                    Some(cosine_similarity(
+                        index.get(&first.code)?,
+                        index.get(&second.code)?,
+                    ))
+                })
+                .unwrap_or(0.0);
+            // No two sets can be more alike than the smaller is to the larger
+            let (smaller, larger) = (
+                first.shingles.len().min(second.shingles.len()),
+                first.shingles.len().max(second.shingles.len()),
+            );
+            let structural_similarity = if (smaller as f32) < threshold * larger as f32 {
+                0.0
+            } else {
+                structural_similarity(&first.shingles, &second.shingles)
+            };
+            let similarity = structural_similarity.max(embedding_similarity);
+            if similarity >= threshold {
+                duplicates.push((i, j, similarity));
+            }
+        }
+    }
+    duplicates.sort_by(|(_, _, a), (_, _, b)| b.total_cmp(a));
+
+    duplicates
+}
+
+fn draft_unify_instructions(language: &str, first: &str, second: &str) -> Prompt {
+    Prompt {
+        system: format!(
+            r#"You are a senior {} engineer consolidating duplicated code.
+
+You are given two functions that do nearly the same thing.
+
+Strictly adhere to the following instructions:
+1. Write a single function that can replace both, keeping the behaviour of each.
+2. Then say in one short sentence per function how its callers should call the new one.
+3. Respond with the code in a single code block, followed by the sentences.
+"#,
+            language
+        ),
+        user: format!("{}\n\n{}", first, second),
+    }
+}
+
+/// Finds near-duplicate functions under the path, compared by the shape of their parse trees and,
+/// optionally, by their embeddings, and suggests consolidating them
+pub async fn run_dupes(path: &Path, settings: &DupesSettings<'_>) -> Result<(), String> {
+    let functions = collect_functions(path, settings);
+    let embeddings = match settings.embed_with {
+        Some(secret) => {
+            let history = repository_history();
+            let mut index = EmbeddingIndex::load(history.as_ref());
+            let codes: Vec<&str> = functions
+                .iter()
+                .map(|function| function.code.as_str())
+                .collect();
+            index.embed_missing(&codes, secret).await?;
+            index.save(history.as_ref())?;
+            Some(index)
+        }
+        None => None,
+    };
+
+    let duplicates = find_duplicates(&functions, embeddings.as_ref(), settings.threshold);
+    for (i, j, similarity) in &duplicates {
+        let (first, second) = (&functions[*i], &functions[*j]);
+        println!(
+            "{} and {} are {:.0}% alike, consider consolidating them",
+            first.location.bold(),
+            second.location.bold(),
+            similarity * 100.0
+        );
+        if let Some((model, secret)) = settings.unify_with {
+            let prompt = draft_unify_instructions(&first.language, &first.code, &second.code);
+            match complete(model, &prompt, secret).await {
+                Ok(answer) => println!("{}\n", answer.trim()),
+                Err(e) => eprintln!("{}", format!("failed to unify them: {}", e).yellow()),
+            }
+        }
+    }
+    println!(
+        "{}",
+        format!(
+            "{} pairs of near-duplicates amongst {} functions",
+            duplicates.len(),
+            functions.len()
+        )
+        .green()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dupes::{find_duplicates, normalized_tokens, shingles, Function};
+    use crate::helpers::tree_sitter::parse_source_with_language;
+
+    const SOURCE: &str = r#"fn total(prices: &[u32]) -> u32 {
+    // adds them up
+    let mut sum = 0;
+    for price in prices {
+        sum += price;
+    }
+    sum
+}
+
+fn weight(items: &[u32]) -> u32 {
+    let mut acc = 10;
+    for item in items {
+        acc += item;
+    }
+    acc
+}
+
+fn greet(name: &str) -> String {
+    format!("Hello, {}!", name)
+}"#;
+
+    #[test]
+    fn near_duplicates() {
+        let tree = parse_source_with_language(SOURCE, tree_sitter_rust::language());
+        let mut cursor = tree.walk();
+        let functions: Vec<Function> = tree
+            .root_node()
+            .named_children(&mut cursor)
+            .enumerate()
+            .map(|(index, function_node)| Function {
+                location: index.to_string(),
+                language: "rust".to_string(),
+                code: "".to_string(),
+                shingles: shingles(&normalized_tokens(SOURCE, function_node)),
+            })
+            .collect();
+
+        let first_tokens = normalized_tokens(SOURCE, tree.root_node().named_child(0).unwrap());
+        assert!(!first_tokens.iter().any(|token| token.contains("adds")));
+        assert!(first_tokens.contains(&"$literal".to_string()));
+
+        assert_eq!(vec![(0, 1, 1.0)], find_duplicates(&functions, None, 0.8));
+    }
+}
@@ -0,0 +1,398 @@
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+use clap::ValueEnum;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tree_sitter::Range;
+
+use crate::color::Palette;
+use crate::diff::render_text_diff;
+use crate::outcome::Outcome;
+
+/// How suggestions are reported
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Highlighted code and prompts, for people
+    #[default]
+    Text,
+    /// One JSON object per function, for tools that apply the edits themselves
+    Json,
+}
+
+/// How applied suggestions are written to the file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum ApplyBackend {
+    /// Overwrite the file with the edited code
+    #[default]
+    Direct,
+    /// Apply a patch with `git apply --3way`, which merges it into a file that changed since it was
+    /// parsed, leaving conflict markers if need be
+    Git,
+}
+
+/// How many unchanged lines surround the change in a patch
+const PATCH_CONTEXT_LINES: usize = 3;
+
+/// The lines of a hunk, each prefixed by how it changed
+fn hunk_lines(prefix: char, lines: &[&str]) -> String {
+    lines
+        .iter()
+        .map(|line| match line.strip_suffix('\n') {
+            Some(line) => format!("{}{}\n", prefix, line),
+            None => format!("{}{}\n\\ No newline at end of file\n", prefix, line),
+        })
+        .collect()
+}
+
+/// A position in the source file. Lines and columns count from zero, and besides the column in
+/// bytes, `character` counts UTF-16 code units, as language servers do.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub character: usize,
+}
+
+impl Position {
+    pub fn new(source_file: &str, byte: usize) -> Self {
+        let line_start = source_file[..byte]
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1);
+
+        Self {
+            line: source_file[..line_start].matches('\n').count(),
+            column: byte - line_start,
+            character: source_file[line_start..byte].encode_utf16().count(),
+        }
+    }
+}
+
+/// Replacing the text of the span with the new text applies the suggestion
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Edit {
+    #[serde(flatten)]
+    pub span: Span,
+    pub new_text: String,
+}
+
+impl Edit {
+    pub fn new(source_file: &str, range: &Range, new_text: &str) -> Self {
+        Self {
+            span: Span::new(source_file, range),
+            new_text: new_text.to_string(),
+        }
+    }
+    /// The edit as a unified diff of the file at the path, for `git apply`. The index line names
+    /// the blobs of the file before and after, which `--3way` merges from.
+    pub fn to_patch(&self, source_file: &str, path: &str, index: &str) -> String {
+        let span = &self.span;
+        let lines: Vec<&str> = source_file.split_inclusive('\n').collect();
+        let first = span.start.line;
+        let last = span.end.line.min(lines.len().saturating_sub(1)).max(first);
+        let changed_start = span.start_byte - span.start.column;
+        let changed_end = changed_start + lines[first..=last].concat().len();
+        let edited = format!(
+            "{}{}{}",
+            &source_file[changed_start..span.start_byte],
+            self.new_text,
+            &source_file[span.end_byte..changed_end]
+        );
+        let edited_lines: Vec<&str> = edited.split_inclusive('\n').collect();
+
+        let before = &lines[first.saturating_sub(PATCH_CONTEXT_LINES)..first];
+        let after = &lines[last + 1..(last + 1 + PATCH_CONTEXT_LINES).min(lines.len())];
+        let start = first - before.len();
+        let old_count = before.len() + last + 1 - first + after.len();
+        let new_count = before.len() + edited_lines.len() + after.len();
+        // Empty ranges start at the line before them
+        let line_number = |count: usize| if count == 0 { start } else { start + 1 };
+
+        format!(
+            "diff --git a/{path} b/{path}\nindex {}\n--- a/{path}\n+++ b/{path}\n@@ -{},{} +{},{} @@\n{}{}{}{}",
+            index,
+            line_number(old_count),
+            old_count,
+            line_number(new_count),
+            new_count,
+            hunk_lines(' ', before),
+            hunk_lines('-', &lines[first..=last]),
+            hunk_lines('+', &edited_lines),
+            hunk_lines(' ', after),
+            path = path,
+        )
+    }
+}
+
+/// Where the code a suggestion replaces was in the file
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Span {
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(source_file: &str, range: &Range) -> Self {
+        Self {
+            start_byte: range.start_byte,
+            end_byte: range.end_byte,
+            start: Position::new(source_file, range.start_byte),
+            end: Position::new(source_file, range.end_byte),
+        }
+    }
+}
+
+/// A suggestion that stands on its own, for tools that keep suggestions and apply them their own
+/// way, later or to a file that changed since
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub original: String,
+    pub edit: Edit,
+    pub language: String,
+}
+
+impl Suggestion {
+    pub fn new(source_file: &str, range: &Range, proposed: &str, language: &str) -> Self {
+        Self {
+            original: source_file[range.start_byte..range.end_byte].to_string(),
+            edit: Edit::new(source_file, range, proposed),
+            language: language.to_string(),
+        }
+    }
+    /// The lines the suggestion removes and adds, in the colors of the palette
+    pub fn preview_diff(&self, palette: Palette) -> String {
+        render_text_diff(&self.original, &self.edit.new_text, palette)
+    }
+    /// The source file with the suggestion applied. The original code is looked for at the span
+    /// first, and then anywhere in the file, as earlier edits may have moved it, as long as it is
+    /// there only once.
+    pub fn apply_to(&self, source_file: &str) -> Result<String, String> {
+        let span = &self.edit.span;
+        let at_span = source_file.get(span.start_byte..span.end_byte);
+        let start_byte = if at_span == Some(self.original.as_str()) {
+            span.start_byte
+        } else {
+            let mut found = source_file.match_indices(&self.original);
+            match (found.next(), found.next()) {
+                (Some((start_byte, _)), None) => start_byte,
+                (Some(_), Some(_)) => {
+                    return Err("the original code is in the file more than once".to_string())
+                }
+                (None, _) => return Err("the original code is no longer in the file".to_string()),
+            }
+        };
+
+        Ok(format!(
+            "{}{}{}",
+            &source_file[..start_byte],
+            self.edit.new_text,
+            &source_file[start_byte + self.original.len()..]
+        ))
+    }
+}
+
+/// The suggestions of reports written with `--output-format json`, one per line, along with the
+/// files they are for. Reports without a suggestion are skipped.
+pub fn parse_reports(reports: &str) -> Result<Vec<(String, Suggestion)>, String> {
+    let mut suggestions = vec![];
+    for (index, line) in reports.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let report: Value = serde_json::from_str(line)
+            .map_err(|e| format!("line {} is not a report: {}", index + 1, e))?;
+        let (Some(file), Some(suggestion)) = (report["file"].as_str(), report.get("suggestion"))
+        else {
+            continue;
+        };
+        if suggestion.is_null() {
+            continue;
+        }
+        let suggestion = Suggestion::deserialize(suggestion)
+            .map_err(|e| format!("line {} has no valid suggestion: {}", index + 1, e))?;
+        suggestions.push((file.to_string(), suggestion));
+    }
+
+    Ok(suggestions)
+}
+
+/// Applies the suggestions of the reports to their files, or only shows them with `preview`.
+/// Suggestions that cannot be applied are reported and skipped, and how many were is returned.
+pub fn run_apply(reports_path: &Path, preview: bool, palette: Palette) -> Result<usize, String> {
+    let reports = read_to_string(reports_path)
+        .map_err(|e| format!("failed to read {}: {}", reports_path.display(), e))?;
+    let mut failures = 0;
+    for (file, suggestion) in parse_reports(&reports)? {
+        if preview {
+            println!("{}", file.bold());
+            println!("{}", suggestion.preview_diff(palette));
+            continue;
+        }
+        let applied = read_to_string(&file)
+            .map_err(|e| e.to_string())
+            .and_then(|source_file| suggestion.apply_to(&source_file))
+            .and_then(|applied| write(&file, applied).map_err(|e| e.to_string()));
+        match applied {
+            Ok(()) => println!("{}", format!("applied a suggestion to {}", file).green()),
+            Err(e) => {
+                eprintln!(
+                    "{}",
+                    format!("skipped a suggestion to {}: {}", file, e).yellow()
+                );
+                failures += 1;
+            }
+        }
+    }
+
+    Ok(failures)
+}
+
+/// What came out of going through a function, as written with `--output-format json`. The edit
+/// of the suggestion is the one both editors and `senior apply` go by.
+#[derive(Serialize, Debug)]
+pub struct Report {
+    pub file: String,
+    pub function: Option<String>,
+    pub outcome: String,
+    pub reason: Option<String>,
+    pub suggestion: Option<Suggestion>,
+}
+
+impl Report {
+    pub fn new(
+        file: &str,
+        function: Option<String>,
+        outcome: &Outcome,
+        suggestion: Option<Suggestion>,
+    ) -> Self {
+        Self {
+            file: file.to_string(),
+            function,
+            outcome: outcome
+                .kind()
+                .to_possible_value()
+                .unwrap()
+                .get_name()
+                .to_string(),
+            reason: outcome.reason().map(str::to_string),
+            suggestion,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::Palette;
+    use crate::edit::{parse_reports, Edit, Position, Report, Suggestion};
+    use crate::helpers::tree_sitter::parse_source_with_language;
+    use crate::outcome::Outcome;
+
+    #[test]
+    fn positions() {
+        let source = "// héllo 👋\nfn greet() {}\n";
+        let emoji = source.find('👋').unwrap();
+        assert_eq!(
+            Position {
+                line: 0,
+                column: 10,
+                character: 9
+            },
+            Position::new(source, emoji)
+        );
+        assert_eq!(
+            Position {
+                line: 1,
+                column: 0,
+                character: 0
+            },
+            Position::new(source, emoji + '👋'.len_utf8() + 1)
+        );
+
+        let tree = parse_source_with_language(source, tree_sitter_rust::language());
+        let function = tree.root_node().named_child(1).unwrap();
+        let suggestion = Suggestion::new(source, &function.range(), "fn greet() { () }", "rust");
+        let span = &suggestion.edit.span;
+        assert_eq!((15, 28), (span.start_byte, span.end_byte));
+        assert_eq!((1, 13), (span.end.line, span.end.character));
+
+        let report = Report::new(
+            "src/lib.rs",
+            Some("greet".to_string()),
+            &Outcome::Suggested("fn greet() { () }".to_string()),
+            Some(suggestion),
+        );
+        let report = serde_json::to_value(report).unwrap();
+        assert_eq!("suggested", report["outcome"]);
+        assert_eq!(15, report["suggestion"]["edit"]["start_byte"]);
+        assert_eq!(
+            "fn greet() { () }",
+            report["suggestion"]["edit"]["new_text"]
+        );
+        assert_eq!(None, report.get("edit"));
+    }
+
+    #[test]
+    fn patches() {
+        let source = "use std::fmt;\n\nfn greet() {\n    println!(\"hi\");\n}\n\nfn main() {}";
+        let start_byte = source.find("fn greet").unwrap();
+        let end_byte = source.find("}\n").unwrap() + 1;
+        let tree = parse_source_with_language(source, tree_sitter_rust::language());
+        let function = tree
+            .root_node()
+            .descendant_for_byte_range(start_byte, end_byte)
+            .unwrap();
+        let edit = Edit::new(source, &function.range(), "fn greet() {}");
+
+        assert_eq!(
+            r#"diff --git a/src/main.rs b/src/main.rs
+index 1111111..2222222
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1,7 +1,5 @@
+ use std::fmt;
+ 
+-fn greet() {
+-    println!("hi");
+-}
++fn greet() {}
+ 
+ fn main() {}
+\ No newline at end of file
+"#,
+            edit.to_patch(source, "src/main.rs", "1111111..2222222")
+        );
+    }
+
+    #[test]
+    fn standalone_suggestions() {
+        let source = "fn greet() {\n    println!(\"hi\");\n}\n";
+        let tree = parse_source_with_language(source, tree_sitter_rust::language());
+        let function = tree.root_node().named_child(0).unwrap();
+        let suggestion = Suggestion::new(source, &function.range(), "fn greet() {}", "rust");
+        assert_eq!("fn greet() {}\n", suggestion.apply_to(source).unwrap());
+
+        // Persisted in a report, and applied once the file gained a function above it
+        let report = Report::new(
+            "src/lib.rs",
+            Some("greet".to_string()),
+            &Outcome::Suggested("fn greet() {}".to_string()),
+            Some(suggestion),
+        );
+        let reports = format!("{}\n", serde_json::to_string(&report).unwrap());
+        let (file, suggestion) = parse_reports(&reports).unwrap().remove(0);
+        assert_eq!("src/lib.rs", file);
+        assert_eq!(
+            "fn main() {}\nfn greet() {}\n",
+            suggestion
+                .apply_to(&format!("fn main() {{}}\n{}", source))
+                .unwrap()
+        );
+        assert!(suggestion.apply_to("fn other() {}\n").is_err());
+        assert!(suggestion
+            .preview_diff(Palette::RedGreen)
+            .contains("+ fn greet() {}"));
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use sha2::{Digest, Sha256};
+
+use crate::history::{HistoryEntry, HistoryStore};
+use crate::openai::embed;
+
+/// The embeddings of the code seen so far, keyed by the hash of the code, so that every piece of
+/// code is only embedded once
+#[derive(Default)]
+pub struct EmbeddingIndex {
+    embeddings: HashMap<String, Vec<f32>>,
+}
+
+fn code_hash(code: &str) -> String {
+    Sha256::digest(code.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+impl EmbeddingIndex {
+    /// Loads the index from the store, starting from scratch if it cannot be read
+    pub fn load(history: &dyn HistoryStore) -> Self {
+        Self {
+            embeddings: history.embeddings(),
+        }
+    }
+
+    pub fn save(&self, history: &dyn HistoryStore) -> Result<(), String> {
+        history.save_embeddings(&self.embeddings)
+    }
+
+    pub fn get(&self, code: &str) -> Option<&[f32]> {
+        self.embeddings.get(&code_hash(code)).map(Vec::as_slice)
+    }
+
+    /// Embeds the pieces of code the index does not hold yet, all in one request
+    pub async fn embed_missing(&mut self, codes: &[&str], secret: &str) -> Result<(), String> {
+        let mut missing: Vec<String> = codes
+            .iter()
+            .filter(|code| self.get(code).is_none())
+            .map(|code| code.to_string())
+            .collect();
+        missing.sort();
+        missing.dedup();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let embeddings = embed(&missing, secret).await?;
+        for (code, embedding) in missing.iter().zip(embeddings) {
+            self.embeddings.insert(code_hash(code), embedding);
+        }
+
+        Ok(())
+    }
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(a, b)| a * b).sum();
+    let norms =
+        a.iter().map(|a| a * a).sum::<f32>().sqrt() * b.iter().map(|b| b * b).sum::<f32>().sqrt();
+
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+/// The `k` applied suggestions whose original code is the most similar to the given code, most
+/// similar first. Suggestions for the very same code are left out, as they are the previous attempt.
+pub fn most_similar<'a>(
+    index: &EmbeddingIndex,
+    code: &str,
+    applied: &'a [HistoryEntry],
+    k: usize,
+) -> Vec<&'a HistoryEntry> {
+    let Some(embedding) = index.get(code) else {
+        return vec![];
+    };
+    let mut similar: Vec<(f32, &HistoryEntry)> = applied
+        .iter()
+        .filter(|entry| entry.original != code)
+        .filter_map(|entry| {
+            let other = index.get(&entry.original)?;
+            Some((cosine_similarity(embedding, other), entry))
+        })
+        .collect();
+    similar.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+    similar
+        .into_iter()
+        .take(k)
+        .map(|(_, entry)| entry)
+        .collect()
+}
+
+/// Turns the similar suggestions into examples for the model to stay consistent with
+pub fn describe_examples(examples: &[&HistoryEntry]) -> String {
+    if examples.is_empty() {
+        return "".to_string();
+    }
+
+    format!(
+        "Similar functions of this repository were optimized like this before, keep your suggestion consistent with them:\n\n{}",
+        examples
+            .iter()
+            .map(|example| format!(
+                "Before:\n```\n{}\n```\nAfter:\n```\n{}\n```",
+                example.original.trim(),
+                example.suggestion.trim()
+            ))
+            .collect::<Vec<String>>()
+            .join("\n\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::embeddings::{
+        code_hash, cosine_similarity, describe_examples, most_similar, EmbeddingIndex,
+    };
+    use crate::history::HistoryEntry;
+
+    #[test]
+    fn similar_examples() {
+        assert_eq!(1.0, cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]));
+        assert_eq!(0.0, cosine_similarity(&[1.0, 0.0], &[0.0, 0.0]));
+
+        let mut index = EmbeddingIndex::default();
+        for (code, embedding) in [
+            ("fn a() {}", vec![1.0, 0.0]),
+            ("fn b() {}", vec![0.9, 0.1]),
+            ("fn c() {}", vec![0.0, 1.0]),
+        ] {
+            index.embeddings.insert(code_hash(code), embedding);
+        }
+        let applied = vec![
+            HistoryEntry::new("src/lib.rs", "c", "fn c() {}", "fn c() { () }", true),
+            HistoryEntry::new("src/lib.rs", "b", "fn b() {}", "fn b() { () }", true),
+            HistoryEntry::new("src/lib.rs", "a", "fn a() {}", "fn a() { () }", true),
+        ];
+
+        let examples = most_similar(&index, "fn a() {}", &applied, 1);
+        assert_eq!(
+            vec!["b"],
+            examples
+                .iter()
+                .map(|e| e.function.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert!(describe_examples(&examples).ends_with("After:\n```\nfn b() { () }\n```"));
+
+        assert!(most_similar(&index, "fn d() {}", &applied, 1).is_empty());
+        assert_eq!("", describe_examples(&[]));
+    }
+}
@@ -0,0 +1,835 @@
+use std::collections::HashSet;
+use std::fs::{read_to_string, write};
+use std::path::{Component, Path, PathBuf};
+
+use tree_sitter::{Node, Tree};
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::context::{name_of, IMPORT_KINDS};
+use crate::helpers::tree_sitter::{node_value, parse_with_thread_parser};
+use crate::supported_languages::supported_language::{
+    detect_language, explain_not_found, FunctionKind,
+};
+
+/// What a function may be wrapped in and still be a top-level item of the file
+const WRAPPER_KINDS: &[&str] = &[
+    // Javascript and Typescript
+    "export_statement",
+];
+
+const DOC_KINDS: &[&str] = &[
+    // Rust
+    "line_comment",
+    "block_comment",
+    "attribute_item",
+    // Go, Javascript and Typescript
+    "comment",
+];
+
+/// Which function to move, as the flags of `senior` pick it
+pub struct Selection {
+    pub parent_identifier: Option<String>,
+    pub function_identifier: Option<String>,
+    pub kind: Option<FunctionKind>,
+}
+
+/// The files once the function moved, and what is left to do by hand
+#[derive(Debug, PartialEq)]
+struct Move {
+    source_file: String,
+    target_file: String,
+    notes: Vec<String>,
+}
+
+/// The path with `.` and `..` resolved without touching the file system, made absolute
+fn normalize(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+/// The path to the file from the directory, like `../utils/format.ts`
+fn relative_path(from_directory: &Path, to: &Path) -> String {
+    let (from, to) = (normalize(from_directory), normalize(to));
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let shared = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts: Vec<String> = vec![".".to_string(); usize::from(shared == from.len())];
+    parts.extend(from[shared..].iter().map(|_| "..".to_string()));
+    parts.extend(
+        to[shared..]
+            .iter()
+            .map(|component| component.as_os_str().to_string_lossy().to_string()),
+    );
+
+    parts.join("/")
+}
+
+/// The directory holding the file
+fn directory_of(path: &Path) -> PathBuf {
+    normalize(path)
+        .parent()
+        .map_or_else(PathBuf::new, Path::to_path_buf)
+}
+
+/// The identifiers, type names and package names under the node
+fn identifiers<'a>(source_file: &'a str, node: Node<'a>) -> HashSet<&'a str> {
+    traverse(node.walk(), Order::Pre)
+        .filter(|node| node.child_count() == 0 && node.kind().ends_with("identifier"))
+        .map(|node| node_value(source_file, node))
+        .collect()
+}
+
+/// The top-level item of the file holding the function, along with the doc comments and
+/// attributes right above it
+fn item_range<'a>(
+    source_file: &str,
+    function_node: Node<'a>,
+) -> Result<(Node<'a>, usize, usize), String> {
+    let mut item = function_node;
+    while let Some(parent) = item.parent().filter(|parent| parent.parent().is_some()) {
+        if !WRAPPER_KINDS.contains(&parent.kind()) {
+            return Err(format!(
+                "only top-level functions can be moved, and this one is in a {}",
+                parent.kind().replace('_', " ")
+            ));
+        }
+        item = parent;
+    }
+
+    Ok((item, doc_comments_start(source_file, item), item.end_byte()))
+}
+
+/// Where the doc comments and attributes right above the node start, or the node itself without
+/// any
+pub fn doc_comments_start(source_file: &str, node: Node) -> usize {
+    let mut start_byte = node.start_byte();
+    let mut previous = node.prev_named_sibling();
+    while let Some(doc) = previous.filter(|node| DOC_KINDS.contains(&node.kind())) {
+        let gap = &source_file[doc.end_byte()..start_byte];
+        // Inner doc comments, like `//!`, document the file rather than the function
+        if gap.matches('\n').count() > 1 || node_value(source_file, doc).starts_with("//!") {
+            break;
+        }
+        start_byte = doc.start_byte();
+        previous = doc.prev_named_sibling();
+    }
+
+    start_byte
+}
+
+/// The source without the text between the two bytes, taking whole lines when the text takes up
+/// the lines it is on, and leaving at most one blank line where it was
+pub fn cut(source_file: &str, start_byte: usize, end_byte: usize) -> String {
+    let line_start = source_file[..start_byte]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let start_byte = if source_file[line_start..start_byte].trim().is_empty() {
+        line_start
+    } else {
+        start_byte
+    };
+    let rest = &source_file[end_byte..];
+    let line_end = rest.find('\n').map_or(rest.len(), |newline| newline + 1);
+    let end_byte = if rest[..line_end].trim().is_empty() {
+        end_byte + line_end
+    } else {
+        end_byte
+    };
+
+    let before = source_file[..start_byte].trim_end_matches('\n');
+    let after = source_file[end_byte..].trim_start_matches('\n');
+    match (before.is_empty(), after.is_empty()) {
+        (true, _) => after.to_string(),
+        (false, true) => format!("{}\n", before),
+        (false, false) => format!("{}\n\n{}", before, after),
+    }
+}
+
+/// The source with the text inserted as lines after the node, or at the start without one
+pub fn insert_after(source_file: &str, node: Option<Node>, text: &str) -> String {
+    let Some(node) = node else {
+        return format!("{}\n{}", text, source_file);
+    };
+    let rest = &source_file[node.end_byte()..];
+    let line_end = node.end_byte() + rest.find('\n').map_or(rest.len(), |newline| newline + 1);
+    let separator = if source_file[..line_end].ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+
+    format!(
+        "{}{}{}\n{}",
+        &source_file[..line_end],
+        separator,
+        text,
+        &source_file[line_end..]
+    )
+}
+
+/// The imports at the top level of the file
+pub fn imports<'a>(tree: &'a Tree) -> Vec<Node<'a>> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let imports: Vec<Node> = root
+        .named_children(&mut cursor)
+        .filter(|node| IMPORT_KINDS.contains(&node.kind()))
+        .collect();
+
+    imports
+}
+
+/// The module path Rust code refers to the file by, like `crate::parser::lexer`, found from the
+/// `src` directory of the package it is in
+fn rust_module_path(path: &Path) -> Option<String> {
+    let path = normalize(path);
+    let source_directory = path
+        .ancestors()
+        .find(|directory| directory.ends_with("src"))?;
+    let relative = path.strip_prefix(source_directory).ok()?.with_extension("");
+    let mut segments: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().to_string())
+        .collect();
+    if matches!(
+        segments.last().map(String::as_str),
+        Some("mod" | "main" | "lib")
+    ) {
+        segments.pop();
+    }
+    segments.insert(0, "crate".to_string());
+
+    Some(segments.join("::"))
+}
+
+/// The file declaring the module of the file, like `src/lib.rs` for `src/parser.rs`
+fn rust_parent_module(path: &Path) -> Option<PathBuf> {
+    let path = normalize(path);
+    let directory = path.parent()?;
+    let candidates = if directory.ends_with("src") {
+        vec![directory.join("lib.rs"), directory.join("main.rs")]
+    } else {
+        vec![directory.with_extension("rs"), directory.join("mod.rs")]
+    };
+
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
+
+/// The parent module with the new file declared in it, unless it is already
+fn declare_rust_module(parent_module: &str, path: &Path) -> Option<String> {
+    let name = path.file_stem()?.to_string_lossy();
+    let tree = parse_with_thread_parser(parent_module, tree_sitter_rust::language())?;
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let items: Vec<Node> = root.named_children(&mut cursor).collect();
+    if items.iter().any(|item| {
+        item.kind() == "mod_item" && name_of(parent_module, *item) == Some(name.as_ref())
+    }) {
+        return None;
+    }
+    let last_declaration = items
+        .iter()
+        .rev()
+        .find(|item| item.kind() == "mod_item")
+        .or_else(|| {
+            items
+                .iter()
+                .rev()
+                .find(|item| item.kind() == "use_declaration")
+        });
+
+    Some(insert_after(
+        parent_module,
+        last_declaration.copied(),
+        &format!("mod {};", name),
+    ))
+}
+
+/// The names an import brings into scope, like `HashMap` for `use std::collections::HashMap;`,
+/// with the node removing it, which in Go is one spec of a grouped import
+fn imported_names<'a>(
+    language: &str,
+    source_file: &'a str,
+    import: Node<'a>,
+) -> Vec<(Node<'a>, HashSet<&'a str>)> {
+    if language == "go" {
+        let specs: Vec<Node> = traverse(import.walk(), Order::Pre)
+            .filter(|node| node.kind() == "import_spec")
+            .collect();
+        let grouped = specs.len() > 1;
+        return specs
+            .into_iter()
+            .map(|spec| {
+                let package = spec.child_by_field_name("name").map_or_else(
+                    || {
+                        spec.child_by_field_name("path")
+                            .map_or("", |path| node_value(source_file, path))
+                            .trim_matches('"')
+                            .rsplit('/')
+                            .next()
+                            .unwrap_or_default()
+                    },
+                    |name| node_value(source_file, name),
+                );
+                (
+                    if grouped { spec } else { import },
+                    HashSet::from([package]),
+                )
+            })
+            .collect();
+    }
+
+    // The leading segments of paths, like `std` in `std::fmt`, name no item of their own
+    let names = traverse(import.walk(), Order::Pre)
+        .filter(|node| node.child_count() == 0 && node.kind().ends_with("identifier"))
+        .filter(|node| {
+            node.parent()
+                .and_then(|parent| parent.child_by_field_name("path"))
+                != Some(*node)
+        })
+        .map(|node| node_value(source_file, node))
+        .filter(|name| !["crate", "self", "super"].contains(name))
+        .collect();
+
+    vec![(import, names)]
+}
+
+/// The Rust path relative to the module of the file, like `super::prices::total` in
+/// `crate::checkout::cart`, written from the root of the crate, like `crate::checkout::prices::total`
+fn absolute_rust_path(path: &str, source_path: &Path) -> Result<String, String> {
+    let unknown = || {
+        format!(
+            "`{}` is relative to the module of {}, whose path is unknown",
+            path,
+            source_path.display()
+        )
+    };
+    let mut module: Vec<String> = rust_module_path(source_path)
+        .ok_or_else(unknown)?
+        .split("::")
+        .map(str::to_string)
+        .collect();
+    let mut rest = path.strip_prefix("self::").unwrap_or(path);
+    while let Some(after) = rest.strip_prefix("super::") {
+        if module.len() == 1 {
+            return Err(format!(
+                "`{}` goes above the root of the crate from {}",
+                path,
+                source_path.display()
+            ));
+        }
+        module.pop();
+        rest = after;
+    }
+
+    Ok(format!("{}::{}", module.join("::"), rest))
+}
+
+/// An import written for the target file, whose relative module is found from another directory
+fn import_for(
+    language: &str,
+    source_file: &str,
+    import: Node,
+    source_path: &Path,
+    target_directory: &Path,
+) -> Result<String, String> {
+    let text = node_value(source_file, import);
+    if language == "go" {
+        return Ok(match import.kind() {
+            "import_spec" => format!("import {}", text),
+            _ => text.to_string(),
+        });
+    }
+    // `self` and `super` mean another module in the target, so the path starts from the crate
+    if language == "rust" {
+        let Some(argument) = import.child_by_field_name("argument") else {
+            return Ok(text.to_string());
+        };
+        let path = node_value(source_file, argument);
+        if !path.starts_with("self::") && !path.starts_with("super::") {
+            return Ok(text.to_string());
+        }
+        let start = argument.start_byte() - import.start_byte();
+        let end = argument.end_byte() - import.start_byte();
+        return Ok(format!(
+            "{}{}{}",
+            &text[..start],
+            absolute_rust_path(path, source_path)?,
+            &text[end..]
+        ));
+    }
+    let source_directory = directory_of(source_path);
+    let specifier = import
+        .child_by_field_name("source")
+        .map(|source| node_value(source_file, source));
+    match specifier {
+        Some(specifier) if specifier[1..].starts_with('.') => {
+            let quote = &specifier[..1];
+            let module = source_directory.join(specifier.trim_matches(|c| c == '"' || c == '\''));
+            let moved = relative_path(target_directory, &module);
+            Ok(text.replace(specifier, &format!("{}{}{}", quote, moved, quote)))
+        }
+        _ => Ok(text.to_string()),
+    }
+}
+
+/// The source without the lines the text between the two bytes is on
+fn cut_lines(source_file: &str, start_byte: usize, end_byte: usize) -> String {
+    let line_start = source_file[..start_byte]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let rest = &source_file[end_byte..];
+    let line_end = end_byte + rest.find('\n').map_or(rest.len(), |newline| newline + 1);
+
+    format!("{}{}", &source_file[..line_start], &source_file[line_end..])
+}
+
+/// Moves the function out of the source into the target, which is created when there is none
+fn move_function(
+    source_path: &Path,
+    source_file: &str,
+    target_path: &Path,
+    target_file: Option<&str>,
+    selection: &Selection,
+) -> Result<Move, String> {
+    let supported_language = detect_language(&source_path.to_string_lossy())?;
+    let language = supported_language.to_string();
+    let target_language = detect_language(&target_path.to_string_lossy())?;
+    if target_language.to_string() != language {
+        return Err(format!(
+            "{} is not a {} file, like the function",
+            target_path.display(),
+            language
+        ));
+    }
+    let source_directory = directory_of(source_path);
+    let target_directory = directory_of(target_path);
+    if language == "go" && source_directory != target_directory {
+        return Err(
+            "Go functions can only be moved within their package, to a file of the same directory"
+                .to_string(),
+        );
+    }
+
+    let tree = parse_with_thread_parser(source_file, supported_language.language())
+        .ok_or("failed to parse the file")?;
+    let function_node = supported_language
+        .find_correct_node(
+            source_file,
+            &tree,
+            &selection.parent_identifier,
+            &selection.function_identifier,
+            &selection.kind,
+        )
+        .map_err(|e| explain_not_found(supported_language.as_ref(), e))?;
+    let name = name_of(source_file, function_node)
+        .ok_or("the function has no name")?
+        .to_string();
+    let (item, start_byte, end_byte) = item_range(source_file, function_node)?;
+
+    // Visible from the file it was moved out of
+    let mut code = source_file[start_byte..end_byte].to_string();
+    let mut cursor = function_node.walk();
+    let visibility = match language.as_str() {
+        "rust"
+            if !function_node
+                .children(&mut cursor)
+                .any(|child| child.kind() == "visibility_modifier") =>
+        {
+            Some((function_node.start_byte(), "pub(crate) "))
+        }
+        "javascript" | "jsx" | "typescript" | "tsx" if item.kind() != "export_statement" => {
+            Some((item.start_byte(), "export "))
+        }
+        _ => None,
+    };
+    if let Some((byte, keyword)) = visibility {
+        code.insert_str(byte - start_byte, keyword);
+    }
+
+    let used = identifiers(source_file, function_node);
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let rest: Vec<Node> = root
+        .named_children(&mut cursor)
+        .filter(|node| *node != item && !IMPORT_KINDS.contains(&node.kind()))
+        .collect();
+    let still_used: HashSet<&str> = rest
+        .iter()
+        .flat_map(|node| identifiers(source_file, *node))
+        .collect();
+
+    // The imports the function needs go along with it, and leave the source if nothing else
+    // there needs them
+    let mut needed_imports = vec![];
+    let mut unused_imports = vec![];
+    for import in imports(&tree) {
+        for (node, names) in imported_names(&language, source_file, import) {
+            if names.is_disjoint(&used) {
+                continue;
+            }
+            needed_imports.push(import_for(
+                &language,
+                source_file,
+                node,
+                source_path,
+                &target_directory,
+            )?);
+            if names.is_disjoint(&still_used) {
+                unused_imports.push(node);
+            }
+        }
+    }
+    // Grouped Go imports whose every spec goes are removed whole
+    let mut cuts: Vec<Node> = vec![];
+    for node in unused_imports.iter().copied() {
+        let declaration = node
+            .parent()
+            .and_then(|list| list.parent())
+            .filter(|_| node.kind() == "import_spec");
+        let node = match declaration {
+            Some(declaration)
+                if traverse(declaration.walk(), Order::Pre)
+                    .filter(|spec| spec.kind() == "import_spec")
+                    .all(|spec| unused_imports.contains(&spec)) =>
+            {
+                declaration
+            }
+            _ => node,
+        };
+        if !cuts.contains(&node) {
+            cuts.push(node);
+        }
+    }
+    cuts.push(item);
+    cuts.sort_by_key(|node| std::cmp::Reverse(node.start_byte()));
+    let mut source = source_file.to_string();
+    for node in cuts {
+        source = if node == item {
+            cut(&source, start_byte, end_byte)
+        } else {
+            cut_lines(&source, node.start_byte(), node.end_byte())
+        };
+    }
+
+    let mut notes = vec![];
+    let import = match language.as_str() {
+        "rust" => rust_module_path(target_path).map(|module| format!("use {}::{};", module, name)),
+        "javascript" | "jsx" | "typescript" | "tsx" => {
+            let mut module = relative_path(&source_directory, target_path);
+            if language == "typescript" || language == "tsx" {
+                module = module
+                    .trim_end_matches(".tsx")
+                    .trim_end_matches(".ts")
+                    .to_string();
+            }
+            Some(format!("import {{ {} }} from \"{}\";", name, module))
+        }
+        _ => None,
+    };
+    if still_used.contains(name.as_str()) && language != "go" {
+        match import {
+            Some(import) => {
+                let source_tree = parse_with_thread_parser(&source, supported_language.language())
+                    .ok_or("failed to parse the file once the function was cut")?;
+                source = insert_after(&source, imports(&source_tree).last().copied(), &import);
+            }
+            None => notes.push(format!(
+                "import {} where it is still used, as the module path of {} is unknown",
+                name,
+                target_path.display()
+            )),
+        }
+    }
+
+    let mut target = target_file.unwrap_or_default().to_string();
+    if target_file.is_none() && language == "go" {
+        let package = root_child(&tree, "package_clause")
+            .map_or("package main", |clause| node_value(source_file, clause));
+        target = format!("{}\n", package);
+    }
+    let target_tree = parse_with_thread_parser(&target, supported_language.language())
+        .ok_or("failed to parse the target file")?;
+    let missing_imports: Vec<String> = needed_imports
+        .into_iter()
+        .filter(|import| !target.contains(import.as_str()))
+        .collect();
+    if !missing_imports.is_empty() {
+        let missing_imports = missing_imports.join("\n");
+        target = match imports(&target_tree).last() {
+            Some(import) => insert_after(&target, Some(*import), &missing_imports),
+            // Go imports come after the package clause, set apart by a blank line
+            None => match root_child(&target_tree, "package_clause") {
+                Some(clause) => {
+                    insert_after(&target, Some(clause), &format!("\n{}", missing_imports))
+                }
+                None => insert_after(&target, None, &missing_imports),
+            },
+        };
+    }
+    target = match target.trim_end() {
+        "" => format!("{}\n", code),
+        rest => format!("{}\n\n{}\n", rest, code),
+    };
+
+    // Items of the source the function refers to are not moved along
+    let left_behind: Vec<&str> = rest
+        .iter()
+        .filter_map(|node| name_of(source_file, *node))
+        .filter(|item_name| used.contains(item_name) && *item_name != name)
+        .collect();
+    if !left_behind.is_empty() && language != "go" {
+        notes.push(format!(
+            "{} still uses {} of {}, which may need to be made visible and imported",
+            name,
+            left_behind.join(", "),
+            source_path.display()
+        ));
+    }
+
+    Ok(Move {
+        source_file: source,
+        target_file: target,
+        notes,
+    })
+}
+
+/// The first item of the file of that kind
+pub fn root_child<'a>(tree: &'a Tree, kind: &str) -> Option<Node<'a>> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let child = root
+        .named_children(&mut cursor)
+        .find(|node| node.kind() == kind);
+
+    child
+}
+
+/// Moves the function, along with its doc comments, to the target file, and updates the imports
+/// of both files. New Rust files are declared as modules. Returns what is left to do by hand.
+pub fn extract_function(
+    source_path: &str,
+    target_path: &str,
+    selection: &Selection,
+) -> Result<Vec<String>, String> {
+    let source_file = read_to_string(source_path)
+        .map_err(|e| format!("failed to read {}: {}", source_path, e))?;
+    let target = Path::new(target_path);
+    let target_file = if target.exists() {
+        Some(read_to_string(target).map_err(|e| format!("failed to read {}: {}", target_path, e))?)
+    } else {
+        None
+    };
+
+    let moved = move_function(
+        Path::new(source_path),
+        &source_file,
+        target,
+        target_file.as_deref(),
+        selection,
+    )?;
+    let mut notes = moved.notes;
+    let mut writes = vec![
+        (PathBuf::from(source_path), moved.source_file),
+        (target.to_path_buf(), moved.target_file),
+    ];
+    if target_file.is_none() && target_path.ends_with(".rs") {
+        match rust_parent_module(target) {
+            Some(parent_path) => {
+                let parent_module = read_to_string(&parent_path)
+                    .map_err(|e| format!("failed to read {}: {}", parent_path.display(), e))?;
+                let parent_module = match writes
+                    .iter()
+                    .find(|(path, _)| normalize(path) == parent_path)
+                {
+                    Some((_, source)) => source.clone(),
+                    None => parent_module,
+                };
+                if let Some(declared) = declare_rust_module(&parent_module, target) {
+                    writes.retain(|(path, _)| normalize(path) != parent_path);
+                    writes.push((parent_path, declared));
+                }
+            }
+            None => notes.push(format!(
+                "declare {} as a module, as no parent module was found",
+                target_path
+            )),
+        }
+    }
+
+    if let Some(directory) = target
+        .parent()
+        .filter(|directory| !directory.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(directory)
+            .map_err(|e| format!("failed to create {}: {}", directory.display(), e))?;
+    }
+    for (path, contents) in writes {
+        write(&path, contents).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+    }
+
+    Ok(notes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::extract::{move_function, relative_path, Selection};
+
+    fn selection(function: &str) -> Selection {
+        Selection {
+            parent_identifier: None,
+            function_identifier: Some(function.to_string()),
+            kind: None,
+        }
+    }
+
+    #[test]
+    fn moves() {
+        let source = r#"//! Prices
+use std::collections::HashMap;
+use std::fmt::Write;
+
+fn main() {
+    println!("{}", total(&HashMap::new()));
+}
+
+/// Adds the prices up
+#[inline]
+fn total(prices: &HashMap<String, u32>) -> u32 {
+    prices.values().sum()
+}
+
+fn other() {}
+"#;
+        let moved = move_function(
+            Path::new("/project/src/main.rs"),
+            source,
+            Path::new("/project/src/prices.rs"),
+            None,
+            &selection("total"),
+        )
+        .unwrap();
+        assert_eq!(
+            r#"//! Prices
+use std::collections::HashMap;
+use std::fmt::Write;
+use crate::prices::total;
+
+fn main() {
+    println!("{}", total(&HashMap::new()));
+}
+
+fn other() {}
+"#,
+            moved.source_file
+        );
+        assert_eq!(
+            r#"use std::collections::HashMap;
+
+/// Adds the prices up
+#[inline]
+pub(crate) fn total(prices: &HashMap<String, u32>) -> u32 {
+    prices.values().sum()
+}
+"#,
+            moved.target_file
+        );
+
+        let source = r#"import { format } from "./format";
+
+/** The price, formatted */
+function price(cents: number): string {
+    return format(cents / 100);
+}
+"#;
+        let moved = move_function(
+            Path::new("/project/src/checkout/cart.ts"),
+            source,
+            Path::new("/project/src/pricing/price.ts"),
+            Some("export const currency = \"EUR\";\n"),
+            &selection("price"),
+        )
+        .unwrap();
+        assert_eq!("", moved.source_file);
+        assert_eq!(
+            r#"import { format } from "../checkout/format";
+export const currency = "EUR";
+
+/** The price, formatted */
+export function price(cents: number): string {
+    return format(cents / 100);
+}
+"#,
+            moved.target_file
+        );
+
+        let source = r#"use super::prices::{Price, Tax};
+use self::discount::rate;
+
+pub fn total(prices: &[Price], tax: Tax) -> u32 {
+    prices.iter().map(|price| price.cents * rate()).sum::<u32>() + tax.cents
+}
+"#;
+        let moved = move_function(
+            Path::new("/project/src/checkout/cart.rs"),
+            source,
+            Path::new("/project/src/total.rs"),
+            None,
+            &selection("total"),
+        )
+        .unwrap();
+        assert_eq!(
+            r#"use crate::checkout::prices::{Price, Tax};
+use crate::checkout::cart::discount::rate;
+
+pub fn total(prices: &[Price], tax: Tax) -> u32 {
+    prices.iter().map(|price| price.cents * rate()).sum::<u32>() + tax.cents
+}
+"#,
+            moved.target_file
+        );
+        assert!(move_function(
+            Path::new("/project/src/main.rs"),
+            "use super::prices::Price;\n\nfn total(price: Price) {}\n",
+            Path::new("/project/src/total.rs"),
+            None,
+            &selection("total"),
+        )
+        .is_err());
+
+        let source = "impl Cart {\n    fn total(&self) -> u32 { 0 }\n}\n";
+        assert!(move_function(
+            Path::new("src/cart.rs"),
+            source,
+            Path::new("src/total.rs"),
+            None,
+            &selection("total"),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn relative_paths() {
+        assert_eq!(
+            "./price.ts",
+            relative_path(Path::new("/src"), Path::new("/src/price.ts"))
+        );
+        assert_eq!(
+            "../../lib/price",
+            relative_path(Path::new("/src/a/b"), Path::new("/src/lib/price"))
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use std::fs::{remove_file, write};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::edit::Edit;
+use crate::openai::Prompt;
+
+/// How many patches were applied so far, which tells apart the patch files of a run
+static PATCHES_APPLIED: AtomicUsize = AtomicUsize::new(0);
+
+/// How the commit of an applied suggestion gets its message
+pub enum CommitMessage {
+    /// Summarised from the diff by the model
+    Generated,
+    Given(String),
+}
+
+fn git(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .args(args)
+        .output()
+        .map_err(|e| format!("failed to run git: {}", e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        Err(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Stores the contents as a blob, and returns its id
+fn hash_object(contents: &str) -> Result<String, String> {
+    let mut child = Command::new("git")
+        .args(["hash-object", "-w", "--stdin"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run git: {}", e))?;
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(contents.as_bytes())
+        .map_err(|e| e.to_string())?;
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(format!(
+            "git hash-object failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// Applies the edit of the file, as it was when parsed, with `git apply --3way`. When the file
+/// changed since, git merges the edit in, and leaves conflict markers where it cannot.
+pub fn apply_with_3way(file_path: &str, parsed_source: &str, edit: &Edit) -> Result<(), String> {
+    let top_level = PathBuf::from(git(&["rev-parse", "--show-toplevel"])?.trim())
+        .canonicalize()
+        .map_err(|e| e.to_string())?;
+    let absolute_path = Path::new(file_path)
+        .canonicalize()
+        .map_err(|e| format!("failed to find {}: {}", file_path, e))?;
+    let Ok(path) = absolute_path.strip_prefix(&top_level) else {
+        return Err(format!("{} is not in the git repository", file_path));
+    };
+    // Both blobs are stored, so that git can merge from the file as it was parsed even if it was
+    // never committed
+    let mut edited = parsed_source.to_string();
+    edited.replace_range(edit.span.start_byte..edit.span.end_byte, &edit.new_text);
+    let index = format!("{}..{}", hash_object(parsed_source)?, hash_object(&edited)?);
+    let patch = edit.to_patch(
+        parsed_source,
+        &path.to_string_lossy().replace('\\', "/"),
+        &index,
+    );
+    let patch_path = std::env::temp_dir().join(format!(
+        "senior-{}-{}.patch",
+        std::process::id(),
+        PATCHES_APPLIED.fetch_add(1, Ordering::SeqCst)
+    ));
+    write(&patch_path, patch).map_err(|e| e.to_string())?;
+    let applied = git(&[
+        "-C",
+        &top_level.to_string_lossy(),
+        "apply",
+        "--3way",
+        &patch_path.to_string_lossy(),
+    ]);
+    let _ = remove_file(&patch_path);
+
+    applied.map(|_| ()).map_err(|e| {
+        if e.contains("with conflicts") {
+            format!("the suggestion conflicts with changes to {}, resolve the conflict markers git left there", file_path)
+        } else {
+            e
+        }
+    })
+}
+
+/// Who runs senior, as `Name <email>` from the git config
+pub fn user() -> Result<String, String> {
+    let setting = |key: &str| {
+        git(&["config", key])
+            .map(|value| value.trim().to_string())
+            .ok()
+            .filter(|value| !value.is_empty())
+            .ok_or_else(|| {
+                format!(
+                    "{} is not set, set it with `git config {} <value>`",
+                    key, key
+                )
+            })
+    };
+
+    Ok(format!(
+        "{} <{}>",
+        setting("user.name")?,
+        setting("user.email")?
+    ))
+}
+
+/// The uncommitted changes of the files
+pub fn diff(file_paths: &[&str]) -> Result<String, String> {
+    git(&[&["diff", "--"], file_paths].concat())
+}
+
+/// Commits the current contents of the files, and only of those files
+pub fn commit(file_paths: &[&str], message: &str) -> Result<(), String> {
+    git(&[&["commit", "--quiet", "-m", message, "--"], file_paths].concat()).map(|_| ())
+}
+
+/// The files with uncommitted changes, untracked ones included, as canonical paths
+pub fn changed_files() -> Result<Vec<PathBuf>, String> {
+    let top_level = git(&["rev-parse", "--show-toplevel"])?;
+    // Without commits there is no HEAD to compare with, and every file is either staged or untracked
+    let modified = if git(&["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok() {
+        git(&["diff", "--name-only", "HEAD"])?
+    } else {
+        git(&["diff", "--name-only", "--cached"])?
+    };
+    let untracked = git(&["ls-files", "--others", "--exclude-standard", "--full-name"])?;
+
+    Ok(modified
+        .lines()
+        .chain(untracked.lines())
+        .filter_map(|file_path| {
+            Path::new(top_level.trim())
+                .join(file_path)
+                .canonicalize()
+                .ok()
+        })
+        .collect())
+}
+
+pub fn draft_commit_message_instructions(diff: &str) -> Prompt {
+    Prompt {
+        system:
+            r#"Summarise the diff you are given into a single line conventional commit message, like
+"perf(parser): avoid re-allocation in tokenize". Respond only with the message."#
+                .to_string(),
+        user: diff.to_string(),
+    }
+}
+
+/// Keeps the first line of the answer, without the quotes or backticks models like to add
+pub fn clean_commit_message(answer: &str) -> String {
+    answer
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default()
+        .trim_matches(|c| c == '`' || c == '"' || c == '\'')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::git::clean_commit_message;
+
+    #[test]
+    fn commit_messages() {
+        assert_eq!(
+            "perf(parser): avoid re-allocation in tokenize",
+            clean_commit_message("\n`perf(parser): avoid re-allocation in tokenize`\n\nBecause...")
+        );
+        assert_eq!("", clean_commit_message(""));
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::BTreeSet;
+use std::process::Command;
+
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+
+pub const GITHUB_TOKEN_ENV_VAR_KEY: &str = "GITHUB_TOKEN";
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+#[derive(Deserialize, Debug)]
+struct PullRequestHead {
+    sha: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct PullRequest {
+    head: PullRequestHead,
+}
+
+/// A file touched by a pull request. Binary and very large files come without a patch.
+#[derive(Deserialize, Debug)]
+pub struct PullRequestFile {
+    pub filename: String,
+    pub status: String,
+    pub patch: Option<String>,
+}
+
+/// A comment attached to a line of the new version of a file
+#[derive(Serialize, Debug)]
+pub struct ReviewComment {
+    pub path: String,
+    pub line: usize,
+    pub side: &'static str,
+    pub body: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Review {
+    pub commit_id: String,
+    pub event: &'static str,
+    pub body: String,
+    pub comments: Vec<ReviewComment>,
+}
+
+/// The lines of the new version of a file that a patch touches
+#[derive(Default, Debug, PartialEq)]
+pub struct ChangedLines {
+    /// Lines that were added or modified
+    pub added: BTreeSet<usize>,
+    /// Lines shown in the diff, added or context, which are the only ones that can be commented on
+    pub commentable: BTreeSet<usize>,
+}
+
+/// Reads the line numbers of the new version of a file out of a unified diff patch, as
+/// GitHub returns it for each file of a pull request.
+pub fn parse_patch(patch: &str) -> ChangedLines {
+    let mut changed_lines = ChangedLines::default();
+    let mut line = 0;
+    for patch_line in patch.lines() {
+        if let Some(header) = patch_line.strip_prefix("@@ ") {
+            // @@ -old_start,old_count +new_start,new_count @@
+            line = header
+                .split_whitespace()
+                .find_map(|range| range.strip_prefix('+'))
+                .and_then(|range| range.split(',').next())
+                .and_then(|start| start.parse().ok())
+                .unwrap_or(0);
+        } else if patch_line.starts_with('+') {
+            changed_lines.added.insert(line);
+            changed_lines.commentable.insert(line);
+            line += 1;
+        } else if patch_line.starts_with(' ') || patch_line.is_empty() {
+            changed_lines.commentable.insert(line);
+            line += 1;
+        }
+    }
+
+    changed_lines
+}
+
+/// Reads `owner/name` out of the url of a GitHub remote, either over https or ssh
+pub fn repo_from_remote_url(url: &str) -> Option<String> {
+    let path = url
+        .trim()
+        .strip_prefix("git@github.com:")
+        .or_else(|| url.trim().split_once("github.com/").map(|(_, path)| path))?;
+    let repo = path.trim_end_matches('/').trim_end_matches(".git");
+
+    (repo.split('/').count() == 2).then(|| repo.to_string())
+}
+
+/// The GitHub repository the `origin` remote of the current directory points to
+pub fn repo_from_origin() -> Result<String, String> {
+    let output = Command::new("git")
+        .args(["remote", "get-url", "origin"])
+        .output()
+        .map_err(|e| e.to_string())?;
+    let url = String::from_utf8_lossy(&output.stdout);
+
+    repo_from_remote_url(&url)
+        .ok_or_else(|| format!("the origin remote `{}` is not on GitHub", url.trim()))
+}
+
+pub struct GitHubClient {
+    client: Client,
+    token: String,
+    repo: String,
+}
+
+impl GitHubClient {
+    pub fn new(token: &str, repo: &str) -> Self {
+        Self {
+            client: Client::new(),
+            token: token.to_string(),
+            repo: repo.to_string(),
+        }
+    }
+    fn request(&self, method: reqwest::Method, path: &str) -> RequestBuilder {
+        self.client
+            .request(
+                method,
+                format!("{}/repos/{}/{}", GITHUB_API_URL, self.repo, path),
+            )
+            .bearer_auth(&self.token)
+            .header("Accept", "application/vnd.github+json")
+            .header("User-Agent", "senior")
+    }
+    async fn send(&self, request: RequestBuilder) -> Result<reqwest::Response, String> {
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(format!(
+                "GitHub answered with {}: {}",
+                response.status(),
+                response.text().await.unwrap_or_default()
+            ))
+        }
+    }
+    /// The commit the pull request currently points to
+    pub async fn pull_request_head(&self, pr: u64) -> Result<String, String> {
+        let request = self.request(reqwest::Method::GET, &format!("pulls/{}", pr));
+        let pull_request: PullRequest = self
+            .send(request)
+            .await?
+            .json()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(pull_request.head.sha)
+    }
+    pub async fn pull_request_files(&self, pr: u64) -> Result<Vec<PullRequestFile>, String> {
+        let mut files = vec![];
+        for page in 1.. {
+            let request = self.request(
+                reqwest::Method::GET,
+                &format!("pulls/{}/files?per_page=100&page={}", pr, page),
+            );
+            let page_files: Vec<PullRequestFile> = self
+                .send(request)
+                .await?
+                .json()
+                .await
+                .map_err(|e| e.to_string())?;
+            if page_files.is_empty() {
+                break;
+            }
+            files.extend(page_files);
+        }
+
+        Ok(files)
+    }
+    /// The contents of a file as of the given commit
+    pub async fn file_contents(&self, path: &str, commit: &str) -> Result<String, String> {
+        let request = self
+            .request(
+                reqwest::Method::GET,
+                &format!("contents/{}?ref={}", path, commit),
+            )
+            .header("Accept", "application/vnd.github.raw");
+
+        self.send(request)
+            .await?
+            .text()
+            .await
+            .map_err(|e| e.to_string())
+    }
+    pub async fn post_review(&self, pr: u64, review: &Review) -> Result<(), String> {
+        let request = self
+            .request(reqwest::Method::POST, &format!("pulls/{}/reviews", pr))
+            .json(review);
+
+        self.send(request).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::{parse_patch, repo_from_remote_url};
+
+    #[test]
+    fn patch_lines() {
+        let patch = r#"@@ -1,4 +1,5 @@
+ fn greet() {
+-    println!("hello");
++    println!("HELLO");
++    println!("WORLD");
+ }
+@@ -10,2 +11,2 @@ fn main() {
+     greet();
+-    greet();
++    shout();"#;
+        let changed_lines = parse_patch(patch);
+
+        assert_eq!(vec![2, 3, 12], Vec::from_iter(changed_lines.added));
+        assert_eq!(
+            vec![1, 2, 3, 4, 11, 12],
+            Vec::from_iter(changed_lines.commentable)
+        );
+    }
+
+    #[test]
+    fn remote_urls() {
+        assert_eq!(
+            Some("brurucy/senior".to_string()),
+            repo_from_remote_url("https://github.com/brurucy/senior.git\n")
+        );
+        assert_eq!(
+            Some("brurucy/senior".to_string()),
+            repo_from_remote_url("git@github.com:brurucy/senior.git")
+        );
+        assert_eq!(
+            None,
+            repo_from_remote_url("https://gitlab.com/brurucy/senior")
+        );
+    }
+}
@@ -0,0 +1,529 @@
+use tree_sitter::Node;
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::helpers::tree_sitter::node_value;
+use crate::supported_languages::supported_language::SupportedLanguage;
+
+const LOOP_KINDS: &[&str] = &[
+    "for_expression",
+    "while_expression",
+    "loop_expression",
+    "for_statement",
+    "for_in_statement",
+    "while_statement",
+    "do_statement",
+];
+
+const MAX_EXPRESSION_LENGTH: usize = 60;
+
+const BLOCK_KINDS: &[&str] = &["block", "statement_block"];
+
+// Calls that block the thread, unless awaited, like `std::thread::sleep` or `fs.readFileSync`
+const BLOCKING_CALLS: &[&str] = &[
+    "thread::sleep",
+    "std::fs::",
+    "fs::read",
+    "fs::write",
+    "File::open",
+    "File::create",
+    "block_on",
+    "blocking::",
+];
+
+const LOCK_METHODS: &[&str] = &["lock", "read", "write"];
+
+// Methods that panic on `None` or `Err`
+const UNWRAPPING_METHODS: &[&str] = &["unwrap", "expect", "unwrap_err", "expect_err"];
+
+// Rust macros that panic, either always or when an assertion fails
+const PANICKING_MACROS: &[&str] = &[
+    "panic",
+    "unreachable",
+    "todo",
+    "unimplemented",
+    "assert",
+    "assert_eq",
+    "assert_ne",
+];
+
+// Indexing out of bounds panics in Rust and Go, and throws in Java
+const INDEX_KINDS: &[&str] = &["index_expression", "array_access"];
+
+const THROW_KINDS: &[&str] = &["throw_statement", "throw_expression"];
+
+/// What the model is asked to optimise the code for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Goal {
+    #[default]
+    Speed,
+    /// Fewer heap allocations
+    Allocations,
+    /// No blocking calls, locks held across awaits or awaits that could run concurrently
+    AsyncCorrectness,
+    /// No panics or exceptions, like failed unwraps, indexing out of bounds or division by zero
+    PanicFreedom,
+}
+
+/// An expression of the original code that the goal calls for a closer look at
+#[derive(Debug, PartialEq)]
+pub struct Suspect {
+    pub line: usize,
+    pub expression: String,
+    pub reason: Option<String>,
+}
+
+impl std::fmt::Display for Suspect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: `{}`", self.line, self.expression)?;
+        if let Some(reason) = &self.reason {
+            write!(f, ", {}", reason)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Suspect {
+    /// The first line of the node, shortened to a readable length
+    pub fn new(source_file: &str, node: Node, reason: Option<String>) -> Self {
+        let expression = node_value(source_file, node);
+        let first_line = expression.lines().next().unwrap_or_default();
+        let expression =
+            if first_line.len() < expression.len() || first_line.len() > MAX_EXPRESSION_LENGTH {
+                let end = first_line
+                    .char_indices()
+                    .map(|(index, _)| index)
+                    .take_while(|index| *index <= MAX_EXPRESSION_LENGTH)
+                    .last()
+                    .unwrap_or_default();
+                format!("{}...", &first_line[..end])
+            } else {
+                first_line.to_string()
+            };
+
+        Self {
+            line: node.start_position().row + 1,
+            expression,
+            reason,
+        }
+    }
+}
+
+/// The suspects as a markdown list
+pub fn render_suspects(suspects: &[Suspect]) -> String {
+    suspects
+        .iter()
+        .map(|suspect| format!("- {}", suspect))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn is_in_loop(node: Node, function_node: Node) -> bool {
+    let mut ancestor = node.parent();
+    while let Some(node) = ancestor.filter(|node| *node != function_node) {
+        if LOOP_KINDS.contains(&node.kind()) {
+            return true;
+        }
+        ancestor = node.parent();
+    }
+
+    false
+}
+
+/// The expressions of the function that likely allocate, outermost first. Those nested in another
+/// suspect, like the `to_string()` in `vec![name.to_string()]`, are left out.
+fn find_allocation_suspects(
+    supported_language: &dyn SupportedLanguage,
+    source_file: &str,
+    function_node: Node,
+) -> Vec<Suspect> {
+    let mut suspects: Vec<Node> = vec![];
+    for node in traverse(function_node.walk(), Order::Pre) {
+        let is_nested = suspects
+            .last()
+            .is_some_and(|outer| node.end_byte() <= outer.end_byte());
+        if !is_nested && supported_language.is_allocation_suspect(source_file, node) {
+            suspects.push(node);
+        }
+    }
+
+    suspects
+        .into_iter()
+        .map(|node| {
+            let reason = is_in_loop(node, function_node).then(|| "in a loop".to_string());
+            Suspect::new(source_file, node, reason)
+        })
+        .collect()
+}
+
+/// Whether the function is declared `async`, looking at what comes before its body
+pub fn is_async_function(code: &str) -> bool {
+    let head = code.split('{').next().unwrap_or_default();
+    let head = head.split("=>").next().unwrap_or_default();
+
+    head.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .any(|word| word == "async")
+}
+
+fn is_awaited(node: Node) -> bool {
+    node.parent()
+        .is_some_and(|parent| parent.kind() == "await_expression")
+}
+
+fn contains_await(node: Node) -> bool {
+    traverse(node.walk(), Order::Pre).any(|node| node.kind() == "await_expression")
+}
+
+// The names a `let` or `const` statement binds
+fn bound_names<'a>(source_file: &'a str, statement: Node<'a>) -> Vec<&'a str> {
+    let patterns: Vec<Node> = match statement.kind() {
+        "let_declaration" => statement
+            .child_by_field_name("pattern")
+            .into_iter()
+            .collect(),
+        "lexical_declaration" | "variable_declaration" => {
+            let mut cursor = statement.walk();
+            let declarators: Vec<Node> = statement
+                .named_children(&mut cursor)
+                .filter_map(|declarator| declarator.child_by_field_name("name"))
+                .collect();
+            declarators
+        }
+        _ => vec![],
+    };
+
+    patterns
+        .into_iter()
+        .flat_map(|pattern| traverse(pattern.walk(), Order::Pre))
+        .filter(|node| node.kind() == "identifier")
+        .map(|node| node_value(source_file, node))
+        .collect()
+}
+
+fn mentions_any(source_file: &str, node: Node, names: &[&str]) -> bool {
+    traverse(node.walk(), Order::Pre)
+        .any(|node| node.kind() == "identifier" && names.contains(&node_value(source_file, node)))
+}
+
+// Whether the statement binds a guard from a lock that is not awaited, like `data.lock().unwrap()`
+fn binds_a_lock_guard(source_file: &str, statement: Node) -> bool {
+    statement.kind() == "let_declaration"
+        && statement.child_by_field_name("value").is_some_and(|value| {
+            traverse(value.walk(), Order::Pre).any(|node| {
+                node.kind() == "call_expression"
+                    && !is_awaited(node)
+                    && node
+                        .child_by_field_name("function")
+                        .and_then(|function| function.child_by_field_name("field"))
+                        .is_some_and(|method| {
+                            LOCK_METHODS.contains(&node_value(source_file, method))
+                        })
+            })
+        })
+}
+
+/// Calls that block the thread, locks held across an await, and awaits that do not depend on the
+/// await right before them, and so could run concurrently
+fn find_async_suspects(source_file: &str, function_node: Node) -> Vec<Suspect> {
+    let mut suspects = vec![];
+    for node in traverse(function_node.walk(), Order::Pre) {
+        if node.kind() == "call_expression" && !is_awaited(node) {
+            let callee = node
+                .child_by_field_name("function")
+                .map(|function| node_value(source_file, function))
+                .unwrap_or_default();
+            if BLOCKING_CALLS.iter().any(|call| callee.contains(call)) || callee.ends_with("Sync") {
+                suspects.push(Suspect::new(
+                    source_file,
+                    node,
+                    Some("blocks the thread".to_string()),
+                ));
+            }
+        }
+
+        if !BLOCK_KINDS.contains(&node.kind()) {
+            continue;
+        }
+        let mut cursor = node.walk();
+        let statements: Vec<Node> = node.named_children(&mut cursor).collect();
+        for (index, statement) in statements.iter().enumerate() {
+            let later_statements = &statements[index + 1..];
+            if binds_a_lock_guard(source_file, *statement) {
+                let guards = bound_names(source_file, *statement);
+                let held_across_await = later_statements
+                    .iter()
+                    .take_while(|later| {
+                        !node_value(source_file, **later).starts_with("drop(")
+                            || !mentions_any(source_file, **later, &guards)
+                    })
+                    .any(|later| contains_await(*later));
+                if held_across_await {
+                    suspects.push(Suspect::new(
+                        source_file,
+                        *statement,
+                        Some("holds a lock across an await".to_string()),
+                    ));
+                }
+            }
+
+            let Some(next_statement) = later_statements.first() else {
+                continue;
+            };
+            let names = bound_names(source_file, *statement);
+            if !names.is_empty()
+                && contains_await(*statement)
+                && contains_await(*next_statement)
+                && !mentions_any(source_file, *next_statement, &names)
+            {
+                suspects.push(Suspect::new(
+                    source_file,
+                    *next_statement,
+                    Some(format!(
+                        "awaited after line {} without depending on it",
+                        statement.start_position().row + 1
+                    )),
+                ));
+            }
+        }
+    }
+    suspects.sort_by_key(|suspect| suspect.line);
+
+    suspects
+}
+
+fn callee_method<'a>(source_file: &'a str, call: Node<'a>) -> Option<&'a str> {
+    let function = call.child_by_field_name("function")?;
+    let method = function
+        .child_by_field_name("field")
+        .or_else(|| function.child_by_field_name("property"))
+        .unwrap_or(function);
+
+    Some(node_value(source_file, method))
+}
+
+// Why the node may panic or throw, if it may
+fn panic_reason(language: &str, source_file: &str, node: Node) -> Option<&'static str> {
+    match node.kind() {
+        "call_expression" => match callee_method(source_file, node)? {
+            method if UNWRAPPING_METHODS.contains(&method) => Some("panics on None or Err"),
+            "panic" if language == "go" => Some("panics"),
+            _ => None,
+        },
+        "macro_invocation" => node
+            .child_by_field_name("macro")
+            .filter(|name| PANICKING_MACROS.contains(&node_value(source_file, *name)))
+            .map(|_| "panics"),
+        kind if INDEX_KINDS.contains(&kind) => Some("may be out of bounds"),
+        kind if THROW_KINDS.contains(&kind) => Some("throws"),
+        // Dividing by zero gives infinity in Javascript, rather than throwing
+        "binary_expression" if !["javascript", "jsx", "typescript", "tsx"].contains(&language) => {
+            let operator = node.child_by_field_name("operator")?;
+            let divisor = node.child_by_field_name("right")?;
+            let divides = ["/", "%"].contains(&node_value(source_file, operator));
+            // Literal divisors are known not to be zero, unless they are
+            let literal_divisor = divisor.kind().contains("literal")
+                && node_value(source_file, divisor)
+                    .chars()
+                    .any(|digit| digit.is_ascii_digit() && digit != '0');
+            (divides && !literal_divisor).then_some("may divide by zero")
+        }
+        _ => None,
+    }
+}
+
+/// The expressions of the function that may panic or throw, in the order they appear
+fn find_panic_suspects(
+    supported_language: &dyn SupportedLanguage,
+    source_file: &str,
+    function_node: Node,
+) -> Vec<Suspect> {
+    let language = supported_language.to_string();
+
+    traverse(function_node.walk(), Order::Pre)
+        .filter_map(|node| {
+            let reason = panic_reason(&language, source_file, node)?;
+            Some(Suspect::new(source_file, node, Some(reason.to_string())))
+        })
+        .collect()
+}
+
+impl Goal {
+    /// The expressions of the function the goal calls for a closer look at
+    pub fn find_suspects(
+        &self,
+        supported_language: &dyn SupportedLanguage,
+        source_file: &str,
+        function_node: Node,
+    ) -> Vec<Suspect> {
+        match self {
+            Goal::Speed => vec![],
+            Goal::Allocations => {
+                find_allocation_suspects(supported_language, source_file, function_node)
+            }
+            Goal::AsyncCorrectness => find_async_suspects(source_file, function_node),
+            Goal::PanicFreedom => {
+                find_panic_suspects(supported_language, source_file, function_node)
+            }
+        }
+    }
+    /// How the suspects are introduced, to the user and to the model
+    pub fn suspects_heading(&self) -> &'static str {
+        match self {
+            Goal::Speed => "",
+            Goal::Allocations => "These expressions likely allocate:",
+            Goal::AsyncCorrectness => "These expressions may be wrong in async code:",
+            Goal::PanicFreedom => "These expressions may panic or throw:",
+        }
+    }
+    /// What to tell the model about the goal, if anything beyond the default of speed
+    pub fn instructions(&self, suspects: &[Suspect]) -> String {
+        let goal = match self {
+            Goal::Speed => return "".to_string(),
+            Goal::Allocations => "Focus on reducing heap allocations.",
+            Goal::AsyncCorrectness => "Instead of optimising the code, review it for calls that block the thread, locks held across awaits, and sequential awaits that could run concurrently, and fix them.",
+            Goal::PanicFreedom => "Instead of optimising the code, make it handle the cases where it panics or throws, reporting them the way the code already reports errors, without changing what it does otherwise.",
+        };
+        if suspects.is_empty() {
+            return goal.to_string();
+        }
+
+        format!(
+            "{} {}\n{}",
+            goal,
+            self.suspects_heading(),
+            render_suspects(suspects)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::goal::{is_async_function, Goal};
+    use crate::helpers::tree_sitter::parse_source_with_language;
+    use crate::supported_languages::supported_language::detect_language;
+
+    #[test]
+    fn rust_allocations() {
+        let source = r#"fn names(people: &[Person]) -> Vec<String> {
+    let mut names = Vec::new();
+    for person in people {
+        names.push(format!("{} {}", person.first.clone(), person.last));
+    }
+    names.iter().map(|name| name.to_uppercase()).collect()
+}"#;
+        let tree = parse_source_with_language(source, tree_sitter_rust::language());
+        let supported_language = detect_language("src/lib.rs").unwrap();
+
+        let suspects =
+            Goal::Allocations.find_suspects(supported_language.as_ref(), source, tree.root_node());
+        let rendered: Vec<String> = suspects.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            vec![
+                "line 2: `Vec::new()`",
+                "line 4: `format!(\"{} {}\", person.first.clone(), person.last)`, in a loop",
+                "line 6: `names.iter().map(|name| name.to_uppercase()).collect()`",
+            ],
+            rendered
+        );
+        assert!(Goal::Allocations
+            .instructions(&suspects)
+            .ends_with("allocate:\n- line 2: `Vec::new()`\n- line 4: `format!(\"{} {}\", person.first.clone(), person.last)`, in a loop\n- line 6: `names.iter().map(|name| name.to_uppercase()).collect()`"));
+        assert_eq!("", Goal::Speed.instructions(&suspects));
+    }
+
+    #[test]
+    fn go_allocations() {
+        let source = r#"func join(names []string) string {
+	parts := make([]string, 0)
+	for _, name := range names {
+		parts = append(parts, fmt.Sprintf("<%s>", name))
+	}
+	return strings.Join(parts, ",")
+}"#;
+        let tree = parse_source_with_language(source, tree_sitter_go::language());
+        let supported_language = detect_language("main.go").unwrap();
+
+        let lines: Vec<(usize, bool)> = Goal::Allocations
+            .find_suspects(supported_language.as_ref(), source, tree.root_node())
+            .iter()
+            .map(|suspect| (suspect.line, suspect.reason.is_some()))
+            .collect();
+        assert_eq!(vec![(2, false), (4, true), (6, false)], lines);
+    }
+
+    #[test]
+    fn async_suspects() {
+        let source = r#"async fn sync_users(db: &Db, cache: Arc<Mutex<Cache>>) -> Result<()> {
+    let users = db.users().await?;
+    let groups = db.groups().await?;
+    let mut cache = cache.lock().unwrap();
+    std::thread::sleep(Duration::from_millis(10));
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    cache.store(users, groups);
+    Ok(())
+}"#;
+        let tree = parse_source_with_language(source, tree_sitter_rust::language());
+        let supported_language = detect_language("src/lib.rs").unwrap();
+
+        let suspects: Vec<String> = Goal::AsyncCorrectness
+            .find_suspects(supported_language.as_ref(), source, tree.root_node())
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            vec![
+                "line 3: `let groups = db.groups().await?;`, awaited after line 2 without depending on it",
+                "line 4: `let mut cache = cache.lock().unwrap();`, holds a lock across an await",
+                "line 5: `std::thread::sleep(Duration::from_millis(10))`, blocks the thread",
+            ],
+            suspects
+        );
+
+        assert!(is_async_function(source));
+        assert!(is_async_function("const load = async (id) => {}"));
+        assert!(!is_async_function(
+            "function load(id) { return asyncLoad(id); }"
+        ));
+    }
+
+    #[test]
+    fn panic_paths() {
+        let source = r#"fn average(scores: &HashMap<String, Vec<u32>>, name: &str) -> u32 {
+    let scores = scores.get(name).unwrap();
+    assert!(!scores.is_empty());
+    let first = scores[0];
+    let half = first / 2;
+    scores.iter().sum::<u32>() / scores.len() as u32 + half
+}"#;
+        let tree = parse_source_with_language(source, tree_sitter_rust::language());
+        let supported_language = detect_language("src/lib.rs").unwrap();
+
+        let suspects: Vec<String> = Goal::PanicFreedom
+            .find_suspects(supported_language.as_ref(), source, tree.root_node())
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            vec![
+                "line 2: `scores.get(name).unwrap()`, panics on None or Err",
+                "line 3: `assert!(!scores.is_empty())`, panics",
+                "line 4: `scores[0]`, may be out of bounds",
+                "line 6: `scores.iter().sum::<u32>() / scores.len() as u32`, may divide by zero",
+            ],
+            suspects
+        );
+
+        let source = "function ratio(a, b) {
+  if (b === 0) throw new Error(\"zero\");
+  return a / b;
+}";
+        let tree = parse_source_with_language(source, tree_sitter_javascript::language());
+        let supported_language = detect_language("ratio.js").unwrap();
+        let lines: Vec<usize> = Goal::PanicFreedom
+            .find_suspects(supported_language.as_ref(), source, tree.root_node())
+            .iter()
+            .map(|suspect| suspect.line)
+            .collect();
+        assert_eq!(vec![2], lines);
+    }
+}
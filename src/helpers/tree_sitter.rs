@@ -1,4 +1,9 @@
-use tree_sitter::{Language, Node, Tree, TreeCursor};
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+
+use tree_sitter::{InputEdit, Language, Node, Parser, Point, Tree, TreeCursor};
 use tree_sitter_traversal::{Order, traverse};
 
 pub fn find_all_of_kind<'a>(cursor: TreeCursor<'a>, kind: &str) -> Vec<Node<'a>> {
@@ -7,35 +12,273 @@ pub fn find_all_of_kind<'a>(cursor: TreeCursor<'a>, kind: &str) -> Vec<Node<'a>>
         .collect()
 }
 
-pub fn find_first_of_kind_with_field_value<'a>(
+pub fn find_all_of_kind_with_field_value<'a>(
     source: &str,
     cursor: TreeCursor<'a>,
     kind: &str,
     field: &str,
     value: &str,
-) -> Option<Node<'a>> {
-    traverse(cursor, Order::Pre).find(|node| {
-        if node.kind() == kind {
-            if let Some(child) = node.child_by_field_name(field) {
-                let nval = node_value(source, child);
-                if nval == value {
-                    return true;
-                }
-            }
-        }
-
-        false
-    })
+) -> Vec<Node<'a>> {
+    traverse(cursor, Order::Pre)
+        .filter(|node| {
+            node.kind() == kind
+                && node
+                    .child_by_field_name(field)
+                    .is_some_and(|child| node_value(source, child) == value)
+        })
+        .collect()
 }
 
+#[allow(clippy::needless_return)]
 pub fn node_value<'a>(source: &'a str, node: Node<'a>) -> &'a str {
     return node.utf8_text(source.as_bytes()).unwrap();
 }
 
-#[allow(dead_code)]
+#[allow(dead_code, clippy::needless_return)]
 pub fn parse_source_with_language(source: &str, language: Language) -> Tree {
     let mut parser = tree_sitter::Parser::new();
     parser.set_language(language).unwrap();
 
     return parser.parse(source.as_bytes(), None).unwrap();
 }
+
+thread_local! {
+    /// One parser per language and thread, set up once and then reused for every file
+    static PARSERS: RefCell<HashMap<Language, Parser>> = RefCell::new(HashMap::new());
+    /// The trees of the files parsed last, along with a hash of the source they were parsed from,
+    /// the latest first
+    static TREES: RefCell<VecDeque<(String, u64, Tree)>> = const { RefCell::new(VecDeque::new()) };
+}
+
+fn with_thread_parser<T>(language: Language, f: impl FnOnce(&mut Parser) -> T) -> T {
+    PARSERS.with(|parsers| {
+        let mut parsers = parsers.borrow_mut();
+        let parser = parsers.entry(language).or_insert_with(|| {
+            let mut parser = Parser::new();
+            parser.set_language(language).unwrap();
+            parser
+        });
+
+        f(parser)
+    })
+}
+
+/// Like `parse_source_with_language`, but reuses the parser the current thread has for the
+/// language
+pub fn parse_with_thread_parser(source: &str, language: Language) -> Option<Tree> {
+    with_thread_parser(language, |parser| parser.parse(source.as_bytes(), None))
+}
+
+/// The edit that turns the old source into the new one, spanning from their first to their last
+/// differing byte
+pub fn edit_between(old_source: &str, new_source: &str) -> InputEdit {
+    let common_prefix = old_source
+        .char_indices()
+        .zip(new_source.chars())
+        .find(|((_, old_char), new_char)| old_char != new_char)
+        .map_or(old_source.len().min(new_source.len()), |((index, _), _)| {
+            index
+        });
+    let common_suffix = old_source[common_prefix..]
+        .chars()
+        .rev()
+        .zip(new_source[common_prefix..].chars().rev())
+        .take_while(|(old_char, new_char)| old_char == new_char)
+        .map(|(old_char, _)| old_char.len_utf8())
+        .sum::<usize>();
+
+    let point_at = |source: &str, byte: usize| {
+        let before = &source[..byte];
+        let row = before.matches('\n').count();
+        let column = byte - before.rfind('\n').map_or(0, |newline| newline + 1);
+
+        Point { row, column }
+    };
+    let old_end_byte = old_source.len() - common_suffix;
+    let new_end_byte = new_source.len() - common_suffix;
+
+    InputEdit {
+        start_byte: common_prefix,
+        old_end_byte,
+        new_end_byte,
+        start_position: point_at(old_source, common_prefix),
+        old_end_position: point_at(old_source, old_end_byte),
+        new_end_position: point_at(new_source, new_end_byte),
+    }
+}
+
+/// Reparses a changed source incrementally, reusing the unchanged parts of its old tree. Returns
+/// the new tree along with the edit.
+pub fn reparse_with_thread_parser(
+    old_source: &str,
+    old_tree: &Tree,
+    new_source: &str,
+    language: Language,
+) -> Option<(Tree, InputEdit)> {
+    let edit = edit_between(old_source, new_source);
+    let mut old_tree = old_tree.clone();
+    old_tree.edit(&edit);
+
+    with_thread_parser(language, |parser| {
+        parser.parse(new_source.as_bytes(), Some(&old_tree))
+    })
+    .map(|tree| (tree, edit))
+}
+
+/// How many trees each thread keeps, as the trees of large files take up many times their size
+const MAX_CACHED_TREES: usize = 8;
+
+/// Parses a file with the parser of the current thread, reusing the tree of a recent parse if the
+/// file did not change since then
+pub fn parse_file(file_path: &str, source: &str, language: Language) -> Option<Tree> {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    let source_hash = hasher.finish();
+
+    let cached_tree = TREES.with(|trees| {
+        let mut trees = trees.borrow_mut();
+        let index = trees.iter().position(|(path, _, _)| path == file_path)?;
+        let (path, hash, tree) = trees.remove(index)?;
+        let cached_tree = (hash == source_hash).then(|| tree.clone());
+        trees.push_front((path, hash, tree));
+
+        cached_tree
+    });
+    if cached_tree.is_some() {
+        return cached_tree;
+    }
+
+    let tree = parse_with_thread_parser(source, language)?;
+    TREES.with(|trees| {
+        let mut trees = trees.borrow_mut();
+        trees.retain(|(path, _, _)| path != file_path);
+        trees.push_front((file_path.to_string(), source_hash, tree.clone()));
+        trees.truncate(MAX_CACHED_TREES);
+    });
+
+    Some(tree)
+}
+
+/// Where the parser could not make sense of the source, along with what it could not make sense
+/// of, or what it expected to find instead
+pub fn syntax_errors(source: &str, tree: &Tree) -> Vec<String> {
+    if !tree.root_node().has_error() {
+        return vec![];
+    }
+
+    let mut errors = vec![];
+    let mut error_ends_at = 0;
+    for node in traverse(tree.walk(), Order::Pre) {
+        // Errors within errors add nothing
+        if node.start_byte() < error_ends_at {
+            continue;
+        }
+        let position = node.start_position();
+        let location = format!("line {}, column {}", position.row + 1, position.column + 1);
+        if node.is_missing() {
+            errors.push(format!("{}: missing `{}`", location, node.kind()));
+        } else if node.is_error() {
+            error_ends_at = node.end_byte();
+            let unexpected = node_value(source, node).lines().next().unwrap_or_default();
+            let unexpected: String = unexpected.chars().take(40).collect();
+            errors.push(format!("{}: unexpected `{}`", location, unexpected.trim()));
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Tree;
+
+    use crate::helpers::tree_sitter::{
+        edit_between, parse_file, parse_source_with_language, reparse_with_thread_parser,
+        syntax_errors, MAX_CACHED_TREES,
+    };
+
+    #[test]
+    fn trees_are_reused_until_the_file_changes() {
+        let language = tree_sitter_rust::language();
+        let tree = parse_file("src/lib.rs", "fn greet() {}", language).unwrap();
+
+        let function_id = |tree: &Tree| tree.root_node().child(0).unwrap().id();
+
+        // Copies of a tree share its nodes
+        let same_tree = parse_file("src/lib.rs", "fn greet() {}", language).unwrap();
+        assert_eq!(function_id(&tree), function_id(&same_tree));
+
+        let new_tree = parse_file("src/lib.rs", "fn shout() {}", language).unwrap();
+        assert_ne!(function_id(&tree), function_id(&new_tree));
+
+        // Only the trees of the files parsed last are kept
+        for index in 0..MAX_CACHED_TREES {
+            parse_file(&format!("src/{}.rs", index), "fn greet() {}", language).unwrap();
+        }
+        let evicted_tree = parse_file("src/lib.rs", "fn shout() {}", language).unwrap();
+        assert_ne!(function_id(&new_tree), function_id(&evicted_tree));
+    }
+
+    #[test]
+    fn edits() {
+        let edit = edit_between(
+            "fn greet() {\n    hello();\n}",
+            "fn greet() {\n    shout();\n}",
+        );
+
+        assert_eq!(17, edit.start_byte);
+        assert_eq!(22, edit.old_end_byte);
+        assert_eq!(22, edit.new_end_byte);
+        assert_eq!(
+            (1, 4),
+            (edit.start_position.row, edit.start_position.column)
+        );
+
+        let edit = edit_between("fn greet() {}", "fn greet() {}\nfn shout() {}");
+        assert_eq!(13, edit.start_byte);
+        assert_eq!(13, edit.old_end_byte);
+        assert_eq!(
+            (1, 13),
+            (edit.new_end_position.row, edit.new_end_position.column)
+        );
+    }
+
+    #[test]
+    fn incremental_reparse() {
+        let language = tree_sitter_rust::language();
+        let old_source = "fn greet() {\n    hello();\n}\n\nfn shout() {}";
+        let new_source = "fn greet() {\n    hello();\n}\n\nfn shout() { loud(); }";
+        let old_tree = parse_source_with_language(old_source, language);
+
+        let (tree, _) =
+            reparse_with_thread_parser(old_source, &old_tree, new_source, language).unwrap();
+
+        assert_eq!(
+            parse_source_with_language(new_source, language)
+                .root_node()
+                .to_sexp(),
+            tree.root_node().to_sexp()
+        );
+    }
+
+    #[test]
+    fn syntax_error_locations() {
+        let language = tree_sitter_rust::language();
+        let source = "fn greet() {\n    println!(\"hello {}\", name\n}\n";
+        let tree = parse_source_with_language(source, language);
+        assert_eq!(
+            vec!["line 2, column 30: missing `)`"],
+            syntax_errors(source, &tree)
+        );
+
+        let source = "fn greet() {}\n)) fn shout() {}\n";
+        let tree = parse_source_with_language(source, language);
+        assert_eq!(
+            vec!["line 2, column 1: unexpected `))`"],
+            syntax_errors(source, &tree)
+        );
+
+        let tree = parse_source_with_language("fn greet() {}", language);
+        assert!(syntax_errors("fn greet() {}", &tree).is_empty());
+    }
+}
@@ -0,0 +1,801 @@
+use std::collections::HashMap;
+use std::fs::{create_dir_all, read_to_string, write, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+const STORE_DIRECTORY_PATH: &str = ".senior";
+#[cfg(feature = "sqlite")]
+const HISTORY_DATABASE_PATH: &str = ".senior/history.sqlite";
+pub const HISTORY_STORE_ENV_VAR_KEY: &str = "SENIOR_HISTORY_STORE";
+
+static HISTORY_STORE_KIND: OnceLock<HistoryStoreKind> = OnceLock::new();
+
+/// Where the history is kept, along with what runs keep for the next ones: the answers of the
+/// verdict cache and the embeddings of the index
+pub trait HistoryStore {
+    fn record_entry(&self, entry: &HistoryEntry) -> Result<(), String>;
+    fn record_summary(&self, summary: &CheckSummary) -> Result<(), String>;
+    /// Every entry, oldest first, or none if the history cannot be read
+    fn entries(&self) -> Vec<HistoryEntry>;
+    /// Every `--check` summary, oldest first, or none if the history cannot be read
+    fn summaries(&self) -> Vec<CheckSummary>;
+    /// The answers of previous runs, by the fingerprint of what was asked
+    fn verdicts(&self) -> HashMap<String, String>;
+    fn save_verdicts(&self, verdicts: &HashMap<String, String>) -> Result<(), String>;
+    /// The embeddings of code, by the hash of the code
+    fn embeddings(&self) -> HashMap<String, Vec<f32>>;
+    fn save_embeddings(&self, embeddings: &HashMap<String, Vec<f32>>) -> Result<(), String>;
+}
+
+/// The history as a file with a line of JSON per entry or summary, with the cache and the index
+/// as JSON files next to it
+pub struct JsonlHistory {
+    directory: PathBuf,
+}
+
+impl JsonlHistory {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    fn append(&self, line: &impl Serialize) -> Result<(), String> {
+        let path = self.directory.join("history.jsonl");
+        let line = serde_json::to_string(line).map_err(|e| e.to_string())?;
+        create_dir_all(&self.directory).map_err(|e| e.to_string())?;
+        let mut history = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("failed to open {}: {}", path.display(), e))?;
+
+        writeln!(history, "{}", line)
+            .map_err(|e| format!("failed to write {}: {}", path.display(), e))
+    }
+
+    /// The lines that are of the type, skipping the others and those that cannot be read
+    fn lines<T: DeserializeOwned>(&self) -> Vec<T> {
+        read_to_string(self.directory.join("history.jsonl"))
+            .map(|history| {
+                history
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Reads the map the JSON file holds under the key, or an empty one if it cannot be read
+fn read_map<T: DeserializeOwned>(path: &Path, key: &str) -> HashMap<String, T> {
+    read_to_string(path)
+        .ok()
+        .and_then(|contents| {
+            serde_json::from_str::<HashMap<String, HashMap<String, T>>>(&contents).ok()
+        })
+        .and_then(|mut contents| contents.remove(key))
+        .unwrap_or_default()
+}
+
+fn write_map<T: Serialize>(path: &Path, key: &str, map: &HashMap<String, T>) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let contents =
+        serde_json::to_string(&HashMap::from([(key, map)])).map_err(|e| e.to_string())?;
+
+    write(path, contents).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+impl HistoryStore for JsonlHistory {
+    fn record_entry(&self, entry: &HistoryEntry) -> Result<(), String> {
+        self.append(entry)
+    }
+
+    fn record_summary(&self, summary: &CheckSummary) -> Result<(), String> {
+        self.append(summary)
+    }
+
+    fn entries(&self) -> Vec<HistoryEntry> {
+        self.lines()
+    }
+
+    fn summaries(&self) -> Vec<CheckSummary> {
+        self.lines()
+    }
+
+    fn verdicts(&self) -> HashMap<String, String> {
+        read_map(&self.directory.join("cache.json"), "verdicts")
+    }
+
+    fn save_verdicts(&self, verdicts: &HashMap<String, String>) -> Result<(), String> {
+        write_map(&self.directory.join("cache.json"), "verdicts", verdicts)
+    }
+
+    fn embeddings(&self) -> HashMap<String, Vec<f32>> {
+        read_map(&self.directory.join("embeddings.json"), "embeddings")
+    }
+
+    fn save_embeddings(&self, embeddings: &HashMap<String, Vec<f32>>) -> Result<(), String> {
+        write_map(
+            &self.directory.join("embeddings.json"),
+            "embeddings",
+            embeddings,
+        )
+    }
+}
+
+/// The history as a SQLite database, with a table for the entries, the summaries, the verdicts
+/// and the embeddings each
+#[cfg(feature = "sqlite")]
+pub struct SqliteHistory {
+    path: PathBuf,
+}
+
+#[cfg(feature = "sqlite")]
+impl SqliteHistory {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn connect(&self) -> Result<rusqlite::Connection, String> {
+        if let Some(parent) = self.path.parent() {
+            create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let connection = rusqlite::Connection::open(&self.path)
+            .map_err(|e| format!("failed to open {}: {}", self.path.display(), e))?;
+        connection
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS suggestions (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    file TEXT NOT NULL,
+                    function TEXT NOT NULL,
+                    original TEXT NOT NULL,
+                    suggestion TEXT NOT NULL,
+                    applied INTEGER NOT NULL,
+                    reason TEXT,
+                    reviewer TEXT,
+                    timestamp INTEGER NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS suggestions_by_function ON suggestions (file, function);
+                CREATE TABLE IF NOT EXISTS check_summaries (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    path TEXT NOT NULL,
+                    checked_functions INTEGER NOT NULL,
+                    flagged_functions INTEGER NOT NULL,
+                    timestamp INTEGER NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS verdicts (
+                    fingerprint TEXT PRIMARY KEY,
+                    answer TEXT NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS embeddings (
+                    code_hash TEXT PRIMARY KEY,
+                    embedding BLOB NOT NULL
+                );",
+            )
+            .map_err(|e| format!("failed to set up {}: {}", self.path.display(), e))?;
+
+        Ok(connection)
+    }
+
+    /// Every row the query selects, or none if the database cannot be read
+    fn select<T>(
+        &self,
+        query: &str,
+        row: impl FnMut(&rusqlite::Row) -> rusqlite::Result<T>,
+    ) -> Vec<T> {
+        let Ok(connection) = self.connect() else {
+            return vec![];
+        };
+        let Ok(mut statement) = connection.prepare(query) else {
+            return vec![];
+        };
+        let rows = statement
+            .query_map((), row)
+            .map(|rows| rows.filter_map(Result::ok).collect())
+            .unwrap_or_default();
+
+        rows
+    }
+
+    /// Inserts a row for every parameters, replacing those with the same key, all at once
+    fn upsert<P: rusqlite::Params>(
+        &self,
+        query: &str,
+        rows: impl Iterator<Item = P>,
+    ) -> Result<(), String> {
+        let write_error =
+            |e: rusqlite::Error| format!("failed to write {}: {}", self.path.display(), e);
+        let mut connection = self.connect()?;
+        let transaction = connection.transaction().map_err(write_error)?;
+        {
+            let mut statement = transaction.prepare(query).map_err(write_error)?;
+            for parameters in rows {
+                statement.execute(parameters).map_err(write_error)?;
+            }
+        }
+
+        transaction.commit().map_err(write_error)
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl HistoryStore for SqliteHistory {
+    fn record_entry(&self, entry: &HistoryEntry) -> Result<(), String> {
+        self.connect()?
+            .execute(
+                "INSERT INTO suggestions (file, function, original, suggestion, applied, reason, reviewer, timestamp)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                rusqlite::params![
+                    entry.file,
+                    entry.function,
+                    entry.original,
+                    entry.suggestion,
+                    entry.applied,
+                    entry.reason,
+                    entry.reviewer,
+                    entry.timestamp,
+                ],
+            )
+            .map(|_| ())
+            .map_err(|e| format!("failed to write {}: {}", self.path.display(), e))
+    }
+
+    fn record_summary(&self, summary: &CheckSummary) -> Result<(), String> {
+        self.connect()?
+            .execute(
+                "INSERT INTO check_summaries (path, checked_functions, flagged_functions, timestamp)
+                VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![
+                    summary.path,
+                    summary.checked_functions,
+                    summary.flagged_functions,
+                    summary.timestamp,
+                ],
+            )
+            .map(|_| ())
+            .map_err(|e| format!("failed to write {}: {}", self.path.display(), e))
+    }
+
+    fn entries(&self) -> Vec<HistoryEntry> {
+        self.select(
+            "SELECT file, function, original, suggestion, applied, reason, reviewer, timestamp
+            FROM suggestions ORDER BY id",
+            |row| {
+                Ok(HistoryEntry {
+                    file: row.get(0)?,
+                    function: row.get(1)?,
+                    original: row.get(2)?,
+                    suggestion: row.get(3)?,
+                    applied: row.get(4)?,
+                    reason: row.get(5)?,
+                    reviewer: row.get(6)?,
+                    timestamp: row.get(7)?,
+                })
+            },
+        )
+    }
+
+    fn summaries(&self) -> Vec<CheckSummary> {
+        self.select(
+            "SELECT path, checked_functions, flagged_functions, timestamp
+            FROM check_summaries ORDER BY id",
+            |row| {
+                Ok(CheckSummary {
+                    path: row.get(0)?,
+                    checked_functions: row.get(1)?,
+                    flagged_functions: row.get(2)?,
+                    timestamp: row.get(3)?,
+                })
+            },
+        )
+    }
+
+    fn verdicts(&self) -> HashMap<String, String> {
+        self.select("SELECT fingerprint, answer FROM verdicts", |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .into_iter()
+        .collect()
+    }
+
+    fn save_verdicts(&self, verdicts: &HashMap<String, String>) -> Result<(), String> {
+        self.upsert(
+            "INSERT OR REPLACE INTO verdicts (fingerprint, answer) VALUES (?1, ?2)",
+            verdicts.iter(),
+        )
+    }
+
+    /// Embeddings are kept as the little-endian bytes of their values
+    fn embeddings(&self) -> HashMap<String, Vec<f32>> {
+        self.select("SELECT code_hash, embedding FROM embeddings", |row| {
+            let bytes: Vec<u8> = row.get(1)?;
+            let embedding = bytes
+                .chunks_exact(4)
+                .map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]]))
+                .collect();
+            Ok((row.get(0)?, embedding))
+        })
+        .into_iter()
+        .collect()
+    }
+
+    fn save_embeddings(&self, embeddings: &HashMap<String, Vec<f32>>) -> Result<(), String> {
+        self.upsert(
+            "INSERT OR REPLACE INTO embeddings (code_hash, embedding) VALUES (?1, ?2)",
+            embeddings.iter().map(|(code_hash, embedding)| {
+                let bytes: Vec<u8> = embedding
+                    .iter()
+                    .flat_map(|value| value.to_le_bytes())
+                    .collect();
+                (code_hash, bytes)
+            }),
+        )
+    }
+}
+
+/// The stores the history can be kept in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryStoreKind {
+    Jsonl,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+impl HistoryStoreKind {
+    /// The store named by `SENIOR_HISTORY_STORE`, `jsonl` or, when built with the `sqlite`
+    /// feature, `sqlite`
+    pub fn from_name(name: Option<&str>) -> Result<Self, String> {
+        match name.unwrap_or("jsonl") {
+            "jsonl" => Ok(HistoryStoreKind::Jsonl),
+            #[cfg(feature = "sqlite")]
+            "sqlite" => Ok(HistoryStoreKind::Sqlite),
+            #[cfg(not(feature = "sqlite"))]
+            "sqlite" => Err(format!(
+                "{}=sqlite needs senior to be built with the `sqlite` feature",
+                HISTORY_STORE_ENV_VAR_KEY
+            )),
+            name => Err(format!(
+                "{} is `{}`, but only `jsonl` and `sqlite` are history stores",
+                HISTORY_STORE_ENV_VAR_KEY, name
+            )),
+        }
+    }
+
+    pub fn open(self) -> Box<dyn HistoryStore> {
+        match self {
+            HistoryStoreKind::Jsonl => Box::new(JsonlHistory::new(STORE_DIRECTORY_PATH)),
+            #[cfg(feature = "sqlite")]
+            HistoryStoreKind::Sqlite => Box::new(SqliteHistory::new(HISTORY_DATABASE_PATH)),
+        }
+    }
+}
+
+/// Picks the store of the repository, once at startup
+pub fn set_history_store(kind: HistoryStoreKind) {
+    let _ = HISTORY_STORE_KIND.set(kind);
+}
+
+/// The history of the repository, which every command records suggestions in and looks them up
+/// from. It is the JSONL file unless another store was picked at startup.
+pub fn repository_history() -> Box<dyn HistoryStore> {
+    HISTORY_STORE_KIND
+        .get()
+        .copied()
+        .unwrap_or(HistoryStoreKind::Jsonl)
+        .open()
+}
+
+/// A suggestion that was either applied or rejected. Functions are told apart by their file and
+/// name.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct HistoryEntry {
+    pub file: String,
+    pub function: String,
+    pub original: String,
+    pub suggestion: String,
+    pub applied: bool,
+    /// Why the suggestion was rejected, if the user said so
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Who applied or rejected the suggestion, recorded in compliance mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reviewer: Option<String>,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+impl HistoryEntry {
+    pub fn new(
+        file: &str,
+        function: &str,
+        original: &str,
+        suggestion: &str,
+        applied: bool,
+    ) -> Self {
+        Self {
+            file: file.to_string(),
+            function: function.to_string(),
+            original: original.to_string(),
+            suggestion: suggestion.to_string(),
+            applied,
+            reason: None,
+            reviewer: None,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs()),
+        }
+    }
+}
+
+/// How many functions a `--check` run went through, and how many of them could be improved. Kept
+/// in the history too, on lines of their own.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct CheckSummary {
+    /// The file or directory the run went through
+    pub path: String,
+    pub checked_functions: usize,
+    pub flagged_functions: usize,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+impl CheckSummary {
+    pub fn new(path: &str, checked_functions: usize, flagged_functions: usize) -> Self {
+        Self {
+            path: path.to_string(),
+            checked_functions,
+            flagged_functions,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs()),
+        }
+    }
+}
+
+/// The latest entry of the function
+pub fn last_entry(history: &dyn HistoryStore, file: &str, function: &str) -> Option<HistoryEntry> {
+    history
+        .entries()
+        .into_iter()
+        .rev()
+        .find(|entry| entry.file == file && entry.function == function)
+}
+
+/// The applied suggestions anywhere in the repository
+pub fn applied_entries(history: &dyn HistoryStore) -> Vec<HistoryEntry> {
+    history
+        .entries()
+        .into_iter()
+        .filter(|entry| entry.applied)
+        .collect()
+}
+
+/// The reasons given for rejecting suggestions anywhere in the repository, latest first, without
+/// repetitions
+pub fn rejection_reasons(history: &dyn HistoryStore, limit: usize) -> Vec<String> {
+    let mut reasons: Vec<String> = vec![];
+    for entry in history.entries().into_iter().rev() {
+        let Some(reason) = entry.reason.filter(|reason| !reason.trim().is_empty()) else {
+            continue;
+        };
+        if !reasons.contains(&reason) {
+            reasons.push(reason);
+        }
+        if reasons.len() == limit {
+            break;
+        }
+    }
+
+    reasons
+}
+
+/// Turns the rejection reasons into standing constraints for the model
+pub fn describe_rejection_reasons(reasons: &[String]) -> String {
+    if reasons.is_empty() {
+        return "".to_string();
+    }
+
+    format!(
+        "Earlier suggestions were rejected for these reasons, treat them as constraints:\n{}",
+        reasons
+            .iter()
+            .map(|reason| format!("- {}", reason))
+            .collect::<Vec<String>>()
+            .join("\n")
+    )
+}
+
+/// The summaries of the `--check` runs through the path, or through any path, oldest first
+pub fn check_summaries(
+    history: &dyn HistoryStore,
+    checked_path: Option<&str>,
+) -> Vec<CheckSummary> {
+    history
+        .summaries()
+        .into_iter()
+        .filter(|summary| checked_path.is_none_or(|checked_path| summary.path == checked_path))
+        .collect()
+}
+
+/// The `YYYY-MM-DD` date of the timestamp, in UTC
+pub fn date(timestamp: u64) -> String {
+    // Counted from 0000-03-01, so that leap days end the years, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = timestamp / 86400 + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// One line per run, with a bar as long as the share of flagged functions, and how that share
+/// changed from the first run to the last
+pub fn render_trends(summaries: &[CheckSummary]) -> String {
+    const BAR_WIDTH: usize = 40;
+    let share = |summary: &CheckSummary| {
+        summary.flagged_functions as f64 / summary.checked_functions.max(1) as f64
+    };
+    let mut lines: Vec<String> = summaries
+        .iter()
+        .map(|summary| {
+            format!(
+                "{} {:>5} of {:>5} flagged {:>5.1}% {}",
+                date(summary.timestamp),
+                summary.flagged_functions,
+                summary.checked_functions,
+                share(summary) * 100.0,
+                "#".repeat((share(summary) * BAR_WIDTH as f64).round() as usize)
+            )
+        })
+        .collect();
+    if let (Some(first), Some(last)) = (summaries.first(), summaries.last()) {
+        if summaries.len() > 1 {
+            let change = (share(last) - share(first)) * 100.0;
+            lines.push(format!(
+                "the share of flagged functions went from {:.1}% to {:.1}% ({:+.1} points) over {} runs",
+                share(first) * 100.0,
+                share(last) * 100.0,
+                change,
+                summaries.len()
+            ));
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// The lines removed from `old` prefixed by `-`, and the ones added in `new` by `+`
+pub fn line_diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    // The length of the longest common subsequence of every pair of suffixes
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut diff = vec![];
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            diff.push(format!("  {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            diff.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            diff.push(format!("+ {}", new[j]));
+            j += 1;
+        }
+    }
+
+    diff.join("\n")
+}
+
+/// Tells the model what it suggested last time, what became of it, and how the code was edited by
+/// hand since, so that it does not propose a rejected idea again
+pub fn describe_previous_attempt(entry: &HistoryEntry, code: &str) -> String {
+    let mut description = format!(
+        "You were asked to optimise this code before, and suggested:\n{}\n",
+        entry.suggestion
+    );
+    if entry.applied {
+        description.push_str("That suggestion was applied.");
+    } else {
+        description.push_str("That suggestion was rejected, so do not propose it again.");
+    }
+
+    let expected_code = if entry.applied {
+        &entry.suggestion
+    } else {
+        &entry.original
+    };
+    if expected_code.trim() != code.trim() {
+        description.push_str(&format!(
+            "\nSince then, the code was edited by hand:\n{}",
+            line_diff(expected_code.trim(), code.trim())
+        ));
+    }
+
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::history::{
+        check_summaries, date, describe_previous_attempt, describe_rejection_reasons, last_entry,
+        line_diff, rejection_reasons, render_trends, CheckSummary, HistoryEntry, HistoryStore,
+        HistoryStoreKind, JsonlHistory,
+    };
+
+    /// Every store, empty, so that each test goes through all of them
+    fn stores(name: &str) -> Vec<Box<dyn HistoryStore>> {
+        let directory =
+            std::env::temp_dir().join(format!("senior-history-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(directory.join(name));
+        #[allow(unused_mut)]
+        let mut stores: Vec<Box<dyn HistoryStore>> =
+            vec![Box::new(JsonlHistory::new(directory.join(name)))];
+        #[cfg(feature = "sqlite")]
+        {
+            let path = directory.join(format!("{}.sqlite", name));
+            let _ = std::fs::remove_file(&path);
+            stores.push(Box::new(crate::history::SqliteHistory::new(path)));
+        }
+
+        stores
+    }
+
+    #[test]
+    fn stores_keep_records() {
+        for history in stores("records") {
+            assert!(history.entries().is_empty());
+            assert!(history.verdicts().is_empty());
+
+            let mut rejected = HistoryEntry::new("src/lib.rs", "greet", "a", "b", false);
+            rejected.reason = Some("keep it readable".to_string());
+            rejected.reviewer = Some("Ada <ada@example.com>".to_string());
+            let applied = HistoryEntry::new("src/lib.rs", "shout", "c", "d", true);
+            history.record_entry(&rejected).unwrap();
+            history
+                .record_summary(&CheckSummary::new("src", 2, 1))
+                .unwrap();
+            history.record_entry(&applied).unwrap();
+            assert_eq!(vec![rejected, applied], history.entries());
+            assert_eq!(1, history.summaries().len());
+
+            let verdicts = HashMap::from([("abc".to_string(), "OPTIMAL".to_string())]);
+            history.save_verdicts(&verdicts).unwrap();
+            assert_eq!(verdicts, history.verdicts());
+
+            let embeddings = HashMap::from([("abc".to_string(), vec![0.5, -1.25])]);
+            history.save_embeddings(&embeddings).unwrap();
+            assert_eq!(embeddings, history.embeddings());
+        }
+
+        assert_eq!(
+            Ok(HistoryStoreKind::Jsonl),
+            HistoryStoreKind::from_name(None)
+        );
+        assert_eq!(
+            Ok(HistoryStoreKind::Jsonl),
+            HistoryStoreKind::from_name(Some("jsonl"))
+        );
+        assert_eq!(
+            cfg!(feature = "sqlite"),
+            HistoryStoreKind::from_name(Some("sqlite")).is_ok()
+        );
+        assert!(HistoryStoreKind::from_name(Some("postgres")).is_err());
+    }
+
+    #[test]
+    fn previous_attempts() {
+        for history in stores("history") {
+            let history = history.as_ref();
+
+            let original = "fn greet() {\n    println!(\"{}\", \"hello\");\n}";
+            let suggestion = "fn greet() {\n    println!(\"hello\");\n}";
+            for (function, applied) in [("greet", false), ("shout", true)] {
+                let entry =
+                    HistoryEntry::new("src/lib.rs", function, original, suggestion, applied);
+                history.record_entry(&entry).unwrap();
+            }
+
+            let entry = last_entry(history, "src/lib.rs", "greet").unwrap();
+            assert!(!entry.applied);
+            assert_eq!(None, last_entry(history, "src/main.rs", "greet"));
+
+            let edited_by_hand = "fn greet() {\n    print!(\"hello\\n\");\n}";
+            assert_eq!(
+                "You were asked to optimise this code before, and suggested:\nfn greet() {\n    println!(\"hello\");\n}\nThat suggestion was rejected, so do not propose it again.\nSince then, the code was edited by hand:\n  fn greet() {\n-     println!(\"{}\", \"hello\");\n+     print!(\"hello\\n\");\n  }",
+                describe_previous_attempt(&entry, edited_by_hand)
+            );
+            assert!(!describe_previous_attempt(&entry, original).contains("by hand"));
+            assert_eq!("  a\n- b\n+ c", line_diff("a\nb", "a\nc"));
+        }
+    }
+
+    #[test]
+    fn reasons() {
+        for history in stores("reasons") {
+            let history = history.as_ref();
+
+            for reason in [
+                Some("we target wasm32, no threads"),
+                None,
+                Some("keep the early return"),
+                Some("we target wasm32, no threads"),
+            ] {
+                let mut entry = HistoryEntry::new("src/lib.rs", "greet", "", "", false);
+                entry.reason = reason.map(str::to_string);
+                history.record_entry(&entry).unwrap();
+            }
+
+            let reasons = rejection_reasons(history, 10);
+            assert_eq!(
+                vec!["we target wasm32, no threads", "keep the early return"],
+                reasons
+            );
+            assert_eq!(1, rejection_reasons(history, 1).len());
+            assert!(describe_rejection_reasons(&reasons).ends_with("\n- keep the early return"));
+            assert_eq!("", describe_rejection_reasons(&[]));
+        }
+    }
+
+    #[test]
+    fn trends() {
+        for history in stores("trends") {
+            let history = history.as_ref();
+
+            assert_eq!("1970-01-01", date(0));
+            assert_eq!("2024-02-29", date(1709164800));
+            assert_eq!("2026-10-16", date(1792108800));
+
+            history
+                .record_entry(&HistoryEntry::new("src/lib.rs", "greet", "", "", true))
+                .unwrap();
+            for (checked_path, flagged_functions) in [("src", 10), ("tests", 1), ("src", 5)] {
+                let mut summary = CheckSummary::new(checked_path, 20, flagged_functions);
+                summary.timestamp = 1792108800;
+                history.record_summary(&summary).unwrap();
+            }
+            assert!(last_entry(history, "src/lib.rs", "greet").is_some());
+
+            let summaries = check_summaries(history, Some("src"));
+            assert_eq!(2, summaries.len());
+            assert_eq!(3, check_summaries(history, None).len());
+            assert_eq!(
+                "2026-10-16    10 of    20 flagged  50.0% ####################\n2026-10-16     5 of    20 flagged  25.0% ##########\nthe share of flagged functions went from 50.0% to 25.0% (-25.0 points) over 2 runs",
+                render_trends(&summaries)
+            );
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::env;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+
+use crate::openai::Message;
+use crate::sandbox::{describe_where, sandboxed_command, Sandbox};
+
+const CONFIG_HOME_ENV_VAR_KEY: &str = "XDG_CONFIG_HOME";
+const PRE_PROMPT_HOOK_NAME: &str = "pre-prompt";
+
+/// What is about to be sent to the model, as handed to the pre-prompt hook and read back from it
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct DraftPrompt {
+    pub model: String,
+    pub messages: Vec<Message>,
+}
+
+/// The words of the post-apply command, with the `{name}` of every variable replaced by its value.
+/// Values are never split, so file names with spaces need no quoting.
+pub fn expand_post_apply_command(command: &str, variables: &[(&str, &str)]) -> Vec<String> {
+    command
+        .split_whitespace()
+        .map(|word| {
+            variables
+                .iter()
+                .fold(word.to_string(), |word, (name, value)| {
+                    word.replace(&format!("{{{}}}", name), value)
+                })
+        })
+        .collect()
+}
+
+/// Runs a command of `post_apply`, without a shell and in the sandbox if there is one, and fails
+/// if it does
+pub fn run_post_apply_hook(
+    command: &str,
+    variables: &[(&str, &str)],
+    sandbox: Option<&Sandbox>,
+) -> Result<(), String> {
+    let words = expand_post_apply_command(command, variables);
+    if words.is_empty() {
+        return Ok(());
+    }
+    let status = sandboxed_command(&words, sandbox)?.status().map_err(|e| {
+        format!(
+            "failed to run `{}`{}: {}",
+            words.join(" "),
+            describe_where(sandbox),
+            e
+        )
+    })?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("`{}` failed with {}", words.join(" "), status))
+    }
+}
+
+/// `$XDG_CONFIG_HOME/senior/hooks`, or `~/.config/senior/hooks`
+fn hooks_directory() -> Option<PathBuf> {
+    let config_home = env::var_os(CONFIG_HOME_ENV_VAR_KEY)
+        .filter(|config_home| !config_home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(config_home.join("senior").join("hooks"))
+}
+
+/// Runs the hook with the draft as JSON on its standard input, and reads the rewritten draft from
+/// its standard output
+fn rewrite_with(hook: &Path, draft: &DraftPrompt) -> Result<DraftPrompt, String> {
+    let mut child = Command::new(hook)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run {}: {}", hook.display(), e))?;
+    let draft = serde_json::to_vec(draft).unwrap();
+    // The hook may well not read it all, which is its own business
+    let _ = child.stdin.take().unwrap().write_all(&draft);
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("failed to run {}: {}", hook.display(), e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed: {}",
+            hook.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("{} wrote an invalid prompt: {}", hook.display(), e))
+}
+
+/// Lets the `pre-prompt` executable of the hooks directory rewrite the prompt before it is sent,
+/// for instance to add policy text or strip paths. Without one, the draft is sent as it is. A hook
+/// that fails stops the prompt from being sent at all.
+pub fn run_pre_prompt_hook(draft: DraftPrompt) -> Result<DraftPrompt, String> {
+    match hooks_directory()
+        .map(|hooks_directory| hooks_directory.join(PRE_PROMPT_HOOK_NAME))
+        .filter(|hook| hook.is_file())
+    {
+        Some(hook) => rewrite_with(&hook, &draft),
+        None => Ok(draft),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs::{create_dir_all, set_permissions, write, Permissions};
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::hooks::{expand_post_apply_command, rewrite_with, run_post_apply_hook, DraftPrompt};
+    use crate::openai::Message;
+
+    #[test]
+    fn pre_prompt_hook() {
+        let directory = std::env::temp_dir().join("senior-hooks-test");
+        create_dir_all(&directory).unwrap();
+        let hook = directory.join("pre-prompt");
+        write(&hook, "#!/bin/sh\nsed 's|/home/alice/||g'\n").unwrap();
+        set_permissions(&hook, Permissions::from_mode(0o755)).unwrap();
+
+        let draft = DraftPrompt {
+            model: "gpt-4o".to_string(),
+            messages: vec![Message::new("user", "Improve /home/alice/src/lib.rs")],
+        };
+        let rewritten = rewrite_with(&hook, &draft).unwrap();
+        assert_eq!("gpt-4o", rewritten.model);
+        assert_eq!("Improve src/lib.rs", rewritten.messages[0].content);
+
+        write(&hook, "#!/bin/sh\necho 'no network prompts' >&2\nexit 1\n").unwrap();
+        let error = rewrite_with(&hook, &draft).unwrap_err();
+        assert!(error.ends_with("failed: no network prompts"));
+    }
+
+    #[test]
+    fn post_apply_hooks() {
+        let variables = [("file", "src/my lib.rs"), ("function", "greet")];
+        assert_eq!(
+            vec!["git", "add", "src/my lib.rs"],
+            expand_post_apply_command("git  add {file}", &variables)
+        );
+        assert_eq!(
+            vec!["echo", "greet@{language}"],
+            expand_post_apply_command("echo {function}@{language}", &variables)
+        );
+
+        assert!(run_post_apply_hook("true {file}", &variables, None).is_ok());
+        assert!(run_post_apply_hook("false", &variables, None).is_err());
+    }
+}
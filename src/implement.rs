@@ -0,0 +1,150 @@
+use std::fs::read_to_string;
+
+use tree_sitter::Node;
+
+use crate::context::function_body;
+use crate::extract::{doc_comments_start, Selection};
+use crate::helpers::tree_sitter::{node_value, parse_with_thread_parser};
+use crate::supported_languages::supported_language::{detect_language, explain_not_found};
+
+/// What stands in for a body yet to be written, as the start of its only statement
+const STUB_STATEMENTS: &[&str] = &[
+    // Rust
+    "todo!(",
+    "unimplemented!(",
+    // Go
+    "panic(\"not implemented",
+    "panic(\"unimplemented",
+    "panic(\"todo",
+    // Javascript and Typescript
+    "throw new Error(\"not implemented",
+    "throw new Error('not implemented",
+    // Java
+    "throw new UnsupportedOperationException(",
+    // C++
+    "throw std::logic_error(\"not implemented",
+];
+
+/// The system prompt of `senior implement`, which writes the body of a stub from its signature
+/// and documentation
+pub const IMPLEMENT_SYSTEM_PROMPT: &str = r#"You are a senior software engineer. Your task is to write the first implementation of the function you are given, whose body is a placeholder.
+
+Strictly adhere to the following instructions:
+1. Do not change the type signature.
+2. Do what the documentation of the function says, and nothing more.
+3. Only use what the rest of the file and the standard library provide.
+4. Respond only with code, and no commentary or explanations.
+"#;
+
+/// Whether the body of the function is only a placeholder, like `todo!()` or
+/// `throw new Error("not implemented")`
+pub fn is_stub(source_file: &str, function_node: Node) -> bool {
+    let Some(body) = function_body(function_node) else {
+        return false;
+    };
+    let mut cursor = body.walk();
+    let statements: Vec<Node> = body
+        .named_children(&mut cursor)
+        .filter(|statement| !statement.kind().contains("comment"))
+        .collect();
+
+    match statements.as_slice() {
+        [statement] => {
+            let statement = node_value(source_file, *statement);
+            STUB_STATEMENTS
+                .iter()
+                .any(|stub| statement.to_lowercase().starts_with(&stub.to_lowercase()))
+        }
+        _ => false,
+    }
+}
+
+/// The doc comments right above the function, or above the export wrapping it
+pub fn documentation<'a>(source_file: &'a str, function_node: Node) -> &'a str {
+    let item = function_node
+        .parent()
+        .filter(|parent| parent.kind() == "export_statement")
+        .unwrap_or(function_node);
+
+    source_file[doc_comments_start(source_file, item)..item.start_byte()].trim()
+}
+
+/// The documentation of the selected function, as long as its body is yet to be written
+pub fn stub_documentation(file_path: &str, selection: &Selection) -> Result<String, String> {
+    let source_file =
+        read_to_string(file_path).map_err(|e| format!("failed to read {}: {}", file_path, e))?;
+    let supported_language = detect_language(file_path)?;
+    let tree = parse_with_thread_parser(&source_file, supported_language.language())
+        .ok_or("failed to parse the file")?;
+    let function_node = supported_language
+        .find_correct_node(
+            &source_file,
+            &tree,
+            &selection.parent_identifier,
+            &selection.function_identifier,
+            &selection.kind,
+        )
+        .map_err(|e| explain_not_found(supported_language.as_ref(), e))?;
+    if !is_stub(&source_file, function_node) {
+        return Err(format!(
+            "{} is implemented already, as its body is not a placeholder like `todo!()`",
+            selection.function_identifier.clone().unwrap_or_default()
+        ));
+    }
+
+    Ok(documentation(&source_file, function_node).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::tree_sitter::parse_source_with_language;
+    use crate::implement::{documentation, is_stub};
+    use crate::supported_languages::supported_language::detect_language;
+
+    #[test]
+    fn stubs() {
+        let source = r#"/// Sums the prices of the items
+fn total(items: &[Item]) -> u32 {
+    todo!()
+}
+
+fn count(items: &[Item]) -> usize {
+    // TODO: skip the free ones
+    unimplemented!("count")
+}
+
+fn first(items: &[Item]) -> &Item {
+    &items[0]
+}
+"#;
+        let tree = parse_source_with_language(source, tree_sitter_rust::language());
+        let supported_language = detect_language("src/lib.rs").unwrap();
+        let find = |name: &str| {
+            supported_language
+                .find_correct_node(source, &tree, &None, &Some(name.to_string()), &None)
+                .unwrap()
+        };
+
+        assert!(is_stub(source, find("total")));
+        assert!(is_stub(source, find("count")));
+        assert!(!is_stub(source, find("first")));
+        assert_eq!(
+            "/// Sums the prices of the items",
+            documentation(source, find("total"))
+        );
+        assert_eq!("", documentation(source, find("count")));
+    }
+
+    #[test]
+    fn exported_javascript_stub() {
+        let source = "/**\n * Greets the user\n */\nexport function greet(name) {\n  throw new Error(\"Not implemented\");\n}\n";
+        let tree = parse_source_with_language(source, tree_sitter_javascript::language());
+        let supported_language = detect_language("greet.js").unwrap();
+        let greet = supported_language
+            .find_correct_node(source, &tree, &None, &Some("greet".to_string()), &None)
+            .unwrap();
+
+        assert!(is_stub(source, greet));
+        assert_eq!("/**\n * Greets the user\n */", documentation(source, greet));
+    }
+}
@@ -0,0 +1,222 @@
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+use colored::*;
+use inquire::Confirm;
+
+use crate::callers::{callers_in, find_callers};
+use crate::color::Palette;
+use crate::context::contains;
+use crate::diff::render_text_diff;
+use crate::extract::{cut, doc_comments_start, Selection};
+use crate::helpers::tree_sitter::{node_value, parse_with_thread_parser, syntax_errors};
+use crate::openai::{complete, Prompt};
+use crate::suggestion::parse_suggestion;
+use crate::supported_languages::supported_language::{detect_language, explain_not_found};
+
+/// How many lines a function may take up to be inlined
+pub const MAX_INLINED_LINES: usize = 15;
+
+pub struct InlineSettings<'a> {
+    pub selection: Selection,
+    pub model: &'a str,
+    pub skip_prompt: bool,
+    pub palette: Palette,
+}
+
+/// The new code of a function of the file, with the helper inlined into it
+struct CallSiteUpdate {
+    start_byte: usize,
+    end_byte: usize,
+    code: String,
+}
+
+fn draft_inline_instructions(language: &str, helper: &str, caller: &str) -> Prompt {
+    Prompt {
+        system: format!(
+            r#"You are a senior {} engineer inlining a small helper function into its callers.
+
+The helper is:
+{}
+
+Strictly adhere to the following instructions:
+1. Replace every call to the helper in the code you are given with the body of the helper, renaming its variables where they would clash.
+2. Keep what the code does, and change nothing else.
+3. Respond only with the whole updated code, and no commentary or explanations."#,
+            language, helper
+        ),
+        user: caller.to_string(),
+    }
+}
+
+/// The source with the callers updated and the helper cut, unless it is still called elsewhere
+fn inline_into(
+    source_file: &str,
+    updates: &[CallSiteUpdate],
+    helper_range: Option<(usize, usize)>,
+) -> String {
+    let mut edits: Vec<(usize, usize, &str)> = updates
+        .iter()
+        .map(|update| (update.start_byte, update.end_byte, update.code.as_str()))
+        .collect();
+    edits.extend(helper_range.map(|(start_byte, end_byte)| (start_byte, end_byte, "")));
+    // From the end, so that the offsets of the edits before stay right
+    edits.sort_by_key(|(start_byte, _, _)| std::cmp::Reverse(*start_byte));
+
+    let mut source = source_file.to_string();
+    for (start_byte, end_byte, code) in edits {
+        if code.is_empty() {
+            source = cut(&source, start_byte, end_byte);
+        } else {
+            source.replace_range(start_byte..end_byte, code);
+        }
+    }
+
+    source
+}
+
+/// Has the model inline a small function into every function of the file calling it, and deletes
+/// it once it is no longer called anywhere. All the edits are shown together, and the file is only
+/// written if they all apply and it still parses.
+pub async fn run_inline(
+    file_path: &str,
+    settings: &InlineSettings<'_>,
+    secret: &str,
+) -> Result<(), String> {
+    let source_file =
+        read_to_string(file_path).map_err(|e| format!("failed to read {}: {}", file_path, e))?;
+    let supported_language = detect_language(file_path)?;
+    let language = supported_language.to_string();
+    let tree = parse_with_thread_parser(&source_file, supported_language.language())
+        .ok_or("failed to parse the file")?;
+    let selection = &settings.selection;
+    let helper = supported_language
+        .find_correct_node(
+            &source_file,
+            &tree,
+            &selection.parent_identifier,
+            &selection.function_identifier,
+            &selection.kind,
+        )
+        .map_err(|e| explain_not_found(supported_language.as_ref(), e))?;
+    let name = selection.function_identifier.clone().unwrap_or_default();
+    let helper_code = node_value(&source_file, helper);
+    let lines = helper_code.lines().count();
+    if lines > MAX_INLINED_LINES {
+        return Err(format!(
+            "{} takes up {} lines, and only functions of at most {} are inlined",
+            name, lines, MAX_INLINED_LINES
+        ));
+    }
+
+    let callers = callers_in(supported_language.as_ref(), &source_file, &tree, &name);
+    if callers.iter().any(|caller| contains(*caller, helper)) {
+        return Err(format!("{} calls itself, so it cannot be inlined", name));
+    }
+    if callers.is_empty() {
+        return Err(format!("{} is not called in {}", name, file_path));
+    }
+
+    let mut updates = vec![];
+    for caller in callers {
+        let caller_code = node_value(&source_file, caller);
+        let prompt = draft_inline_instructions(&language, helper_code, caller_code);
+        let code = parse_suggestion(&complete(settings.model, &prompt, secret).await?).code;
+        println!(
+            "{}",
+            format!("{}:{}", file_path, caller.start_position().row + 1).bold()
+        );
+        println!("{}", render_text_diff(caller_code, &code, settings.palette));
+        updates.push(CallSiteUpdate {
+            start_byte: caller.start_byte(),
+            end_byte: caller.end_byte(),
+            code,
+        });
+    }
+
+    // Functions of other files may call it too, in which case it stays
+    let called_elsewhere = find_callers(Path::new("."), &language, file_path, &name)
+        .iter()
+        .any(|caller| {
+            Path::new(&caller.file).canonicalize().ok() != Path::new(file_path).canonicalize().ok()
+        });
+    let helper_range = if called_elsewhere {
+        println!(
+            "{}",
+            format!("{} is called by other files too, so it is kept.", name).yellow()
+        );
+        None
+    } else {
+        let item = helper
+            .parent()
+            .filter(|parent| parent.kind() == "export_statement")
+            .unwrap_or(helper);
+        println!("{}", format!("{} is deleted.", name).dimmed());
+        Some((doc_comments_start(&source_file, item), item.end_byte()))
+    };
+
+    let inlined = inline_into(&source_file, &updates, helper_range);
+    let inlined_tree = parse_with_thread_parser(&inlined, supported_language.language())
+        .ok_or("failed to parse the file once inlined")?;
+    let errors = syntax_errors(&inlined, &inlined_tree);
+    if !errors.is_empty() {
+        return Err(format!(
+            "the file would not parse once inlined, so it was left as it was: {}",
+            errors.join(", ")
+        ));
+    }
+
+    let apply = settings.skip_prompt
+        || Confirm::new(&format!("Inline {} into {} callers?", name, updates.len()))
+            .with_default(false)
+            .prompt()
+            .unwrap();
+    if apply {
+        write(file_path, inlined).map_err(|e| format!("failed to write {}: {}", file_path, e))?;
+        println!("{}", format!("inlined {}", name).green());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inline::{inline_into, CallSiteUpdate};
+
+    #[test]
+    fn inlining() {
+        let source = r#"fn double(x: u32) -> u32 {
+    x * 2
+}
+
+fn quadruple(x: u32) -> u32 {
+    double(double(x))
+}
+
+fn sextuple(x: u32) -> u32 {
+    double(x) * 3
+}
+"#;
+        let quadruple = source.find("fn quadruple").unwrap();
+        let sextuple = source.find("fn sextuple").unwrap();
+        let updates = [
+            CallSiteUpdate {
+                start_byte: quadruple,
+                end_byte: quadruple
+                    + "fn quadruple(x: u32) -> u32 {\n    double(double(x))\n}".len(),
+                code: "fn quadruple(x: u32) -> u32 {\n    x * 2 * 2\n}".to_string(),
+            },
+            CallSiteUpdate {
+                start_byte: sextuple,
+                end_byte: source.len() - 1,
+                code: "fn sextuple(x: u32) -> u32 {\n    x * 2 * 3\n}".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            "fn quadruple(x: u32) -> u32 {\n    x * 2 * 2\n}\n\nfn sextuple(x: u32) -> u32 {\n    x * 2 * 3\n}\n",
+            inline_into(source, &updates, Some((0, "fn double(x: u32) -> u32 {\n    x * 2\n}".len())))
+        );
+        assert!(inline_into(source, &updates, None).starts_with("fn double"));
+    }
+}
@@ -0,0 +1,52 @@
+use crate::supported_languages::supported_language::{
+    analyser, language_globs, test_globs, Language, LANGUAGES,
+};
+
+/// The providers senior can ask for suggestions
+const PROVIDERS: &[&str] = &["openai"];
+
+fn globs(patterns: Vec<glob::Pattern>) -> String {
+    patterns
+        .iter()
+        .map(glob::Pattern::as_str)
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+// What `senior languages` prints about the language
+fn describe_language(language: &Language) -> String {
+    format!(
+        "{}\n  grammar: {} {} (ABI version {})\n  files: {}\n  test files: {}",
+        language,
+        language.grammar_crate(),
+        language.grammar_version(),
+        analyser(language).language().version(),
+        globs(language_globs(language)),
+        globs(test_globs(language))
+    )
+}
+
+/// The language analysers and providers senior was built with, the grammars they parse with and
+/// the files they match. Every one of them is compiled in, as no cargo feature leaves any out.
+pub fn languages_report() -> String {
+    let mut report: Vec<String> = LANGUAGES.iter().map(describe_language).collect();
+    report.push(format!("providers: {}", PROVIDERS.join(", ")));
+
+    report.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::languages::languages_report;
+
+    #[test]
+    fn every_language_is_reported() {
+        let report = languages_report();
+
+        assert!(report.contains(
+            "tsx\n  grammar: tree-sitter-typescript 0.20.2 (ABI version 14)\n  files: *.tsx\n  test files: *.test.tsx *.spec.tsx **/__tests__/*"
+        ));
+        assert!(report.contains("rust\n  grammar: tree-sitter-rust"));
+        assert!(report.ends_with("providers: openai"));
+    }
+}
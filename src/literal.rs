@@ -0,0 +1,196 @@
+use tree_sitter::Node;
+
+use crate::regexes::{
+    backtracking_risks, find_regex_literals, pattern_of, regex_instructions, split_regex_literal,
+    validate_pattern, REGEX_SYSTEM_PROMPT,
+};
+use crate::sql::{find_sql_literals, sql_instructions, SQL_SYSTEM_PROMPT};
+
+/// String literals of each supported language, including raw and template ones
+pub const STRING_KINDS: &[&str] = &[
+    "string_literal",
+    "raw_string_literal",
+    "interpreted_string_literal",
+    "string",
+    "template_string",
+    "text_block",
+];
+
+/// What the model optimises in the literals of the function with `--sql` and `--regex`, rather
+/// than the function itself
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LiteralPass {
+    Sql,
+    Regex,
+}
+
+/// A literal, apart from what opens and closes it
+#[derive(Debug, PartialEq)]
+pub struct Literal {
+    pub contents: String,
+    /// What opens the literal, like `"`, `r#"` or `/`
+    pub opening: String,
+    /// What closes it, like `"`, `"#` or `/gi`
+    pub closing: String,
+}
+
+impl Literal {
+    /// The literal with other contents, keeping the whitespace around the original ones. Contents
+    /// given along with what opens and closes the literal are taken out of it first.
+    pub fn requote(&self, contents: &str) -> String {
+        let contents = contents.trim();
+        let contents = contents
+            .strip_prefix(self.opening.as_str())
+            .and_then(|contents| contents.strip_suffix(self.closing.as_str()))
+            .unwrap_or(contents);
+        let leading = &self.contents[..self.contents.len() - self.contents.trim_start().len()];
+        let trailing = &self.contents[self.contents.trim_end().len()..];
+
+        format!(
+            "{}{}{}{}{}",
+            self.opening,
+            leading,
+            contents.trim(),
+            trailing,
+            self.closing
+        )
+    }
+}
+
+/// Splits a string literal into its quotes and what they hold. Prefixes like `r#` and `R"(` and
+/// the triple quotes of Java text blocks go with the quotes.
+pub fn split_quotes(literal: &str) -> Option<Literal> {
+    let start = literal.find(['"', '\'', '`'])?;
+    let quote = literal[start..].chars().next()?;
+    let quotes = if literal[start..].starts_with(&quote.to_string().repeat(3)) {
+        3
+    } else {
+        1
+    };
+    // C++ raw strings, like `R"(SELECT ...)"`
+    let raw =
+        usize::from(literal[..start].ends_with('R') && literal[start + quotes..].starts_with('('));
+    let opening_end = start + quotes + raw;
+    let closing_start = (literal.rfind(quote)? + 1).checked_sub(quotes + raw)?;
+    if closing_start < opening_end {
+        return None;
+    }
+
+    Some(Literal {
+        contents: literal[opening_end..closing_start].to_string(),
+        opening: literal[..opening_end].to_string(),
+        closing: literal[closing_start..].to_string(),
+    })
+}
+
+/// What the escape sequences of a string literal stand for, as far as they matter to what it holds
+pub fn unescape(contents: &str) -> String {
+    let mut unescaped = String::with_capacity(contents.len());
+    let mut characters = contents.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            unescaped.push(character);
+            continue;
+        }
+        match characters.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some(escaped @ ('\\' | '"' | '\'' | '`')) => unescaped.push(escaped),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+impl LiteralPass {
+    pub fn system_prompt(self) -> &'static str {
+        match self {
+            LiteralPass::Sql => SQL_SYSTEM_PROMPT,
+            LiteralPass::Regex => REGEX_SYSTEM_PROMPT,
+        }
+    }
+    /// What the literals of the pass hold, for messages
+    pub fn contents_name(self) -> &'static str {
+        match self {
+            LiteralPass::Sql => "SQL",
+            LiteralPass::Regex => "regular expression",
+        }
+    }
+    /// The literals of the pass in the node, in the order they appear
+    pub fn find<'a>(self, source_file: &str, node: Node<'a>) -> Vec<Node<'a>> {
+        match self {
+            LiteralPass::Sql => find_sql_literals(source_file, node),
+            LiteralPass::Regex => find_regex_literals(source_file, node),
+        }
+    }
+    pub fn split(self, literal: &str) -> Option<Literal> {
+        match self {
+            LiteralPass::Sql => split_quotes(literal),
+            LiteralPass::Regex => split_regex_literal(literal),
+        }
+    }
+    /// What is found wrong with the literal ahead of time
+    pub fn findings(self, language: &str, literal: &Literal) -> Vec<String> {
+        match self {
+            LiteralPass::Sql => vec![],
+            LiteralPass::Regex => backtracking_risks(language, &pattern_of(language, literal)),
+        }
+    }
+    /// What the model is told about the literal and the function around it
+    pub fn instructions(self, language: &str, literal: &Literal, function: &str) -> String {
+        match self {
+            LiteralPass::Sql => sql_instructions(language, literal, function),
+            LiteralPass::Regex => regex_instructions(
+                language,
+                literal,
+                function,
+                &self.findings(language, literal),
+            ),
+        }
+    }
+    /// Why the suggested literal cannot replace the original one, if it cannot
+    pub fn check(self, language: &str, literal: &Literal) -> Result<(), String> {
+        match self {
+            LiteralPass::Sql => Ok(()),
+            LiteralPass::Regex => validate_pattern(language, literal),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::literal::{split_quotes, unescape, Literal};
+
+    #[test]
+    fn quoted_literals() {
+        assert_eq!(
+            Some(Literal {
+                contents: "SELECT id FROM orders".to_string(),
+                opening: "r#\"".to_string(),
+                closing: "\"#".to_string(),
+            }),
+            split_quotes("r#\"SELECT id FROM orders\"#")
+        );
+        assert_eq!(
+            "SELECT 1 FROM t",
+            split_quotes("R\"(SELECT 1 FROM t)\"").unwrap().contents
+        );
+        let text_block = split_quotes("\"\"\"\nSELECT 1 FROM t\n\"\"\"").unwrap();
+        assert_eq!("\nSELECT 1 FROM t\n", text_block.contents);
+        assert_eq!(
+            "\"\"\"\nSELECT 2 FROM t\n\"\"\"",
+            text_block.requote(" SELECT 2 FROM t")
+        );
+        assert_eq!(
+            "\"\"\"\nSELECT 2 FROM t\n\"\"\"",
+            text_block.requote("\"\"\"SELECT 2 FROM t\"\"\"")
+        );
+        assert_eq!("\\d+\"", unescape("\\\\d+\\\""));
+    }
+}
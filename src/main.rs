@@ -1,47 +1,331 @@
+use std::collections::HashMap;
 use std::env;
-use std::fs::{OpenOptions, read_to_string};
+use std::fs::{read_to_string, OpenOptions};
 use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use colored::*;
-use inquire::Confirm;
-use reqwest::Url;
-use serde::{Deserialize, Serialize};
-use tree_sitter::Tree;
+use inquire::{Confirm, Text};
+use tree_sitter::{Node, Range, Tree};
 
-use crate::helpers::tree_sitter::node_value;
-use crate::supported_languages::supported_language::{detect_language, SupportedLanguage};
+use crate::attachment::image_data_url;
+use crate::batch::{batch_files, source_files, BatchFilters, IdenticalFunctions};
+use crate::build_errors::{parse_build_errors, run_build, FIX_SYSTEM_PROMPT};
+use crate::cache::{fingerprint, VerdictCache};
+use crate::callers::{
+    apply_caller_updates, draft_caller_update_instructions, find_callers, signature_change,
+    CallerUpdate,
+};
+use crate::capabilities::{capabilities, is_known_model};
+use crate::changelog::{draft_changelog_entry_instructions, write_changelog, ChangelogStyle};
+use crate::checks::{introduced_dependencies, introduced_symbols, introduces_unsafe_blocks};
+use crate::color::{ColorChoice, Palette, NO_COLOR_ENV_VAR_KEY};
+use crate::complexity::{refactor_system_prompt, run_complexity_check};
+use crate::config::{
+    effective_policy, load_constraints, load_org_policy, Config, CONFIG_FILE_PATH,
+    CONSTRAINTS_FILE_PATH,
+};
+use crate::context::{
+    estimate_tokens, function_body, name_of, pack_context, PackedContext, DEFAULT_TOKEN_BUDGET,
+};
+use crate::conversation::Conversation;
+use crate::coverage::Coverage;
+use crate::diagnostics::{load_diagnostics, render_diagnostics, Diagnostic, DIAGNOSTICS_HEADING};
+use crate::diff::{render_structural_diff, render_text_diff, DiffMode};
+use crate::doctor::run_doctor;
+use crate::dupes::{run_dupes, DupesSettings};
+use crate::edit::{
+    run_apply, ApplyBackend, Edit, OutputFormat, Report, Suggestion as PortableSuggestion,
+};
+use crate::embeddings::{describe_examples, most_similar, EmbeddingIndex};
+use crate::extract::{extract_function, Selection};
+use crate::git::{clean_commit_message, draft_commit_message_instructions, CommitMessage};
+use crate::github::{repo_from_origin, GitHubClient, GITHUB_TOKEN_ENV_VAR_KEY};
+use crate::goal::{is_async_function, render_suspects, Goal, Suspect};
+use crate::helpers::tree_sitter::{node_value, parse_file, syntax_errors};
+use crate::history::{
+    applied_entries, check_summaries, describe_previous_attempt, describe_rejection_reasons,
+    last_entry, rejection_reasons, render_trends, repository_history, set_history_store,
+    CheckSummary, HistoryEntry, HistoryStoreKind, HISTORY_STORE_ENV_VAR_KEY,
+};
+use crate::hooks::run_post_apply_hook;
+use crate::implement::{stub_documentation, IMPLEMENT_SYSTEM_PROMPT};
+use crate::inline::{run_inline, InlineSettings};
+use crate::languages::languages_report;
+use crate::literal::{Literal, LiteralPass};
+use crate::nightly::run_nightly;
+use crate::notify::{post_to_webhook, BatchSummary, FlaggedFunction};
+use crate::openai::{
+    chat, complete, prompt_token_usage, set_reasoning_settings, Message, Prompt, ReasoningEffort,
+    ReasoningSettings, OPENAI_API_KEY_ENV_VAR_KEY,
+};
+use crate::outcome::{Outcome, OutcomeKind, OutcomeSummary};
+use crate::panics::run_panic_paths;
+use crate::prepass::{find_dead_code, DEAD_CODE_HEADING};
+use crate::provenance::{
+    default_provenance_template, implemented_provenance_template, place_provenance_comment,
+    render_provenance_comment,
+};
+use crate::queue::{
+    load_queue, render_queue, run_queue_add, run_queue_approve, run_queue_reject, QueueStatus,
+    QUEUE_FILE_PATH,
+};
+use crate::rate_limit::{set_rate_limits, RateLimits};
+use crate::review::run_review;
+use crate::sandbox::Sandbox;
+use crate::scan::run_scan;
+use crate::selftest::{render_support_matrix, run_selftest};
+use crate::snapshot::{list_snapshots, revert_to, take_snapshot, SNAPSHOTS_DIRECTORY_PATH};
+use crate::snippet::{parse_span, run_snippet, SnippetSettings, Span as SnippetSpan};
+use crate::suggestion::{
+    parse_suggestion, render_rationale, suggestion_response_format, Risk, Suggestion,
+    SuggestionStatus, EXPLAIN_INSTRUCTIONS, STRUCTURED_ANSWER_INSTRUCTIONS,
+};
+use crate::supported_languages::supported_language::{
+    detect_language, dump_tree, explain_not_found, FunctionKind, SupportedLanguage,
+};
+use crate::test_generation::{run_test_generation, TestSettings};
+use crate::trace::{
+    enclosing_function, parse_trace, repository_frames, trace_context, traced_functions,
+    TRACE_SYSTEM_PROMPT,
+};
+use crate::watch::{run_watch, WatchSettings};
+use crate::workspace::{find_packages, group_by_package};
 
+mod attachment;
+mod batch;
+mod build_errors;
+mod cache;
+mod callers;
+mod capabilities;
+mod changelog;
+mod checks;
+mod color;
+mod complexity;
+mod config;
+mod context;
+mod conversation;
+mod coverage;
+mod diagnostics;
+mod diff;
+mod doctor;
+mod dupes;
+mod edit;
+mod embeddings;
+mod extract;
+mod git;
+mod github;
+mod goal;
 mod helpers;
+mod history;
+mod hooks;
+mod implement;
+mod inline;
+mod languages;
+mod literal;
+mod nightly;
+mod notify;
+mod openai;
+mod outcome;
+mod panics;
+mod prepass;
+mod provenance;
+mod queue;
+mod rate_limit;
+mod regexes;
+mod review;
+mod sandbox;
+mod scan;
+mod selftest;
+mod snapshot;
+mod snippet;
+mod sql;
+mod suggestion;
 mod supported_languages;
+mod test_generation;
+mod trace;
+mod watch;
+mod workspace;
 
-const OPENAI_API_KEY_ENV_VAR_KEY: &str = "OPENAI_API_KEY";
-const COMPLETION_URL: &str = "https://api.openai.com/v1/chat/completions";
+const DEFAULT_MODEL: &str = "gpt-3.5-turbo";
+const DEFAULT_THEME: &str = "Dracula";
+/// Uses the colors of the terminal, which can be set up for color blindness
+const HIGH_CONTRAST_THEME: &str = "ansi";
+/// How many of the latest rejection reasons are passed on to the model
+const MAX_REJECTION_REASONS: usize = 20;
 
 struct Optimizer {
     supported_language: Box<dyn SupportedLanguage>,
     file_name: String,
     parent_element: Option<String>,
     function_name: Option<String>,
-    source_file: String,
+    kind: Option<FunctionKind>,
+    /// Shared rather than copied, as generated files can take up megabytes
+    source_file: Arc<str>,
     code: String,
+    /// Other parts of the file that help understand the code, packed by `pack_context`
+    context: PackedContext,
+    /// How many tokens the code and its context may take up in the prompt
+    token_budget: usize,
+    /// Advice on optimising code in the language of the file
+    language_instructions: String,
+    goal: Goal,
+    /// The expressions of the code the goal calls for a closer look at
+    suspects: Vec<Suspect>,
+    /// Unused parameters and unreachable statements of the code
+    dead_code: Vec<Suspect>,
+    /// What linters reported about the files, from `--diagnostics`
+    diagnostics: Arc<Vec<Diagnostic>>,
+    /// The name of the selected function, which tells it apart in the history
+    selected_name: Option<String>,
+    /// What became of the last suggestion for the code, if there was one
+    previous_attempt: String,
+    /// Why suggestions were rejected before, as standing constraints
+    rejection_reasons: String,
+    /// The hard constraints of the repository, from `.senior/constraints.md`
+    constraints: String,
+    /// How many of the applied suggestions most similar to the code are given as examples
+    similar_examples: Option<usize>,
+    /// The applied suggestions most similar to the code, as examples
+    examples: String,
     extra_context: Option<String>,
     model: String,
     theme: String,
     dry_run: bool,
     add_comments: bool,
     skip_prompt: bool,
+    git_commit: Option<CommitMessage>,
+    /// The original code and the suggestion of every suggestion applied so far
+    applied_suggestions: Vec<(String, String)>,
+    conversation: Option<Conversation>,
+    /// Replaces the default system prompt
+    system_prompt: Option<String>,
+    /// Leave out the advice on optimising, for `senior annotate` and `senior implement`
+    skip_optimising_advice: bool,
+    /// Apply suggestions up to this risk without asking
+    auto_apply: Option<Risk>,
+    /// Ask the model why its change is faster, when its answer does not already say so
+    explain: bool,
+    /// Symbols suggestions may not introduce, from `.senior.toml`
+    forbidden_symbols: Vec<String>,
+    /// Commands run after every applied suggestion, from `.senior.toml`
+    post_apply: Vec<String>,
+    /// Where the post-apply commands run
+    sandbox: Option<Sandbox>,
+    /// Images shown along with the code, like profiler screenshots, as `data:` URLs
+    attachments: Vec<String>,
+    /// How applied suggestions are written to the file
+    apply_backend: ApplyBackend,
+    /// The comment left after applied suggestions, before its model and date are filled in
+    provenance_comment: Option<String>,
+    /// Who reviews the suggestions, recorded in the history in compliance mode
+    reviewer: Option<String>,
+    /// Let suggestions change the signature of the function, and update its callers to match
+    allow_signature_change: bool,
+    /// Ask for a revision of suggestions that need new dependencies
+    no_new_dependencies: bool,
+    /// Which lines tests run, from `--coverage`
+    coverage: Option<Arc<Coverage>>,
+    /// Apply suggestions to functions no test covers without asking
+    force: bool,
+    /// Refuse suggestions that add `unsafe` blocks
+    deny_unsafe: bool,
+    /// How suggestions are reported
+    output_format: OutputFormat,
+    /// Show suggestions as a diff against the code instead of on their own
+    diff: Option<DiffMode>,
+    /// The colors of diffs
+    palette: Palette,
+    /// Whether `bat` colors suggestions
+    colored_output: bool,
     tree: Option<Tree>,
     function_node_id: usize,
-    parser: tree_sitter::Parser,
+    /// Where the code a suggestion replaces lies in the source file
+    function_range: Option<Range>,
+    /// Only replace the body of the function, keeping its signature as is
+    body_only: bool,
+    /// With `body_only`, the code of the function before and after its body
+    around_body: Option<(String, String)>,
+    /// With `--sql` or `--regex`, what the literals of the function are optimised for
+    literal_pass: Option<LiteralPass>,
+    /// The literal whose contents the suggestion replaces
+    literal: Option<Literal>,
 }
 
+/// Lets the model change the signature of the function with `--allow-signature-change`
+const SIGNATURE_CHANGE_INSTRUCTIONS: &str = "Unlike instruction 1 says, you may change the signature of the function if that makes it faster, as its callers will be updated to match.";
+/// Asks for the body of the function alone with `--body-only`
+const BODY_ONLY_INSTRUCTIONS: &str = "Respond only with the new body of the function, from its opening brace to its closing brace, as its signature may not change.";
+
+const DEFAULT_SYSTEM_PROMPT: &str = r#"You are a senior software engineer. Your task is to optimise the code you are given.
+
+Strictly adhere to the following instructions:
+1. Do not change the type signature.
+2. Only propose small, incremental changes.
+3. Respond only with code, and no commentary or explanations.
+4. If the code cannot be optimised further, respond with "OPTIMAL"
+"#;
+
+/// The system prompt of `senior cleanup`, which only removes what does nothing
+const CLEANUP_SYSTEM_PROMPT: &str = r#"You are a senior software engineer. Your task is to clean up the code you are given, without optimising it.
+
+Strictly adhere to the following instructions:
+1. Do not change the type signature.
+2. Do not change what the code does, for any input, including the errors it returns, the panics it raises and the order of its side effects.
+3. Only remove dead code, unused variables and redundant branches, and simplify what their removal leaves behind.
+4. Respond only with code, and no commentary or explanations.
+5. If there is nothing to remove, respond with "OPTIMAL"
+"#;
+
+/// The system prompt of `senior annotate`, which only adds types to code that lacks them
+fn annotate_system_prompt(language: &str) -> Result<String, String> {
+    let types = match language {
+        "typescript" | "tsx" => {
+            "TypeScript type annotations to its parameters, return type and untyped variables"
+        }
+        "javascript" | "jsx" => {
+            "JSDoc comments with the types of its parameters and return value, as the file is JavaScript"
+        }
+        _ => {
+            return Err(format!(
+                "annotate only adds types to JavaScript and TypeScript, and {} code has them already",
+                language
+            ))
+        }
+    };
+
+    Ok(format!(
+        r#"You are a senior software engineer. Your task is to add {} to the code you are given.
+
+Strictly adhere to the following instructions:
+1. Do not change anything else, not even formatting, names or the order of statements.
+2. Add no comments other than JSDoc ones.
+3. Prefer precise types over `any`, and infer them from how the values are used.
+4. Respond only with code, and no commentary or explanations.
+5. If the code is fully typed already, respond with "OPTIMAL"
+"#,
+        types
+    ))
+}
+
+/// Drafts the prompt for the code. What stays the same across a run, the standing guidance, goes
+/// in the system message, and what is particular to the code, the guidance, in the user one. The
+/// system message is then the same for every function, which providers cache.
+#[allow(clippy::too_many_arguments)]
 fn draft_instructions(
     code: &str,
+    context: &str,
     function_name: &Option<String>,
     add_comments: bool,
-    extra_context: &Option<String>,
-) -> String {
+    standing_guidance: &[&str],
+    guidance: &[&str],
+    system_prompt: &Option<String>,
+    structured: bool,
+) -> Prompt {
     let add_comments = if add_comments {
         "Do"
     } else {
@@ -55,67 +339,128 @@ fn draft_instructions(
     } else {
         format!("the following code:\n {}", code)
     };
+    let context = if context.is_empty() {
+        "".to_string()
+    } else {
+        format!(
+            "\n\nFor context, here are other parts of the same file, which are not to be optimised:\n{}",
+            context
+        )
+    };
+    let add_comments = format!("{} add comments.", add_comments);
+    let structured = if structured {
+        STRUCTURED_ANSWER_INSTRUCTIONS
+    } else {
+        ""
+    };
+    let standing_guidance: Vec<&str> = [add_comments.as_str()]
+        .iter()
+        .chain(standing_guidance)
+        .chain([&structured])
+        .copied()
+        .filter(|guidance| !guidance.is_empty())
+        .collect();
+    let guidance: Vec<&str> = guidance
+        .iter()
+        .copied()
+        .filter(|guidance| !guidance.is_empty())
+        .collect();
 
-    format!(
-        r#"You are a senior software engineer.
-
-Your task is to optimise {}
-
-Strictly adhere to the following instructions:
-1. Do not change the type signature.
-2. Only propose small, incremental changes.
-3. {} add comments.
-4. Respond only with code, and no commentary or explanations.
-5. If the code cannot be optimised further, respond with "OPTIMAL"
-{}
-"#,
-        task,
-        add_comments,
-        if let Some(context) = extra_context {
-            format!("6. {}", context)
-        } else {
-            "".to_string()
-        }
-    )
+    Prompt {
+        system: format!(
+            "{}\n{}",
+            system_prompt.as_deref().unwrap_or(DEFAULT_SYSTEM_PROMPT),
+            standing_guidance.join("\n")
+        ),
+        user: format!("Optimise {}{}\n\n{}", task, context, guidance.join("\n"))
+            .trim_end()
+            .to_string(),
+    }
 }
 
 impl Optimizer {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         file_name: &str,
         parent_element: Option<String>,
         function_name: Option<String>,
+        kind: Option<FunctionKind>,
         extra_context: Option<String>,
         theme: &str,
         model: &str,
         dry_run: bool,
         add_comments: bool,
         skip_prompt: bool,
-    ) -> Self {
-        return Self {
-            supported_language: detect_language(file_name).unwrap(),
+    ) -> Result<Self, String> {
+        let supported_language = detect_language(file_name)?;
+
+        Ok(Self {
+            language_instructions: supported_language.default_instructions().to_string(),
+            supported_language,
             file_name: file_name.to_string(),
             code: "".to_string(),
+            context: PackedContext::default(),
+            token_budget: DEFAULT_TOKEN_BUDGET,
+            goal: Goal::Speed,
+            suspects: vec![],
+            dead_code: vec![],
+            diagnostics: Arc::default(),
+            selected_name: None,
+            previous_attempt: "".to_string(),
+            rejection_reasons: "".to_string(),
+            similar_examples: None,
+            examples: "".to_string(),
+            constraints: load_constraints(Path::new(CONSTRAINTS_FILE_PATH)),
             parent_element,
             function_name,
-            source_file: read_to_string(file_name).unwrap(),
+            kind,
+            source_file: read_to_string(file_name)
+                .map_err(|e| format!("failed to read {}: {}", file_name, e))?
+                .into(),
             extra_context,
             model: model.to_string(),
             theme: theme.to_string(),
             dry_run,
             add_comments,
             skip_prompt,
+            git_commit: None,
+            applied_suggestions: vec![],
+            conversation: None,
+            system_prompt: None,
+            auto_apply: None,
+            explain: false,
+            forbidden_symbols: vec![],
+            post_apply: vec![],
+            sandbox: None,
+            attachments: vec![],
+            apply_backend: ApplyBackend::Direct,
+            provenance_comment: None,
+            reviewer: None,
+            allow_signature_change: false,
+            skip_optimising_advice: false,
+            no_new_dependencies: false,
+            coverage: None,
+            force: false,
+            deny_unsafe: true,
+            output_format: OutputFormat::Text,
+            diff: None,
+            palette: Palette::RedGreen,
+            colored_output: true,
             tree: None,
             function_node_id: 0,
-            parser: tree_sitter::Parser::new(),
-        };
+            function_range: None,
+            body_only: false,
+            around_body: None,
+            literal_pass: None,
+            literal: None,
+        })
     }
     fn build(&mut self) -> Result<(), String> {
-        self.parser = tree_sitter::Parser::new();
-        self.parser
-            .set_language(self.supported_language.language())
-            .unwrap();
-
-        if let Some(tree) = self.parser.parse(&self.source_file, None) {
+        if let Some(tree) = parse_file(
+            &self.file_name,
+            &self.source_file,
+            self.supported_language.language(),
+        ) {
             self.tree = Some(tree);
 
             Ok(())
@@ -125,28 +470,6 @@ impl Optimizer {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-struct Message {
-    role: String,
-    content: String,
-}
-
-#[derive(Serialize)]
-struct OpenAIChatRequest {
-    model: String,
-    messages: Vec<Message>,
-}
-
-#[derive(Deserialize, Debug)]
-struct OpenAIChatResponseChoice {
-    message: Message,
-}
-
-#[derive(Deserialize, Debug)]
-struct OpenAIChatResponse {
-    choices: Vec<OpenAIChatResponseChoice>,
-}
-
 fn do_render(tree: &Tree, src: &str, editor: &impl tree_sitter_edit::Editor) -> Vec<u8> {
     let mut v: Vec<u8> = Vec::new();
     tree_sitter_edit::render(&mut v, tree, src.as_bytes(), editor).unwrap();
@@ -154,54 +477,520 @@ fn do_render(tree: &Tree, src: &str, editor: &impl tree_sitter_edit::Editor) ->
 }
 
 impl Optimizer {
-    async fn optimise(&mut self, secret: &str) -> Result<String, String> {
-        let client = reqwest::Client::new();
-        let model = self.model.clone();
-        let node = self.supported_language.find_correct_node(
+    /// Selects the function named by the parent and function identifiers, or the whole file
+    fn select_function(&mut self) -> Result<(), String> {
+        let tree = self.tree.take().unwrap();
+        let selected = self
+            .supported_language
+            .find_correct_node(
+                &self.source_file,
+                &tree,
+                &self.parent_element,
+                &self.function_name,
+                &self.kind,
+            )
+            .map(|function_node| self.select_node(&tree, function_node))
+            .map_err(|e| explain_not_found(self.supported_language.as_ref(), e));
+        self.tree = Some(tree);
+
+        selected
+    }
+    /// Selects the function spanning the given range of the current tree
+    fn select_function_at(&mut self, range: &Range) {
+        let tree = self.tree.take().unwrap();
+        let function_node = tree
+            .root_node()
+            .descendant_for_byte_range(range.start_byte, range.end_byte)
+            .unwrap();
+        self.select_node(&tree, function_node);
+        self.tree = Some(tree);
+    }
+    /// Selects the node of the tree, along with everything the prompt says about it. The tree is
+    /// the current one, taken out of `self.tree` while the node borrows it.
+    fn select_node(&mut self, tree: &Tree, function_node: Node) {
+        let function_range = self
+            .supported_language
+            .replaced_range(&self.source_file, function_node);
+        self.code =
+            self.source_file[function_range.start_byte..function_range.end_byte].to_string();
+        self.context = pack_context(
+            self.supported_language.as_ref(),
             &self.source_file,
-            self.tree.as_ref().unwrap(),
-            &self.parent_element,
-            &self.function_name,
+            tree,
+            function_node,
+            self.token_budget,
         );
-        match node {
-            Ok(function_node) => {
-                self.code = node_value(&self.source_file, function_node).to_string();
-                self.function_node_id = function_node.id();
+        let clauses = self
+            .supported_language
+            .clauses(&self.source_file, function_node);
+        self.suspects = clauses
+            .iter()
+            .flat_map(|clause| {
+                self.goal.find_suspects(
+                    self.supported_language.as_ref(),
+                    &self.source_file,
+                    *clause,
+                )
+            })
+            .collect();
+        self.dead_code = if function_node == tree.root_node() {
+            vec![]
+        } else {
+            clauses
+                .iter()
+                .flat_map(|clause| find_dead_code(&self.source_file, *clause))
+                .collect()
+        };
+        self.selected_name = name_of(&self.source_file, function_node).map(str::to_string);
+        self.previous_attempt = self
+            .selected_name
+            .as_ref()
+            .and_then(|name| last_entry(repository_history().as_ref(), &self.file_name, name))
+            .map(|entry| describe_previous_attempt(&entry, &self.code))
+            .unwrap_or_default();
+        self.rejection_reasons = describe_rejection_reasons(&rejection_reasons(
+            repository_history().as_ref(),
+            MAX_REJECTION_REASONS,
+        ));
+        let replaced_node = self.replaced_node(function_node);
+        let replaced_range = self
+            .supported_language
+            .replaced_range(&self.source_file, replaced_node);
+        self.select_replaced_node(function_range, replaced_node.id(), replaced_range);
+    }
+    /// The node a suggestion replaces, which with `body_only` is the body of the function, unless
+    /// it has none
+    fn replaced_node<'a>(&self, function_node: Node<'a>) -> Node<'a> {
+        self.body_only
+            .then(|| function_body(function_node))
+            .flatten()
+            .unwrap_or(function_node)
+    }
+    /// The literals of the selected function that the literal pass optimises. A function split
+    /// in several nodes has none of its own, so they are looked for in the one holding them all.
+    fn literals(&self) -> Vec<Node<'_>> {
+        let (Some(literal_pass), Some(range)) = (self.literal_pass, self.function_range) else {
+            return vec![];
+        };
+        let function_node = self
+            .tree
+            .as_ref()
+            .unwrap()
+            .root_node()
+            .descendant_for_byte_range(range.start_byte, range.end_byte)
+            .unwrap();
+
+        literal_pass
+            .find(&self.source_file, function_node)
+            .into_iter()
+            .filter(|literal| {
+                literal.start_byte() >= range.start_byte && literal.end_byte() <= range.end_byte
+            })
+            .collect()
+    }
+    /// Narrows the selected function down to the contents of one of its literals, which is all the
+    /// suggestion replaces
+    fn select_literal(&mut self, index: usize) -> Result<(), String> {
+        let (literal_pass, function_range) = self
+            .literal_pass
+            .zip(self.function_range)
+            .ok_or("no function is selected")?;
+        let literal_node = *self
+            .literals()
+            .get(index)
+            .ok_or("the function holds no more literals")?;
+        let (id, range) = (literal_node.id(), literal_node.range());
+        let literal = literal_pass.split(node_value(&self.source_file, literal_node));
+        self.select_replaced_node(function_range, id, range);
+        self.literal = literal;
+
+        Ok(())
+    }
+    fn select_replaced_node(&mut self, function_range: Range, id: usize, range: Range) {
+        self.literal = None;
+        self.function_node_id = id;
+        self.function_range = Some(range);
+        self.around_body = (range != function_range).then(|| {
+            let start = range.start_byte - function_range.start_byte;
+            let end = range.end_byte - function_range.start_byte;
+            (self.code[..start].to_string(), self.code[end..].to_string())
+        });
+    }
+    /// With `body_only`, puts the suggested body back between the code before and after the
+    /// original body, so that the suggestion is a whole function again
+    fn around_suggested_body(&self, body: &str) -> String {
+        let Some((before, after)) = &self.around_body else {
+            return body.to_string();
+        };
+        // Models sometimes answer with the whole function anyway
+        let body = body.trim();
+        let body = body.strip_prefix(before.trim()).unwrap_or(body).trim();
+        let body = body.strip_suffix(after.trim()).unwrap_or(body).trim();
+
+        format!("{}{}{}", before, body, after)
+    }
+    /// The part of the suggestion that replaces the selected node, which with `body_only` leaves
+    /// out everything around the body
+    fn replaced_part<'a>(&self, suggestion: &'a str) -> &'a str {
+        match &self.around_body {
+            Some((before, after)) => suggestion
+                .strip_prefix(before.as_str())
+                .and_then(|suggestion| suggestion.strip_suffix(after.as_str()))
+                .unwrap_or(suggestion),
+            None => suggestion,
+        }
+    }
+    fn prompt(&self) -> Prompt {
+        let goal_instructions = if self.skip_optimising_advice {
+            "".to_string()
+        } else {
+            self.goal.instructions(&self.suspects)
+        };
+        let language_instructions = if self.skip_optimising_advice {
+            ""
+        } else {
+            &self.language_instructions
+        };
+        let dead_code_instructions = if self.dead_code.is_empty() || self.skip_optimising_advice {
+            "".to_string()
+        } else {
+            format!(
+                "{}\n{}",
+                DEAD_CODE_HEADING,
+                render_suspects(&self.dead_code)
+            )
+        };
+        let diagnostics = self.function_range.map_or("".to_string(), |range| {
+            render_diagnostics(
+                &self.diagnostics,
+                &self.file_name,
+                &self.source_file,
+                range.start_point.row + 1,
+                range.end_point.row + 1,
+            )
+        });
+        let diagnostics_instructions = if diagnostics.is_empty() {
+            "".to_string()
+        } else {
+            format!("{}\n{}", DIAGNOSTICS_HEADING, diagnostics)
+        };
+        let body_only_instructions = match self.literal_pass.zip(self.literal.as_ref()) {
+            Some((literal_pass, literal)) => {
+                literal_pass.instructions(&self.supported_language.to_string(), literal, &self.code)
             }
-            // :D code gore
-            Err(e) => {
-                return Err(e.to_string());
+            None if self.around_body.is_some() => BODY_ONLY_INSTRUCTIONS.to_string(),
+            None => "".to_string(),
+        };
+        let signature_change_instructions = if self.allow_signature_change {
+            SIGNATURE_CHANGE_INSTRUCTIONS
+        } else {
+            ""
+        };
+        draft_instructions(
+            self.literal
+                .as_ref()
+                .map_or(&self.code, |literal| &literal.contents),
+            &self.context.packed,
+            &self.function_name,
+            self.add_comments,
+            &[
+                &self.constraints,
+                &self.rejection_reasons,
+                language_instructions,
+                self.extra_context.as_deref().unwrap_or_default(),
+                &body_only_instructions,
+                signature_change_instructions,
+            ],
+            &[
+                &self.previous_attempt,
+                &self.examples,
+                &goal_instructions,
+                &dead_code_instructions,
+                &diagnostics_instructions,
+            ],
+            &self.system_prompt,
+            capabilities(&self.model).structured_outputs,
+        )
+    }
+    fn response_format(&self) -> Option<serde_json::Value> {
+        capabilities(&self.model)
+            .structured_outputs
+            .then(suggestion_response_format)
+    }
+    /// The messages of the prompt, with the attachments shown along with the code
+    fn messages(&self) -> Vec<Message> {
+        let mut messages = self.prompt().messages();
+        if let Some(user) = messages.last_mut() {
+            user.images = self.attachments.clone();
+        }
+
+        messages
+    }
+    async fn optimise(&mut self, secret: &str) -> Result<String, String> {
+        let prompt = self.prompt();
+        let messages = self.messages();
+        let response_format = self.response_format();
+        match &mut self.conversation {
+            Some(conversation) => {
+                // The images are shown once, rather than again on every turn
+                let shown = conversation
+                    .messages
+                    .iter()
+                    .any(|message| !message.images.is_empty());
+                conversation.ask(&prompt);
+                if let Some(user) = conversation.messages.last_mut().filter(|_| !shown) {
+                    user.images = self.attachments.clone();
+                }
+                let answer =
+                    chat(&self.model, &conversation.messages, response_format, secret).await?;
+                conversation.answer(&answer);
+                conversation.save()?;
+
+                Ok(answer)
             }
+            None => chat(&self.model, &messages, response_format, secret).await,
+        }
+    }
+    /// Looks up the applied suggestions whose original code is the most similar to the code,
+    /// embedding whatever was not embedded yet
+    async fn find_similar_examples(&mut self, secret: &str) -> Result<(), String> {
+        let Some(k) = self.similar_examples else {
+            return Ok(());
+        };
+        let history = repository_history();
+        let applied = applied_entries(history.as_ref());
+        if applied.is_empty() {
+            return Ok(());
         }
+        let mut index = EmbeddingIndex::load(history.as_ref());
+        let codes: Vec<&str> = applied
+            .iter()
+            .map(|entry| entry.original.as_str())
+            .chain([self.code.as_str()])
+            .collect();
+        index.embed_missing(&codes, secret).await?;
+        index.save(history.as_ref())?;
+        self.examples = describe_examples(&most_similar(&index, &self.code, &applied, k));
 
-        let body = OpenAIChatRequest {
-            model,
-            messages: vec![Message {
-                role: "system".to_string(),
-                content: draft_instructions(
-                    &self.code,
-                    &self.function_name,
-                    self.add_comments,
-                    &self.extra_context,
-                ),
-            }],
+        Ok(())
+    }
+    /// Asks something about the answer in a second turn
+    async fn follow_up(
+        &self,
+        answer: &str,
+        request: &str,
+        response_format: Option<serde_json::Value>,
+        secret: &str,
+    ) -> Result<String, String> {
+        let mut messages = self.messages();
+        messages.push(Message::new("assistant", answer));
+        messages.push(Message::new("user", request));
+
+        chat(&self.model, &messages, response_format, secret).await
+    }
+    /// The forbidden symbols the suggestion introduces
+    fn introduced_symbols(&self, suggestion: &str) -> Vec<String> {
+        introduced_symbols(
+            self.supported_language.language(),
+            &self.code,
+            suggestion,
+            &self.forbidden_symbols,
+        )
+        .into_iter()
+        .map(str::to_string)
+        .collect()
+    }
+    /// The packages the suggestion needs that the file does not import and its manifest does not
+    /// declare
+    fn introduced_dependencies(&self, suggestion: &str) -> Vec<String> {
+        introduced_dependencies(
+            self.supported_language.as_ref(),
+            Path::new(&self.file_name),
+            &self.source_file,
+            suggestion,
+        )
+    }
+    /// Replaces the advice for the language of the file with the one of `.senior.toml`, if any
+    fn configure_language_instructions(&mut self, instructions: &HashMap<String, String>) {
+        let language = self.supported_language.to_string();
+        if let Some(language_instructions) = instructions.get(&language) {
+            self.language_instructions = language_instructions.clone();
+        }
+    }
+    /// Leaves a comment after every applied suggestion, in the syntax of the language unless
+    /// `.senior.toml` gives one for it
+    fn configure_provenance_comment(&mut self, templates: &HashMap<String, String>) {
+        let language = self.supported_language.to_string();
+        self.provenance_comment = Some(templates.get(&language).cloned().unwrap_or_else(|| {
+            default_provenance_template(self.supported_language.line_comment())
+        }));
+    }
+    /// Whether the coverage report says no test runs the selected function
+    fn is_uncovered(&self) -> bool {
+        self.coverage
+            .as_ref()
+            .zip(self.function_range)
+            .is_some_and(|(coverage, range)| {
+                !coverage.is_covered(
+                    &self.file_name,
+                    range.start_point.row + 1,
+                    range.end_point.row + 1,
+                )
+            })
+    }
+    /// Whether the suggestion adds `unsafe` blocks while they are denied
+    fn introduces_denied_unsafe(&self, suggestion: &str) -> bool {
+        self.deny_unsafe
+            && introduces_unsafe_blocks(self.supported_language.language(), &self.code, suggestion)
+    }
+    /// What to ask the model to change in its suggestion, if anything
+    fn revision_request(
+        &self,
+        suggestion: &str,
+        introduced_symbols: &[String],
+        new_dependencies: &[String],
+    ) -> Option<String> {
+        let mut problems = vec![];
+        if self.introduces_denied_unsafe(suggestion) {
+            problems.push("Your code adds unsafe blocks, which are not allowed.".to_string());
+        }
+        if let Some((literal_pass, problem)) =
+            self.literal_pass.zip(self.literal_problem(suggestion))
+        {
+            problems.push(format!(
+                "Your {} does not compile: {}.",
+                literal_pass.contents_name(),
+                problem
+            ));
+        }
+        if !introduced_symbols.is_empty() {
+            problems.push(format!(
+                "Your code uses {}, which may not be used in this codebase.",
+                introduced_symbols.join(", ")
+            ));
+        }
+        if self.no_new_dependencies && !new_dependencies.is_empty() {
+            problems.push(format!(
+                "Your code needs {}, which the code does not depend on yet.",
+                new_dependencies.join(", ")
+            ));
+        }
+
+        (!problems.is_empty()).then(|| {
+            format!(
+                "{} Optimise the code again without them.",
+                problems.join(" ")
+            )
+        })
+    }
+    /// Why the literal of the suggestion cannot replace the original one, with `--sql` or `--regex`
+    fn literal_problem(&self, suggestion: &str) -> Option<String> {
+        let literal_pass = self.literal_pass.filter(|_| self.literal.is_some())?;
+        let Some(literal) = literal_pass.split(self.replaced_part(suggestion)) else {
+            return Some("it is not a literal anymore".to_string());
         };
 
-        let url = Url::parse(COMPLETION_URL).unwrap();
+        literal_pass
+            .check(&self.supported_language.to_string(), &literal)
+            .err()
+    }
+    /// What is found wrong with the selected literal ahead of time
+    fn literal_findings(&self) -> Vec<String> {
+        self.literal_pass
+            .zip(self.literal.as_ref())
+            .map_or(vec![], |(literal_pass, literal)| {
+                literal_pass.findings(&self.supported_language.to_string(), literal)
+            })
+    }
+    /// Records in the history whether the suggestion was applied, and why not
+    fn record_in_history(&self, suggestion: &str, applied: bool, reason: Option<String>) {
+        let Some(function) = &self.selected_name else {
+            return;
+        };
+        let mut entry =
+            HistoryEntry::new(&self.file_name, function, &self.code, suggestion, applied);
+        entry.reason = reason;
+        entry.reviewer = self.reviewer.clone();
+        if let Err(e) = repository_history().record_entry(&entry) {
+            eprintln!(
+                "{}",
+                format!("failed to record the suggestion: {}", e).yellow()
+            );
+        }
+    }
+    /// Offers to add each of the dependencies with the command of the language, if it has one
+    fn offer_to_add_dependencies(&self, new_dependencies: &[String]) {
+        let directory = Path::new(&self.file_name)
+            .parent()
+            .filter(|directory| !directory.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        for dependency in new_dependencies {
+            let Some(command) = self.supported_language.add_dependency_command(dependency) else {
+                continue;
+            };
+            let add_dependency =
+                Confirm::new(&format!("Add {} with `{}`?", dependency, command.join(" ")))
+                    .with_default(false)
+                    .prompt()
+                    .unwrap();
+            if !add_dependency {
+                continue;
+            }
+            let status = std::process::Command::new(&command[0])
+                .args(&command[1..])
+                .current_dir(directory)
+                .status();
+            if !status.is_ok_and(|status| status.success()) {
+                eprintln!("{}", format!("failed to add {}", dependency).yellow());
+            }
+        }
+    }
+    /// Reads the answer, with the suggested body put back in the function with `body_only`, or the
+    /// suggested contents in their literal with `--sql` or `--regex`
+    fn parse_suggestion(&self, answer: &str) -> Suggestion {
+        let mut suggestion = parse_suggestion(answer);
+        if !suggestion.is_optimal() {
+            let replaced_part = match &self.literal {
+                Some(literal) => literal.requote(&suggestion.code),
+                None => suggestion.code,
+            };
+            suggestion.code = self.around_suggested_body(&replaced_part);
+        }
 
-        match client.post(url).bearer_auth(secret).json(&body).build() {
-            Ok(req) => match client.execute(req).await {
-                Ok(resp) => match resp.json::<OpenAIChatResponse>().await {
-                    Ok(parsed_resp) => {
-                        let content = parsed_resp.choices[0].message.content.clone();
+        suggestion
+    }
+    /// What came out of the selected function, with the edit that applies its suggestion
+    fn report(&self, outcome: &Outcome) -> Report {
+        let suggestion =
+            outcome
+                .suggestion()
+                .zip(self.function_range)
+                .map(|(suggestion, range)| {
+                    PortableSuggestion::new(
+                        &self.source_file,
+                        &range,
+                        self.replaced_part(suggestion),
+                        &self.supported_language.to_string(),
+                    )
+                });
 
-                        Ok(content)
-                    }
-                    Err(e) => Err(e.to_string()),
-                },
-                Err(e) => Err(e.to_string()),
-            },
-            Err(e) => Err(e.to_string()),
+        Report::new(
+            &self.file_name,
+            self.selected_name.clone(),
+            outcome,
+            suggestion,
+        )
+    }
+    /// Tells the ongoing conversation, if any, what became of the suggestion
+    fn reply_to_conversation(&mut self, reply: &str) {
+        if let Some(conversation) = &mut self.conversation {
+            conversation.reply(reply);
+            if let Err(e) = conversation.save() {
+                eprintln!(
+                    "{}",
+                    format!("failed to save the conversation: {}", e).yellow()
+                );
+            }
         }
     }
     fn apply_suggestion_to_source_file(&mut self, suggestion: &[u8]) -> String {
@@ -215,122 +1004,2357 @@ impl Optimizer {
 
         String::from_utf8(r).unwrap()
     }
-}
+    /// Overwrites the source file with the suggestion, or patches it with git, and re-parses it so
+    /// that further functions can be selected
+    fn write_suggestion(&mut self, suggestion: &str) -> Result<(), String> {
+        let replacement = self.replaced_part(suggestion);
+        let edit = self.function_range.map(|range| {
+            let Some(template) = &self.provenance_comment else {
+                return Edit::new(&self.source_file, &range, replacement);
+            };
+            let (start_byte, end_byte, new_text) = place_provenance_comment(
+                &self.source_file,
+                range.start_byte,
+                range.end_byte,
+                replacement,
+                &render_provenance_comment(template, &self.model),
+                template,
+            );
+            let range = Range {
+                start_byte,
+                end_byte,
+                ..range
+            };
+            Edit::new(&self.source_file, &range, &new_text)
+        });
+        let edited_file = match (self.apply_backend, edit) {
+            (ApplyBackend::Git, Some(edit)) => {
+                git::apply_with_3way(&self.file_name, &self.source_file, &edit)?;
+                // Whatever else changed in the file since it was parsed is in there too
+                read_to_string(&self.file_name).map_err(|e| e.to_string())?
+            }
+            (_, edit) => {
+                let edited_file = match edit {
+                    Some(edit) => {
+                        let mut edited_file = self.source_file.to_string();
+                        edited_file.replace_range(
+                            edit.span.start_byte..edit.span.end_byte,
+                            &edit.new_text,
+                        );
+                        edited_file
+                    }
+                    None => self.apply_suggestion_to_source_file(replacement.as_bytes()),
+                };
+                let mut opened_file = OpenOptions::new()
+                    .write(true)
+                    .truncate(true)
+                    .open(&self.file_name)
+                    .map_err(|e| e.to_string())?;
+                opened_file
+                    .write_all(edited_file.as_bytes())
+                    .map_err(|e| e.to_string())?;
+                edited_file
+            }
+        };
 
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Cli {
-    /// Location of the source code file
-    #[arg()]
-    file_path: String,
+        self.source_file = edited_file.into();
+        self.applied_suggestions
+            .push((self.code.clone(), suggestion.to_string()));
+        self.build()
+    }
+    /// Runs the post-apply commands in order, stopping at the first one that fails
+    fn run_post_apply_hooks(&self) -> Result<(), String> {
+        let language = self.supported_language.to_string();
+        let variables = [
+            ("file", self.file_name.as_str()),
+            (
+                "function",
+                self.selected_name.as_deref().unwrap_or_default(),
+            ),
+            ("language", language.as_str()),
+        ];
 
-    /// Name of the function or method that will be searched for in the file at the given file_path
-    #[arg(short = 'f', long)]
-    function_identifier: Option<String>,
+        self.post_apply
+            .iter()
+            .try_for_each(|command| run_post_apply_hook(command, &variables, self.sandbox.as_ref()))
+    }
+    /// Commits the applied suggestion, along with the updates of its callers in other files, with
+    /// the given message or one summarised from the diff
+    async fn commit_suggestion(&self, caller_files: &[String], secret: &str) -> Result<(), String> {
+        let Some(commit_message) = &self.git_commit else {
+            return Ok(());
+        };
+        let mut files = vec![self.file_name.as_str()];
+        files.extend(caller_files.iter().map(String::as_str));
+        let message = match commit_message {
+            CommitMessage::Given(message) => message.clone(),
+            CommitMessage::Generated => {
+                let diff = git::diff(&files)?;
+                let prompt = draft_commit_message_instructions(&diff);
+                // Summarising a diff does not need anything bigger than the default model
+                clean_commit_message(&complete(DEFAULT_MODEL, &prompt, secret).await?)
+            }
+        };
+        println!("{}", format!("committing: {}", message).dimmed());
 
-    /// Parent syntactic element(Class, struct, ... etc) of the function to be optimised. If not given
-    /// then the first function with function_name argument as identifier will be picked up
-    #[arg(short = 'p', long)]
-    parent_identifier: Option<String>,
+        git::commit(&files, &message)
+    }
+    /// With `allow_signature_change`, has the model update every caller of the function when the
+    /// suggestion changed its signature, and applies the updates once reviewed as a whole. Returns
+    /// the files updated.
+    async fn update_callers(
+        &mut self,
+        suggestion: &str,
+        secret: &str,
+    ) -> Result<Vec<String>, String> {
+        let Some(function_name) = self
+            .selected_name
+            .clone()
+            .filter(|_| self.allow_signature_change)
+        else {
+            return Ok(vec![]);
+        };
+        let Some(change) =
+            signature_change(self.supported_language.language(), &self.code, suggestion)
+        else {
+            return Ok(vec![]);
+        };
+        let language = self.supported_language.to_string();
+        let callers = find_callers(Path::new("."), &language, &self.file_name, &function_name);
+        if callers.is_empty() {
+            return Ok(vec![]);
+        }
+        println!(
+            "{}",
+            format!(
+                "the signature of {} changed, updating its {} callers.",
+                function_name,
+                callers.len()
+            )
+            .dimmed()
+        );
 
-    /// The OpenAI model. Check out https://platform.openai.com/docs/models/overview
-    #[arg(short, long)]
-    model: Option<String>,
+        let mut updates = vec![];
+        for caller in callers {
+            let prompt =
+                draft_caller_update_instructions(&language, &function_name, &change, &caller.code);
+            let code = parse_suggestion(&complete(&self.model, &prompt, secret).await?).code;
+            if code.trim() == caller.code.trim() {
+                continue;
+            }
+            println!("{}", format!("{}:{}", caller.file, caller.line).bold());
+            println!("{}", render_text_diff(&caller.code, &code, self.palette));
+            updates.push(CallerUpdate { caller, code });
+        }
+        if updates.is_empty() {
+            return Ok(vec![]);
+        }
+        let apply = self.skip_prompt
+            || Confirm::new(&format!("Apply the updates of {} callers?", updates.len()))
+                .with_default(false)
+                .prompt()
+                .unwrap();
+        if !apply {
+            return Err(
+                "the callers were left as they were, so the code may not build".to_string(),
+            );
+        }
+        let files = apply_caller_updates(&updates)?;
+        // Callers in the same file moved the function around
+        let file_path = Path::new(&self.file_name).canonicalize().ok();
+        if files
+            .iter()
+            .any(|file| Path::new(file).canonicalize().ok() == file_path)
+        {
+            self.source_file = read_to_string(&self.file_name)
+                .map_err(|e| e.to_string())?
+                .into();
+            self.build()?;
+        }
 
-    /// Anything else you would like to tell your chosen LLM
-    #[arg(short, long)]
-    extra_context: Option<String>,
+        Ok(files)
+    }
+}
 
-    /// Setting this option to true will print out the suggestion without a confirmation prompt
-    #[arg(short, long)]
-    dry_run: bool,
+/// Prints the line, or sends it to stderr when stdout is kept for JSON
+fn say(output_format: OutputFormat, line: impl std::fmt::Display) {
+    match output_format {
+        OutputFormat::Text => println!("{}", line),
+        OutputFormat::Json => eprintln!("{}", line),
+    }
+}
 
-    /// Setting this option to true will print and automatically overwrite the function in the source file
-    #[arg(short, long)]
-    skip_prompt: bool,
+/// Prints the report as a line of JSON
+fn print_report(report: &Report) {
+    match serde_json::to_string(report) {
+        Ok(report) => println!("{}", report),
+        Err(e) => eprintln!("{}", format!("failed to write the report: {}", e).red()),
+    }
+}
 
-    /// Should the new code have comments?
-    #[arg(short, long)]
-    add_comments: bool,
+/// Asks for a suggestion for the selected function, prints it and, if accepted, applies it.
+/// Answers found in the cache are reused instead of asking again.
+async fn suggest(opt: &mut Optimizer, secret: &str, cache: Option<&mut VerdictCache>) -> Outcome {
+    match ask_for_suggestion(opt, secret, cache).await {
+        Ok(answer) => review_suggestion(opt, &answer, secret).await,
+        Err(e) => Outcome::ApiError(e),
+    }
+}
 
-    /// The `bat` theme. Check out https://github.com/sharkdp/bat/tree/master/assets/themes for a list of available themes
-    #[arg(short, long)]
-    theme: Option<String>,
+/// The answer of the model for the selected function, revised if it needs changes
+async fn ask_for_suggestion(
+    opt: &mut Optimizer,
+    secret: &str,
+    mut cache: Option<&mut VerdictCache>,
+) -> Result<String, String> {
+    if opt.goal == Goal::AsyncCorrectness && !is_async_function(&opt.code) {
+        eprintln!("{}", "the code is not an async function.".yellow());
+    }
+    for (heading, suspects) in [
+        (opt.goal.suspects_heading(), &opt.suspects),
+        ("found ahead of time:", &opt.dead_code),
+    ] {
+        if !suspects.is_empty() && !opt.skip_optimising_advice {
+            say(opt.output_format, heading.dimmed());
+            for suspect in suspects {
+                say(opt.output_format, format!("  {}", suspect).dimmed());
+            }
+        }
+    }
+    if let Some(overflow) = opt.context.render_overflow(opt.token_budget) {
+        say(opt.output_format, overflow.dimmed());
+        let prompt = opt.prompt();
+        say(
+            opt.output_format,
+            format!(
+                "the whole prompt takes up about {} tokens.",
+                estimate_tokens(&prompt.system) + estimate_tokens(&prompt.user)
+            )
+            .dimmed(),
+        );
+    }
+    if let Err(e) = opt.find_similar_examples(secret).await {
+        eprintln!(
+            "{}",
+            format!("failed to find similar examples: {}", e).yellow()
+        );
+    }
+    let fingerprint = fingerprint(&opt.model, &opt.prompt());
+    let cached_suggestion = cache
+        .as_ref()
+        .and_then(|cache| cache.get(&fingerprint))
+        .map(str::to_string);
+    let mut answer = if let Some(answer) = cached_suggestion {
+        say(opt.output_format, "unchanged since the last run.".dimmed());
+        answer
+    } else {
+        opt.optimise(secret).await?
+    };
+
+    let suggestion = opt.parse_suggestion(&answer);
+    let introduced_symbols = opt.introduced_symbols(&suggestion.code);
+    let new_dependencies = opt.introduced_dependencies(&suggestion.code);
+    let revision_request =
+        opt.revision_request(&suggestion.code, &introduced_symbols, &new_dependencies);
+    if let Some(request) = revision_request.filter(|_| !suggestion.is_optimal()) {
+        say(
+            opt.output_format,
+            "the suggestion needs changes, asking for a revision.".dimmed(),
+        );
+        answer = opt
+            .follow_up(&answer, &request, opt.response_format(), secret)
+            .await?;
+    }
+    if let Some(cache) = cache.as_mut() {
+        cache.record(fingerprint, answer.clone());
+    }
+
+    Ok(answer)
 }
 
-#[tokio::main]
-async fn main() {
-    let args = Cli::parse();
-    let secret = if let Ok(api_key) = env::var(OPENAI_API_KEY_ENV_VAR_KEY) {
-        api_key
+/// Checks the suggestion the answer holds, prints it and, if accepted, applies it
+async fn review_suggestion(opt: &mut Optimizer, answer: &str, secret: &str) -> Outcome {
+    let suggestion = opt.parse_suggestion(answer);
+    let introduced_symbols = opt.introduced_symbols(&suggestion.code);
+    let new_dependencies = opt.introduced_dependencies(&suggestion.code);
+    let Suggestion {
+        status,
+        code,
+        rationale,
+        risk,
+    } = suggestion;
+    if status == SuggestionStatus::Optimal {
+        say(
+            opt.output_format,
+            "the current code is already optimal.".green(),
+        );
+        return Outcome::Optimal;
+    }
+    if opt.introduces_denied_unsafe(&code) {
+        eprintln!("{}", "rejected a suggestion that adds unsafe blocks.".red());
+        opt.reply_to_conversation("I rejected this suggestion, as it adds unsafe blocks.");
+        opt.record_in_history(&code, false, None);
+        return Outcome::Rejected("adds unsafe blocks".to_string());
+    }
+    if let Some(problem) = opt.literal_problem(&code) {
+        eprintln!(
+            "{}",
+            format!("rejected a suggestion that does not compile: {}", problem).red()
+        );
+        opt.reply_to_conversation("I rejected this suggestion, as it does not compile.");
+        opt.record_in_history(&code, false, None);
+        return Outcome::Rejected(format!("does not compile: {}", problem));
+    }
+    // Tools reading the JSON output apply the edit themselves
+    if opt.output_format == OutputFormat::Json {
+        return Outcome::Suggested(code);
+    }
+
+    let diff = match opt.diff {
+        Some(DiffMode::Text) => Some(render_text_diff(&opt.code, &code, opt.palette)),
+        Some(DiffMode::Structural) => render_structural_diff(
+            opt.supported_language.language(),
+            &opt.code,
+            &code,
+            opt.palette,
+        ),
+        None => None,
+    };
+    if let Some(diff) = diff {
+        println!("{}", diff);
     } else {
-        panic!("{}", "the OPENAI_API_KEY env var is not present".red())
+        let supported_language = opt.supported_language.to_string();
+        bat::PrettyPrinter::new()
+            .input_from_bytes(code.as_bytes())
+            .language(&supported_language)
+            .vcs_modification_markers(true)
+            .colored_output(opt.colored_output)
+            .theme(opt.theme.clone())
+            .print()
+            .unwrap();
+    }
+
+    let rationale = if rationale.is_empty() && opt.explain {
+        let explanation = opt.follow_up(answer, EXPLAIN_INSTRUCTIONS, None, secret);
+        explanation.await.unwrap_or_else(|e| {
+            eprintln!("{}", format!("failed to get a rationale: {}", e).yellow());
+            "".to_string()
+        })
+    } else {
+        rationale
     };
+    if !rationale.is_empty() {
+        println!("{}", render_rationale(&rationale, risk).dimmed());
+    }
 
-    let mut opt = Optimizer::new(
-        &args.file_path,
-        args.parent_identifier,
-        args.function_identifier,
-        args.extra_context,
-        &if let Some(theme) = args.theme {
-            theme
-        } else {
-            "Dracula".to_string()
-        },
-        &if let Some(model) = args.model {
-            model
+    if opt.dry_run {
+        return Outcome::Suggested(code);
+    }
+    let within_auto_apply_risk = risk
+        .zip(opt.auto_apply)
+        .is_some_and(|(risk, auto_apply)| risk <= auto_apply);
+    if !introduced_symbols.is_empty() {
+        eprintln!(
+            "{}",
+            format!(
+                "the suggestion introduces forbidden symbols: {}",
+                introduced_symbols.join(", ")
+            )
+            .red()
+            .bold()
+        );
+    }
+    if !new_dependencies.is_empty() {
+        eprintln!(
+            "{}",
+            format!(
+                "the suggestion needs {}, which the file does not import and its manifest does not declare.",
+                new_dependencies.join(", ")
+            )
+            .yellow()
+        );
+    }
+    let uncovered = opt.is_uncovered();
+    if uncovered {
+        eprintln!(
+            "{}",
+            "no test covers this function, so nothing would catch the suggestion changing what it does."
+                .yellow()
+        );
+    }
+    let held_back = uncovered && !opt.force;
+    let mut overwrite_file = true;
+    let mut asked = false;
+    if within_auto_apply_risk && introduced_symbols.is_empty() && !held_back {
+        println!(
+            "{}",
+            format!(
+                "applying a {} risk suggestion.",
+                risk.unwrap().to_possible_value().unwrap().get_name()
+            )
+            .dimmed()
+        );
+    } else if !opt.skip_prompt {
+        asked = true;
+        overwrite_file = Confirm::new("Apply suggestion?")
+            .with_default(false)
+            .prompt()
+            .unwrap();
+    } else if !introduced_symbols.is_empty() {
+        overwrite_file = false;
+    } else if held_back {
+        eprintln!(
+            "{}",
+            "left the suggestion unapplied, --force applies it anyway.".yellow()
+        );
+        overwrite_file = false;
+    }
+    if !overwrite_file {
+        let reason = if asked {
+            Text::new("Why? (optional)")
+                .prompt()
+                .ok()
+                .map(|reason| reason.trim().to_string())
+                .filter(|reason| !reason.is_empty())
         } else {
-            "gpt-3.5-turbo".to_string()
-        },
-        args.dry_run,
-        args.add_comments,
-        args.skip_prompt,
-    );
-    if let Err(e) = opt.build() {
-        panic!("{}", e.red())
-    }
-
-    match opt.optimise(&secret).await {
-        Ok(suggestion) => {
-            if &suggestion == "OPTIMAL" {
-                println!("{}", "the current code is already optimal.".green())
-            } else {
-                let supported_language = opt.supported_language.to_string();
-
-                bat::PrettyPrinter::new()
-                    .input_from_bytes(suggestion.as_bytes())
-                    .language(&supported_language)
-                    .vcs_modification_markers(true)
-                    .theme(opt.theme.clone())
-                    .print()
-                    .unwrap();
-
-                if !opt.dry_run {
-                    let mut overwrite_file = true;
-                    if !opt.skip_prompt {
-                        overwrite_file = Confirm::new("Apply suggestion?")
-                            .with_default(false)
-                            .prompt()
-                            .unwrap();
-                    }
-                    if overwrite_file {
-                        let edited_file = opt.apply_suggestion_to_source_file(suggestion.as_bytes());
-                        let file = OpenOptions::new()
-                            .write(true)
-                            .truncate(true)
-                            .open(opt.file_name);
-                        match file {
-                            Ok(mut opened_file) => {
-                                if let Err(e) = opened_file.write(edited_file.as_bytes()) {
-                                    panic!("{}", e.to_string().red())
-                                }
-                            }
-                            Err(e) => panic!("{}", e.to_string().red()),
-                        }
-                    }
-                }
+            None
+        };
+        match &reason {
+            Some(reason) => {
+                opt.reply_to_conversation(&format!("I rejected this suggestion: {}", reason))
             }
+            None => opt.reply_to_conversation("I rejected this suggestion."),
         }
-        Err(e) => panic!("{}", e.red()),
+        opt.record_in_history(&code, false, reason);
+        return Outcome::Suggested(code);
+    }
+    if let Err(e) = opt.write_suggestion(&code) {
+        eprintln!("{}", format!("failed to apply the suggestion: {}", e).red());
+        return Outcome::Suggested(code);
+    }
+    opt.record_in_history(&code, true, None);
+    if asked {
+        opt.offer_to_add_dependencies(&new_dependencies);
+    }
+    finish_applying(opt, &code, secret).await;
+
+    Outcome::Applied(code)
+}
+
+/// Runs the post-apply commands, updates the callers and commits, once a suggestion is written
+async fn finish_applying(opt: &mut Optimizer, code: &str, secret: &str) {
+    // Before committing, so that what they change, like formatting, is part of the commit
+    if let Err(e) = opt.run_post_apply_hooks() {
+        eprintln!("{}", e.yellow());
+    }
+    let caller_files = match opt.update_callers(code, secret).await {
+        Ok(caller_files) => caller_files,
+        Err(e) => {
+            eprintln!(
+                "{}",
+                format!("failed to update the callers: {}", e).yellow()
+            );
+            vec![]
+        }
+    };
+    if let Err(e) = opt.commit_suggestion(&caller_files, secret).await {
+        eprintln!(
+            "{}",
+            format!("failed to commit the suggestion: {}", e).yellow()
+        );
+    }
+    opt.reply_to_conversation("I applied this suggestion.");
+}
+
+/// Describes every applied suggestion, and writes the descriptions down in the changelog
+async fn write_changelog_entries(
+    path: &str,
+    style: ChangelogStyle,
+    applied_suggestions: &[(String, String, String)],
+    secret: &str,
+) -> Result<(), String> {
+    let mut entries = vec![];
+    for (file_path, original, suggestion) in applied_suggestions {
+        let prompt = draft_changelog_entry_instructions(original, suggestion);
+        let description = complete(DEFAULT_MODEL, &prompt, secret).await?;
+        entries.push(format!("{} (`{}`)", description.trim(), file_path));
+    }
+
+    write_changelog(Path::new(path), style, &entries)
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Location of the source code file. With `--all`, it can also be a directory
+    #[arg(required = true)]
+    file_path: Option<String>,
+
+    /// Name of the function or method that will be searched for in the file at the given file_path
+    #[arg(short = 'f', long)]
+    function_identifier: Option<String>,
+
+    /// Parent syntactic element(Class, struct, ... etc) of the function to be optimised. If not given
+    /// then the first function with function_name argument as identifier will be picked up. In
+    /// Javascript and Typescript it can be a dotted path, like `config.handlers`, through object
+    /// literals, classes and namespaces
+    #[arg(short = 'p', long)]
+    parent_identifier: Option<String>,
+
+    /// Only consider functions of this kind. Useful when several functions share the same name
+    #[arg(short, long, value_enum)]
+    kind: Option<FunctionKind>,
+
+    /// Go through every function of the file, or of every supported file in the directory
+    #[arg(long, conflicts_with_all = ["function_identifier", "parent_identifier"])]
+    all: bool,
+
+    /// With `--all`, only go through the files with uncommitted changes, untracked ones included
+    #[arg(long, requires = "all")]
+    changed: bool,
+
+    /// With `--all`, also go through the files matched by `.gitignore`, `.ignore` and similar files
+    #[arg(long, requires = "all")]
+    no_ignore: bool,
+
+    /// With `--all`, also go through generated files, like `*.pb.go` or files marked as
+    /// `@generated`
+    #[arg(long, requires = "all")]
+    include_generated: bool,
+
+    /// With `--all`, skip test files and test functions
+    #[arg(long, requires = "all", conflicts_with = "only_tests")]
+    skip_tests: bool,
+
+    /// With `--all`, only go through test files and test functions
+    #[arg(long, requires = "all")]
+    only_tests: bool,
+
+    /// With `--all`, skip functions shorter than this many lines
+    #[arg(long, requires = "all")]
+    min_lines: Option<usize>,
+
+    /// With `--all`, skip functions longer than this many lines
+    #[arg(long, requires = "all")]
+    max_lines: Option<usize>,
+
+    /// With `--all`, only report which functions could be improved, exiting with a non-zero code
+    /// if there are any
+    #[arg(long, requires = "all")]
+    check: bool,
+
+    /// With `--all`, exit with a non-zero code if any function or file ends up with one of these
+    /// outcomes
+    #[arg(
+        long,
+        requires = "all",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "parse-error,api-error"
+    )]
+    fail_on: Vec<OutcomeKind>,
+
+    /// With `--all`, post a JSON summary of the suggestions to this URL once done. Slack incoming
+    /// webhooks understand it too
+    #[arg(long, requires = "all")]
+    notify_webhook: Option<String>,
+
+    /// Include the original code and the suggestions in the summary posted to the webhook
+    #[arg(long, requires = "notify_webhook")]
+    notify_diffs: bool,
+
+    /// With `--all`, ask about every function again, even those whose answers were cached by a
+    /// previous run
+    #[arg(long, requires = "all")]
+    no_cache: bool,
+
+    /// Keep the messages exchanged about the function in this file, so that later invocations
+    /// continue the same conversation, with the model remembering earlier constraints and
+    /// rejected attempts
+    #[arg(long, conflicts_with = "all")]
+    conversation: Option<String>,
+
+    /// Commit every applied suggestion, with a conventional commit message summarised from its
+    /// diff
+    #[arg(long)]
+    git_commit: bool,
+
+    /// The message of the commits made by `--git-commit`, instead of a summarised one
+    #[arg(long, requires = "git_commit")]
+    commit_message: Option<String>,
+
+    /// Describe the applied suggestions in this changelog file once done
+    #[arg(long)]
+    changelog: Option<String>,
+
+    /// How the applied suggestions are written down in the changelog
+    #[arg(
+        long,
+        value_enum,
+        requires = "changelog",
+        default_value = "keepachangelog"
+    )]
+    changelog_style: ChangelogStyle,
+
+    /// The OpenAI model. Check out https://platform.openai.com/docs/models/overview
+    #[arg(short, long)]
+    model: Option<String>,
+
+    /// Use the contents of this file as the system prompt, instead of the default one. The code and
+    /// the other settings are sent apart, as the user message
+    #[arg(long)]
+    system_prompt_file: Option<String>,
+
+    /// Anything else you would like to tell your chosen LLM
+    #[arg(short, long)]
+    extra_context: Option<String>,
+
+    /// A file the model is shown along with the code, like the schema of the database with `--sql`
+    #[arg(long, value_name = "FILE")]
+    context_file: Option<String>,
+
+    /// Optimise the SQL queries in the string literals of the function, one at a time, rather than
+    /// the function. Only the literals are replaced
+    #[arg(long, conflicts_with_all = ["all", "body_only", "allow_signature_change"])]
+    sql: bool,
+
+    /// Optimise the regular expressions of the function, one at a time, rather than the function,
+    /// pointing out those that may backtrack catastrophically. Suggested patterns must compile
+    #[arg(long, conflicts_with_all = ["all", "body_only", "allow_signature_change", "sql"])]
+    regex: bool,
+
+    /// Setting this option to true will print out the suggestion without a confirmation prompt
+    #[arg(short, long)]
+    dry_run: bool,
+
+    /// When the model does not say why its suggestion is faster by itself, ask it in a second turn,
+    /// and show its rationale under the suggestion
+    #[arg(long)]
+    explain: bool,
+
+    /// What to optimise the code for. With allocations, the expressions that likely allocate are
+    /// pointed out to the model. With async-correctness, the code is reviewed for blocking calls,
+    /// locks held across awaits and awaits that could run concurrently instead.
+    #[arg(long, value_enum, default_value_t = Goal::Speed)]
+    goal: Goal,
+
+    /// How many tokens the function, along with the signatures of the functions it calls, the
+    /// types it names and the imports of its file, may take up in the prompt. Context is left out
+    /// by priority, imports first, until it fits.
+    #[arg(long, default_value_t = DEFAULT_TOKEN_BUDGET)]
+    token_budget: usize,
+
+    /// Give the model this many of the applied suggestions whose original code is the most
+    /// similar to the function, found by embedding them, as examples to stay consistent with
+    #[arg(long, value_name = "K")]
+    similar_examples: Option<usize>,
+
+    /// Show the model this image along with the code, like a flamegraph or a profiler
+    /// screenshot. Can be given more than once. Left out for models without vision.
+    #[arg(long, value_name = "IMAGE")]
+    attach: Vec<String>,
+
+    /// Ask for a revision of suggestions that need crates, modules or packages that the file does
+    /// not import and its manifest does not declare, instead of warning about them
+    #[arg(long)]
+    no_new_dependencies: bool,
+
+    /// The JSON output of clippy (`--message-format=json`), ESLint (`-f json`) or golangci-lint
+    /// (`--out-format json`). What they report within a function is passed on to the model, to
+    /// be fixed along the way
+    #[arg(long, value_name = "FILE")]
+    diagnostics: Option<String>,
+
+    /// An lcov report, like `lcov.info`, or a Go cover profile, like `coverage.out`. Suggestions
+    /// for functions no test covers are pointed out, and only applied without asking with
+    /// `--force`
+    #[arg(long, value_name = "REPORT")]
+    coverage: Option<String>,
+
+    /// Apply suggestions for functions no test covers with `--skip-prompt` and `--auto-apply`
+    #[arg(long, requires = "coverage")]
+    force: bool,
+
+    /// Refuse Rust suggestions that add `unsafe` blocks, after asking for a revision without them
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    deny_unsafe: bool,
+
+    /// Show suggestions as a diff against the original code. The structural diff compares the
+    /// tokens of both, so that formatting changes are left out and statements that only moved are
+    /// told apart from the ones that changed.
+    #[arg(long, value_enum)]
+    diff: Option<DiffMode>,
+
+    /// Ask the model for a new body of the function alone, and replace only the body, so that the
+    /// signature stays the same byte for byte. Functions without a block body, like arrow
+    /// functions returning an expression, are replaced whole.
+    #[arg(long)]
+    body_only: bool,
+
+    /// How applied suggestions are written. With git, the suggestion is applied as a patch with
+    /// `git apply --3way`, so that it merges into a file that changed since it was read, leaving
+    /// conflict markers when it cannot.
+    #[arg(long, value_enum, default_value_t = ApplyBackend::Direct)]
+    apply_with: ApplyBackend,
+
+    /// Leave a comment like `// optimised by senior (gpt-4o, 2024-06-01)` after every applied
+    /// suggestion, for teams that must label generated code. `[provenance_comment]` in
+    /// `.senior.toml` changes it per language. Not allowed with `--sql` and `--regex`, as the
+    /// literals they replace sit in the middle of expressions
+    #[arg(long, conflicts_with_all = ["sql", "regex"])]
+    provenance_comment: bool,
+
+    /// Run the `post_apply` commands of `.senior.toml`, like test runners, in a container of the
+    /// image, like `docker:rust:1.79`, with the repository mounted in it, so that they cannot
+    /// change anything outside of it, or on a host over SSH, like `ssh:ci@builder`, which the
+    /// repository is copied to first
+    #[arg(long, value_name = "docker:IMAGE|ssh:HOST")]
+    sandbox: Option<Sandbox>,
+
+    /// Require a person to review every suggestion: --skip-prompt and --auto-apply are refused,
+    /// and who applied or rejected each suggestion is recorded in the history. `[policy]` in
+    /// `.senior.toml`, or a locked one in the policy file of the organisation, can require it too
+    #[arg(long)]
+    compliance: bool,
+
+    /// Let suggestions change the signature of the function. When one does, the model updates
+    /// every function of the repository calling it, and the updates are shown and applied together
+    #[arg(long, conflicts_with_all = ["all", "body_only"])]
+    allow_signature_change: bool,
+
+    /// With json, nothing is applied. Instead, every function is written out as a line of JSON
+    /// holding its outcome and, for suggestions, the byte offsets and the line and column spans of
+    /// the replaced code along with the new text, for tools to apply the edit themselves.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+
+    /// Apply suggestions without asking if the model deems them at most this risky. Only models
+    /// that support structured outputs, like gpt-4o, tell how risky their suggestions are
+    #[arg(long, value_enum)]
+    auto_apply: Option<Risk>,
+
+    /// Setting this option to true will print and automatically overwrite the function in the source file
+    #[arg(short, long)]
+    skip_prompt: bool,
+
+    /// Should the new code have comments?
+    #[arg(short, long)]
+    add_comments: bool,
+
+    /// Fail on files with syntax errors, instead of warning about them and going on
+    #[arg(long)]
+    strict_parse: bool,
+
+    /// When the function is not found, print the nodes of the file that functions are found by,
+    /// with their kinds, names and lines, to show why
+    #[arg(long)]
+    debug_tree: bool,
+
+    /// Optimise this part of the file as it is, without looking for a function, given by bytes as
+    /// `<start>:<end>` or by lines as `L<first>-L<last>`. Works for any file, and checks that the
+    /// result still parses when the language is supported
+    #[arg(
+        long,
+        value_parser = parse_span,
+        conflicts_with_all = ["function_identifier", "parent_identifier", "kind", "all"]
+    )]
+    span: Option<SnippetSpan>,
+
+    /// Whether to color the output. With auto, it is colored when it goes to a terminal, unless the
+    /// NO_COLOR env var is set
+    #[arg(long, value_enum, global = true, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
+
+    /// How many requests a minute every senior process using the same API key may send together.
+    /// They coordinate through a ledger in the temporary directory.
+    #[arg(long, global = true)]
+    rpm: Option<usize>,
+
+    /// How many tokens a minute every senior process using the same API key may send together
+    #[arg(long, global = true)]
+    tpm: Option<usize>,
+
+    /// How much reasoning models, like o3, think before answering. Left to the provider by default,
+    /// and ignored by other models
+    #[arg(long, value_enum, global = true)]
+    reasoning_effort: Option<ReasoningEffort>,
+
+    /// Print what reasoning models reasoned, when their provider tells
+    #[arg(long, global = true)]
+    show_reasoning: bool,
+
+    /// The colors of diffs. high-contrast uses yellow, blue and magenta instead of red, green and
+    /// cyan, and the `ansi` theme of `bat` unless `--theme` is given, which follows the colors of
+    /// the terminal
+    #[arg(long, value_enum, default_value_t = Palette::RedGreen)]
+    palette: Palette,
+
+    /// The `bat` theme. Check out https://github.com/sharkdp/bat/tree/master/assets/themes for a list of available themes
+    #[arg(short, long)]
+    theme: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum QueueAction {
+    /// Queues the suggestions of reports written with `--output-format json`, one per line
+    Add {
+        /// The file of the reports
+        reports: String,
+    },
+    /// Prints the queued suggestions with their diffs
+    List {
+        #[arg(long, value_enum, default_value_t = QueueStatus::Pending)]
+        status: QueueStatus,
+    },
+    /// Applies a pending suggestion to its file
+    Approve {
+        id: usize,
+
+        /// Commit the file, so that the commit can be pushed as a pull request
+        #[arg(long)]
+        commit: bool,
+    },
+    /// Rejects a pending suggestion, leaving its file as it is
+    Reject { id: usize },
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Meant for cron or CI. Suggests improvements to the functions that changed since the last
+    /// run, without applying them, writes a report and posts a summary of it to Slack
+    Nightly {
+        /// Location of the configuration file, whose `[nightly]` section holds the settings
+        #[arg(short, long, default_value = CONFIG_FILE_PATH)]
+        config: String,
+    },
+    /// Lists the functions `--all` would go through, grouped by the packages of the Cargo, Go or
+    /// pnpm workspace at the given path, without asking the model about any of them
+    Scan {
+        /// File or directory to go through
+        #[arg(default_value = ".")]
+        path: String,
+
+        /// Only go through the files with uncommitted changes, untracked ones included
+        #[arg(long)]
+        changed: bool,
+
+        /// Also go through the files matched by `.gitignore`, `.ignore` and similar files
+        #[arg(long)]
+        no_ignore: bool,
+
+        /// Also go through generated files, like `*.pb.go` or files marked as `@generated`
+        #[arg(long)]
+        include_generated: bool,
+
+        /// Only consider functions of this kind
+        #[arg(short, long, value_enum)]
+        kind: Option<FunctionKind>,
+
+        /// Skip test files and test functions
+        #[arg(long, conflicts_with = "only_tests")]
+        skip_tests: bool,
+
+        /// Only go through test files and test functions
+        #[arg(long)]
+        only_tests: bool,
+
+        /// Skip functions shorter than this many lines
+        #[arg(long)]
+        min_lines: Option<usize>,
+
+        /// Skip functions longer than this many lines
+        #[arg(long)]
+        max_lines: Option<usize>,
+    },
+    /// Runs the build, and has the model fix each function the build reports errors in. Or, given a
+    /// stack trace, goes through the functions of the repository it passes through, deepest first
+    Fix {
+        /// The build command, run with `sh -c`, like `cargo build`, `go build ./...` or `tsc`
+        #[arg(long, required_unless_present = "trace", conflicts_with = "trace")]
+        build: Option<String>,
+
+        /// A file with a Rust panic and backtrace, a Go goroutine dump, or a Node.js or JVM stack
+        /// trace
+        #[arg(long, value_name = "FILE")]
+        trace: Option<String>,
+
+        /// Run the build in a container of the image, like `docker:rust:1.79`, with the repository
+        /// mounted in it, or on a host over SSH, like `ssh:ci@builder`
+        #[arg(long, value_name = "docker:IMAGE|ssh:HOST", requires = "build")]
+        sandbox: Option<Sandbox>,
+
+        /// File or directory whose functions to fix
+        #[arg(default_value = ".")]
+        path: String,
+
+        /// The OpenAI model. Check out https://platform.openai.com/docs/models/overview
+        #[arg(short, long)]
+        model: Option<String>,
+
+        /// Apply the fixes without asking
+        #[arg(short, long)]
+        skip_prompt: bool,
+    },
+    /// Reports the functions over the complexity budget of the `[complexity]` section of
+    /// `.senior.toml`, from the tree alone, and exits with a non-zero code if there are any
+    Check {
+        /// File or directory to go through
+        #[arg(default_value = ".")]
+        path: String,
+
+        /// Only go through the files with uncommitted changes, untracked ones included
+        #[arg(long)]
+        changed: bool,
+
+        /// Also go through the files matched by `.gitignore`, `.ignore` and similar files
+        #[arg(long)]
+        no_ignore: bool,
+    },
+    /// Has the model split every function over the complexity budget into smaller ones, like
+    /// `--all` does
+    FixComplexity {
+        /// File or directory to go through
+        #[arg(default_value = ".")]
+        path: String,
+
+        /// Only go through the files with uncommitted changes, untracked ones included
+        #[arg(long)]
+        changed: bool,
+
+        /// Also go through the files matched by `.gitignore`, `.ignore` and similar files
+        #[arg(long)]
+        no_ignore: bool,
+
+        /// The OpenAI model. Check out https://platform.openai.com/docs/models/overview
+        #[arg(short, long)]
+        model: Option<String>,
+
+        /// Apply the suggestions without asking
+        #[arg(short, long)]
+        skip_prompt: bool,
+    },
+    /// Keeps watching the file, or every supported file in the directory, and reports the
+    /// functions that change
+    Watch {
+        /// File or directory to watch
+        #[arg(default_value = ".")]
+        path: String,
+
+        /// Also watch the files matched by `.gitignore`, `.ignore` and similar files
+        #[arg(long)]
+        no_ignore: bool,
+
+        /// How often to check for changes, in milliseconds
+        #[arg(long, default_value_t = 500)]
+        interval: u64,
+
+        /// Print a suggestion for every changed function, without applying it
+        #[arg(long)]
+        suggest: bool,
+
+        /// The OpenAI model. Check out https://platform.openai.com/docs/models/overview
+        #[arg(short, long)]
+        model: Option<String>,
+    },
+    /// Finds near-duplicate functions, compared by the shape of their parse trees regardless of
+    /// names, literals and comments, and suggests consolidating them
+    Dupes {
+        /// File or directory to go through
+        #[arg(default_value = ".")]
+        path: String,
+
+        /// Also go through the files matched by `.gitignore`, `.ignore` and similar files
+        #[arg(long)]
+        no_ignore: bool,
+
+        /// Skip functions shorter than this many lines
+        #[arg(long, default_value_t = 5)]
+        min_lines: usize,
+
+        /// How alike two functions must be, from 0 to 1, to be reported
+        #[arg(long, default_value_t = 0.85)]
+        threshold: f32,
+
+        /// Also compare the OpenAI embeddings of the functions, which catches the same logic
+        /// written differently
+        #[arg(long)]
+        embeddings: bool,
+
+        /// Ask the model for a single implementation that can replace both functions of every pair
+        #[arg(long)]
+        unify: bool,
+
+        /// The OpenAI model. Check out https://platform.openai.com/docs/models/overview
+        #[arg(short, long)]
+        model: Option<String>,
+    },
+    /// Keeps a copy of every supported file under the path, which `senior revert` can restore
+    /// regardless of git. Runs with `--all` that may apply suggestions take one by themselves.
+    Snapshot {
+        /// File or directory to keep a copy of
+        #[arg(default_value = ".")]
+        path: String,
+
+        /// Also keep the files matched by `.gitignore`, `.ignore` and similar files
+        #[arg(long)]
+        no_ignore: bool,
+    },
+    /// Puts back the files of a snapshot that changed since it was taken. Lists the snapshots
+    /// without `--to`
+    Revert {
+        /// The id of the snapshot
+        #[arg(long)]
+        to: Option<String>,
+    },
+    /// Shows how many functions the `--check` runs recorded in the history flagged over time
+    Trends {
+        /// Only show the runs through this file or directory, as it was given to them
+        #[arg(long)]
+        path: Option<String>,
+    },
+    /// Shows what the model supports, like structured answers or images. Features that need what
+    /// it does not support are left out
+    Capabilities {
+        /// The OpenAI model. Check out https://platform.openai.com/docs/models/overview
+        #[arg(short, long)]
+        model: Option<String>,
+    },
+    /// Lists the language analysers and providers senior was built with, the grammars they parse
+    /// with and the files they match
+    Languages,
+    /// Applies the suggestions of reports written with `--output-format json`, one per line, to
+    /// the files they are for, even if the files changed since
+    Apply {
+        /// The file of the reports
+        reports: String,
+
+        /// Only print the diff of every suggestion, without applying it
+        #[arg(long)]
+        preview: bool,
+    },
+    /// Keeps suggestions, like those CI reports, for a reviewer to approve or reject
+    Queue {
+        #[command(subcommand)]
+        action: QueueAction,
+
+        /// Location of the queue
+        #[arg(long, default_value = QUEUE_FILE_PATH)]
+        queue: String,
+    },
+    /// Resolves a function of every kind in a sample of every language, and prints which kinds
+    /// each analyser supports. Fails if any of them is not found
+    Selftest,
+    /// Checks that senior can run here: that the API key works and can use the model, that the
+    /// grammars load, that the configuration parses and that the commands it runs are installed.
+    /// Prints how to fix every problem found
+    Doctor {
+        /// The OpenAI model. Check out https://platform.openai.com/docs/models/overview
+        #[arg(short, long)]
+        model: Option<String>,
+
+        /// Location of the configuration file
+        #[arg(short, long, default_value = CONFIG_FILE_PATH)]
+        config: String,
+    },
+    /// Moves a function, along with its doc comments, to another file, which is created if need
+    /// be, and updates the imports and exports of both files
+    Extract {
+        /// Location of the source code file holding the function
+        file_path: String,
+
+        /// Name of the function to move
+        #[arg(short = 'f', long)]
+        function_identifier: String,
+
+        /// Parent syntactic element of the function, when several functions share its name
+        #[arg(short = 'p', long)]
+        parent_identifier: Option<String>,
+
+        /// Only consider functions of this kind
+        #[arg(short, long, value_enum)]
+        kind: Option<FunctionKind>,
+
+        /// The file to move the function to
+        #[arg(long)]
+        to: String,
+    },
+    /// Has the model inline a small function into every function of the file calling it, and
+    /// deletes it unless other files call it too. The edits are applied together, or not at all
+    Inline {
+        /// Location of the source code file holding the function
+        file_path: String,
+
+        /// Name of the function to inline
+        #[arg(short = 'f', long)]
+        function_identifier: String,
+
+        /// Parent syntactic element of the function, when several functions share its name
+        #[arg(short = 'p', long)]
+        parent_identifier: Option<String>,
+
+        /// Only consider functions of this kind
+        #[arg(short, long, value_enum)]
+        kind: Option<FunctionKind>,
+
+        /// The OpenAI model. Check out https://platform.openai.com/docs/models/overview
+        #[arg(short, long)]
+        model: Option<String>,
+
+        /// Apply the edits without asking
+        #[arg(short, long)]
+        skip_prompt: bool,
+    },
+    /// Has the model remove the dead code, unused variables and redundant branches of a function,
+    /// without changing what it does. The suggestion is always shown as a structural diff
+    Cleanup {
+        /// Location of the source code file holding the function
+        file_path: String,
+
+        /// Name of the function to clean up
+        #[arg(short = 'f', long)]
+        function_identifier: String,
+
+        /// Parent syntactic element of the function, when several functions share its name
+        #[arg(short = 'p', long)]
+        parent_identifier: Option<String>,
+
+        /// Only consider functions of this kind
+        #[arg(short, long, value_enum)]
+        kind: Option<FunctionKind>,
+
+        /// The OpenAI model. Check out https://platform.openai.com/docs/models/overview
+        #[arg(short, long)]
+        model: Option<String>,
+
+        /// Apply the suggestion without asking
+        #[arg(short, long)]
+        skip_prompt: bool,
+    },
+    /// Has the model add types to a JavaScript or TypeScript function, as JSDoc comments or type
+    /// annotations respectively, without any other change
+    Annotate {
+        /// Location of the source code file holding the function
+        file_path: String,
+
+        /// Name of the function to annotate
+        #[arg(short = 'f', long)]
+        function_identifier: String,
+
+        /// Parent syntactic element of the function, when several functions share its name
+        #[arg(short = 'p', long)]
+        parent_identifier: Option<String>,
+
+        /// Only consider functions of this kind
+        #[arg(short, long, value_enum)]
+        kind: Option<FunctionKind>,
+
+        /// The OpenAI model. Check out https://platform.openai.com/docs/models/overview
+        #[arg(short, long)]
+        model: Option<String>,
+
+        /// Apply the suggestion without asking
+        #[arg(short, long)]
+        skip_prompt: bool,
+    },
+    /// Has the model write the first implementation of a function whose body is a placeholder,
+    /// like `todo!()`, from its signature, its documentation and the rest of the file. The code is
+    /// labeled as generated
+    Implement {
+        /// Location of the source code file holding the function
+        file_path: String,
+
+        /// Name of the function to implement
+        #[arg(short = 'f', long)]
+        function_identifier: String,
+
+        /// Parent syntactic element of the function, when several functions share its name
+        #[arg(short = 'p', long)]
+        parent_identifier: Option<String>,
+
+        /// Only consider functions of this kind
+        #[arg(short, long, value_enum)]
+        kind: Option<FunctionKind>,
+
+        /// The OpenAI model. Check out https://platform.openai.com/docs/models/overview
+        #[arg(short, long)]
+        model: Option<String>,
+
+        /// Apply the implementation without asking
+        #[arg(short, long)]
+        skip_prompt: bool,
+    },
+    /// Has the model list the ways a function can panic or throw, like failed unwraps, indexing
+    /// out of bounds or division by zero, by line
+    Panics {
+        /// Location of the source code file holding the function
+        file_path: String,
+
+        /// Name of the function to look into
+        #[arg(short = 'f', long)]
+        function_identifier: String,
+
+        /// Parent syntactic element of the function, when several functions share its name
+        #[arg(short = 'p', long)]
+        parent_identifier: Option<String>,
+
+        /// Only consider functions of this kind
+        #[arg(short, long, value_enum)]
+        kind: Option<FunctionKind>,
+
+        /// The OpenAI model. Check out https://platform.openai.com/docs/models/overview
+        #[arg(short, long)]
+        model: Option<String>,
+
+        /// Then ask for a version of the function that neither panics nor throws, as with
+        /// `--goal panic-freedom`
+        #[arg(long)]
+        fix: bool,
+
+        /// Apply the fixed version without asking
+        #[arg(short, long, requires = "fix")]
+        skip_prompt: bool,
+    },
+    /// Has the model write unit tests for a function, and adds them to the test file it
+    /// conventionally goes with, next to its existing tests
+    Test {
+        /// Location of the source code file holding the function
+        file_path: String,
+
+        /// Name of the function to test
+        #[arg(short = 'f', long)]
+        function_identifier: String,
+
+        /// Parent syntactic element of the function, when several functions share its name
+        #[arg(short = 'p', long)]
+        parent_identifier: Option<String>,
+
+        /// Only consider functions of this kind
+        #[arg(short, long, value_enum)]
+        kind: Option<FunctionKind>,
+
+        /// The OpenAI model. Check out https://platform.openai.com/docs/models/overview
+        #[arg(short, long)]
+        model: Option<String>,
+
+        /// Add the tests without asking
+        #[arg(short, long)]
+        skip_prompt: bool,
+    },
+    /// Reviews the functions a GitHub pull request changed, and posts the findings as inline
+    /// review comments. Needs a GITHUB_TOKEN env var allowed to comment on pull requests
+    Review {
+        /// Number of the pull request
+        #[arg(long)]
+        pr: u64,
+
+        /// The `owner/name` of the repository. Defaults to the one the `origin` remote points to
+        #[arg(long)]
+        repo: Option<String>,
+
+        /// The OpenAI model. Check out https://platform.openai.com/docs/models/overview
+        #[arg(short, long)]
+        model: Option<String>,
+
+        /// Print the findings without posting them
+        #[arg(short, long)]
+        dry_run: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    let mut args = Cli::parse();
+    match HistoryStoreKind::from_name(env::var(HISTORY_STORE_ENV_VAR_KEY).ok().as_deref()) {
+        Ok(kind) => set_history_store(kind),
+        Err(e) => Cli::command().error(ErrorKind::InvalidValue, e).exit(),
+    }
+    let colored_output = args
+        .color
+        .should_color(env::var(NO_COLOR_ENV_VAR_KEY).ok().as_deref());
+    colored::control::set_override(colored_output);
+    set_rate_limits(RateLimits {
+        requests_per_minute: args.rpm,
+        tokens_per_minute: args.tpm,
+    });
+    set_reasoning_settings(ReasoningSettings {
+        effort: args.reasoning_effort,
+        show_reasoning: args.show_reasoning,
+    });
+    if let Some(Command::Scan {
+        path,
+        changed,
+        no_ignore,
+        include_generated,
+        kind,
+        skip_tests,
+        only_tests,
+        min_lines,
+        max_lines,
+    }) = &args.command
+    {
+        // Scanning does not ask the model anything, so it does not need an API key
+        let filters = BatchFilters {
+            kind: *kind,
+            skip_tests: *skip_tests,
+            only_tests: *only_tests,
+            min_lines: *min_lines,
+            max_lines: *max_lines,
+            skip_generated: !*include_generated,
+            over_budget: None,
+            diagnosed: None,
+        };
+        if let Err(e) = run_scan(Path::new(path), *changed, *no_ignore, &filters) {
+            panic!("{}", e.red())
+        }
+        return;
+    }
+
+    if let Some(Command::Watch {
+        path,
+        no_ignore,
+        interval,
+        suggest,
+        model,
+    }) = &args.command
+    {
+        // Only suggestions need an API key
+        let secret = suggest.then(|| match env::var(OPENAI_API_KEY_ENV_VAR_KEY) {
+            Ok(api_key) => api_key,
+            Err(_) => panic!("{}", "the OPENAI_API_KEY env var is not present".red()),
+        });
+        let model = model.as_deref().unwrap_or(DEFAULT_MODEL);
+        let settings = WatchSettings {
+            no_ignore: *no_ignore,
+            interval: Duration::from_millis(*interval),
+            suggest_with: secret.as_deref().map(|secret| (model, secret)),
+        };
+        if let Err(e) = run_watch(Path::new(path), &settings).await {
+            panic!("{}", e.red())
+        }
+        return;
+    }
+
+    match &args.command {
+        // None of them asks the model anything
+        Some(Command::Snapshot { path, no_ignore }) => {
+            let files = source_files(Path::new(path), *no_ignore);
+            match take_snapshot(Path::new(SNAPSHOTS_DIRECTORY_PATH), &files) {
+                Ok(id) => println!(
+                    "{}",
+                    format!("took snapshot {} of {} files", id, files.len()).green()
+                ),
+                Err(e) => panic!("{}", e.red()),
+            }
+            return;
+        }
+        Some(Command::Revert { to: Some(id) }) => {
+            match revert_to(Path::new(SNAPSHOTS_DIRECTORY_PATH), id) {
+                Ok(reverted_files) => {
+                    for file in &reverted_files {
+                        println!("  {}", file);
+                    }
+                    println!(
+                        "{}",
+                        format!("reverted {} files to snapshot {}", reverted_files.len(), id)
+                            .green()
+                    );
+                }
+                Err(e) => panic!("{}", e.red()),
+            }
+            return;
+        }
+        Some(Command::Trends { path }) => {
+            let summaries = check_summaries(repository_history().as_ref(), path.as_deref());
+            if summaries.is_empty() {
+                println!("{}", "no --check run was recorded yet.".yellow());
+            } else {
+                println!("{}", render_trends(&summaries));
+            }
+            return;
+        }
+        Some(Command::Capabilities { model }) => {
+            let model = model.as_deref().unwrap_or(DEFAULT_MODEL);
+            if !is_known_model(model) {
+                println!(
+                    "{}",
+                    format!("{} is not a known model, assuming:", model).yellow()
+                );
+            }
+            println!("{}", capabilities(model));
+            return;
+        }
+        Some(Command::Check {
+            path,
+            changed,
+            no_ignore,
+        }) => {
+            let config = if Path::new(CONFIG_FILE_PATH).exists() {
+                match Config::load(Path::new(CONFIG_FILE_PATH)) {
+                    Ok(config) => config,
+                    Err(e) => panic!("{}", e.red()),
+                }
+            } else {
+                Config::default()
+            };
+            match run_complexity_check(Path::new(path), *changed, *no_ignore, &config.complexity) {
+                Ok(0) => {}
+                Ok(_) => std::process::exit(1),
+                Err(e) => panic!("{}", e.red()),
+            }
+            return;
+        }
+        Some(Command::Apply { reports, preview }) => {
+            match run_apply(Path::new(reports), *preview, args.palette) {
+                Ok(0) => {}
+                Ok(_) => std::process::exit(1),
+                Err(e) => panic!("{}", e.red()),
+            }
+            return;
+        }
+        Some(Command::Queue { action, queue }) => {
+            let queue_path = Path::new(queue);
+            let queued = match action {
+                QueueAction::Add { reports } => run_queue_add(queue_path, Path::new(reports))
+                    .map(|ids| println!("queued {} suggestions", ids.len())),
+                QueueAction::List { status } => load_queue(queue_path)
+                    .map(|queue| println!("{}", render_queue(&queue, *status, args.palette))),
+                QueueAction::Approve { id, commit } => run_queue_approve(queue_path, *id, *commit),
+                QueueAction::Reject { id } => run_queue_reject(queue_path, *id),
+            };
+            if let Err(e) = queued {
+                panic!("{}", e.red())
+            }
+            return;
+        }
+        Some(Command::Languages) => {
+            println!("{}", languages_report());
+            return;
+        }
+        Some(Command::Selftest) => {
+            let resolutions = run_selftest();
+            println!("{}", render_support_matrix(&resolutions));
+            if resolutions
+                .iter()
+                .any(|resolution| resolution.failure.is_some())
+            {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Doctor { model, config }) => {
+            // A missing API key is one of the problems reported, rather than a reason to stop
+            let secret = env::var(OPENAI_API_KEY_ENV_VAR_KEY).ok();
+            let model = model.as_deref().unwrap_or(DEFAULT_MODEL);
+            if !run_doctor(Path::new(config), model, secret.as_deref()).await {
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(Command::Extract {
+            file_path,
+            function_identifier,
+            parent_identifier,
+            kind,
+            to,
+        }) => {
+            let selection = Selection {
+                parent_identifier: parent_identifier.clone(),
+                function_identifier: Some(function_identifier.clone()),
+                kind: *kind,
+            };
+            match extract_function(file_path, to, &selection) {
+                Ok(notes) => {
+                    println!(
+                        "{}",
+                        format!("moved {} from {} to {}", function_identifier, file_path, to)
+                            .green()
+                    );
+                    for note in notes {
+                        println!("{}", note.yellow());
+                    }
+                }
+                Err(e) => panic!("{}", e.red()),
+            }
+            return;
+        }
+        Some(Command::Revert { to: None }) => {
+            for snapshot in list_snapshots(Path::new(SNAPSHOTS_DIRECTORY_PATH)) {
+                println!(
+                    "{} {}",
+                    snapshot.id.bold(),
+                    format!("{} files", snapshot.files.len()).dimmed()
+                );
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    if let Some(Command::Dupes {
+        path,
+        no_ignore,
+        min_lines,
+        threshold,
+        embeddings,
+        unify,
+        model,
+    }) = &args.command
+    {
+        // Only embeddings and unified implementations need an API key
+        let secret = (*embeddings || *unify).then(|| match env::var(OPENAI_API_KEY_ENV_VAR_KEY) {
+            Ok(api_key) => api_key,
+            Err(_) => panic!("{}", "the OPENAI_API_KEY env var is not present".red()),
+        });
+        let model = model.as_deref().unwrap_or(DEFAULT_MODEL);
+        let settings = DupesSettings {
+            no_ignore: *no_ignore,
+            min_lines: *min_lines,
+            threshold: *threshold,
+            embed_with: secret.as_deref().filter(|_| *embeddings),
+            unify_with: secret
+                .as_deref()
+                .filter(|_| *unify)
+                .map(|secret| (model, secret)),
+        };
+        if let Err(e) = run_dupes(Path::new(path), &settings).await {
+            panic!("{}", e.red())
+        }
+        return;
+    }
+
+    let secret = if let Ok(api_key) = env::var(OPENAI_API_KEY_ENV_VAR_KEY) {
+        api_key
+    } else {
+        panic!("{}", "the OPENAI_API_KEY env var is not present".red())
+    };
+
+    let mut system_prompt_override = None;
+    let mut skip_optimising_advice = false;
+    let mut implementing = false;
+    let mut fixing_complexity = false;
+    let mut build_errors = None;
+    let mut traced = None;
+    match args.command {
+        Some(Command::Nightly { config }) => {
+            let config = match Config::load(Path::new(&config)) {
+                Ok(config) => config,
+                Err(e) => panic!("{}", e.red()),
+            };
+            if let Err(e) = run_nightly(&config.nightly, &config.instructions, &secret).await {
+                panic!("{}", e.red())
+            }
+            return;
+        }
+        Some(
+            Command::Scan { .. }
+            | Command::Watch { .. }
+            | Command::Dupes { .. }
+            | Command::Snapshot { .. }
+            | Command::Revert { .. }
+            | Command::Trends { .. }
+            | Command::Capabilities { .. }
+            | Command::Languages
+            | Command::Apply { .. }
+            | Command::Queue { .. }
+            | Command::Selftest
+            | Command::Doctor { .. }
+            | Command::Check { .. }
+            | Command::Extract { .. },
+        ) => unreachable!(),
+        Some(Command::Inline {
+            file_path,
+            function_identifier,
+            parent_identifier,
+            kind,
+            model,
+            skip_prompt,
+        }) => {
+            let model = model.unwrap_or_else(|| DEFAULT_MODEL.to_string());
+            let settings = InlineSettings {
+                selection: Selection {
+                    parent_identifier,
+                    function_identifier: Some(function_identifier),
+                    kind,
+                },
+                model: &model,
+                skip_prompt,
+                palette: args.palette,
+            };
+            if let Err(e) = run_inline(&file_path, &settings, &secret).await {
+                panic!("{}", e.red())
+            }
+            return;
+        }
+        Some(Command::Cleanup {
+            file_path,
+            function_identifier,
+            parent_identifier,
+            kind,
+            model,
+            skip_prompt,
+        }) => {
+            // The usual flow, with a stricter prompt and the structural diff shown in any case
+            args.file_path = Some(file_path);
+            args.function_identifier = Some(function_identifier);
+            args.parent_identifier = parent_identifier;
+            args.kind = kind;
+            args.model = model;
+            args.skip_prompt = skip_prompt;
+            args.diff = Some(DiffMode::Structural);
+            system_prompt_override = Some(CLEANUP_SYSTEM_PROMPT.to_string());
+        }
+        Some(Command::Annotate {
+            file_path,
+            function_identifier,
+            parent_identifier,
+            kind,
+            model,
+            skip_prompt,
+        }) => {
+            let language = match detect_language(&file_path) {
+                Ok(supported_language) => supported_language.to_string(),
+                Err(e) => panic!("{}", e.red()),
+            };
+            system_prompt_override = match annotate_system_prompt(&language) {
+                Ok(system_prompt) => Some(system_prompt),
+                Err(e) => panic!("{}", e.red()),
+            };
+            args.file_path = Some(file_path);
+            args.function_identifier = Some(function_identifier);
+            args.parent_identifier = parent_identifier;
+            args.kind = kind;
+            args.model = model;
+            args.skip_prompt = skip_prompt;
+            args.diff = Some(DiffMode::Text);
+            // JSDoc types are comments
+            args.add_comments = language == "javascript" || language == "jsx";
+            skip_optimising_advice = true;
+        }
+        Some(Command::Implement {
+            file_path,
+            function_identifier,
+            parent_identifier,
+            kind,
+            model,
+            skip_prompt,
+        }) => {
+            let selection = Selection {
+                parent_identifier,
+                function_identifier: Some(function_identifier),
+                kind,
+            };
+            let documentation = match stub_documentation(&file_path, &selection) {
+                Ok(documentation) => documentation,
+                Err(e) => panic!("{}", e.red()),
+            };
+            args.file_path = Some(file_path);
+            args.function_identifier = selection.function_identifier;
+            args.parent_identifier = selection.parent_identifier;
+            args.kind = selection.kind;
+            args.model = model;
+            args.skip_prompt = skip_prompt;
+            args.extra_context = (!documentation.is_empty())
+                .then(|| format!("The function is documented as:\n{}", documentation));
+            system_prompt_override = Some(IMPLEMENT_SYSTEM_PROMPT.to_string());
+            skip_optimising_advice = true;
+            implementing = true;
+        }
+        Some(Command::Panics {
+            file_path,
+            function_identifier,
+            parent_identifier,
+            kind,
+            model,
+            fix,
+            skip_prompt,
+        }) => {
+            let selection = Selection {
+                parent_identifier,
+                function_identifier: Some(function_identifier),
+                kind,
+            };
+            let panics_model = model.clone().unwrap_or_else(|| DEFAULT_MODEL.to_string());
+            if let Err(e) = run_panic_paths(&file_path, &selection, &panics_model, &secret).await {
+                panic!("{}", e.red())
+            }
+            if !fix {
+                return;
+            }
+            args.file_path = Some(file_path);
+            args.function_identifier = selection.function_identifier;
+            args.parent_identifier = selection.parent_identifier;
+            args.kind = selection.kind;
+            args.model = model;
+            args.skip_prompt = skip_prompt;
+            args.goal = Goal::PanicFreedom;
+        }
+        Some(Command::FixComplexity {
+            path,
+            changed,
+            no_ignore,
+            model,
+            skip_prompt,
+        }) => {
+            args.file_path = Some(path);
+            args.all = true;
+            args.changed = changed;
+            args.no_ignore = no_ignore;
+            args.model = model;
+            args.skip_prompt = skip_prompt;
+            skip_optimising_advice = true;
+            fixing_complexity = true;
+        }
+        Some(Command::Fix {
+            build: None,
+            trace: Some(trace),
+            path,
+            model,
+            skip_prompt,
+            ..
+        }) => {
+            let trace = match read_to_string(&trace) {
+                Ok(trace) => trace,
+                Err(e) => panic!("{}", format!("failed to read {}: {}", trace, e).red()),
+            };
+            let frames = repository_frames(
+                &parse_trace(&trace),
+                &source_files(Path::new(&path), args.no_ignore),
+            );
+            let functions = match traced_functions(&frames) {
+                Ok(functions) if functions.is_empty() => panic!(
+                    "{}",
+                    format!("the trace passes through no function of {}", path).red()
+                ),
+                Ok(functions) => functions,
+                Err(e) => panic!("{}", e.red()),
+            };
+            println!(
+                "the trace passes through {} functions of the repository",
+                functions.len()
+            );
+            args.file_path = Some(path);
+            args.model = model;
+            args.skip_prompt = skip_prompt;
+            system_prompt_override = Some(TRACE_SYSTEM_PROMPT.to_string());
+            skip_optimising_advice = true;
+            traced = Some((trace, functions));
+        }
+        Some(Command::Fix {
+            build,
+            path,
+            model,
+            skip_prompt,
+            sandbox,
+            ..
+        }) => {
+            let build = build.unwrap();
+            let output = match run_build(&build, sandbox.as_ref()) {
+                Ok((true, _)) => {
+                    println!("{}", "the build passes, there is nothing to fix.".green());
+                    return;
+                }
+                Ok((false, output)) => output,
+                Err(e) => panic!("{}", e.red()),
+            };
+            let errors = parse_build_errors(&output);
+            if errors.is_empty() {
+                panic!(
+                    "{}",
+                    format!(
+                        "the build failed, but none of its errors point into a supported file:\n{}",
+                        output
+                    )
+                    .red()
+                )
+            }
+            println!("the build reported {} errors", errors.len());
+            args.file_path = Some(path);
+            args.all = true;
+            args.model = model;
+            args.skip_prompt = skip_prompt;
+            system_prompt_override = Some(FIX_SYSTEM_PROMPT.to_string());
+            skip_optimising_advice = true;
+            build_errors = Some(errors);
+        }
+        Some(Command::Test {
+            file_path,
+            function_identifier,
+            parent_identifier,
+            kind,
+            model,
+            skip_prompt,
+        }) => {
+            let model = model.unwrap_or_else(|| DEFAULT_MODEL.to_string());
+            let settings = TestSettings {
+                selection: Selection {
+                    parent_identifier,
+                    function_identifier: Some(function_identifier),
+                    kind,
+                },
+                model: &model,
+                skip_prompt,
+                palette: args.palette,
+            };
+            if let Err(e) = run_test_generation(&file_path, &settings, &secret).await {
+                panic!("{}", e.red())
+            }
+            return;
+        }
+        Some(Command::Review {
+            pr,
+            repo,
+            model,
+            dry_run,
+        }) => {
+            let Ok(token) = env::var(GITHUB_TOKEN_ENV_VAR_KEY) else {
+                panic!("{}", "the GITHUB_TOKEN env var is not present".red())
+            };
+            let repo = match repo.map_or_else(repo_from_origin, Ok) {
+                Ok(repo) => repo,
+                Err(e) => panic!("{}", e.red()),
+            };
+            let model = model.unwrap_or_else(|| DEFAULT_MODEL.to_string());
+            let github = GitHubClient::new(&token, &repo);
+            if let Err(e) = run_review(&github, pr, &model, &secret, dry_run).await {
+                panic!("{}", e.red())
+            }
+            return;
+        }
+        None => {}
+    }
+
+    // Present whenever there is no subcommand, and set by the subcommands going through the usual flow otherwise
+    let path = args.file_path.unwrap();
+    let theme = args.theme.unwrap_or_else(|| match args.palette {
+        Palette::RedGreen => DEFAULT_THEME.to_string(),
+        Palette::HighContrast => HIGH_CONTRAST_THEME.to_string(),
+    });
+    let model = args.model.unwrap_or_else(|| DEFAULT_MODEL.to_string());
+    if !is_known_model(&model) {
+        eprintln!(
+            "{}",
+            format!(
+                "{} is not a known model, so it is assumed to only answer in plain text, within {} tokens.",
+                model,
+                capabilities(&model).max_context_tokens
+            )
+            .dimmed()
+        );
+    }
+    let attachments: Vec<String> = if args.attach.is_empty() || capabilities(&model).vision {
+        args.attach
+            .iter()
+            .map(|path| match image_data_url(Path::new(path)) {
+                Ok(data_url) => data_url,
+                Err(e) => panic!("{}", e.red()),
+            })
+            .collect()
+    } else {
+        eprintln!(
+            "{}",
+            format!("{} cannot see images, so they are left out.", model).yellow()
+        );
+        vec![]
+    };
+    let system_prompt = system_prompt_override.or_else(|| {
+        args.system_prompt_file.as_ref().map(|system_prompt_file| {
+            match read_to_string(system_prompt_file) {
+                Ok(system_prompt) => system_prompt,
+                Err(e) => panic!(
+                    "{}",
+                    format!("failed to read {}: {}", system_prompt_file, e).red()
+                ),
+            }
+        })
+    });
+    let literal_pass = if args.sql {
+        Some(LiteralPass::Sql)
+    } else if args.regex {
+        Some(LiteralPass::Regex)
+    } else {
+        None
+    };
+    let system_prompt = system_prompt
+        .or_else(|| literal_pass.map(|literal_pass| literal_pass.system_prompt().to_string()));
+    if let Some(context_file) = &args.context_file {
+        let contents = match read_to_string(context_file) {
+            Ok(contents) => contents,
+            Err(e) => panic!(
+                "{}",
+                format!("failed to read {}: {}", context_file, e).red()
+            ),
+        };
+        let context = format!("{}:\n```\n{}\n```", context_file, contents.trim_end());
+        args.extra_context = Some(match args.extra_context.take() {
+            Some(extra_context) => format!("{}\n{}", extra_context, context),
+            None => context,
+        });
+    }
+    if let Some(span) = &args.span {
+        let settings = SnippetSettings {
+            model: &model,
+            extra_context: args.extra_context.clone(),
+            dry_run: args.dry_run,
+            skip_prompt: args.skip_prompt,
+            palette: args.palette,
+        };
+        if let Err(e) = run_snippet(&path, span, &settings, &secret).await {
+            panic!("{}", e.red())
+        }
+        return;
+    }
+    let config = if Path::new(CONFIG_FILE_PATH).exists() {
+        match Config::load(Path::new(CONFIG_FILE_PATH)) {
+            Ok(config) => config,
+            Err(e) => panic!("{}", e.red()),
+        }
+    } else {
+        Config::default()
+    };
+    let policy = match load_org_policy() {
+        Ok(org_policy) => effective_policy(org_policy, config.policy),
+        Err(e) => panic!("{}", e.red()),
+    };
+    let reviewer = (args.compliance || policy.compliance).then(|| {
+        if args.skip_prompt || args.auto_apply.is_some() {
+            panic!(
+                "{}",
+                "compliance mode requires a person to review every suggestion, so --skip-prompt and --auto-apply are not allowed"
+                    .red()
+            )
+        }
+        match git::user() {
+            Ok(user) => user,
+            Err(e) => panic!(
+                "{}",
+                format!("compliance mode records who reviews suggestions, but {}", e).red()
+            ),
+        }
+    });
+    let fixing_build = build_errors.is_some();
+    let diagnostics = match build_errors.map(Ok).or_else(|| {
+        args.diagnostics
+            .as_ref()
+            .map(|file| load_diagnostics(Path::new(file)))
+    }) {
+        Some(Ok(diagnostics)) => Arc::new(diagnostics),
+        Some(Err(e)) => panic!("{}", e.red()),
+        None => Arc::default(),
+    };
+    let coverage = args
+        .coverage
+        .as_ref()
+        .map(|report| match Coverage::load(Path::new(report)) {
+            Ok(coverage) => Arc::new(coverage),
+            Err(e) => panic!("{}", e.red()),
+        });
+    let new_optimizer = |file_path: &str| -> Result<Optimizer, Outcome> {
+        let mut opt = Optimizer::new(
+            file_path,
+            args.parent_identifier.clone(),
+            args.function_identifier.clone(),
+            args.kind,
+            args.extra_context.clone(),
+            &theme,
+            &model,
+            args.dry_run || args.check || args.output_format == OutputFormat::Json,
+            args.add_comments,
+            args.skip_prompt,
+        )
+        .map_err(Outcome::Skipped)?;
+        opt.git_commit = args.git_commit.then(|| {
+            args.commit_message
+                .clone()
+                .map_or(CommitMessage::Generated, CommitMessage::Given)
+        });
+        opt.system_prompt = if fixing_complexity {
+            Some(refactor_system_prompt(&config.complexity))
+        } else {
+            system_prompt.clone()
+        };
+        opt.skip_optimising_advice = skip_optimising_advice || literal_pass.is_some();
+        opt.literal_pass = literal_pass;
+        opt.auto_apply = args.auto_apply;
+        opt.explain = args.explain;
+        opt.forbidden_symbols = config.forbidden_symbols.clone();
+        opt.post_apply = config.post_apply.clone();
+        opt.sandbox = args.sandbox.clone();
+        opt.attachments = attachments.clone();
+        opt.apply_backend = args.apply_with;
+        opt.reviewer = reviewer.clone();
+        opt.allow_signature_change = args.allow_signature_change;
+        opt.no_new_dependencies = args.no_new_dependencies;
+        opt.coverage = coverage.clone();
+        opt.diagnostics = diagnostics.clone();
+        opt.force = args.force;
+        opt.deny_unsafe = args.deny_unsafe;
+        opt.output_format = args.output_format;
+        opt.body_only = args.body_only;
+        opt.diff = args.diff;
+        opt.palette = args.palette;
+        opt.colored_output = colored_output;
+        // The code and the rest of the prompt need room too
+        opt.token_budget = args
+            .token_budget
+            .min(capabilities(&model).max_context_tokens / 2);
+        opt.similar_examples = args.similar_examples;
+        opt.goal = args.goal;
+        opt.configure_language_instructions(&config.instructions);
+        if implementing {
+            opt.provenance_comment = Some(implemented_provenance_template(
+                opt.supported_language.line_comment(),
+            ));
+        } else if args.provenance_comment {
+            opt.configure_provenance_comment(&config.provenance_comment);
+        }
+        if let Some(conversation) = &args.conversation {
+            opt.conversation =
+                Some(Conversation::load(Path::new(conversation)).map_err(Outcome::Skipped)?);
+        }
+        opt.build().map_err(Outcome::ParseError)?;
+        // Functions are hard to find in broken files, so better to say so before asking anything
+        let syntax_errors = syntax_errors(&opt.source_file, opt.tree.as_ref().unwrap());
+        if !syntax_errors.is_empty() {
+            let message = format!(
+                "{} has syntax errors:\n  {}",
+                file_path,
+                syntax_errors.join("\n  ")
+            );
+            if args.strict_parse {
+                return Err(Outcome::ParseError(message));
+            }
+            eprintln!("{}", message.yellow());
+        }
+
+        Ok(opt)
+    };
+
+    let mut applied_suggestions = vec![];
+    let mut failed = false;
+    if args.all {
+        let filters = BatchFilters {
+            kind: args.kind,
+            skip_tests: args.skip_tests,
+            only_tests: args.only_tests,
+            min_lines: args.min_lines,
+            max_lines: args.max_lines,
+            skip_generated: !args.include_generated,
+            over_budget: fixing_complexity.then_some(config.complexity),
+            diagnosed: fixing_build.then(|| diagnostics.clone()),
+        };
+
+        let history = repository_history();
+        let mut cache = (!args.no_cache).then(|| VerdictCache::load(history.as_ref()));
+        let mut checked_functions = 0;
+        let mut flagged_functions = vec![];
+        let mut outcomes = OutcomeSummary::default();
+        let mut identical_functions = IdenticalFunctions::default();
+
+        let packages = find_packages(Path::new(&path));
+        let files = match batch_files(Path::new(&path), args.changed, args.no_ignore) {
+            Ok(files) => files,
+            Err(e) => panic!("{}", e.red()),
+        };
+        // So that the whole run can be undone, whatever the state of git
+        if !(args.dry_run || args.check || args.output_format == OutputFormat::Json) {
+            match take_snapshot(Path::new(SNAPSHOTS_DIRECTORY_PATH), &files) {
+                Ok(id) => say(
+                    args.output_format,
+                    format!(
+                        "took snapshot {}, `senior revert --to {}` undoes this run.",
+                        id, id
+                    )
+                    .dimmed(),
+                ),
+                Err(e) => panic!("{}", format!("failed to take a snapshot: {}", e).red()),
+            }
+        }
+        for (package, files) in group_by_package(&packages, files) {
+            if let Some(package) = &package {
+                say(
+                    args.output_format,
+                    format!("package {}", package).bold().underline(),
+                );
+            }
+            for file_path in files {
+                let mut opt = match new_optimizer(&file_path) {
+                    Ok(opt) => opt,
+                    Err(outcome) => {
+                        eprintln!("{}", format!("{}: {}", file_path, outcome).red());
+                        if args.output_format == OutputFormat::Json {
+                            print_report(&Report::new(&file_path, None, &outcome, None));
+                        }
+                        outcomes.record(&outcome);
+                        continue;
+                    }
+                };
+                let targets = filters.find_targets(
+                    opt.supported_language.as_ref(),
+                    &file_path,
+                    &opt.source_file,
+                    opt.tree.as_ref().unwrap(),
+                );
+
+                // Going backwards keeps the ranges of the remaining targets valid after applying
+                for target in targets.iter().rev() {
+                    let location = format!("{}:{}", file_path, target.start_point.row + 1);
+                    say(args.output_format, location.bold());
+                    opt.select_function_at(target);
+                    checked_functions += 1;
+                    let language = opt.supported_language.to_string();
+                    let answer = match identical_functions.first_of(&language, &opt.code) {
+                        Some((first, answer)) => {
+                            say(
+                                args.output_format,
+                                format!("identical to {}, reusing its answer.", first).dimmed(),
+                            );
+                            Ok(answer)
+                        }
+                        None => ask_for_suggestion(&mut opt, &secret, cache.as_mut())
+                            .await
+                            .inspect(|answer| {
+                                identical_functions.record(&language, &opt.code, &location, answer)
+                            }),
+                    };
+                    let outcome = match answer {
+                        Ok(answer) => review_suggestion(&mut opt, &answer, &secret).await,
+                        Err(e) => Outcome::ApiError(e),
+                    };
+                    if let Outcome::ApiError(e) = &outcome {
+                        eprintln!("{}", e.red());
+                    }
+                    if args.output_format == OutputFormat::Json {
+                        print_report(&opt.report(&outcome));
+                    }
+                    if let Some(suggestion) = outcome.suggestion() {
+                        flagged_functions.push(FlaggedFunction {
+                            location,
+                            package: package.clone(),
+                            language: opt.supported_language.to_string(),
+                            original: args.notify_diffs.then(|| opt.code.clone()),
+                            suggestion: args.notify_diffs.then(|| suggestion.to_string()),
+                        });
+                    }
+                    outcomes.record(&outcome);
+                }
+
+                for (original, suggestion) in opt.applied_suggestions.drain(..) {
+                    applied_suggestions.push((file_path.clone(), original, suggestion));
+                }
+                if let Some(cache) = &cache {
+                    if let Err(e) = cache.save(history.as_ref()) {
+                        eprintln!("{}", format!("failed to save the cache: {}", e).yellow());
+                    }
+                }
+            }
+        }
+
+        let any_flagged_functions = !flagged_functions.is_empty();
+        if args.check {
+            let summary = CheckSummary::new(&path, checked_functions, flagged_functions.len());
+            if let Err(e) = repository_history().record_summary(&summary) {
+                eprintln!(
+                    "{}",
+                    format!("failed to record the summary: {}", e).yellow()
+                );
+            }
+            if any_flagged_functions {
+                say(
+                    args.output_format,
+                    format!("{} functions could be improved:", flagged_functions.len()).red(),
+                );
+                for flagged_function in &flagged_functions {
+                    match &flagged_function.package {
+                        Some(package) => say(
+                            args.output_format,
+                            format!("  {} ({})", flagged_function.location, package),
+                        ),
+                        None => say(
+                            args.output_format,
+                            format!("  {}", flagged_function.location),
+                        ),
+                    }
+                }
+            } else {
+                say(
+                    args.output_format,
+                    "every function is already optimal.".green(),
+                );
+            }
+        }
+
+        if let Some(webhook) = &args.notify_webhook {
+            let summary = BatchSummary::new(checked_functions, flagged_functions);
+            if let Err(e) = post_to_webhook(webhook, &summary).await {
+                eprintln!(
+                    "{}",
+                    format!("failed to notify the webhook: {}", e).yellow()
+                );
+            }
+        }
+
+        say(args.output_format, outcomes.render().bold());
+        if identical_functions.reused() > 0 {
+            say(
+                args.output_format,
+                format!(
+                    "{} functions were identical to ones the model was asked about already.",
+                    identical_functions.reused()
+                )
+                .dimmed(),
+            );
+        }
+        let (prompt_tokens, cached_prompt_tokens) = prompt_token_usage();
+        if cached_prompt_tokens > 0 {
+            say(
+                args.output_format,
+                format!(
+                    "{} of {} prompt tokens were read from the cache of the provider.",
+                    cached_prompt_tokens, prompt_tokens
+                )
+                .dimmed(),
+            );
+        }
+        failed = (args.check && any_flagged_functions) || outcomes.fails_on(&args.fail_on);
+    } else if let Some((trace, functions)) = traced {
+        // How many lines the fixes so far added, or removed if negative, after a line of a file
+        let mut shifts: Vec<(String, usize, isize)> = vec![];
+        for (i, function) in functions.iter().enumerate() {
+            let shift: isize = shifts
+                .iter()
+                .filter(|(file, end_line, _)| *file == function.file && *end_line < function.line)
+                .map(|(_, _, shift)| shift)
+                .sum();
+            let line = function.line.saturating_add_signed(shift);
+            let location = format!("{}:{}", function.file, line);
+            say(args.output_format, location.bold());
+            let mut opt = match new_optimizer(&function.file) {
+                Ok(opt) => opt,
+                Err(Outcome::Skipped(e) | Outcome::ParseError(e)) => panic!("{}", e.red()),
+                Err(outcome) => unreachable!("{:?}", outcome),
+            };
+            let Some(range) = enclosing_function(
+                opt.supported_language.as_ref(),
+                opt.tree.as_ref().unwrap(),
+                line,
+            ) else {
+                eprintln!(
+                    "{}",
+                    format!("{} is no longer in a function", location).yellow()
+                );
+                continue;
+            };
+            opt.select_function_at(&range);
+            let line_of_frame = opt.source_file.lines().nth(line - 1).unwrap_or_default();
+            opt.extra_context = Some(trace_context(&trace, line_of_frame));
+
+            let outcome = suggest(&mut opt, &secret, None).await;
+            if args.output_format == OutputFormat::Json {
+                print_report(&opt.report(&outcome));
+            }
+            if let Outcome::ApiError(e) = outcome {
+                panic!("{}", e.red())
+            }
+            for (original, suggestion) in opt.applied_suggestions.drain(..) {
+                let shift = suggestion.lines().count() as isize - original.lines().count() as isize;
+                shifts.push((function.file.clone(), function.end_line, shift));
+                applied_suggestions.push((function.file.clone(), original, suggestion));
+            }
+
+            let go_on = i + 1 == functions.len()
+                || args.skip_prompt
+                || Confirm::new("Go on to its caller?")
+                    .with_default(true)
+                    .prompt()
+                    .unwrap();
+            if !go_on {
+                break;
+            }
+        }
+    } else {
+        let mut opt = match new_optimizer(&path) {
+            Ok(opt) => opt,
+            Err(Outcome::Skipped(e) | Outcome::ParseError(e)) => panic!("{}", e.red()),
+            Err(outcome) => unreachable!("{:?}", outcome),
+        };
+        if let Err(e) = opt.select_function() {
+            if args.debug_tree {
+                eprintln!(
+                    "{}",
+                    dump_tree(
+                        opt.supported_language.as_ref(),
+                        &opt.source_file,
+                        opt.tree.as_ref().unwrap()
+                    )
+                );
+            }
+            panic!("{}", e.red())
+        }
+        let rounds = match literal_pass {
+            Some(literal_pass) => match opt.literals().len() {
+                0 => panic!(
+                    "{}",
+                    format!("the function holds no {}", literal_pass.contents_name()).red()
+                ),
+                literals => literals,
+            },
+            None => 1,
+        };
+
+        for round in 0..rounds {
+            if literal_pass.is_some() {
+                // Applying a suggestion parses the file again, so the literals are looked up anew
+                if let Err(e) = opt
+                    .select_function()
+                    .and_then(|_| opt.select_literal(round))
+                {
+                    panic!("{}", e.red())
+                }
+                let findings = opt.literal_findings();
+                if !findings.is_empty() {
+                    say(args.output_format, "found ahead of time:".dimmed());
+                    for finding in findings {
+                        say(args.output_format, format!("  {}", finding).dimmed());
+                    }
+                }
+            }
+            let outcome = suggest(&mut opt, &secret, None).await;
+            if args.output_format == OutputFormat::Json {
+                print_report(&opt.report(&outcome));
+            }
+            if let Outcome::ApiError(e) = outcome {
+                panic!("{}", e.red())
+            }
+        }
+        for (original, suggestion) in opt.applied_suggestions.drain(..) {
+            applied_suggestions.push((path.clone(), original, suggestion));
+        }
+    }
+
+    if let Some(changelog) = &args.changelog {
+        if let Err(e) = write_changelog_entries(
+            changelog,
+            args.changelog_style,
+            &applied_suggestions,
+            &secret,
+        )
+        .await
+        {
+            panic!("{}", e.red())
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
     }
 }
@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::fs::{create_dir_all, write};
+use std::path::Path;
+
+use colored::*;
+
+use crate::batch::{source_files, BatchFilters};
+use crate::cache::{fingerprint, VerdictCache};
+use crate::config::NightlyConfig;
+use crate::history::repository_history;
+use crate::notify::post_to_slack;
+use crate::suggestion::parse_suggestion;
+use crate::{Optimizer, DEFAULT_MODEL, DEFAULT_THEME};
+
+struct Finding {
+    location: String,
+    language: String,
+    suggestion: String,
+}
+
+/// Suggests improvements to the functions that changed since the last run, without applying
+/// them. Then writes a report, and posts a summary of it to Slack.
+pub async fn run_nightly(
+    config: &NightlyConfig,
+    instructions: &HashMap<String, String>,
+    secret: &str,
+) -> Result<(), String> {
+    let filters = BatchFilters {
+        kind: None,
+        skip_tests: config.skip_tests,
+        only_tests: false,
+        min_lines: config.min_lines,
+        max_lines: config.max_lines,
+        skip_generated: !config.include_generated,
+        over_budget: None,
+        diagnosed: None,
+    };
+    let model = config.model.as_deref().unwrap_or(DEFAULT_MODEL);
+    let history = repository_history();
+    let mut cache = VerdictCache::load(history.as_ref());
+    let mut changed_functions = 0;
+    let mut findings = vec![];
+
+    for file_path in source_files(Path::new(&config.path), config.no_ignore) {
+        let mut opt = Optimizer::new(
+            &file_path,
+            None,
+            None,
+            None,
+            config.extra_context.clone(),
+            DEFAULT_THEME,
+            model,
+            true,
+            false,
+            true,
+        )?;
+        opt.configure_language_instructions(instructions);
+        opt.build()?;
+
+        let targets = filters.find_targets(
+            opt.supported_language.as_ref(),
+            &file_path,
+            &opt.source_file,
+            opt.tree.as_ref().unwrap(),
+        );
+        for target in targets.iter() {
+            opt.select_function_at(target);
+
+            // Functions with a cached answer did not change since the last run
+            let fingerprint = fingerprint(&opt.model, &opt.prompt());
+            if cache.get(&fingerprint).is_some() {
+                continue;
+            }
+            changed_functions += 1;
+
+            let answer = opt.optimise(secret).await?;
+            cache.record(fingerprint, answer.clone());
+            let suggestion = parse_suggestion(&answer);
+            if !suggestion.is_optimal() {
+                findings.push(Finding {
+                    location: format!("{}:{}", file_path, target.start_point.row + 1),
+                    language: opt.supported_language.to_string(),
+                    suggestion: suggestion.code,
+                });
+            }
+        }
+    }
+    cache.save(history.as_ref())?;
+
+    let report_path = Path::new(&config.report);
+    if let Some(parent) = report_path.parent() {
+        create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    write(report_path, render_report(changed_functions, &findings)).map_err(|e| e.to_string())?;
+
+    let summary = summarise(changed_functions, findings.len(), &config.report);
+    println!("{}", summary.green());
+    if let Some(webhook) = &config.slack_webhook {
+        post_to_slack(webhook, &summary).await?;
+    }
+
+    Ok(())
+}
+
+fn summarise(changed_functions: usize, findings: usize, report_path: &str) -> String {
+    format!(
+        "senior nightly: {} functions changed since the last run, {} of which could be improved. See {}",
+        changed_functions, findings, report_path
+    )
+}
+
+fn render_report(changed_functions: usize, findings: &[Finding]) -> String {
+    let mut report = format!(
+        "# senior nightly report\n\n{} functions changed since the last run, {} of which could be improved.\n",
+        changed_functions,
+        findings.len()
+    );
+    for finding in findings {
+        report.push_str(&format!(
+            "\n## {}\n\n```{}\n{}\n```\n",
+            finding.location, finding.language, finding.suggestion
+        ));
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nightly::{render_report, Finding};
+
+    #[test]
+    fn report() {
+        let findings = vec![Finding {
+            location: "src/main.rs:3".to_string(),
+            language: "rust".to_string(),
+            suggestion: "fn greet() {}".to_string(),
+        }];
+
+        assert_eq!(
+            r#"# senior nightly report
+
+2 functions changed since the last run, 1 of which could be improved.
+
+## src/main.rs:3
+
+```rust
+fn greet() {}
+```
+"#,
+            render_report(2, &findings)
+        )
+    }
+}
@@ -0,0 +1,88 @@
+use serde::Serialize;
+
+#[derive(Serialize)]
+struct SlackMessage<'a> {
+    text: &'a str,
+}
+
+/// A function for which there was a suggestion
+#[derive(Serialize, Debug)]
+pub struct FlaggedFunction {
+    pub location: String,
+    /// The workspace package holding the function, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    pub language: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+}
+
+/// What `--notify-webhook` receives at the end of an `--all` run. The `text` field makes it
+/// understandable by Slack incoming webhooks as well.
+#[derive(Serialize, Debug)]
+pub struct BatchSummary {
+    pub text: String,
+    pub checked_functions: usize,
+    pub flagged_functions: Vec<FlaggedFunction>,
+}
+
+impl BatchSummary {
+    pub fn new(checked_functions: usize, flagged_functions: Vec<FlaggedFunction>) -> Self {
+        Self {
+            text: format!(
+                "senior: {} of {} functions could be improved",
+                flagged_functions.len(),
+                checked_functions
+            ),
+            checked_functions,
+            flagged_functions,
+        }
+    }
+}
+
+/// Posts a message to a Slack incoming webhook
+pub async fn post_to_slack(webhook: &str, text: &str) -> Result<(), String> {
+    post_to_webhook(webhook, &SlackMessage { text }).await
+}
+
+/// Posts any JSON body to a webhook
+pub async fn post_to_webhook(webhook: &str, body: &impl Serialize) -> Result<(), String> {
+    let response = reqwest::Client::new()
+        .post(webhook)
+        .json(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("{} answered with {}", webhook, response.status()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::notify::{BatchSummary, FlaggedFunction};
+
+    #[test]
+    fn summary_without_diffs() {
+        let summary = BatchSummary::new(
+            3,
+            vec![FlaggedFunction {
+                location: "src/main.rs:3".to_string(),
+                package: None,
+                language: "rust".to_string(),
+                original: None,
+                suggestion: None,
+            }],
+        );
+
+        assert_eq!(
+            r#"{"text":"senior: 1 of 3 functions could be improved","checked_functions":3,"flagged_functions":[{"location":"src/main.rs:3","language":"rust"}]}"#,
+            serde_json::to_string(&summary).unwrap()
+        )
+    }
+}
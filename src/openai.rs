@@ -0,0 +1,673 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use clap::ValueEnum;
+
+use colored::*;
+use reqwest::{RequestBuilder, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::capabilities::capabilities;
+use crate::context::estimate_tokens;
+use crate::hooks::{run_pre_prompt_hook, DraftPrompt};
+use crate::rate_limit::wait_for_budget;
+
+pub const OPENAI_API_KEY_ENV_VAR_KEY: &str = "OPENAI_API_KEY";
+const COMPLETION_URL: &str = "https://api.openai.com/v1/chat/completions";
+const EMBEDDINGS_URL: &str = "https://api.openai.com/v1/embeddings";
+const MODELS_URL: &str = "https://api.openai.com/v1/models";
+const EMBEDDING_MODEL: &str = "text-embedding-3-small";
+/// Answers are read in order from the JSON array of strings in this file, instead of asking the
+/// API. Only builds with the `e2e` feature, which the end-to-end tests need, read it.
+#[cfg(any(test, feature = "e2e"))]
+pub const MOCK_ANSWERS_ENV_VAR_KEY: &str = "SENIOR_MOCK_ANSWERS";
+
+/// How many mock answers were given so far
+#[cfg(any(test, feature = "e2e"))]
+static MOCK_ANSWERS_GIVEN: AtomicUsize = AtomicUsize::new(0);
+/// How many prompt tokens were sent so far, and how many of them the provider read from its cache
+static PROMPT_TOKENS: AtomicUsize = AtomicUsize::new(0);
+static CACHED_PROMPT_TOKENS: AtomicUsize = AtomicUsize::new(0);
+static REASONING_SETTINGS: OnceLock<ReasoningSettings> = OnceLock::new();
+
+/// How long to wait for an answer. Reasoning models think for a while before answering.
+const TIMEOUT: Duration = Duration::from_secs(120);
+const REASONING_TIMEOUT: Duration = Duration::from_secs(600);
+/// How many times to ask again when the API answers without any choice
+const EMPTY_RESPONSE_RETRIES: usize = 2;
+/// How much of a body that cannot be read is shown
+const MAX_SHOWN_RESPONSE_LENGTH: usize = 500;
+/// How many times a request that failed on the network is sent again, and how long to wait before
+const NETWORK_RETRIES: usize = 3;
+const NETWORK_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// How much reasoning models think before answering
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+/// How reasoning models are asked, and whether what they reasoned is shown
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReasoningSettings {
+    pub effort: Option<ReasoningEffort>,
+    pub show_reasoning: bool,
+}
+
+/// Sets how every later request to a reasoning model is made
+pub fn set_reasoning_settings(reasoning_settings: ReasoningSettings) {
+    let _ = REASONING_SETTINGS.set(reasoning_settings);
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Message {
+    pub role: String,
+    pub content: String,
+    /// Images shown along with the content, as `data:` URLs, for models with vision
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<String>,
+}
+
+impl Message {
+    pub fn new(role: &str, content: &str) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.to_string(),
+            images: vec![],
+        }
+    }
+    /// The message as the chat completions API takes it. Messages with images have their content
+    /// split in parts, the text first.
+    fn to_request_message(&self) -> Value {
+        if self.images.is_empty() {
+            return json!({ "role": self.role, "content": self.content });
+        }
+        let mut parts = vec![json!({ "type": "text", "text": self.content })];
+        parts.extend(
+            self.images
+                .iter()
+                .map(|url| json!({ "type": "image_url", "image_url": { "url": url } })),
+        );
+
+        json!({ "role": self.role, "content": parts })
+    }
+}
+
+/// What is sent to the model: the standing instructions, and the request at hand
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prompt {
+    pub system: String,
+    pub user: String,
+}
+
+impl Prompt {
+    pub fn messages(&self) -> Vec<Message> {
+        vec![
+            Message::new("system", &self.system),
+            Message::new("user", &self.user),
+        ]
+    }
+}
+
+#[derive(Serialize)]
+struct OpenAIChatRequest {
+    model: String,
+    messages: Vec<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<Value>,
+    /// Routes requests sharing a prefix to the same cache
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt_cache_key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning_effort: Option<ReasoningEffort>,
+}
+
+/// Reasoning models may leave the content out, and some providers add what they reasoned
+#[derive(Deserialize, Debug, Default)]
+struct OpenAIChatResponseMessage {
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default, alias = "reasoning")]
+    reasoning_content: Option<String>,
+    #[serde(default)]
+    refusal: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAIChatResponseChoice {
+    message: OpenAIChatResponseMessage,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct OpenAIPromptTokensDetails {
+    #[serde(default)]
+    cached_tokens: usize,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAIUsage {
+    prompt_tokens: usize,
+    #[serde(default)]
+    prompt_tokens_details: Option<OpenAIPromptTokensDetails>,
+}
+
+/// How the API reports what went wrong, like an exceeded quota
+#[derive(Deserialize, Debug)]
+struct OpenAIError {
+    message: String,
+    #[serde(default)]
+    code: Option<String>,
+    #[serde(default)]
+    param: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAIErrorResponse {
+    error: OpenAIError,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAIChatResponse {
+    choices: Vec<OpenAIChatResponseChoice>,
+    #[serde(default)]
+    usage: Option<OpenAIUsage>,
+}
+
+/// Requests whose system messages are the same share a key, so that the provider looks up its
+/// cache of that prefix for all of them
+fn prompt_cache_key(model: &str, messages: &[Message]) -> Option<String> {
+    let system = messages
+        .first()
+        .filter(|message| message.role == "system")?;
+    if !capabilities(model).prompt_caching {
+        return None;
+    }
+    let hash: String = Sha256::digest(system.content.as_bytes())
+        .iter()
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    Some(format!("senior-{}", hash))
+}
+
+/// How many prompt tokens were sent so far, and how many of them were read from the cache of the
+/// provider
+pub fn prompt_token_usage() -> (usize, usize) {
+    (
+        PROMPT_TOKENS.load(Ordering::SeqCst),
+        CACHED_PROMPT_TOKENS.load(Ordering::SeqCst),
+    )
+}
+
+#[derive(Serialize)]
+struct OpenAIEmbeddingsRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAIEmbedding {
+    embedding: Vec<f32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAIEmbeddingsResponse {
+    data: Vec<OpenAIEmbedding>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAIModel {
+    id: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenAIModelsResponse {
+    data: Vec<OpenAIModel>,
+}
+
+/// The next answer of the mock answers file
+#[cfg(any(test, feature = "e2e"))]
+fn mock_answer(path: &str) -> Result<String, String> {
+    let answers =
+        std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+    let answers: Vec<String> =
+        serde_json::from_str(&answers).map_err(|e| format!("failed to parse {}: {}", path, e))?;
+    let index = MOCK_ANSWERS_GIVEN.fetch_add(1, Ordering::SeqCst);
+
+    answers
+        .get(index)
+        .cloned()
+        .ok_or_else(|| format!("{} has no answer left", path))
+}
+
+/// Sends the prompt to the chat completions API and returns the answer
+pub async fn complete(model: &str, prompt: &Prompt, secret: &str) -> Result<String, String> {
+    chat(model, &prompt.messages(), None, secret).await
+}
+
+/// Sends the messages to the chat completions API, once the pre-prompt hook had its say, and
+/// returns the answer. The response format, if any, constrains the answer to JSON following a
+/// schema.
+pub async fn chat(
+    model: &str,
+    messages: &[Message],
+    response_format: Option<Value>,
+    secret: &str,
+) -> Result<String, String> {
+    #[cfg(any(test, feature = "e2e"))]
+    if let Ok(path) = std::env::var(MOCK_ANSWERS_ENV_VAR_KEY) {
+        return mock_answer(&path);
+    }
+    let draft = run_pre_prompt_hook(DraftPrompt {
+        model: model.to_string(),
+        messages: messages.to_vec(),
+    })?;
+    let tokens = draft
+        .messages
+        .iter()
+        .map(|message| estimate_tokens(&message.content))
+        .sum();
+    wait_for_budget(secret, tokens).await?;
+    let reasoning_settings = REASONING_SETTINGS.get().copied().unwrap_or_default();
+    let reasoning = capabilities(&draft.model).reasoning;
+    let mut body = OpenAIChatRequest {
+        prompt_cache_key: prompt_cache_key(&draft.model, &draft.messages),
+        reasoning_effort: reasoning_settings.effort.filter(|_| reasoning),
+        model: draft.model,
+        messages: draft
+            .messages
+            .iter()
+            .map(Message::to_request_message)
+            .collect(),
+        response_format,
+    };
+
+    match send_chat(&body, secret).await {
+        // Models are not always known to support what they are asked for, so better to ask again
+        // for a plain answer than to fail
+        Err(e) if body.response_format.is_some() && e.contains("response_format") => {
+            eprintln!(
+                "{}",
+                format!(
+                    "{} does not support structured answers, asking for a plain one.",
+                    body.model
+                )
+                .dimmed()
+            );
+            body.response_format = None;
+            send_chat(&body, secret).await
+        }
+        result => result,
+    }
+}
+
+/// The answer of the first choice, after printing what the model reasoned if asked to
+fn read_answer(choice: OpenAIChatResponseChoice, show_reasoning: bool) -> Result<String, String> {
+    if let Some(reasoning) = choice
+        .message
+        .reasoning_content
+        .filter(|reasoning| show_reasoning && !reasoning.trim().is_empty())
+    {
+        eprintln!("{}\n{}", "reasoning:".dimmed(), reasoning.trim().dimmed());
+    }
+    if let Some(refusal) = choice.message.refusal {
+        return Err(format!("the model refused to answer: {}", refusal));
+    }
+
+    match choice
+        .message
+        .content
+        .filter(|content| !content.trim().is_empty())
+    {
+        Some(content) => Ok(content),
+        // Reasoning models may spend every token they are given on reasoning
+        None if choice.finish_reason.as_deref() == Some("length") => Err(
+            "the model ran out of tokens before answering, try a lower --reasoning-effort"
+                .to_string(),
+        ),
+        None => Err("the model answered nothing".to_string()),
+    }
+}
+
+/// Adds the prompt tokens of the response to the ones sent so far
+fn record_usage(usage: Option<&OpenAIUsage>) {
+    let Some(usage) = usage else {
+        return;
+    };
+    PROMPT_TOKENS.fetch_add(usage.prompt_tokens, Ordering::SeqCst);
+    CACHED_PROMPT_TOKENS.fetch_add(
+        usage
+            .prompt_tokens_details
+            .as_ref()
+            .map_or(0, |details| details.cached_tokens),
+        Ordering::SeqCst,
+    );
+}
+
+/// Reads the body of a response of the API. Errors, like an exceeded quota or an invalid model,
+/// are given as the API words them, and bodies that cannot be read are shown as they are.
+fn read_response<T: DeserializeOwned>(status: StatusCode, text: &str) -> Result<T, String> {
+    if let Ok(OpenAIErrorResponse { error }) = serde_json::from_str(text) {
+        return Err(match error.code.or(error.param) {
+            Some(code) => format!("the API answered {}: {} ({})", status, error.message, code),
+            None => format!("the API answered {}: {}", status, error.message),
+        });
+    }
+    if !status.is_success() {
+        return Err(format!("the API answered {}: {}", status, text.trim()));
+    }
+
+    serde_json::from_str(text).map_err(|e| {
+        format!(
+            "failed to read the answer of the API, {}: {}",
+            e,
+            text.chars()
+                .take(MAX_SHOWN_RESPONSE_LENGTH)
+                .collect::<String>()
+        )
+    })
+}
+
+/// How a request failed on the network
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum NetworkFailure {
+    /// The request never reached the provider, so sending it again is always safe
+    NeverSent,
+    /// The request may have reached the provider but its answer was lost, so sending it again may
+    /// generate and bill it twice
+    AnswerLost,
+}
+
+impl NetworkFailure {
+    fn of(e: &reqwest::Error) -> Self {
+        if e.is_connect() || e.is_builder() {
+            NetworkFailure::NeverSent
+        } else {
+            NetworkFailure::AnswerLost
+        }
+    }
+    /// Whether the request may be sent again. OpenAI's API does not answer a request sent twice
+    /// once, so requests whose answer was lost are not sent again.
+    fn may_retry(&self) -> bool {
+        *self == NetworkFailure::NeverSent
+    }
+}
+
+/// Sends the request, and sends it again when it failed on the network before reaching the
+/// provider
+async fn send_with_retries(request: impl Fn() -> RequestBuilder) -> Result<Response, String> {
+    let mut attempt = 0;
+    loop {
+        let e = match request().send().await {
+            Ok(response) => return Ok(response),
+            Err(e) => e,
+        };
+        let failure = NetworkFailure::of(&e);
+        if attempt == NETWORK_RETRIES || !failure.may_retry() {
+            return Err(match failure {
+                NetworkFailure::NeverSent => format!("the request never reached the API: {}", e),
+                NetworkFailure::AnswerLost => format!(
+                    "the request was sent but its answer was lost, so it may still be billed: {}",
+                    e
+                ),
+            });
+        }
+        attempt += 1;
+        eprintln!(
+            "{}",
+            format!(
+                "the request failed on the network, sending it again ({}/{}).",
+                attempt, NETWORK_RETRIES
+            )
+            .dimmed()
+        );
+        tokio::time::sleep(NETWORK_RETRY_DELAY * attempt as u32).await;
+    }
+}
+
+async fn send_chat(body: &OpenAIChatRequest, secret: &str) -> Result<String, String> {
+    let timeout = if capabilities(&body.model).reasoning {
+        REASONING_TIMEOUT
+    } else {
+        TIMEOUT
+    };
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let show_reasoning = REASONING_SETTINGS
+        .get()
+        .is_some_and(|settings| settings.show_reasoning);
+
+    // Providers now and then answer without any choice, which asking again usually fixes
+    for _ in 0..=EMPTY_RESPONSE_RETRIES {
+        let response = send_with_retries(|| {
+            client
+                .post(Url::parse(COMPLETION_URL).unwrap())
+                .bearer_auth(secret)
+                .json(body)
+        })
+        .await?;
+        let status = response.status();
+        let text = response.text().await.map_err(|e| e.to_string())?;
+        let parsed_resp: OpenAIChatResponse = read_response(status, &text)?;
+        record_usage(parsed_resp.usage.as_ref());
+
+        match parsed_resp.choices.into_iter().next() {
+            Some(choice) => return read_answer(choice, show_reasoning),
+            None => eprintln!(
+                "{}",
+                "the API answered without any choice, asking again.".dimmed()
+            ),
+        }
+    }
+
+    Err(format!(
+        "the API answered without any choice {} times in a row",
+        EMPTY_RESPONSE_RETRIES + 1
+    ))
+}
+
+/// Embeds every input with the embeddings API, in the same order
+pub async fn embed(inputs: &[String], secret: &str) -> Result<Vec<Vec<f32>>, String> {
+    #[cfg(any(test, feature = "e2e"))]
+    if std::env::var(MOCK_ANSWERS_ENV_VAR_KEY).is_ok() {
+        return Err("embeddings are not mocked".to_string());
+    }
+    wait_for_budget(
+        secret,
+        inputs.iter().map(|input| estimate_tokens(input)).sum(),
+    )
+    .await?;
+    let body = OpenAIEmbeddingsRequest {
+        model: EMBEDDING_MODEL,
+        input: inputs,
+    };
+    let client = reqwest::Client::new();
+    let response = send_with_retries(|| {
+        client
+            .post(Url::parse(EMBEDDINGS_URL).unwrap())
+            .bearer_auth(secret)
+            .json(&body)
+    })
+    .await?;
+    let status = response.status();
+    let text = response.text().await.map_err(|e| e.to_string())?;
+    let response: OpenAIEmbeddingsResponse = read_response(status, &text)?;
+    if response.data.len() != inputs.len() {
+        return Err(format!(
+            "asked for {} embeddings, got {}",
+            inputs.len(),
+            response.data.len()
+        ));
+    }
+
+    Ok(response
+        .data
+        .into_iter()
+        .map(|data| data.embedding)
+        .collect())
+}
+
+/// The models the API key may use. Listing them costs nothing, so it tells whether the key works.
+pub async fn list_models(secret: &str) -> Result<Vec<String>, String> {
+    let response = reqwest::Client::builder()
+        .timeout(TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?
+        .get(Url::parse(MODELS_URL).unwrap())
+        .bearer_auth(secret)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = response.status();
+    let text = response.text().await.map_err(|e| e.to_string())?;
+    let models: OpenAIModelsResponse = read_response(status, &text)?;
+
+    Ok(models.data.into_iter().map(|model| model.id).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    use reqwest::StatusCode;
+
+    use crate::openai::{
+        prompt_cache_key, read_answer, read_response, Message, NetworkFailure, OpenAIChatResponse,
+        OpenAIChatResponseChoice, OpenAIChatResponseMessage,
+    };
+
+    #[test]
+    fn prompt_cache_keys() {
+        let messages = |user: &str| {
+            vec![
+                Message::new("system", "You are a senior software engineer."),
+                Message::new("user", user),
+            ]
+        };
+
+        let key = prompt_cache_key("gpt-4o", &messages("fn a() {}"));
+        assert!(key.is_some());
+        assert_eq!(key, prompt_cache_key("gpt-4o", &messages("fn b() {}")));
+        assert_eq!(
+            None,
+            prompt_cache_key("gpt-3.5-turbo", &messages("fn a() {}"))
+        );
+        assert_eq!(
+            None,
+            prompt_cache_key("gpt-4o", &[Message::new("user", "fn a() {}")])
+        );
+    }
+
+    #[test]
+    fn messages_with_images() {
+        let mut message = Message::new("user", "Why is it slow?");
+        assert_eq!(
+            json!({ "role": "user", "content": "Why is it slow?" }),
+            message.to_request_message()
+        );
+
+        message.images = vec!["data:image/png;base64,iVBORw==".to_string()];
+        let request_message = message.to_request_message();
+        assert_eq!("text", request_message["content"][0]["type"]);
+        assert_eq!(
+            "data:image/png;base64,iVBORw==",
+            request_message["content"][1]["image_url"]["url"]
+        );
+    }
+
+    #[test]
+    fn answers_of_reasoning_models() {
+        let choice = |content: Option<&str>, finish_reason: &str| OpenAIChatResponseChoice {
+            message: OpenAIChatResponseMessage {
+                content: content.map(str::to_string),
+                reasoning_content: Some("The loop clones every item.".to_string()),
+                refusal: None,
+            },
+            finish_reason: Some(finish_reason.to_string()),
+        };
+
+        assert_eq!(
+            Ok("OPTIMAL".to_string()),
+            read_answer(choice(Some("OPTIMAL"), "stop"), true)
+        );
+        assert!(read_answer(choice(None, "length"), false)
+            .unwrap_err()
+            .contains("ran out of tokens"));
+        assert_eq!(
+            Err("the model answered nothing".to_string()),
+            read_answer(choice(Some(""), "stop"), false)
+        );
+
+        let message: OpenAIChatResponseMessage =
+            serde_json::from_str(r#"{"role": "assistant", "content": null, "reasoning": "..."}"#)
+                .unwrap();
+        assert_eq!(None, message.content);
+        assert_eq!(Some("...".to_string()), message.reasoning_content);
+    }
+
+    #[test]
+    fn malformed_responses() {
+        let quota = r#"{"error": {"message": "You exceeded your current quota.", "type": "insufficient_quota", "param": null, "code": "insufficient_quota"}}"#;
+        assert_eq!(
+            Err("the API answered 429 Too Many Requests: You exceeded your current quota. (insufficient_quota)".to_string()),
+            read_response::<OpenAIChatResponse>(StatusCode::TOO_MANY_REQUESTS, quota).map(|_| ())
+        );
+        assert_eq!(
+            Err("the API answered 502 Bad Gateway: upstream connect error".to_string()),
+            read_response::<OpenAIChatResponse>(
+                StatusCode::BAD_GATEWAY,
+                "upstream connect error\n"
+            )
+            .map(|_| ())
+        );
+        assert!(read_response::<OpenAIChatResponse>(
+            StatusCode::OK,
+            r#"{"object": "chat.completion"}"#
+        )
+        .unwrap_err()
+        .contains("chat.completion"));
+
+        let empty: OpenAIChatResponse =
+            read_response(StatusCode::OK, r#"{"choices": []}"#).unwrap();
+        assert!(empty.choices.is_empty());
+    }
+
+    #[tokio::test]
+    async fn network_failures() {
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        // Nothing listens on the port of a listener that is gone
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed_url = format!("http://{}", closed.local_addr().unwrap());
+        drop(closed);
+        let e = client.get(&closed_url).send().await.unwrap_err();
+        assert_eq!(NetworkFailure::NeverSent, NetworkFailure::of(&e));
+
+        // The request is read, but the connection is closed without an answer
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let _ = stream.read(&mut [0; 1024]);
+        });
+        let e = client.get(&url).send().await.unwrap_err();
+        assert_eq!(NetworkFailure::AnswerLost, NetworkFailure::of(&e));
+
+        assert!(NetworkFailure::NeverSent.may_retry());
+        assert!(!NetworkFailure::AnswerLost.may_retry());
+    }
+}
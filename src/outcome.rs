@@ -0,0 +1,128 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use clap::ValueEnum;
+
+/// What came out of going through a function, or through a file when it could not be read or
+/// parsed
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Optimal,
+    /// There was a suggestion, which was not applied
+    Suggested(String),
+    Applied(String),
+    /// There was a suggestion, which a check refused, like `--deny-unsafe`
+    Rejected(String),
+    ParseError(String),
+    ApiError(String),
+    /// The file could not be read, or is not supported
+    Skipped(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum OutcomeKind {
+    Optimal,
+    Suggested,
+    Applied,
+    Rejected,
+    ParseError,
+    ApiError,
+    Skipped,
+}
+
+impl Outcome {
+    pub fn kind(&self) -> OutcomeKind {
+        match self {
+            Outcome::Optimal => OutcomeKind::Optimal,
+            Outcome::Suggested(_) => OutcomeKind::Suggested,
+            Outcome::Applied(_) => OutcomeKind::Applied,
+            Outcome::Rejected(_) => OutcomeKind::Rejected,
+            Outcome::ParseError(_) => OutcomeKind::ParseError,
+            Outcome::ApiError(_) => OutcomeKind::ApiError,
+            Outcome::Skipped(_) => OutcomeKind::Skipped,
+        }
+    }
+    /// Why the function was rejected, skipped or failed
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            Outcome::Rejected(reason)
+            | Outcome::ParseError(reason)
+            | Outcome::ApiError(reason)
+            | Outcome::Skipped(reason) => Some(reason),
+            _ => None,
+        }
+    }
+    pub fn suggestion(&self) -> Option<&str> {
+        match self {
+            Outcome::Suggested(suggestion) | Outcome::Applied(suggestion) => Some(suggestion),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let kind = self.kind().to_possible_value().unwrap();
+        match self.reason() {
+            Some(reason) => write!(f, "{}: {}", kind.get_name(), reason),
+            None => write!(f, "{}", kind.get_name()),
+        }
+    }
+}
+
+/// How many times each kind of outcome came up during a batch run
+#[derive(Default, Debug)]
+pub struct OutcomeSummary {
+    counts: BTreeMap<OutcomeKind, usize>,
+}
+
+impl OutcomeSummary {
+    pub fn record(&mut self, outcome: &Outcome) {
+        *self.counts.entry(outcome.kind()).or_default() += 1;
+    }
+    pub fn count(&self, kind: OutcomeKind) -> usize {
+        self.counts.get(&kind).copied().unwrap_or_default()
+    }
+    /// Whether any of the given kinds of outcome came up
+    pub fn fails_on(&self, kinds: &[OutcomeKind]) -> bool {
+        kinds.iter().any(|kind| self.count(*kind) > 0)
+    }
+    pub fn render(&self) -> String {
+        let counts: Vec<String> = self
+            .counts
+            .iter()
+            .map(|(kind, count)| {
+                format!("{} {}", count, kind.to_possible_value().unwrap().get_name())
+            })
+            .collect();
+
+        if counts.is_empty() {
+            "nothing to go through".to_string()
+        } else {
+            counts.join(", ")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::outcome::{Outcome, OutcomeKind, OutcomeSummary};
+
+    #[test]
+    fn summary() {
+        let mut summary = OutcomeSummary::default();
+        summary.record(&Outcome::Optimal);
+        summary.record(&Outcome::Suggested("fn greet() {}".to_string()));
+        summary.record(&Outcome::ApiError("timed out".to_string()));
+        summary.record(&Outcome::Optimal);
+
+        assert_eq!("2 optimal, 1 suggested, 1 api-error", summary.render());
+        assert!(summary.fails_on(&[OutcomeKind::ParseError, OutcomeKind::ApiError]));
+        assert!(!summary.fails_on(&[OutcomeKind::ParseError, OutcomeKind::Skipped]));
+        assert_eq!("nothing to go through", OutcomeSummary::default().render());
+        assert_eq!(
+            "skipped: failed to read src/lib.rs",
+            Outcome::Skipped("failed to read src/lib.rs".to_string()).to_string()
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use std::fs::read_to_string;
+
+use colored::*;
+
+use crate::extract::Selection;
+use crate::goal::{render_suspects, Goal};
+use crate::helpers::tree_sitter::{node_value, parse_with_thread_parser};
+use crate::openai::{complete, Prompt};
+use crate::supported_languages::supported_language::{detect_language, explain_not_found};
+
+/// The code with the numbers of its lines in the file, so that the model can refer to them
+fn number_lines(code: &str, first_line: usize) -> String {
+    code.lines()
+        .enumerate()
+        .map(|(index, line)| format!("{:>4} | {}", first_line + index, line))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn draft_panic_paths_instructions(language: &str, numbered_code: &str, suspects: &str) -> Prompt {
+    let suspects = if suspects.is_empty() {
+        "".to_string()
+    } else {
+        format!(
+            "\n\nThese expressions were found ahead of time, and may be part of them:\n{}",
+            suspects
+        )
+    };
+
+    Prompt {
+        system: r#"You are a senior software engineer reviewing code for the ways it can panic or throw.
+
+Strictly adhere to the following instructions:
+1. List every way the code can panic or throw, like unwrapping None or Err, indexing out of bounds, dividing by zero, overflowing or calling something that does.
+2. Write each of them on its own line, as "- line <number>: `<expression>`, <when it happens>", using the line numbers the code is given with.
+3. Respond only with the list, and no commentary or explanations.
+4. If the code can never panic or throw, respond with "NONE""#
+            .to_string(),
+        user: format!(
+            "List the panic and exception paths of the following {} code:\n{}{}",
+            language, numbered_code, suspects
+        ),
+    }
+}
+
+/// The findings of the answer, one per line, leaving out anything that does not refer to a line
+fn parse_panic_paths(answer: &str) -> Vec<String> {
+    answer
+        .lines()
+        .map(|line| line.trim().trim_start_matches(['-', '*']).trim())
+        .filter(|line| {
+            line.strip_prefix("line ")
+                .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Has the model list the ways the selected function can panic or throw, pointing out the ones
+/// found in the tree, and prints them by line
+pub async fn run_panic_paths(
+    file_path: &str,
+    selection: &Selection,
+    model: &str,
+    secret: &str,
+) -> Result<(), String> {
+    let source_file =
+        read_to_string(file_path).map_err(|e| format!("failed to read {}: {}", file_path, e))?;
+    let supported_language = detect_language(file_path)?;
+    let tree = parse_with_thread_parser(&source_file, supported_language.language())
+        .ok_or("failed to parse the file")?;
+    let function_node = supported_language
+        .find_correct_node(
+            &source_file,
+            &tree,
+            &selection.parent_identifier,
+            &selection.function_identifier,
+            &selection.kind,
+        )
+        .map_err(|e| explain_not_found(supported_language.as_ref(), e))?;
+    let suspects =
+        Goal::PanicFreedom.find_suspects(supported_language.as_ref(), &source_file, function_node);
+    let numbered_code = number_lines(
+        node_value(&source_file, function_node),
+        function_node.start_position().row + 1,
+    );
+    let prompt = draft_panic_paths_instructions(
+        &supported_language.to_string(),
+        &numbered_code,
+        &render_suspects(&suspects),
+    );
+
+    let panic_paths = parse_panic_paths(&complete(model, &prompt, secret).await?);
+    if panic_paths.is_empty() {
+        println!("{}", "no panic or exception paths found.".green());
+    } else {
+        println!("{}", format!("{}:", file_path).bold());
+        for panic_path in panic_paths {
+            println!("  {}", panic_path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::panics::{number_lines, parse_panic_paths};
+
+    #[test]
+    fn panic_path_answers() {
+        assert_eq!(
+            "  12 | fn first(v: &[u8]) -> u8 {\n  13 |     v[0]\n  14 | }",
+            number_lines("fn first(v: &[u8]) -> u8 {\n    v[0]\n}", 12)
+        );
+
+        let answer = "Here they are:\n- line 13: `v[0]`, when `v` is empty\n* line 14: `x / y`, when `y` is 0\n- lines are fine";
+        assert_eq!(
+            vec![
+                "line 13: `v[0]`, when `v` is empty",
+                "line 14: `x / y`, when `y` is 0"
+            ],
+            parse_panic_paths(answer)
+        );
+        assert!(parse_panic_paths("NONE").is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+use tree_sitter::Node;
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::goal::Suspect;
+use crate::helpers::tree_sitter::node_value;
+
+const BLOCK_KINDS: &[&str] = &["block", "statement_block"];
+
+// Statements after which nothing in the same block runs
+const TERMINATOR_KINDS: &[&str] = &[
+    "return_statement",
+    "throw_statement",
+    "break_statement",
+    "continue_statement",
+    // Rust, within an expression statement
+    "return_expression",
+    "break_expression",
+    "continue_expression",
+];
+
+/// What the model is told about the findings of the pre-pass
+pub const DEAD_CODE_HEADING: &str = "These were found ahead of time, address them:";
+
+// The function node holding the parameters, which for javascript variables lies in their value
+fn innermost_function(function_node: Node) -> Option<Node> {
+    traverse(function_node.walk(), Order::Pre).find(|node| {
+        node.child_by_field_name("parameters").is_some()
+            || node.child_by_field_name("parameter").is_some()
+    })
+}
+
+fn parameter_names<'a>(source_file: &'a str, function_node: Node<'a>) -> Vec<Node<'a>> {
+    let mut names = vec![];
+    if let Some(parameter) = function_node.child_by_field_name("parameter") {
+        names.push(parameter);
+    }
+    let Some(parameters) = function_node.child_by_field_name("parameters") else {
+        return names;
+    };
+    let mut cursor = parameters.walk();
+    for parameter in parameters.named_children(&mut cursor) {
+        if parameter.kind() == "identifier" {
+            names.push(parameter);
+            continue;
+        }
+        // Go declares several parameters of the same type at once, as in `a, b int`
+        let mut cursor = parameter.walk();
+        names.extend(parameter.children_by_field_name("name", &mut cursor));
+        names.extend(
+            ["pattern", "left"]
+                .iter()
+                .find_map(|field| parameter.child_by_field_name(field)),
+        );
+    }
+    names.retain(|name| {
+        name.kind() == "identifier" && !node_value(source_file, *name).starts_with('_')
+    });
+
+    names
+}
+
+fn is_terminator(statement: Node) -> bool {
+    TERMINATOR_KINDS.contains(&statement.kind())
+        || (statement.kind() == "expression_statement"
+            && statement
+                .named_child(0)
+                .is_some_and(|expression| TERMINATOR_KINDS.contains(&expression.kind())))
+}
+
+/// Parameters the function never uses, and statements that can never run, as they follow a
+/// `return`, `break`, `continue` or `throw` in the same block
+pub fn find_dead_code(source_file: &str, function_node: Node) -> Vec<Suspect> {
+    let mut findings = vec![];
+    if let Some(function) = innermost_function(function_node) {
+        if let Some(body) = function.child_by_field_name("body") {
+            for name in parameter_names(source_file, function) {
+                let name_value = node_value(source_file, name);
+                let is_used = traverse(body.walk(), Order::Pre).any(|node| {
+                    node.kind() == "identifier" && node_value(source_file, node) == name_value
+                });
+                if !is_used {
+                    findings.push(Suspect::new(
+                        source_file,
+                        name,
+                        Some("a parameter that is never used".to_string()),
+                    ));
+                }
+            }
+        }
+    }
+
+    for block in
+        traverse(function_node.walk(), Order::Pre).filter(|node| BLOCK_KINDS.contains(&node.kind()))
+    {
+        let mut cursor = block.walk();
+        let unreachable = block
+            .named_children(&mut cursor)
+            .filter(|statement| !statement.kind().contains("comment"))
+            .skip_while(|statement| !is_terminator(*statement))
+            .nth(1);
+        if let Some(statement) = unreachable {
+            findings.push(Suspect::new(
+                source_file,
+                statement,
+                Some("can never run".to_string()),
+            ));
+        }
+    }
+    findings.sort_by_key(|finding| finding.line);
+
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::tree_sitter::parse_source_with_language;
+    use crate::prepass::find_dead_code;
+
+    #[test]
+    fn dead_code() {
+        let source = r#"fn greet(name: &str, _unused: u8, loud: bool) -> String {
+    if name.is_empty() {
+        return String::new();
+        println!("unreachable");
+    }
+    format!("hello {}", name)
+}"#;
+        let tree = parse_source_with_language(source, tree_sitter_rust::language());
+        let findings: Vec<String> = find_dead_code(source, tree.root_node())
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            vec![
+                "line 1: `loud`, a parameter that is never used",
+                "line 4: `println!(\"unreachable\");`, can never run",
+            ],
+            findings
+        );
+
+        let source = "function greet(name, loud = false) {\n    return `hello ${name}`;\n}\n";
+        let tree = parse_source_with_language(source, tree_sitter_javascript::language());
+        let findings: Vec<usize> = find_dead_code(source, tree.root_node())
+            .iter()
+            .map(|finding| finding.line)
+            .collect();
+        assert_eq!(vec![1], findings);
+
+        let source = "func add(a, b int, c int) int {\n\treturn a + b\n}\n";
+        let tree = parse_source_with_language(source, tree_sitter_go::language());
+        let findings: Vec<String> = find_dead_code(source, tree.root_node())
+            .iter()
+            .map(|finding| finding.expression.clone())
+            .collect();
+        assert_eq!(vec!["c"], findings);
+    }
+}
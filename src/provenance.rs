@@ -0,0 +1,203 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::history::date;
+
+/// The comment left after applied suggestions with `--provenance-comment`, unless `.senior.toml`
+/// overrides it for the language. `{model}` and `{date}` are replaced by those of the suggestion.
+pub fn default_provenance_template(line_comment: &str) -> String {
+    format!("{} optimised by senior ({{model}}, {{date}})", line_comment)
+}
+
+/// The comment left after the functions `senior implement` writes, which are always labeled
+pub fn implemented_provenance_template(line_comment: &str) -> String {
+    format!(
+        "{} implemented by senior ({{model}}, {{date}})",
+        line_comment
+    )
+}
+
+/// The comment, for a suggestion of the model applied today
+pub fn render_provenance_comment(template: &str, model: &str) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+
+    template
+        .replace("{model}", model)
+        .replace("{date}", &date(timestamp))
+}
+
+/// How many bytes a comment left by an earlier suggestion takes up at the start of the text, on
+/// the same line, so that applying another suggestion replaces it rather than piling up. Comments
+/// are told apart by the text of the template before its first placeholder.
+pub fn stale_provenance_length(text_after: &str, template: &str) -> usize {
+    let marker = template.split('{').next().unwrap_or_default().trim();
+    let line = &text_after[..text_after.find('\n').unwrap_or(text_after.len())];
+    let line = line.trim_end_matches('\r');
+    if marker.is_empty() || !line.trim_start().starts_with(marker) {
+        return 0;
+    }
+
+    line.len()
+}
+
+/// Where the replacement of a node goes along with the comment, as the bytes of the source file
+/// to replace and what replaces them. The comment ends the last line of the node if nothing
+/// follows the node on that line, and goes on a line of its own above the node otherwise, so that
+/// it comments nothing out, like the `,` after an object member or the `;` after a C++ lambda. A
+/// comment an earlier suggestion left in either place is replaced.
+pub fn place_provenance_comment(
+    source_file: &str,
+    start_byte: usize,
+    end_byte: usize,
+    replacement: &str,
+    comment: &str,
+    template: &str,
+) -> (usize, usize, String) {
+    let text_after = &source_file[end_byte..];
+    let rest_of_line = &text_after[..text_after.find('\n').unwrap_or(text_after.len())];
+    let stale_after = stale_provenance_length(text_after, template);
+    if stale_after > 0 || rest_of_line.trim().is_empty() {
+        return (
+            start_byte,
+            end_byte + stale_after,
+            format!("{} {}", replacement, comment),
+        );
+    }
+
+    let line_start = source_file[..start_byte]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let line = &source_file[line_start..];
+    let indentation = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+    let previous_line_start = source_file[..line_start.saturating_sub(1)]
+        .rfind('\n')
+        .map_or(0, |newline| newline + 1);
+    let stale_before = line_start > 0
+        && stale_provenance_length(&source_file[previous_line_start..line_start], template) > 0;
+    let start = if stale_before {
+        previous_line_start
+    } else {
+        line_start
+    };
+
+    (
+        start,
+        end_byte,
+        format!(
+            "{}{}\n{}{}",
+            indentation,
+            comment,
+            &source_file[line_start..start_byte],
+            replacement
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::tree_sitter::parse_source_with_language;
+    use crate::provenance::{
+        default_provenance_template, place_provenance_comment, render_provenance_comment,
+        stale_provenance_length,
+    };
+
+    const COMMENT: &str = "// optimised by senior (gpt-4o, 2024-06-01)";
+
+    // The source with the node, found by its code, replaced along with the comment
+    fn replace(source: &str, node: &str, replacement: &str) -> String {
+        let start_byte = source.find(node).unwrap();
+        let (start, end, new_text) = place_provenance_comment(
+            source,
+            start_byte,
+            start_byte + node.len(),
+            replacement,
+            COMMENT,
+            &default_provenance_template("//"),
+        );
+
+        format!("{}{}{}", &source[..start], new_text, &source[end..])
+    }
+
+    #[test]
+    fn provenance_comments() {
+        let template = default_provenance_template("//");
+        let comment = render_provenance_comment(&template, "gpt-4o");
+        assert!(comment.starts_with("// optimised by senior (gpt-4o, 20"));
+
+        let text_after = " // optimised by senior (gpt-4, 2024-06-01)\nfn next() {}";
+        assert_eq!(43, stale_provenance_length(text_after, &template));
+        assert_eq!(0, stale_provenance_length(" // TODO\n", &template));
+        assert_eq!(
+            0,
+            stale_provenance_length("\n// optimised by senior", &template)
+        );
+    }
+
+    #[test]
+    fn comments_at_the_end_of_the_line() {
+        let source = "fn greet() {\n    println!(\"hi\");\n}\n\nfn main() {}\n";
+        let greeted = replace(
+            source,
+            "fn greet() {\n    println!(\"hi\");\n}",
+            "fn greet() {}",
+        );
+        assert_eq!(
+            format!("fn greet() {{}} {}\n\nfn main() {{}}\n", COMMENT),
+            greeted
+        );
+        // Applying again replaces the comment
+        assert_eq!(greeted, replace(&greeted, "fn greet() {}", "fn greet() {}"));
+    }
+
+    #[test]
+    fn javascript_object_method() {
+        let source = "const handlers = {\n  greet(name) {\n    return \"hi \" + name;\n  },\n  shout() {},\n};\n";
+        let method = "greet(name) {\n    return \"hi \" + name;\n  }";
+        let replaced = replace(
+            source,
+            method,
+            "greet(name) {\n    return `hi ${name}`;\n  }",
+        );
+
+        assert_eq!(
+            format!(
+                "const handlers = {{\n  {}\n  greet(name) {{\n    return `hi ${{name}}`;\n  }},\n  shout() {{}},\n}};\n",
+                COMMENT
+            ),
+            replaced
+        );
+        let tree = parse_source_with_language(&replaced, tree_sitter_javascript::language());
+        assert!(!tree.root_node().has_error());
+        // Applying again replaces the comment above
+        assert_eq!(
+            replaced,
+            replace(
+                &replaced,
+                "greet(name) {\n    return `hi ${name}`;\n  }",
+                "greet(name) {\n    return `hi ${name}`;\n  }"
+            )
+        );
+    }
+
+    #[test]
+    fn cpp_lambda() {
+        let source =
+            "int main() {\n    auto g = [](int x) { return x * 2; };\n    return g(1);\n}\n";
+        let replaced = replace(
+            source,
+            "[](int x) { return x * 2; }",
+            "[](int x) { return x << 1; }",
+        );
+
+        assert_eq!(
+            format!(
+                "int main() {{\n    {}\n    auto g = [](int x) {{ return x << 1; }};\n    return g(1);\n}}\n",
+                COMMENT
+            ),
+            replaced
+        );
+        let tree = parse_source_with_language(&replaced, tree_sitter_cpp::language());
+        assert!(!tree.root_node().has_error());
+    }
+}
@@ -0,0 +1,209 @@
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::Path;
+
+use clap::ValueEnum;
+use colored::*;
+use serde::{Deserialize, Serialize};
+
+use crate::color::Palette;
+use crate::edit::{parse_reports, Suggestion};
+use crate::git;
+
+pub const QUEUE_FILE_PATH: &str = ".senior/queue.json";
+
+#[derive(Serialize, Deserialize, ValueEnum, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+/// A suggestion waiting for a reviewer, or one they went through
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct QueuedSuggestion {
+    pub id: usize,
+    pub file: String,
+    pub suggestion: Suggestion,
+    pub status: QueueStatus,
+    /// Who approved or rejected the suggestion, if git knows them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reviewer: Option<String>,
+}
+
+/// The queued suggestions, or none if nothing was ever queued
+pub fn load_queue(queue_path: &Path) -> Result<Vec<QueuedSuggestion>, String> {
+    if !queue_path.exists() {
+        return Ok(vec![]);
+    }
+    let queue = read_to_string(queue_path)
+        .map_err(|e| format!("failed to read {}: {}", queue_path.display(), e))?;
+
+    serde_json::from_str(&queue)
+        .map_err(|e| format!("{} is not a queue: {}", queue_path.display(), e))
+}
+
+fn save_queue(queue_path: &Path, queue: &[QueuedSuggestion]) -> Result<(), String> {
+    if let Some(parent) = queue_path.parent() {
+        create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let queue = serde_json::to_string_pretty(queue).map_err(|e| e.to_string())?;
+
+    write(queue_path, queue).map_err(|e| format!("failed to write {}: {}", queue_path.display(), e))
+}
+
+/// Queues the suggestions, skipping those already pending, as CI may report the same ones run
+/// after run. Returns the ids they were given.
+pub fn enqueue(
+    queue: &mut Vec<QueuedSuggestion>,
+    suggestions: Vec<(String, Suggestion)>,
+) -> Vec<usize> {
+    let mut ids = vec![];
+    for (file, suggestion) in suggestions {
+        let pending = queue.iter().any(|queued| {
+            queued.status == QueueStatus::Pending
+                && queued.file == file
+                && queued.suggestion == suggestion
+        });
+        if pending {
+            continue;
+        }
+        let id = queue.iter().map(|queued| queued.id).max().unwrap_or(0) + 1;
+        queue.push(QueuedSuggestion {
+            id,
+            file,
+            suggestion,
+            status: QueueStatus::Pending,
+            reviewer: None,
+        });
+        ids.push(id);
+    }
+
+    ids
+}
+
+fn pending_suggestion(
+    queue: &mut [QueuedSuggestion],
+    id: usize,
+) -> Result<&mut QueuedSuggestion, String> {
+    let queued = queue
+        .iter_mut()
+        .find(|queued| queued.id == id)
+        .ok_or_else(|| format!("no suggestion has the id {}", id))?;
+    if queued.status != QueueStatus::Pending {
+        return Err(format!(
+            "suggestion {} was {} already",
+            id,
+            queued.status.to_possible_value().unwrap().get_name()
+        ));
+    }
+
+    Ok(queued)
+}
+
+/// Queues the suggestions of reports written with `--output-format json`
+pub fn run_queue_add(queue_path: &Path, reports_path: &Path) -> Result<Vec<usize>, String> {
+    let reports = read_to_string(reports_path)
+        .map_err(|e| format!("failed to read {}: {}", reports_path.display(), e))?;
+    let mut queue = load_queue(queue_path)?;
+    let ids = enqueue(&mut queue, parse_reports(&reports)?);
+    save_queue(queue_path, &queue)?;
+
+    Ok(ids)
+}
+
+/// Every queued suggestion with the given status, with its diff
+pub fn render_queue(queue: &[QueuedSuggestion], status: QueueStatus, palette: Palette) -> String {
+    queue
+        .iter()
+        .filter(|queued| queued.status == status)
+        .map(|queued| {
+            let mut header = format!("#{} {}", queued.id, queued.file);
+            if let Some(reviewer) = &queued.reviewer {
+                header.push_str(&format!(" ({})", reviewer));
+            }
+            format!(
+                "{}\n{}",
+                header.bold(),
+                queued.suggestion.preview_diff(palette)
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Applies the pending suggestion to its file, and commits the file alone with `commit`, so that
+/// the commit can be pushed as a pull request
+pub fn run_queue_approve(queue_path: &Path, id: usize, commit: bool) -> Result<(), String> {
+    let mut queue = load_queue(queue_path)?;
+    let queued = pending_suggestion(&mut queue, id)?;
+    let source_file = read_to_string(&queued.file)
+        .map_err(|e| format!("failed to read {}: {}", queued.file, e))?;
+    let applied = queued.suggestion.apply_to(&source_file)?;
+    write(&queued.file, applied).map_err(|e| e.to_string())?;
+    if commit {
+        git::commit(
+            &[&queued.file],
+            &format!("Apply suggestion {} of senior to {}", id, queued.file),
+        )?;
+    }
+    queued.status = QueueStatus::Approved;
+    queued.reviewer = git::user().ok();
+    println!(
+        "{}",
+        format!("applied suggestion {} to {}", id, queued.file).green()
+    );
+
+    save_queue(queue_path, &queue)
+}
+
+/// Marks the pending suggestion as rejected, leaving its file as it is
+pub fn run_queue_reject(queue_path: &Path, id: usize) -> Result<(), String> {
+    let mut queue = load_queue(queue_path)?;
+    let queued = pending_suggestion(&mut queue, id)?;
+    queued.status = QueueStatus::Rejected;
+    queued.reviewer = git::user().ok();
+
+    save_queue(queue_path, &queue)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::edit::Suggestion;
+    use crate::helpers::tree_sitter::parse_source_with_language;
+    use crate::queue::{enqueue, load_queue, run_queue_approve, run_queue_reject, QueueStatus};
+
+    #[test]
+    fn reviewed_suggestions() {
+        let dir = std::env::temp_dir().join(format!("senior-queue-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("greet.rs");
+        let queue_path = dir.join("queue.json");
+        let source = "fn greet() {\n    println!(\"hi\");\n}\n";
+        std::fs::write(&file, source).unwrap();
+        let tree = parse_source_with_language(source, tree_sitter_rust::language());
+        let function = tree.root_node().child(0).unwrap();
+        let suggestion = Suggestion::new(source, &function.range(), "fn greet() {}", "rust");
+        let file = file.to_string_lossy().to_string();
+
+        let mut queue = vec![];
+        let suggestions = vec![
+            (file.clone(), suggestion.clone()),
+            (file.clone(), suggestion),
+        ];
+        assert_eq!(vec![1], enqueue(&mut queue, suggestions.clone()));
+        assert!(enqueue(&mut queue, suggestions.clone()).is_empty());
+        std::fs::write(&queue_path, serde_json::to_string(&queue).unwrap()).unwrap();
+
+        run_queue_approve(&queue_path, 1, false).unwrap();
+        assert_eq!("fn greet() {}\n", std::fs::read_to_string(&file).unwrap());
+        assert!(run_queue_approve(&queue_path, 1, false).is_err());
+        assert!(run_queue_reject(&queue_path, 2).is_err());
+
+        let mut queue = load_queue(&queue_path).unwrap();
+        assert_eq!(QueueStatus::Approved, queue[0].status);
+        assert_eq!(vec![2], enqueue(&mut queue, suggestions));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
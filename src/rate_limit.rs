@@ -0,0 +1,247 @@
+use std::fs::{create_dir_all, metadata, read_to_string, remove_file, write, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// The budgets are per minute
+const WINDOW: Duration = Duration::from_secs(60);
+/// A lock held for longer was left behind by a process that died
+const STALE_LOCK_AGE: Duration = Duration::from_secs(10);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(20);
+
+static RATE_LIMITS: OnceLock<RateLimits> = OnceLock::new();
+
+/// How many requests and tokens a minute every senior process sharing an API key may send
+/// together
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RateLimits {
+    pub requests_per_minute: Option<usize>,
+    pub tokens_per_minute: Option<usize>,
+}
+
+impl RateLimits {
+    fn is_unlimited(&self) -> bool {
+        self.requests_per_minute.is_none() && self.tokens_per_minute.is_none()
+    }
+}
+
+/// Sets the limits every later request waits for
+pub fn set_rate_limits(rate_limits: RateLimits) {
+    let _ = RATE_LIMITS.set(rate_limits);
+}
+
+/// A request sent in the last minute, by any process
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct SentRequest {
+    /// Milliseconds since the Unix epoch
+    sent_at: u64,
+    tokens: usize,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+/// How long to wait before a request of that many tokens fits in the budgets, if at all. The
+/// requests sent before the last minute are dropped.
+fn wait_time(
+    sent_requests: &mut Vec<SentRequest>,
+    now: u64,
+    rate_limits: &RateLimits,
+    tokens: usize,
+) -> Option<Duration> {
+    let window = WINDOW.as_millis() as u64;
+    sent_requests.retain(|request| request.sent_at + window > now);
+
+    // The oldest requests expire first, so the request fits once enough of them did
+    let mut waits = vec![];
+    if let Some(requests_per_minute) = rate_limits.requests_per_minute {
+        let excess = (sent_requests.len() + 1).saturating_sub(requests_per_minute.max(1));
+        if excess > 0 {
+            waits.push((sent_requests[excess - 1].sent_at + window).saturating_sub(now));
+        }
+    }
+    if let Some(tokens_per_minute) = rate_limits.tokens_per_minute {
+        // A request larger than the whole budget only has to wait for an empty minute
+        let tokens = tokens.min(tokens_per_minute);
+        let mut sent_tokens: usize = sent_requests.iter().map(|request| request.tokens).sum();
+        for request in sent_requests.iter() {
+            if sent_tokens + tokens <= tokens_per_minute {
+                break;
+            }
+            sent_tokens -= request.tokens;
+            if sent_tokens + tokens <= tokens_per_minute {
+                waits.push((request.sent_at + window).saturating_sub(now));
+            }
+        }
+    }
+
+    waits.into_iter().max().map(Duration::from_millis)
+}
+
+/// Where the processes using the API key keep their requests, without writing down the key itself
+fn ledger_path(secret: &str) -> PathBuf {
+    let hash: String = Sha256::digest(secret.as_bytes())
+        .iter()
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    std::env::temp_dir()
+        .join("senior-rate-limits")
+        .join(format!("{}.json", hash))
+}
+
+/// Holds the lock file of the ledger until dropped
+struct LedgerLock(PathBuf);
+
+impl Drop for LedgerLock {
+    fn drop(&mut self) {
+        let _ = remove_file(&self.0);
+    }
+}
+
+async fn lock_ledger(ledger: &Path) -> Result<LedgerLock, String> {
+    if let Some(parent) = ledger.parent() {
+        create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let lock = ledger.with_extension("lock");
+    loop {
+        // Creating a file that must not exist yet is atomic, so only one process gets it
+        match OpenOptions::new().write(true).create_new(true).open(&lock) {
+            Ok(_) => return Ok(LedgerLock(lock)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                let is_stale = metadata(&lock)
+                    .and_then(|metadata| metadata.modified())
+                    .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age > STALE_LOCK_AGE));
+                if is_stale {
+                    let _ = remove_file(&lock);
+                }
+                tokio::time::sleep(LOCK_RETRY_INTERVAL).await;
+            }
+            Err(e) => return Err(format!("failed to lock {}: {}", lock.display(), e)),
+        }
+    }
+}
+
+/// Waits until a request of that many tokens fits in the budgets shared by every process using
+/// the ledger, and records it there
+async fn wait_in_ledger(
+    ledger: &Path,
+    rate_limits: &RateLimits,
+    tokens: usize,
+) -> Result<(), String> {
+    loop {
+        let lock = lock_ledger(ledger).await?;
+        let mut sent_requests: Vec<SentRequest> = read_to_string(ledger)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        let now = now();
+        let wait = wait_time(&mut sent_requests, now, rate_limits, tokens);
+        if wait.is_none() {
+            sent_requests.push(SentRequest {
+                sent_at: now,
+                tokens,
+            });
+        }
+        let contents = serde_json::to_string(&sent_requests).map_err(|e| e.to_string())?;
+        write(ledger, contents)
+            .map_err(|e| format!("failed to write {}: {}", ledger.display(), e))?;
+        drop(lock);
+
+        match wait {
+            Some(wait) => tokio::time::sleep(wait).await,
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Waits until a request of that many tokens fits in the `--rpm` and `--tpm` budgets of the API
+/// key, which every senior process using it shares through a ledger in the temporary directory
+pub async fn wait_for_budget(secret: &str, tokens: usize) -> Result<(), String> {
+    match RATE_LIMITS
+        .get()
+        .filter(|rate_limits| !rate_limits.is_unlimited())
+    {
+        Some(rate_limits) => wait_in_ledger(&ledger_path(secret), rate_limits, tokens).await,
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::rate_limit::{wait_in_ledger, wait_time, RateLimits, SentRequest};
+
+    fn sent(sent_at: u64, tokens: usize) -> SentRequest {
+        SentRequest { sent_at, tokens }
+    }
+
+    #[test]
+    fn budgets() {
+        let rate_limits = RateLimits {
+            requests_per_minute: Some(2),
+            tokens_per_minute: Some(1000),
+        };
+
+        let mut sent_requests = vec![sent(0, 100), sent(30_000, 100)];
+        assert_eq!(
+            None,
+            wait_time(&mut sent_requests, 70_000, &rate_limits, 100)
+        );
+        assert_eq!(1, sent_requests.len());
+
+        let mut sent_requests = vec![sent(10_000, 100), sent(30_000, 100)];
+        assert_eq!(
+            Some(Duration::from_secs(10)),
+            wait_time(&mut sent_requests, 60_000, &rate_limits, 100)
+        );
+
+        let mut sent_requests = vec![sent(10_000, 600), sent(20_000, 300)];
+        let rate_limits = RateLimits {
+            requests_per_minute: None,
+            tokens_per_minute: Some(1000),
+        };
+        assert_eq!(
+            None,
+            wait_time(&mut sent_requests, 30_000, &rate_limits, 100)
+        );
+        assert_eq!(
+            Some(Duration::from_secs(40)),
+            wait_time(&mut sent_requests, 30_000, &rate_limits, 200)
+        );
+        assert_eq!(
+            Some(Duration::from_secs(50)),
+            wait_time(&mut sent_requests, 30_000, &rate_limits, 5000)
+        );
+    }
+
+    #[tokio::test]
+    async fn ledger() {
+        let ledger = std::env::temp_dir().join("senior-rate-limit-test/ledger.json");
+        let _ = std::fs::remove_file(&ledger);
+        let rate_limits = RateLimits {
+            requests_per_minute: Some(10),
+            tokens_per_minute: None,
+        };
+
+        for _ in 0..3 {
+            wait_in_ledger(&ledger, &rate_limits, 10).await.unwrap();
+        }
+        let contents = std::fs::read_to_string(&ledger).unwrap();
+        assert_eq!(
+            3,
+            serde_json::from_str::<Vec<serde_json::Value>>(&contents)
+                .unwrap()
+                .len()
+        );
+        assert!(!ledger.with_extension("lock").exists());
+    }
+}
@@ -0,0 +1,284 @@
+use std::process::Command;
+
+use regex::Regex;
+use tree_sitter::Node;
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::helpers::tree_sitter::node_value;
+use crate::literal::{split_quotes, unescape, Literal, STRING_KINDS};
+
+/// The system prompt of `--regex`, which optimises the regular expressions of a function rather
+/// than the code around them
+pub const REGEX_SYSTEM_PROMPT: &str = r#"You are a senior software engineer. Your task is to make the regular expression you are given faster and safer.
+
+Strictly adhere to the following instructions:
+1. It must match exactly the same strings, with the same capture groups in the same order.
+2. Avoid nested or overlapping repetitions, which backtrack catastrophically, and anchor it where that helps.
+3. Only use syntax that the regex engine of the language supports.
+4. Respond only with the pattern, and no commentary or explanations.
+"#;
+
+/// Functions and constructors whose first argument is a pattern, as written in the code, with or
+/// without what qualifies them
+const REGEX_CONSTRUCTORS: &[&str] = &[
+    "Regex::new",
+    "RegexBuilder::new",
+    "regexp.MustCompile",
+    "regexp.Compile",
+    "regexp.MatchString",
+    "RegExp",
+    "Pattern.compile",
+    "Pattern.matches",
+    "std::regex",
+];
+
+/// Languages whose regex engines run in linear time, and so never backtrack catastrophically
+const LINEAR_TIME_LANGUAGES: &[&str] = &["rust", "go"];
+
+fn is_regex_constructor(callee: &str) -> bool {
+    let callee = callee.trim().trim_start_matches("new ").trim();
+    REGEX_CONSTRUCTORS.iter().any(|constructor| {
+        callee == *constructor
+            || callee.ends_with(&format!("::{}", constructor))
+            || callee.ends_with(&format!(".{}", constructor))
+    })
+}
+
+// Whether the string literal is the first argument of a regex constructor, like
+// `Regex::new(r"\d+")`, `new RegExp("\\d+")` or `std::regex re("\\d+")`
+fn is_pattern_argument(source_file: &str, literal: Node) -> bool {
+    let Some(arguments) = literal
+        .parent()
+        .filter(|arguments| arguments.named_child(0) == Some(literal))
+    else {
+        return false;
+    };
+    let Some(call) = arguments.parent() else {
+        return false;
+    };
+    let callee = if call.kind() == "init_declarator" {
+        call.parent()
+            .and_then(|declaration| declaration.child_by_field_name("type"))
+            .map_or("", |type_node| node_value(source_file, type_node))
+    } else {
+        &source_file[call.start_byte()..arguments.start_byte()]
+    };
+
+    is_regex_constructor(callee)
+}
+
+/// The regular expressions in the node, as Javascript literals or as strings passed to a regex
+/// constructor, in the order they appear
+pub fn find_regex_literals<'a>(source_file: &str, node: Node<'a>) -> Vec<Node<'a>> {
+    traverse(node.walk(), Order::Pre)
+        .filter(|node| {
+            node.kind() == "regex"
+                || (STRING_KINDS.contains(&node.kind()) && is_pattern_argument(source_file, *node))
+        })
+        .collect()
+}
+
+/// Splits a Javascript regex literal, like `/\d+/g`, into its slashes and flags and its pattern,
+/// or any other literal into its quotes and what they hold
+pub fn split_regex_literal(literal: &str) -> Option<Literal> {
+    let Some(rest) = literal.strip_prefix('/') else {
+        return split_quotes(literal);
+    };
+    let closing_start = rest.rfind('/')?;
+
+    Some(Literal {
+        contents: rest[..closing_start].to_string(),
+        opening: "/".to_string(),
+        closing: rest[closing_start..].to_string(),
+    })
+}
+
+/// The pattern the regex engine sees, once the string literal it is in is unescaped
+pub fn pattern_of(language: &str, literal: &Literal) -> String {
+    let raw = literal.opening == "/"
+        || literal.opening.contains(['r', 'R'])
+        || (language == "go" && literal.opening == "`");
+    if raw {
+        literal.contents.clone()
+    } else {
+        unescape(&literal.contents)
+    }
+}
+
+// How long the quantifier the pattern starts with is, if it repeats without bound, like `*`, `+`
+// or `{2,}`, and otherwise 0
+fn unbounded_quantifier(pattern: &str) -> usize {
+    match pattern.chars().next() {
+        Some('*' | '+') => 1,
+        Some('{') => pattern
+            .find('}')
+            .filter(|end| pattern[..*end].ends_with(','))
+            .map_or(0, |end| end + 1),
+        _ => 0,
+    }
+}
+
+/// Repetitions of the pattern nested in other repetitions, like `(\w+\s?)*`, which take
+/// exponential time to fail to match in backtracking regex engines
+pub fn backtracking_risks(language: &str, pattern: &str) -> Vec<String> {
+    if LINEAR_TIME_LANGUAGES.contains(&language) {
+        return vec![];
+    }
+
+    let mut risks = vec![];
+    // Where each open group starts, and whether it repeats anything unboundedly
+    let mut groups: Vec<(usize, bool)> = vec![];
+    let mut in_class = false;
+    let mut characters = pattern.char_indices();
+    while let Some((index, character)) = characters.next() {
+        match character {
+            '\\' => {
+                characters.next();
+            }
+            '[' => in_class = true,
+            ']' => in_class = false,
+            _ if in_class => {}
+            '(' => groups.push((index, false)),
+            ')' => {
+                let Some((start, repeats)) = groups.pop() else {
+                    continue;
+                };
+                let quantifier = unbounded_quantifier(&pattern[index + 1..]);
+                if repeats && quantifier > 0 {
+                    risks.push(format!(
+                        "`{}` repeats a repetition",
+                        &pattern[start..index + 1 + quantifier]
+                    ));
+                }
+                // A group repeating anything makes the group around it repeat it too
+                if let Some(outer) = groups.last_mut() {
+                    outer.1 |= repeats || quantifier > 0;
+                }
+            }
+            _ => {
+                if unbounded_quantifier(&pattern[index..]) > 0 {
+                    if let Some(group) = groups.last_mut() {
+                        group.1 = true;
+                    }
+                }
+            }
+        }
+    }
+
+    risks
+}
+
+/// What the model is told about the literal, the function around it and how it may backtrack
+pub fn regex_instructions(
+    language: &str,
+    literal: &Literal,
+    function: &str,
+    risks: &[String],
+) -> String {
+    let mut instructions = format!(
+        "The pattern is the contents of a {} literal opened by `{}` and closed by `{}`, and must stay valid in it. It is used by this function:\n```{}\n{}\n```",
+        language, literal.opening, literal.closing, language, function
+    );
+    if !risks.is_empty() {
+        instructions.push_str("\nThese parts of it may backtrack catastrophically:\n- ");
+        instructions.push_str(&risks.join("\n- "));
+    }
+
+    instructions
+}
+
+/// Whether the pattern of the literal compiles, with the regex crate for Rust and Go, whose RE2
+/// syntax it shares, and with Node.js for Javascript and Typescript when it is installed. Other
+/// languages are not checked.
+pub fn validate_pattern(language: &str, literal: &Literal) -> Result<(), String> {
+    let pattern = pattern_of(language, literal);
+    match language {
+        "rust" | "go" => Regex::new(&pattern).map(|_| ()).map_err(|e| e.to_string()),
+        "javascript" | "jsx" | "typescript" | "tsx" => {
+            // Only regex literals have flags, constructors take them apart
+            let flags = if literal.opening == "/" {
+                literal.closing.trim_start_matches('/')
+            } else {
+                ""
+            };
+            let Ok(output) = Command::new("node")
+                .args([
+                    "-e",
+                    "new RegExp(process.argv[1], process.argv[2])",
+                    &pattern,
+                    flags,
+                ])
+                .output()
+            else {
+                return Ok(());
+            };
+            if output.status.success() {
+                return Ok(());
+            }
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Err(stderr
+                .lines()
+                .find(|line| line.contains("SyntaxError"))
+                .unwrap_or("the pattern does not compile")
+                .trim()
+                .to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::tree_sitter::{node_value, parse_source_with_language};
+    use crate::literal::split_quotes;
+    use crate::regexes::{
+        backtracking_risks, find_regex_literals, split_regex_literal, validate_pattern,
+    };
+
+    #[test]
+    fn regex_literals() {
+        let source = r#"function words(text) {
+  const email = new RegExp("^(\\w+\\.?)+@example\\.com$");
+  return text.split(/\s+/g).filter((word) => email.test(word) || word === "(a+)+");
+}"#;
+        let tree = parse_source_with_language(source, tree_sitter_javascript::language());
+        let literals: Vec<&str> = find_regex_literals(source, tree.root_node())
+            .into_iter()
+            .map(|literal| node_value(source, literal))
+            .collect();
+        assert_eq!(
+            vec!["\"^(\\\\w+\\\\.?)+@example\\\\.com$\"", "/\\s+/g"],
+            literals
+        );
+
+        let source = r#"fn digits(text: &str) -> Vec<&str> {
+    let pattern = regex::Regex::new(r"\d+").unwrap();
+    pattern.find_iter(text).map(|m| m.as_str()).collect()
+}"#;
+        let tree = parse_source_with_language(source, tree_sitter_rust::language());
+        assert_eq!(1, find_regex_literals(source, tree.root_node()).len());
+
+        let literal = split_regex_literal("/\\s+/gi").unwrap();
+        assert_eq!(
+            ("\\s+", "/gi"),
+            (literal.contents.as_str(), literal.closing.as_str())
+        );
+    }
+
+    #[test]
+    fn catastrophic_backtracking() {
+        assert_eq!(
+            vec!["`(\\w+\\.?)+` repeats a repetition"],
+            backtracking_risks("javascript", "^(\\w+\\.?)+@example\\.com$")
+        );
+        assert_eq!(
+            vec!["`((ab)*c)*` repeats a repetition"],
+            backtracking_risks("java", "((ab)*c)*")
+        );
+        assert!(backtracking_risks("javascript", "^[(a+)+]\\(a+\\)+$").is_empty());
+        assert!(backtracking_risks("rust", "(a+)+").is_empty());
+
+        assert!(validate_pattern("rust", &split_quotes("r\"\\d+\"").unwrap()).is_ok());
+        assert!(validate_pattern("go", &split_quotes("\"(\\\\d+\"").unwrap()).is_err());
+    }
+}
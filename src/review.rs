@@ -0,0 +1,190 @@
+use std::path::Path;
+
+use colored::*;
+
+use crate::config::{load_constraints, CONSTRAINTS_FILE_PATH};
+use crate::github::{parse_patch, GitHubClient, Review, ReviewComment};
+use crate::goal::{render_suspects, Suspect};
+use crate::helpers::tree_sitter::{node_value, parse_with_thread_parser};
+use crate::openai::{complete, Prompt};
+use crate::prepass::{find_dead_code, DEAD_CODE_HEADING};
+use crate::supported_languages::supported_language::detect_language;
+
+fn draft_review_instructions(
+    code: &str,
+    first_line: usize,
+    dead_code: &[Suspect],
+    constraints: &str,
+) -> Prompt {
+    let mut numbered_code = code
+        .lines()
+        .enumerate()
+        .map(|(offset, line)| format!("{}: {}", first_line + offset, line))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if !dead_code.is_empty() {
+        numbered_code = format!(
+            "{}\n\n{}\n{}",
+            numbered_code,
+            DEAD_CODE_HEADING,
+            render_suspects(dead_code)
+        );
+    }
+    if !constraints.is_empty() {
+        numbered_code = format!("{}\n\n{}", numbered_code, constraints);
+    }
+
+    Prompt {
+        system: r#"You are a senior software engineer reviewing a pull request.
+
+Your task is to review the function you are given, whose lines are prefixed by their line numbers.
+
+Strictly adhere to the following instructions:
+1. Only point out bugs, performance problems and readability issues worth fixing.
+2. Respond with one finding per line, formatted as "<line number>: <finding>".
+3. If there is nothing worth pointing out, respond with "LGTM"
+"#
+        .to_string(),
+        user: numbered_code,
+    }
+}
+
+/// Reads the `<line number>: <finding>` lines of an answer, ignoring everything else
+fn parse_findings(answer: &str) -> Vec<(usize, String)> {
+    answer
+        .lines()
+        .filter_map(|line| {
+            let (line_number, finding) = line.split_once(':')?;
+            let line_number = line_number.trim().parse().ok()?;
+            let finding = finding.trim();
+
+            (!finding.is_empty()).then(|| (line_number, finding.to_string()))
+        })
+        .collect()
+}
+
+/// Reviews every function the pull request changed, and posts the findings as inline comments.
+/// Findings on lines outside of the diff, which GitHub does not accept inline, go to the body of
+/// the review instead.
+pub async fn run_review(
+    github: &GitHubClient,
+    pr: u64,
+    model: &str,
+    secret: &str,
+    dry_run: bool,
+) -> Result<(), String> {
+    let head = github.pull_request_head(pr).await?;
+    let mut comments = vec![];
+    let mut outside_of_diff = vec![];
+    let mut reviewed_functions = 0;
+    let constraints = load_constraints(Path::new(CONSTRAINTS_FILE_PATH));
+
+    for file in github.pull_request_files(pr).await? {
+        let Some(patch) = &file.patch else {
+            continue;
+        };
+        if file.status == "removed" {
+            continue;
+        }
+        let Ok(supported_language) = detect_language(&file.filename) else {
+            continue;
+        };
+        let changed_lines = parse_patch(patch);
+        let source_file = github.file_contents(&file.filename, &head).await?;
+        let tree = parse_with_thread_parser(&source_file, supported_language.language())
+            .ok_or_else(|| format!("{}: failed to parse source file", file.filename))?;
+
+        // Nested functions are reviewed as part of their outermost function
+        let mut reviewed_up_to = 0;
+        for function_node in supported_language.find_all_functions(&tree) {
+            if function_node.end_byte() <= reviewed_up_to {
+                continue;
+            }
+            let first_line = function_node.start_position().row + 1;
+            let last_line = function_node.end_position().row + 1;
+            if changed_lines
+                .added
+                .range(first_line..=last_line)
+                .next()
+                .is_none()
+            {
+                continue;
+            }
+
+            reviewed_up_to = function_node.end_byte();
+            println!("{}", format!("{}:{}", file.filename, first_line).bold());
+            reviewed_functions += 1;
+            let prompt = draft_review_instructions(
+                node_value(&source_file, function_node),
+                first_line,
+                &find_dead_code(&source_file, function_node),
+                &constraints,
+            );
+            let answer = complete(model, &prompt, secret).await?;
+
+            for (line, finding) in parse_findings(&answer) {
+                println!("  {}: {}", line, finding);
+                if changed_lines.commentable.contains(&line) {
+                    comments.push(ReviewComment {
+                        path: file.filename.clone(),
+                        line,
+                        side: "RIGHT",
+                        body: finding,
+                    });
+                } else {
+                    outside_of_diff.push(format!("- `{}:{}`: {}", file.filename, line, finding));
+                }
+            }
+        }
+    }
+
+    let findings = comments.len() + outside_of_diff.len();
+    let mut body = format!(
+        "senior reviewed {} changed functions and has {} findings.",
+        reviewed_functions, findings
+    );
+    if !outside_of_diff.is_empty() {
+        body.push_str("\n\n");
+        body.push_str(&outside_of_diff.join("\n"));
+    }
+    println!("{}", body.green());
+
+    if !dry_run {
+        let review = Review {
+            commit_id: head,
+            event: "COMMENT",
+            body,
+            comments,
+        };
+        github.post_review(pr, &review).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::review::{draft_review_instructions, parse_findings};
+
+    #[test]
+    fn numbered_lines() {
+        let prompt =
+            draft_review_instructions("fn greet() {\n    println!(\"hello\");\n}", 7, &[], "");
+
+        assert_eq!(
+            "7: fn greet() {\n8:     println!(\"hello\");\n9: }",
+            prompt.user
+        );
+    }
+
+    #[test]
+    fn findings() {
+        let answer = "8: `println!` is slow in a loop\nLGTM\nnote: something else\n9:";
+
+        assert_eq!(
+            vec![(8, "`println!` is slow in a loop".to_string())],
+            parse_findings(answer)
+        );
+        assert!(parse_findings("LGTM").is_empty());
+    }
+}
@@ -0,0 +1,184 @@
+use std::env;
+use std::fmt::{Display, Formatter};
+use std::process::Command;
+use std::str::FromStr;
+
+/// Where the commands that check a suggestion, like the `post_apply` ones and the build of
+/// `senior fix`, run, so that they cannot change anything outside of the repository, or so that
+/// heavy ones run on another machine
+#[derive(Clone, Debug, PartialEq)]
+pub enum Sandbox {
+    /// A container of the image, with the repository mounted at the same path
+    Docker { image: String },
+    /// The host, reached over SSH, which the repository is copied to at the same path first
+    Ssh { host: String },
+}
+
+impl FromStr for Sandbox {
+    type Err = String;
+
+    fn from_str(sandbox: &str) -> Result<Self, Self::Err> {
+        match sandbox.split_once(':') {
+            Some(("docker", image)) if !image.is_empty() => Ok(Sandbox::Docker {
+                image: image.to_string(),
+            }),
+            Some(("ssh", host)) if !host.is_empty() => Ok(Sandbox::Ssh {
+                host: host.to_string(),
+            }),
+            _ => Err(format!(
+                "unknown sandbox `{}`, expected one like `docker:rust:1.79` or `ssh:user@builder`",
+                sandbox
+            )),
+        }
+    }
+}
+
+impl Display for Sandbox {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sandbox::Docker { image } => write!(f, "docker:{}", image),
+            Sandbox::Ssh { host } => write!(f, "ssh:{}", host),
+        }
+    }
+}
+
+/// How the messages about a command say where it ran
+pub fn describe_where(sandbox: Option<&Sandbox>) -> String {
+    sandbox.map_or("".to_string(), |sandbox| format!(" in {}", sandbox))
+}
+
+// Who owns the repository, so that what the container writes to it belongs to them rather than
+// to root
+#[cfg(unix)]
+fn repository_owner(directory: &str) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let metadata = std::fs::metadata(directory).ok()?;
+    Some(format!("{}:{}", metadata.uid(), metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn repository_owner(_directory: &str) -> Option<String> {
+    None
+}
+
+// The word quoted for a POSIX shell, which runs it as it is
+fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+impl Sandbox {
+    /// The words that run the command in the sandbox, from the given directory
+    pub fn wrap(&self, words: &[String], directory: &str) -> Vec<String> {
+        match self {
+            Sandbox::Docker { image } => {
+                let mut wrapped = vec![
+                    "docker".to_string(),
+                    "run".to_string(),
+                    "--rm".to_string(),
+                    "--volume".to_string(),
+                    format!("{}:{}", directory, directory),
+                    "--workdir".to_string(),
+                    directory.to_string(),
+                ];
+                if let Some(owner) = repository_owner(directory) {
+                    wrapped.extend(["--user".to_string(), owner]);
+                }
+                wrapped.push(image.clone());
+                wrapped.extend(words.iter().cloned());
+
+                wrapped
+            }
+            Sandbox::Ssh { host } => {
+                // The remote shell reads the command once more, so it is quoted twice
+                let remote_command = format!(
+                    "cd {} && {}",
+                    shell_quote(directory),
+                    words
+                        .iter()
+                        .map(|word| shell_quote(word))
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                );
+                // Only what changed is copied, and the git directory stays behind
+                let script = format!(
+                    "rsync -az --mkpath --exclude .git {}/ {}:{}/ && ssh {} {}",
+                    shell_quote(directory),
+                    shell_quote(host),
+                    shell_quote(directory),
+                    shell_quote(host),
+                    shell_quote(&remote_command)
+                );
+
+                vec!["sh".to_string(), "-c".to_string(), script]
+            }
+        }
+    }
+}
+
+/// A command of the words, run in the sandbox from the current directory if there is one
+pub fn sandboxed_command(words: &[String], sandbox: Option<&Sandbox>) -> Result<Command, String> {
+    let words = match sandbox {
+        Some(sandbox) => {
+            let directory = env::current_dir()
+                .map_err(|e| format!("failed to find the current directory: {}", e))?;
+            sandbox.wrap(words, &directory.to_string_lossy())
+        }
+        None => words.to_vec(),
+    };
+    let (program, args) = words.split_first().ok_or("the command is empty")?;
+    let mut command = Command::new(program);
+    command.args(args);
+
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sandbox::Sandbox;
+
+    #[test]
+    fn docker_sandbox() {
+        let sandbox: Sandbox = "docker:rust:1.79".parse().unwrap();
+        assert_eq!(
+            Sandbox::Docker {
+                image: "rust:1.79".to_string()
+            },
+            sandbox
+        );
+        assert!("podman:rust".parse::<Sandbox>().is_err());
+        assert!("docker:".parse::<Sandbox>().is_err());
+
+        let wrapped = sandbox.wrap(
+            &["cargo".to_string(), "test".to_string()],
+            "/nonexistent/shop",
+        );
+        assert_eq!(
+            "docker run --rm --volume /nonexistent/shop:/nonexistent/shop --workdir /nonexistent/shop rust:1.79 cargo test",
+            wrapped.join(" ")
+        );
+    }
+
+    #[test]
+    fn ssh_sandbox() {
+        let sandbox: Sandbox = "ssh:ci@builder".parse().unwrap();
+        assert_eq!(
+            Sandbox::Ssh {
+                host: "ci@builder".to_string()
+            },
+            sandbox
+        );
+        assert_eq!("ssh:ci@builder", sandbox.to_string());
+        assert!("ssh:".parse::<Sandbox>().is_err());
+
+        let wrapped = sandbox.wrap(
+            &["cargo".to_string(), "bench".to_string(), "it's".to_string()],
+            "/nonexistent/shop",
+        );
+        assert_eq!(vec!["sh", "-c"], wrapped[..2]);
+        assert_eq!(
+            r#"rsync -az --mkpath --exclude .git '/nonexistent/shop'/ 'ci@builder':'/nonexistent/shop'/ && ssh 'ci@builder' 'cd '\''/nonexistent/shop'\'' && '\''cargo'\'' '\''bench'\'' '\''it'\''\'\'''\''s'\'''"#,
+            wrapped[2]
+        );
+    }
+}
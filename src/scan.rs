@@ -0,0 +1,98 @@
+use std::fs::read_to_string;
+use std::path::Path;
+use std::sync::mpsc::channel;
+use std::thread;
+
+use clap::ValueEnum;
+use colored::*;
+use rayon::prelude::*;
+
+use crate::batch::{batch_files, BatchFilters};
+use crate::helpers::tree_sitter::parse_with_thread_parser;
+use crate::supported_languages::supported_language::{detect_language, FunctionKind};
+use crate::workspace::{find_packages, group_by_package};
+
+/// A function `--all` would go through
+struct ScannedFunction {
+    location: String,
+    kind: FunctionKind,
+    lines: usize,
+}
+
+fn scan_file(file_path: &str, filters: &BatchFilters) -> Result<Vec<ScannedFunction>, String> {
+    let supported_language = detect_language(file_path)?;
+    let source_file = read_to_string(file_path).map_err(|e| format!("{}: {}", file_path, e))?;
+    let tree = parse_with_thread_parser(&source_file, supported_language.language())
+        .ok_or_else(|| format!("{}: failed to parse source file", file_path))?;
+
+    Ok(filters
+        .find_targets(supported_language.as_ref(), file_path, &source_file, &tree)
+        .iter()
+        .map(|target| {
+            let function_node = tree
+                .root_node()
+                .descendant_for_byte_range(target.start_byte, target.end_byte)
+                .unwrap();
+
+            ScannedFunction {
+                location: format!("{}:{}", file_path, target.start_point.row + 1),
+                kind: supported_language.function_kind(&source_file, function_node),
+                lines: target.end_point.row - target.start_point.row + 1,
+            }
+        })
+        .collect())
+}
+
+/// Lists the functions `--all` would go through, grouped by workspace package, without asking
+/// the model about any of them. Files are parsed in parallel, and printed as soon as they are
+/// done, so the functions of a package are not necessarily in order.
+pub fn run_scan(
+    path: &Path,
+    changed: bool,
+    no_ignore: bool,
+    filters: &BatchFilters,
+) -> Result<(), String> {
+    let packages = find_packages(path);
+    let files = batch_files(path, changed, no_ignore)?;
+    let mut total_functions = 0;
+
+    for (package, files) in group_by_package(&packages, files) {
+        if !packages.is_empty() {
+            let package = package.unwrap_or_else(|| "(outside of any package)".to_string());
+            println!("{}", package.bold());
+        }
+
+        let (sender, receiver) = channel();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                files.par_iter().for_each_with(sender, |sender, file_path| {
+                    sender.send(scan_file(file_path, filters)).unwrap();
+                })
+            });
+
+            for scanned_file in receiver {
+                match scanned_file {
+                    Ok(functions) => {
+                        total_functions += functions.len();
+                        for function in functions {
+                            println!(
+                                "  {} {}",
+                                function.location,
+                                format!(
+                                    "{}, {} lines",
+                                    function.kind.to_possible_value().unwrap().get_name(),
+                                    function.lines
+                                )
+                                .dimmed()
+                            );
+                        }
+                    }
+                    Err(e) => eprintln!("{}", e.yellow()),
+                }
+            }
+        });
+    }
+    println!("{}", format!("{} functions", total_functions).green());
+
+    Ok(())
+}
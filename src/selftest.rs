@@ -0,0 +1,279 @@
+use clap::ValueEnum;
+use colored::*;
+
+use crate::helpers::tree_sitter::{node_value, parse_source_with_language};
+use crate::supported_languages::supported_language::{analyser, FunctionKind, Language};
+
+/// A function of a sample that its analyser must find, by its parent and name, and classify
+struct Construct {
+    kind: FunctionKind,
+    parent: Option<&'static str>,
+    function: &'static str,
+}
+
+const fn construct(
+    kind: FunctionKind,
+    parent: Option<&'static str>,
+    function: &'static str,
+) -> Construct {
+    Construct {
+        kind,
+        parent,
+        function,
+    }
+}
+
+/// The sample of every language, bundled into senior, with the constructs it holds. Kinds a
+/// language has no construct of are left out of its list, and shown as `-`.
+const SAMPLES: &[(Language, &str, &[Construct])] = &[
+    (
+        Language::Rust,
+        include_str!("../selftest_sources/rust.rs"),
+        &[
+            construct(FunctionKind::Function, None, "checkout"),
+            construct(FunctionKind::Method, Some("Cart"), "add"),
+            construct(FunctionKind::Getter, Some("Cart"), "total"),
+            construct(FunctionKind::Constructor, Some("Cart"), "new"),
+            construct(FunctionKind::Test, None, "checkout_is_free"),
+        ],
+    ),
+    (
+        Language::Go,
+        include_str!("../selftest_sources/go.go"),
+        &[
+            construct(FunctionKind::Function, None, "Checkout"),
+            construct(FunctionKind::Method, Some("Cart"), "Add"),
+            construct(FunctionKind::Getter, Some("Cart"), "Total"),
+            construct(FunctionKind::Constructor, None, "NewCart"),
+            construct(FunctionKind::Test, None, "TestCheckout"),
+        ],
+    ),
+    (
+        Language::JavaScript,
+        include_str!("../selftest_sources/javascript.js"),
+        &[
+            construct(FunctionKind::Function, None, "checkout"),
+            construct(FunctionKind::Method, Some("Cart"), "add"),
+            construct(FunctionKind::Getter, Some("Cart"), "count"),
+            construct(FunctionKind::Constructor, Some("Cart"), "constructor"),
+            construct(FunctionKind::Test, None, "isFree"),
+        ],
+    ),
+    (
+        Language::Jsx,
+        include_str!("../selftest_sources/jsx.jsx"),
+        &[
+            construct(FunctionKind::Function, None, "Checkout"),
+            construct(FunctionKind::Method, Some("Cart"), "render"),
+            construct(FunctionKind::Getter, Some("Cart"), "count"),
+            construct(FunctionKind::Constructor, Some("Cart"), "constructor"),
+            construct(FunctionKind::Test, None, "rendersButton"),
+        ],
+    ),
+    (
+        Language::TypeScript,
+        include_str!("../selftest_sources/typescript.ts"),
+        &[
+            construct(FunctionKind::Function, None, "checkout"),
+            construct(FunctionKind::Method, Some("Cart"), "add"),
+            construct(FunctionKind::Getter, Some("Cart"), "count"),
+            construct(FunctionKind::Constructor, Some("Cart"), "constructor"),
+            construct(FunctionKind::Test, None, "isFree"),
+        ],
+    ),
+    (
+        Language::Tsx,
+        include_str!("../selftest_sources/tsx.tsx"),
+        &[
+            construct(FunctionKind::Function, None, "Checkout"),
+            construct(FunctionKind::Method, Some("Cart"), "render"),
+            construct(FunctionKind::Getter, Some("Cart"), "count"),
+            construct(FunctionKind::Constructor, Some("Cart"), "constructor"),
+            construct(FunctionKind::Test, None, "rendersButton"),
+        ],
+    ),
+    (
+        Language::Java,
+        include_str!("../selftest_sources/java.java"),
+        &[
+            construct(FunctionKind::Function, Some("Cart"), "checkout"),
+            construct(FunctionKind::Method, Some("Cart"), "add"),
+            construct(FunctionKind::Getter, Some("Cart"), "getTotal"),
+            construct(FunctionKind::Constructor, Some("Cart"), "Cart"),
+            construct(FunctionKind::Test, Some("Cart"), "checkoutIsFree"),
+        ],
+    ),
+    (
+        Language::Cpp,
+        include_str!("../selftest_sources/cpp.cpp"),
+        &[
+            construct(FunctionKind::Function, None, "checkout"),
+            construct(FunctionKind::Method, Some("Cart"), "add"),
+            construct(FunctionKind::Getter, Some("Cart"), "total"),
+            construct(FunctionKind::Constructor, Some("Cart"), "Cart"),
+            construct(FunctionKind::Test, None, "TEST"),
+        ],
+    ),
+    (
+        Language::Bash,
+        include_str!("../selftest_sources/bash.sh"),
+        &[
+            construct(FunctionKind::Function, None, "checkout"),
+            construct(FunctionKind::Test, None, "test_checkout_is_free"),
+        ],
+    ),
+    (
+        Language::Scala,
+        include_str!("../selftest_sources/scala.scala"),
+        &[
+            construct(FunctionKind::Function, Some("Cart"), "checkout"),
+            construct(FunctionKind::Method, Some("Cart"), "add"),
+            construct(FunctionKind::Getter, Some("Cart"), "total"),
+            construct(FunctionKind::Constructor, Some("Cart"), "this"),
+            construct(FunctionKind::Test, Some("CartTest"), "checkoutIsFree"),
+        ],
+    ),
+    (
+        Language::Erlang,
+        include_str!("../selftest_sources/erlang.erl"),
+        &[
+            construct(FunctionKind::Function, Some("cart"), "checkout/1"),
+            construct(FunctionKind::Test, Some("cart"), "checkout_is_free_test/0"),
+        ],
+    ),
+    (
+        Language::Sql,
+        include_str!("../selftest_sources/sql.sql"),
+        &[construct(FunctionKind::Function, Some("shop"), "checkout")],
+    ),
+    (
+        Language::Solidity,
+        include_str!("../selftest_sources/solidity.sol"),
+        &[
+            construct(FunctionKind::Function, Some("Prices"), "checkout"),
+            construct(FunctionKind::Method, Some("Cart"), "add"),
+            construct(FunctionKind::Constructor, Some("Cart"), "constructor"),
+            construct(FunctionKind::Test, Some("CartTest"), "testCheckoutIsFree"),
+        ],
+    ),
+];
+
+/// How a construct of a language fared
+pub struct Resolution {
+    pub language: String,
+    pub kind: FunctionKind,
+    /// Why the construct was not found, or not classified as its kind
+    pub failure: Option<String>,
+}
+
+// Finds the construct in the tree of the sample, and checks that it is classified as its kind
+fn resolve(language: &Language, source: &str, construct: &Construct) -> Option<String> {
+    let analyser = analyser(language);
+    let tree = parse_source_with_language(source, analyser.language());
+    let name = match construct.parent {
+        Some(parent) => format!("{}.{}", parent, construct.function),
+        None => construct.function.to_string(),
+    };
+    let function_node = match analyser.find_correct_node(
+        source,
+        &tree,
+        &construct.parent.map(str::to_string),
+        &Some(construct.function.to_string()),
+        &None,
+    ) {
+        Ok(function_node) => function_node,
+        Err(e) => return Some(format!("{}: {}", name, e)),
+    };
+    let kind = analyser.function_kind(source, function_node);
+    if kind != construct.kind {
+        return Some(format!(
+            "{} is classified as a {} in `{}`",
+            name,
+            kind.to_possible_value().unwrap().get_name(),
+            node_value(source, function_node)
+                .lines()
+                .next()
+                .unwrap_or_default()
+        ));
+    }
+
+    None
+}
+
+/// Resolves every construct of every bundled sample
+pub fn run_selftest() -> Vec<Resolution> {
+    SAMPLES
+        .iter()
+        .flat_map(|(language, source, constructs)| {
+            constructs.iter().map(move |construct| Resolution {
+                language: language.to_string(),
+                kind: construct.kind,
+                failure: resolve(language, source, construct),
+            })
+        })
+        .collect()
+}
+
+/// A table with a row per language and a column per kind of function, followed by what failed
+pub fn render_support_matrix(resolutions: &[Resolution]) -> String {
+    let kinds = FunctionKind::value_variants();
+    let mut matrix = format!("{:<12}", "language");
+    for kind in kinds {
+        matrix.push_str(&format!(
+            "{:<13}",
+            kind.to_possible_value().unwrap().get_name()
+        ));
+    }
+
+    let mut languages: Vec<&str> = vec![];
+    for resolution in resolutions {
+        if !languages.contains(&resolution.language.as_str()) {
+            languages.push(&resolution.language);
+        }
+    }
+    for language in languages {
+        matrix.push_str(&format!("\n{:<12}", language));
+        for kind in kinds {
+            let resolution = resolutions
+                .iter()
+                .find(|resolution| resolution.language == language && resolution.kind == *kind);
+            let cell = match resolution.map(|resolution| &resolution.failure) {
+                Some(Some(_)) => format!("{:<13}", "fail").red(),
+                Some(None) => format!("{:<13}", "ok").green(),
+                None => format!("{:<13}", "-").normal(),
+            };
+            matrix.push_str(&cell.to_string());
+        }
+    }
+
+    for resolution in resolutions {
+        if let Some(failure) = &resolution.failure {
+            matrix.push_str(
+                &format!("\n{}: {}", resolution.language, failure)
+                    .red()
+                    .to_string(),
+            );
+        }
+    }
+
+    matrix
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::selftest::run_selftest;
+
+    #[test]
+    fn every_analyser_resolves_its_sample() {
+        let failures: Vec<String> = run_selftest()
+            .into_iter()
+            .filter_map(|resolution| {
+                resolution
+                    .failure
+                    .map(|failure| format!("{}: {}", resolution.language, failure))
+            })
+            .collect();
+
+        assert!(failures.is_empty(), "{:?}", failures);
+    }
+}
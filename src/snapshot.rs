@@ -0,0 +1,165 @@
+use std::fs::{create_dir_all, read, read_dir, read_to_string, write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+pub const SNAPSHOTS_DIRECTORY_PATH: &str = ".senior/snapshots";
+const MANIFEST_FILE_NAME: &str = "manifest.json";
+/// Where the contents of the files are kept, named by their hash
+const CONTENTS_DIRECTORY_NAME: &str = "contents";
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct SnapshotFile {
+    pub path: String,
+    pub sha256: String,
+}
+
+/// The files about to be touched, as they were before. Reverting to it restores them regardless
+/// of git.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Snapshot {
+    pub id: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub files: Vec<SnapshotFile>,
+}
+
+fn content_hash(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Copies the files into a new snapshot under the directory, and returns its id. Ids are the
+/// time the snapshot was taken, made unique by a suffix if needed.
+pub fn take_snapshot(directory: &Path, files: &[String]) -> Result<String, String> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let id = (0..)
+        .map(|attempt| match attempt {
+            0 => timestamp.to_string(),
+            _ => format!("{}-{}", timestamp, attempt),
+        })
+        .find(|id| !directory.join(id).exists())
+        .unwrap();
+    let snapshot_directory = directory.join(&id);
+    let contents_directory = snapshot_directory.join(CONTENTS_DIRECTORY_NAME);
+    create_dir_all(&contents_directory).map_err(|e| e.to_string())?;
+
+    let mut snapshot_files = vec![];
+    for path in files {
+        let contents = read(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+        let sha256 = content_hash(&contents);
+        let copy = contents_directory.join(&sha256);
+        if !copy.exists() {
+            write(&copy, contents).map_err(|e| format!("failed to copy {}: {}", path, e))?;
+        }
+        snapshot_files.push(SnapshotFile {
+            path: path.clone(),
+            sha256,
+        });
+    }
+    let snapshot = Snapshot {
+        id: id.clone(),
+        timestamp,
+        files: snapshot_files,
+    };
+    let manifest = serde_json::to_string_pretty(&snapshot).map_err(|e| e.to_string())?;
+    write(snapshot_directory.join(MANIFEST_FILE_NAME), manifest).map_err(|e| e.to_string())?;
+
+    Ok(id)
+}
+
+fn load_snapshot(directory: &Path, id: &str) -> Result<Snapshot, String> {
+    let manifest_path = directory.join(id).join(MANIFEST_FILE_NAME);
+    let manifest = read_to_string(&manifest_path).map_err(|_| format!("no snapshot {}", id))?;
+
+    serde_json::from_str(&manifest)
+        .map_err(|e| format!("failed to parse {}: {}", manifest_path.display(), e))
+}
+
+/// Every snapshot under the directory, oldest first
+pub fn list_snapshots(directory: &Path) -> Vec<Snapshot> {
+    let Ok(entries) = read_dir(directory) else {
+        return vec![];
+    };
+    let mut snapshots: Vec<Snapshot> = entries
+        .filter_map(|entry| {
+            let id = entry.ok()?.file_name().to_string_lossy().to_string();
+            load_snapshot(directory, &id).ok()
+        })
+        .collect();
+    // Suffixes grow in length, so that `-10` comes after `-9`
+    snapshots
+        .sort_by(|a, b| (a.timestamp, a.id.len(), &a.id).cmp(&(b.timestamp, b.id.len(), &b.id)));
+
+    snapshots
+}
+
+/// Puts back the files of the snapshot that changed since, and returns their paths
+pub fn revert_to(directory: &Path, id: &str) -> Result<Vec<String>, String> {
+    let snapshot = load_snapshot(directory, id)?;
+    let contents_directory = directory.join(id).join(CONTENTS_DIRECTORY_NAME);
+
+    let mut reverted_files = vec![];
+    for file in snapshot.files {
+        let unchanged =
+            read(&file.path).is_ok_and(|contents| content_hash(&contents) == file.sha256);
+        if unchanged {
+            continue;
+        }
+        let contents = read(contents_directory.join(&file.sha256))
+            .map_err(|e| format!("the copy of {} is gone: {}", file.path, e))?;
+        if let Some(parent) = Path::new(&file.path).parent() {
+            create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        write(&file.path, contents).map_err(|e| format!("failed to write {}: {}", file.path, e))?;
+        reverted_files.push(file.path);
+    }
+
+    Ok(reverted_files)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+
+    use crate::snapshot::{list_snapshots, revert_to, take_snapshot};
+
+    #[test]
+    fn snapshots() {
+        let root = std::env::temp_dir().join("senior-snapshot-test");
+        let _ = remove_dir_all(&root);
+        create_dir_all(&root).unwrap();
+        let directory = root.join("snapshots");
+        let (first, second) = (root.join("a.rs"), root.join("b.rs"));
+        write(&first, "fn a() {}").unwrap();
+        write(&second, "fn b() {}").unwrap();
+        let files = vec![
+            first.to_string_lossy().to_string(),
+            second.to_string_lossy().to_string(),
+        ];
+
+        let id = take_snapshot(&directory, &files).unwrap();
+        let other_id = take_snapshot(&directory, &files[..1]).unwrap();
+        assert_ne!(id, other_id);
+        assert_eq!(
+            vec![2, 1],
+            list_snapshots(&directory)
+                .iter()
+                .map(|s| s.files.len())
+                .collect::<Vec<_>>()
+        );
+
+        write(&first, "fn a() { () }").unwrap();
+        assert_eq!(vec![files[0].clone()], revert_to(&directory, &id).unwrap());
+        assert_eq!("fn a() {}", read_to_string(&first).unwrap());
+        assert_eq!("fn b() {}", read_to_string(&second).unwrap());
+        assert!(revert_to(&directory, &id).unwrap().is_empty());
+        assert!(revert_to(&directory, "missing").is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+use colored::*;
+use inquire::Confirm;
+
+use crate::color::Palette;
+use crate::diff::render_text_diff;
+use crate::helpers::tree_sitter::{parse_with_thread_parser, syntax_errors};
+use crate::openai::{complete, Prompt};
+use crate::suggestion::parse_suggestion;
+use crate::supported_languages::supported_language::detect_language;
+
+/// The system prompt of `--span`, which optimises a snippet that is not necessarily a function
+const SNIPPET_SYSTEM_PROMPT: &str = r#"You are a senior software engineer. Your task is to optimise the snippet you are given, which is part of a larger file.
+
+Strictly adhere to the following instructions:
+1. The snippet replaces the original one in the file, so keep whatever the rest of the file may depend on, like names and indentation.
+2. Do not add anything before or after the snippet.
+3. Respond only with code, and no commentary or explanations.
+4. If the snippet is already optimal, respond with "OPTIMAL""#;
+
+/// A part of a file given with `--span`, either by bytes, as `<start>:<end>` with the end left
+/// out, or by lines, as `L<first>-L<last>` with both in, counted from 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Span {
+    Bytes(usize, usize),
+    Lines(usize, usize),
+}
+
+/// Reads the span as `--span` takes it
+pub fn parse_span(span: &str) -> Result<Span, String> {
+    let number = |number: &str| {
+        number
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| format!("`{}` is not a number", number))
+    };
+    if let Some((first, last)) = span.split_once('-') {
+        let (Some(first), Some(last)) = (first.strip_prefix('L'), last.strip_prefix('L')) else {
+            return Err("lines are given as `L<first>-L<last>`, like `L10-L24`".to_string());
+        };
+        let (first, last) = (number(first)?, number(last)?);
+        if first == 0 || last < first {
+            return Err(format!("L{}-L{} holds no lines", first, last));
+        }
+        return Ok(Span::Lines(first, last));
+    }
+    let Some((start, end)) = span.split_once(':') else {
+        return Err(
+            "a span is given as `<start byte>:<end byte>` or `L<first>-L<last>`".to_string(),
+        );
+    };
+    let (start, end) = (number(start)?, number(end)?);
+    if end <= start {
+        return Err(format!("{}:{} holds no bytes", start, end));
+    }
+
+    Ok(Span::Bytes(start, end))
+}
+
+impl Span {
+    /// The bytes of the source file the span covers, with the line break of its last line left
+    /// out for lines
+    pub fn byte_range(&self, source_file: &str) -> Result<(usize, usize), String> {
+        match *self {
+            Span::Bytes(start, end) => {
+                if end > source_file.len() {
+                    return Err(format!("the file is only {} bytes long", source_file.len()));
+                }
+                if !source_file.is_char_boundary(start) || !source_file.is_char_boundary(end) {
+                    return Err(format!("{}:{} splits a character", start, end));
+                }
+                Ok((start, end))
+            }
+            Span::Lines(first, last) => {
+                let lines: Vec<&str> = source_file.split_inclusive('\n').collect();
+                if last > lines.len() {
+                    return Err(format!("the file is only {} lines long", lines.len()));
+                }
+                let start: usize = lines[..first - 1].iter().map(|line| line.len()).sum();
+                let end = start
+                    + lines[first - 1..last]
+                        .concat()
+                        .trim_end_matches(['\r', '\n'])
+                        .len();
+                Ok((start, end))
+            }
+        }
+    }
+}
+
+/// How `--span` asks about the snippet and applies what comes back
+pub struct SnippetSettings<'a> {
+    pub model: &'a str,
+    pub extra_context: Option<String>,
+    pub dry_run: bool,
+    pub skip_prompt: bool,
+    pub palette: Palette,
+}
+
+/// The language of the file for the model, which is its extension when senior does not support it
+fn language_name(file_path: &str) -> String {
+    match detect_language(file_path) {
+        Ok(supported_language) => supported_language.to_string(),
+        Err(_) => Path::new(file_path)
+            .extension()
+            .map_or("plain text".to_string(), |extension| {
+                format!("`.{}`", extension.to_string_lossy())
+            }),
+    }
+}
+
+/// Why the file would not parse with the snippet replaced, if senior supports its language and it
+/// parsed before
+fn parse_problem(file_path: &str, source_file: &str, edited_file: &str) -> Option<String> {
+    let supported_language = detect_language(file_path).ok()?;
+    let errors = |source: &str| {
+        parse_with_thread_parser(source, supported_language.language())
+            .map(|tree| syntax_errors(source, &tree))
+            .unwrap_or_default()
+    };
+    if !errors(source_file).is_empty() {
+        return None;
+    }
+    let errors = errors(edited_file);
+
+    (!errors.is_empty()).then(|| errors.join(", "))
+}
+
+/// Has the model optimise the span of the file as it is, without looking for the function around
+/// it, and applies the suggestion once accepted
+pub async fn run_snippet(
+    file_path: &str,
+    span: &Span,
+    settings: &SnippetSettings<'_>,
+    secret: &str,
+) -> Result<(), String> {
+    let source_file =
+        read_to_string(file_path).map_err(|e| format!("failed to read {}: {}", file_path, e))?;
+    let (start, end) = span.byte_range(&source_file)?;
+    let snippet = &source_file[start..end];
+    let extra_context = settings
+        .extra_context
+        .as_ref()
+        .map_or("".to_string(), |extra_context| {
+            format!("\n\nHere is some context:\n{}", extra_context)
+        });
+    let prompt = Prompt {
+        system: SNIPPET_SYSTEM_PROMPT.to_string(),
+        user: format!(
+            "Optimise the following {} snippet of {}:\n{}{}",
+            language_name(file_path),
+            file_path,
+            snippet,
+            extra_context
+        ),
+    };
+
+    let suggestion = parse_suggestion(&complete(settings.model, &prompt, secret).await?);
+    if suggestion.is_optimal() {
+        println!("{}", "the current code is already optimal.".green());
+        return Ok(());
+    }
+    let edited_file = format!(
+        "{}{}{}",
+        &source_file[..start],
+        suggestion.code,
+        &source_file[end..]
+    );
+    if let Some(problem) = parse_problem(file_path, &source_file, &edited_file) {
+        return Err(format!(
+            "the file would not parse with the suggestion, so it was left as it was: {}",
+            problem
+        ));
+    }
+    println!(
+        "{}",
+        render_text_diff(snippet, &suggestion.code, settings.palette)
+    );
+    if settings.dry_run {
+        return Ok(());
+    }
+
+    let apply = settings.skip_prompt
+        || Confirm::new("Apply suggestion?")
+            .with_default(false)
+            .prompt()
+            .unwrap();
+    if apply {
+        write(file_path, edited_file)
+            .map_err(|e| format!("failed to write {}: {}", file_path, e))?;
+        println!("{}", "applied the suggestion.".green());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::snippet::{parse_problem, parse_span, Span};
+
+    #[test]
+    fn spans() {
+        let source = "[server]\nport = 80\nhost = \"a\"\n";
+
+        assert_eq!(Ok(Span::Bytes(9, 18)), parse_span("9:18"));
+        assert_eq!(Ok(Span::Lines(2, 3)), parse_span("L2-L3"));
+        assert!(parse_span("18:9").is_err());
+        assert!(parse_span("L0-L2").is_err());
+        assert!(parse_span("2-3").is_err());
+        assert!(parse_span("nine").is_err());
+
+        assert_eq!(Ok((9, 18)), Span::Bytes(9, 18).byte_range(source));
+        assert_eq!(Ok((9, 29)), Span::Lines(2, 3).byte_range(source));
+        assert_eq!("port = 80", &source[9..18]);
+        assert!(Span::Lines(2, 4).byte_range(source).is_err());
+        assert!(Span::Bytes(0, 40).byte_range(source).is_err());
+        assert!(Span::Bytes(0, 2).byte_range("é").is_ok());
+        assert!(Span::Bytes(0, 1).byte_range("é").is_err());
+    }
+
+    #[test]
+    fn parse_problems() {
+        let source = "fn main() {\n    let x = 1;\n}\n";
+
+        assert_eq!(
+            None,
+            parse_problem("main.rs", source, "fn main() {\n    let x = 2;\n}\n")
+        );
+        assert!(parse_problem("main.rs", source, "fn main() {\n    let x = (2;\n}\n").is_some());
+        assert_eq!(None, parse_problem("main.toml", source, "{{{"));
+        assert_eq!(None, parse_problem("main.rs", "fn main( {", "{{{"));
+    }
+}
@@ -0,0 +1,82 @@
+use tree_sitter::Node;
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::helpers::tree_sitter::node_value;
+use crate::literal::{split_quotes, Literal, STRING_KINDS};
+
+/// The system prompt of `--sql`, which optimises the queries in string literals rather than the
+/// code around them
+pub const SQL_SYSTEM_PROMPT: &str = r#"You are a senior database engineer. Your task is to optimise the SQL query you are given, which a function runs, so that the database answers it faster.
+
+Strictly adhere to the following instructions:
+1. The query must return the same rows, in the same order if it has one.
+2. Keep every placeholder, like `?`, `$1` or `:name`, and every interpolation of the string it is in.
+3. Only rely on the tables, columns and indexes of the schema, when you are given one.
+4. Respond only with the query, and no commentary or explanations.
+"#;
+
+/// The first keyword of a statement, with one that must come after it, so that prose like "select
+/// a product" is not taken for SQL
+const STATEMENT_KEYWORDS: &[(&str, &str)] = &[
+    ("SELECT", "FROM"),
+    ("WITH", "SELECT"),
+    ("INSERT", "INTO"),
+    ("UPDATE", "SET"),
+    ("DELETE", "FROM"),
+];
+
+/// Whether the text reads as a SQL statement
+pub fn is_sql(text: &str) -> bool {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| !word.is_empty())
+        .map(str::to_uppercase)
+        .collect();
+
+    STATEMENT_KEYWORDS.iter().any(|(first, then)| {
+        words.first().is_some_and(|word| word == first)
+            && words[1..].iter().any(|word| word == then)
+    })
+}
+
+/// The string literals holding SQL in the node, in the order they appear
+pub fn find_sql_literals<'a>(source_file: &str, node: Node<'a>) -> Vec<Node<'a>> {
+    traverse(node.walk(), Order::Pre)
+        .filter(|node| STRING_KINDS.contains(&node.kind()))
+        .filter(|node| {
+            split_quotes(node_value(source_file, *node))
+                .is_some_and(|literal| is_sql(&literal.contents))
+        })
+        .collect()
+}
+
+/// What the model is told about the literal and the function around it
+pub fn sql_instructions(language: &str, literal: &Literal, function: &str) -> String {
+    format!(
+        "The query is the contents of a {} string literal opened by `{}` and closed by `{}`, and must stay valid in it. It is run by this function:\n```{}\n{}\n```",
+        language, literal.opening, literal.closing, language, function
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::tree_sitter::{node_value, parse_source_with_language};
+    use crate::sql::{find_sql_literals, is_sql};
+
+    #[test]
+    fn sql_in_string_literals() {
+        assert!(is_sql("select * from orders where id = $1"));
+        assert!(is_sql("\n  UPDATE orders SET total = ? WHERE id = ?"));
+        assert!(!is_sql("select a product"));
+
+        let source = r#"fn unpaid(db: &Db, customer: u64) -> Vec<Order> {
+    let label = "select a product";
+    db.query("SELECT * FROM orders WHERE customer_id = $1 AND paid = false", &[&customer])
+}"#;
+        let tree = parse_source_with_language(source, tree_sitter_rust::language());
+        let literals = find_sql_literals(source, tree.root_node());
+
+        assert_eq!(1, literals.len());
+        assert!(node_value(source, literals[0]).starts_with("\"SELECT *"));
+    }
+}
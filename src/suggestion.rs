@@ -0,0 +1,171 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SuggestionStatus {
+    Optimal,
+    Suggestion,
+}
+
+/// How likely a suggestion is to change the behaviour of the code, according to the model
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Risk {
+    Low,
+    Medium,
+    High,
+}
+
+/// An answer of the model. Answers in plain text have neither a rationale nor a risk.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub status: SuggestionStatus,
+    pub code: String,
+    #[serde(default)]
+    pub rationale: String,
+    pub risk: Option<Risk>,
+}
+
+impl Suggestion {
+    pub fn is_optimal(&self) -> bool {
+        self.status == SuggestionStatus::Optimal
+    }
+}
+
+/// What the user message asks for when the answer is structured
+pub const STRUCTURED_ANSWER_INSTRUCTIONS: &str = r#"Answer with a status of "optimal" if the code cannot be optimised further, and of "suggestion" otherwise, along with the optimised code, a short rationale of why it is faster, and the risk of the change altering the behaviour of the code."#;
+
+/// The `response_format` of a chat completions request that asks for a `Suggestion`
+pub fn suggestion_response_format() -> Value {
+    json!({
+        "type": "json_schema",
+        "json_schema": {
+            "name": "suggestion",
+            "strict": true,
+            "schema": {
+                "type": "object",
+                "properties": {
+                    "status": { "type": "string", "enum": ["optimal", "suggestion"] },
+                    "code": { "type": "string" },
+                    "rationale": { "type": "string" },
+                    "risk": { "type": "string", "enum": ["low", "medium", "high"] }
+                },
+                "required": ["status", "code", "rationale", "risk"],
+                "additionalProperties": false
+            }
+        }
+    })
+}
+
+/// What the model is asked after a plain text answer, to find out why the change is faster
+pub const EXPLAIN_INSTRUCTIONS: &str =
+    "In at most three sentences, and without any code, explain why your change makes the code faster.";
+
+const RATIONALE_PANEL_WIDTH: usize = 100;
+
+/// Frames the rationale, wrapped, in a panel to be shown under the suggested code
+pub fn render_rationale(rationale: &str, risk: Option<Risk>) -> String {
+    let title = match risk {
+        Some(risk) => format!(
+            "rationale, {} risk",
+            risk.to_possible_value().unwrap().get_name()
+        ),
+        None => "rationale".to_string(),
+    };
+    let mut panel = format!("╭─ {}\n", title);
+    for paragraph in rationale.trim().lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            if !line.is_empty() && line.len() + word.len() + 1 > RATIONALE_PANEL_WIDTH {
+                panel.push_str(&format!("│ {}\n", line));
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        panel.push_str(&format!("│ {}\n", line));
+    }
+    panel.push('╰');
+
+    panel
+}
+
+/// Reads an answer, be it structured or in plain text. Plain text answers are either "OPTIMAL"
+/// or code, possibly within a markdown code fence.
+pub fn parse_suggestion(answer: &str) -> Suggestion {
+    if let Ok(suggestion) = serde_json::from_str::<Suggestion>(answer.trim()) {
+        return suggestion;
+    }
+
+    let answer = answer.trim();
+    if answer == "OPTIMAL" {
+        return Suggestion {
+            status: SuggestionStatus::Optimal,
+            code: "".to_string(),
+            rationale: "".to_string(),
+            risk: None,
+        };
+    }
+    let code = match answer.strip_prefix("```") {
+        // The opening fence may name the language
+        Some(fenced) => fenced
+            .split_once('\n')
+            .map_or("", |(_, code)| code)
+            .trim_end()
+            .trim_end_matches("```")
+            .trim_end_matches('\n'),
+        None => answer,
+    };
+
+    Suggestion {
+        status: SuggestionStatus::Suggestion,
+        code: code.to_string(),
+        rationale: "".to_string(),
+        risk: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::suggestion::{parse_suggestion, render_rationale, Risk, SuggestionStatus};
+
+    #[test]
+    fn structured_answers() {
+        let suggestion = parse_suggestion(
+            r#"{"status":"suggestion","code":"fn greet() {}","rationale":"No allocation","risk":"low"}"#,
+        );
+
+        assert_eq!(SuggestionStatus::Suggestion, suggestion.status);
+        assert_eq!("fn greet() {}", suggestion.code);
+        assert_eq!("No allocation", suggestion.rationale);
+        assert_eq!(Some(Risk::Low), suggestion.risk);
+    }
+
+    #[test]
+    fn text_answers() {
+        assert!(parse_suggestion("OPTIMAL\n").is_optimal());
+
+        let suggestion = parse_suggestion("```rust\nfn greet() {\n}\n```");
+        assert_eq!("fn greet() {\n}", suggestion.code);
+        assert_eq!(None, suggestion.risk);
+
+        assert_eq!("fn greet() {}", parse_suggestion("fn greet() {}").code);
+    }
+
+    #[test]
+    fn rationale_panel() {
+        assert_eq!(
+            "╭─ rationale, low risk\n│ Avoids cloning the name.\n╰",
+            render_rationale("Avoids cloning the name.\n", Some(Risk::Low))
+        );
+
+        let long_rationale = "word ".repeat(30);
+        let panel = render_rationale(&long_rationale, None);
+        assert_eq!(4, panel.lines().count());
+        assert!(panel.lines().all(|line| line.chars().count() <= 102));
+    }
+}
@@ -1,5 +1,13 @@
+mod bash;
+mod cpp;
+mod ecmascript;
+mod erlang;
 mod go;
+mod java;
 mod javascript;
 mod rust;
+mod scala;
+mod solidity;
+mod sql;
 pub mod supported_language;
 mod typescript;
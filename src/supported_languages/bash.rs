@@ -0,0 +1,239 @@
+use std::fmt::{Display, Formatter};
+
+use tree_sitter::{Node, Tree};
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::helpers::tree_sitter::node_value;
+use crate::supported_languages::supported_language::{FunctionKind, Language, SupportedLanguage};
+
+/// Expressions that fork a process of their own, which is what is slow in shell scripts
+const FORKING_KINDS: &[&str] = &["command_substitution", "process_substitution", "subshell"];
+
+pub struct BashAnalyser {
+    inner: Language,
+}
+
+impl Default for BashAnalyser {
+    fn default() -> Self {
+        Self {
+            inner: Language::Bash,
+        }
+    }
+}
+
+impl Display for BashAnalyser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl SupportedLanguage for BashAnalyser {
+    /// Functions are defined either as `foo() { ... }` or as `function foo { ... }`. Their parent
+    /// is the function they are defined in, as shell scripts have no classes.
+    fn find_correct_node<'a>(
+        &self,
+        source_file: &str,
+        root_tree: &'a Tree,
+        parent_identifier: &Option<String>,
+        function_identifier: &Option<String>,
+        kind: &Option<FunctionKind>,
+    ) -> Result<Node<'a>, &'a str> {
+        let Some(function) = function_identifier else {
+            return Ok(root_tree.root_node());
+        };
+
+        self.find_all_functions(root_tree)
+            .into_iter()
+            .filter(|function_node| function_name(source_file, *function_node) == Some(function))
+            .filter(|function_node| self.is_of_kind(source_file, *function_node, kind))
+            .find(|function_node| {
+                parent_identifier.as_ref().is_none_or(|parent| {
+                    enclosing_function(*function_node)
+                        .and_then(|enclosing| function_name(source_file, enclosing))
+                        == Some(parent)
+                })
+            })
+            .ok_or("function not found")
+    }
+
+    fn find_all_functions<'a>(&self, root_tree: &'a Tree) -> Vec<Node<'a>> {
+        traverse(root_tree.walk(), Order::Pre)
+            .filter(|node| node.kind() == "function_definition")
+            .collect()
+    }
+
+    fn node_kinds(&self) -> &'static [&'static str] {
+        &["function_definition", "compound_statement", "word"]
+    }
+
+    /// Test frameworks like shunit2 and bats-style helpers run the functions whose name starts
+    /// with `test`
+    fn function_kind(&self, source_file: &str, function_node: Node) -> FunctionKind {
+        match function_name(source_file, function_node) {
+            Some(name) if name.starts_with("test") => FunctionKind::Test,
+            _ => FunctionKind::Function,
+        }
+    }
+
+    fn language(&self) -> tree_sitter::Language {
+        tree_sitter_bash::language()
+    }
+
+    fn is_allocation_suspect(&self, _source_file: &str, node: Node) -> bool {
+        FORKING_KINDS.contains(&node.kind())
+    }
+
+    fn default_instructions(&self) -> &'static str {
+        "Prefer builtins and parameter expansion to spawning `sed`, `awk`, `cut` or `basename` in loops, avoid useless uses of `cat` and subshells, and keep expansions quoted."
+    }
+
+    /// Scripts declare the commands they run nowhere
+    fn manifest_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn declared_packages(&self, _manifest: &str) -> Vec<String> {
+        vec![]
+    }
+
+    fn referenced_packages(&self, _source: &str, _tree: &Tree) -> Vec<String> {
+        vec![]
+    }
+
+    fn line_comment(&self) -> &'static str {
+        "#"
+    }
+}
+
+fn function_name<'a>(source_file: &'a str, function_node: Node<'a>) -> Option<&'a str> {
+    function_node
+        .child_by_field_name("name")
+        .map(|name| node_value(source_file, name))
+}
+
+/// The function the function is defined in, if any
+fn enclosing_function(function_node: Node) -> Option<Node> {
+    let mut node = function_node.parent();
+    while let Some(ancestor) = node {
+        if ancestor.kind() == "function_definition" {
+            return Some(ancestor);
+        }
+        node = ancestor.parent();
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Tree;
+
+    use crate::helpers::tree_sitter::{node_value, parse_source_with_language};
+    use crate::supported_languages::bash::BashAnalyser;
+    use crate::supported_languages::supported_language::{FunctionKind, SupportedLanguage};
+
+    const BASH_SOURCE: &str = r#"#!/usr/bin/env bash
+set -euo pipefail
+
+greet() {
+    echo "Hello from $(basename "$0")"
+}
+
+function deploy {
+    log() {
+        echo "[deploy] $*"
+    }
+    log "starting"
+}
+
+test_greet() {
+    [ "$(greet)" != "" ]
+}
+
+greet
+"#;
+
+    fn bash_source_tree() -> Tree {
+        parse_source_with_language(BASH_SOURCE, tree_sitter_bash::language())
+    }
+
+    fn find(parent: Option<&str>, function: &str) -> Option<String> {
+        let tree = bash_source_tree();
+        let ra: BashAnalyser = Default::default();
+
+        ra.find_correct_node(
+            BASH_SOURCE,
+            &tree,
+            &parent.map(str::to_string),
+            &Some(function.to_string()),
+            &None,
+        )
+        .ok()
+        .map(|function_node| node_value(BASH_SOURCE, function_node).to_string())
+    }
+
+    #[test]
+    fn no_function() {
+        let tree = bash_source_tree();
+        let ra: BashAnalyser = Default::default();
+
+        assert_eq!(
+            BASH_SOURCE,
+            node_value(
+                BASH_SOURCE,
+                ra.find_correct_node(BASH_SOURCE, &tree, &None, &None, &None)
+                    .unwrap(),
+            ),
+        )
+    }
+
+    #[test]
+    fn both_definition_styles() {
+        let target = r#"greet() {
+    echo "Hello from $(basename "$0")"
+}"#;
+        assert_eq!(Some(target), find(None, "greet").as_deref());
+
+        let target = r#"function deploy {
+    log() {
+        echo "[deploy] $*"
+    }
+    log "starting"
+}"#;
+        assert_eq!(Some(target), find(None, "deploy").as_deref());
+    }
+
+    #[test]
+    fn nested_function() {
+        let target = r#"log() {
+        echo "[deploy] $*"
+    }"#;
+
+        assert_eq!(Some(target), find(None, "log").as_deref());
+        assert_eq!(Some(target), find(Some("deploy"), "log").as_deref());
+        assert_eq!(None, find(Some("greet"), "log"));
+    }
+
+    #[test]
+    fn function_kinds() {
+        let tree = bash_source_tree();
+        let ra: BashAnalyser = Default::default();
+
+        for (function, kind) in [
+            ("greet", FunctionKind::Function),
+            ("test_greet", FunctionKind::Test),
+        ] {
+            let function_node = ra
+                .find_correct_node(
+                    BASH_SOURCE,
+                    &tree,
+                    &None,
+                    &Some(function.to_string()),
+                    &None,
+                )
+                .unwrap();
+
+            assert_eq!(kind, ra.function_kind(BASH_SOURCE, function_node))
+        }
+    }
+}
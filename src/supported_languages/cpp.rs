@@ -0,0 +1,459 @@
+use std::fmt::{Display, Formatter};
+
+use tree_sitter::{Node, Tree};
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::context::declared_name;
+use crate::helpers::tree_sitter::node_value;
+use crate::supported_languages::supported_language::{FunctionKind, Language, SupportedLanguage};
+
+const CLASS_KINDS: &[&str] = &["class_specifier", "struct_specifier", "union_specifier"];
+
+/// The macros of GoogleTest and Catch2 defining tests, which parse as functions of that name
+const TEST_MACROS: &[&str] = &[
+    "TEST",
+    "TEST_F",
+    "TEST_P",
+    "TYPED_TEST",
+    "TEST_CASE",
+    "SCENARIO",
+];
+
+const ALLOCATING_FUNCTIONS: &[&str] = &["make_shared", "make_unique", "to_string", "malloc"];
+
+pub struct CppAnalyser {
+    inner: Language,
+}
+
+impl Default for CppAnalyser {
+    fn default() -> Self {
+        Self {
+            inner: Language::Cpp,
+        }
+    }
+}
+
+impl Display for CppAnalyser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl SupportedLanguage for CppAnalyser {
+    fn find_correct_node<'a>(
+        &self,
+        source_file: &str,
+        root_tree: &'a Tree,
+        parent_identifier: &Option<String>,
+        function_identifier: &Option<String>,
+        kind: &Option<FunctionKind>,
+    ) -> Result<Node<'a>, &'a str> {
+        let Some(function) = function_identifier else {
+            return Ok(root_tree.root_node());
+        };
+
+        let candidates: Vec<Node> = self
+            .find_all_functions(root_tree)
+            .into_iter()
+            .filter(|function_node| {
+                declared_name(*function_node)
+                    .is_some_and(|name| node_value(source_file, name) == function)
+            })
+            .filter(|function_node| self.is_of_kind(source_file, *function_node, kind))
+            .collect();
+
+        if let Some(parent) = parent_identifier {
+            // The parent may be a path through namespaces and classes, like `engine::Foo`, and
+            // matches methods defined in the class as well as out of it, like `void Foo::bar()`
+            let path: Vec<&str> = parent
+                .split("::")
+                .flat_map(|segment| segment.split('.'))
+                .collect();
+            return candidates
+                .into_iter()
+                .find(|function_node| scopes(source_file, *function_node).ends_with(&path))
+                .ok_or("method not found");
+        }
+
+        // Free functions come first, as methods are usually given a parent
+        candidates
+            .iter()
+            .find(|function_node| !is_member(**function_node))
+            .or(candidates.first())
+            .copied()
+            .ok_or("function not found")
+    }
+
+    /// Function templates are found by the function they hold, and declarations without a body
+    /// are left out
+    fn find_all_functions<'a>(&self, root_tree: &'a Tree) -> Vec<Node<'a>> {
+        traverse(root_tree.walk(), Order::Pre)
+            .filter(|node| node.kind() == "function_definition")
+            .filter(|node| node.child_by_field_name("body").is_some())
+            .collect()
+    }
+
+    fn node_kinds(&self) -> &'static [&'static str] {
+        &[
+            "function_definition",
+            "function_declarator",
+            "class_specifier",
+            "struct_specifier",
+            "namespace_definition",
+        ]
+    }
+
+    fn function_kind(&self, source_file: &str, function_node: Node) -> FunctionKind {
+        let Some(name_node) = declared_name(function_node) else {
+            return FunctionKind::Function;
+        };
+        let name = node_value(source_file, name_node);
+        if !is_member(function_node) {
+            return if TEST_MACROS.contains(&name) {
+                FunctionKind::Test
+            } else {
+                FunctionKind::Function
+            };
+        }
+
+        let class_name = scopes(source_file, function_node).last().copied();
+        let takes_nothing = traverse(function_node.walk(), Order::Pre)
+            .find(|node| node.kind() == "function_declarator")
+            .and_then(|declarator| declarator.child_by_field_name("parameters"))
+            .is_some_and(|parameters| parameters.named_child_count() == 0);
+
+        if class_name == Some(name) {
+            FunctionKind::Constructor
+        } else if takes_nothing && returns_a_field(function_node) {
+            FunctionKind::Getter
+        } else {
+            FunctionKind::Method
+        }
+    }
+
+    fn language(&self) -> tree_sitter::Language {
+        tree_sitter_cpp::language()
+    }
+
+    fn is_allocation_suspect(&self, source_file: &str, node: Node) -> bool {
+        match node.kind() {
+            "new_expression" => true,
+            "call_expression" => node
+                .child_by_field_name("function")
+                .map(|function| match function.kind() {
+                    "qualified_identifier" | "template_function" => {
+                        declared_name_of_callee(function)
+                    }
+                    _ => function,
+                })
+                .is_some_and(|function| {
+                    ALLOCATING_FUNCTIONS.contains(&node_value(source_file, function))
+                }),
+            _ => false,
+        }
+    }
+
+    fn default_instructions(&self) -> &'static str {
+        "Reserve vectors whose size is known, pass large objects by const reference, move rather than copy, and avoid allocating in hot loops."
+    }
+
+    fn manifest_name(&self) -> Option<&'static str> {
+        Some("CMakeLists.txt")
+    }
+
+    /// The packages `find_package` looks for, lowercased like the directories of their headers
+    fn declared_packages(&self, manifest: &str) -> Vec<String> {
+        manifest
+            .split("find_package(")
+            .skip(1)
+            .filter_map(|arguments| arguments.split([' ', ')', '\n']).next())
+            .map(|package| package.trim().to_lowercase())
+            .filter(|package| !package.is_empty())
+            .collect()
+    }
+
+    /// The libraries the code includes the headers of, by the directory they are in, like `boost`
+    /// for `<boost/optional.hpp>`. Headers of the standard library are not in one.
+    fn referenced_packages(&self, source: &str, tree: &Tree) -> Vec<String> {
+        let mut packages: Vec<String> = traverse(tree.walk(), Order::Pre)
+            .filter(|node| node.kind() == "preproc_include")
+            .filter_map(|include| include.child_by_field_name("path"))
+            .filter(|path| path.kind() == "system_lib_string")
+            .filter_map(|path| {
+                let header = node_value(source, path).trim_matches(['<', '>']);
+                let (directory, _) = header.split_once('/')?;
+                Some(directory.to_lowercase())
+            })
+            .filter(|package| package != "sys")
+            .collect();
+        packages.sort();
+        packages.dedup();
+
+        packages
+    }
+}
+
+/// The last name of a qualified callee, like `make_unique` in `std::make_unique<Foo>`
+fn declared_name_of_callee(function: Node) -> Node {
+    let mut callee = function;
+    while let Some(name) = callee.child_by_field_name("name") {
+        callee = name;
+    }
+
+    callee
+}
+
+/// The name of a class or namespace, without its template arguments
+fn scope_name<'a>(source_file: &'a str, scope: Node<'a>) -> &'a str {
+    let scope = match scope.kind() {
+        "template_type" => scope.child_by_field_name("name").unwrap_or(scope),
+        _ => scope,
+    };
+
+    node_value(source_file, scope)
+}
+
+/// The namespaces and classes the function is defined in, outermost first, followed by the ones
+/// its name is qualified by, like `engine`, `Foo` for `void Foo::bar()` inside `namespace engine`
+fn scopes<'a>(source_file: &'a str, function_node: Node<'a>) -> Vec<&'a str> {
+    let mut enclosing = vec![];
+    let mut node = function_node.parent();
+    while let Some(ancestor) = node {
+        if ancestor.kind() == "namespace_definition" || CLASS_KINDS.contains(&ancestor.kind()) {
+            if let Some(name) = ancestor.child_by_field_name("name") {
+                // Nested namespaces can be defined at once, like `namespace a::b`
+                enclosing.extend(scope_name(source_file, name).rsplit("::"));
+            }
+        }
+        node = ancestor.parent();
+    }
+    enclosing.reverse();
+
+    let mut declarator = function_node.child_by_field_name("declarator");
+    while let Some(current) = declarator {
+        declarator = match current.kind() {
+            "qualified_identifier" => {
+                enclosing.extend(
+                    current
+                        .child_by_field_name("scope")
+                        .map(|scope| scope_name(source_file, scope)),
+                );
+                current.child_by_field_name("name")
+            }
+            "reference_declarator" => current.named_child(0),
+            _ => current.child_by_field_name("declarator"),
+        };
+    }
+
+    enclosing
+}
+
+/// Whether the function is defined in a class, or out of it with a name qualified by it
+fn is_member(function_node: Node) -> bool {
+    let in_class = function_node
+        .parent()
+        .filter(|list| list.kind() == "field_declaration_list")
+        .is_some();
+    let qualified = traverse(function_node.walk(), Order::Pre)
+        .take_while(|node| node.kind() != "parameter_list")
+        .any(|node| node.kind() == "qualified_identifier");
+
+    in_class || qualified
+}
+
+// Whether the body is a single `return field`, `return this->field` or `return other.field`
+fn returns_a_field(function_node: Node) -> bool {
+    let Some(body) = function_node.child_by_field_name("body") else {
+        return false;
+    };
+    if body.named_child_count() != 1 {
+        return false;
+    }
+
+    let statement = body.named_child(0).unwrap();
+    statement.kind() == "return_statement"
+        && statement.named_child(0).is_some_and(|expression| {
+            expression.kind() == "identifier" || expression.kind() == "field_expression"
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Tree;
+
+    use crate::helpers::tree_sitter::{node_value, parse_source_with_language};
+    use crate::supported_languages::cpp::CppAnalyser;
+    use crate::supported_languages::supported_language::{FunctionKind, SupportedLanguage};
+
+    const CPP_SOURCE: &str = r#"#include <iostream>
+#include <boost/optional.hpp>
+
+// Top-level function
+void greet() {
+    std::cout << "Hello from top-level function!" << std::endl;
+}
+
+namespace engine {
+
+// A class with methods defined in it, and one defined out of it
+class Greeter {
+public:
+    Greeter(std::string name) : name_(name) {}
+
+    const std::string& name() const {
+        return name_;
+    }
+
+    void greet() {
+        std::cout << "Hello from " << name_ << ", inside the Greeter class!" << std::endl;
+    }
+
+    void shout();
+
+private:
+    std::string name_;
+};
+
+void Greeter::shout() {
+    std::cout << "HELLO FROM " << name_ << "!" << std::endl;
+}
+
+template <typename T>
+T twice(T value) {
+    return value + value;
+}
+
+}
+
+int main() {
+    engine::Greeter greeter("Bob");
+    greeter.greet();
+    greet();
+}"#;
+
+    fn cpp_source_tree() -> Tree {
+        parse_source_with_language(CPP_SOURCE, tree_sitter_cpp::language())
+    }
+
+    fn find(parent: Option<&str>, function: &str, kind: Option<FunctionKind>) -> Option<String> {
+        let tree = cpp_source_tree();
+        let ra: CppAnalyser = Default::default();
+
+        ra.find_correct_node(
+            CPP_SOURCE,
+            &tree,
+            &parent.map(str::to_string),
+            &Some(function.to_string()),
+            &kind,
+        )
+        .ok()
+        .map(|function_node| node_value(CPP_SOURCE, function_node).to_string())
+    }
+
+    #[test]
+    fn no_function() {
+        let tree = cpp_source_tree();
+        let ra: CppAnalyser = Default::default();
+
+        assert_eq!(
+            CPP_SOURCE,
+            node_value(
+                CPP_SOURCE,
+                ra.find_correct_node(CPP_SOURCE, &tree, &None, &None, &None)
+                    .unwrap(),
+            ),
+        )
+    }
+
+    #[test]
+    fn top_level_function() {
+        let target = r#"void greet() {
+    std::cout << "Hello from top-level function!" << std::endl;
+}"#;
+
+        assert_eq!(Some(target), find(None, "greet", None).as_deref());
+    }
+
+    #[test]
+    fn method() {
+        let target = r#"void greet() {
+        std::cout << "Hello from " << name_ << ", inside the Greeter class!" << std::endl;
+    }"#;
+
+        assert_eq!(
+            Some(target),
+            find(Some("Greeter"), "greet", None).as_deref()
+        );
+        assert_eq!(
+            Some(target),
+            find(Some("engine::Greeter"), "greet", None).as_deref()
+        );
+    }
+
+    #[test]
+    fn out_of_class_method() {
+        let target = r#"void Greeter::shout() {
+    std::cout << "HELLO FROM " << name_ << "!" << std::endl;
+}"#;
+
+        assert_eq!(
+            Some(target),
+            find(Some("Greeter"), "shout", None).as_deref()
+        );
+        assert_eq!(
+            Some(target),
+            find(Some("engine::Greeter"), "shout", None).as_deref()
+        );
+        assert_eq!(None, find(Some("Other"), "shout", None));
+    }
+
+    #[test]
+    fn template_function() {
+        let target = r#"T twice(T value) {
+    return value + value;
+}"#;
+
+        assert_eq!(Some(target), find(Some("engine"), "twice", None).as_deref());
+        assert_eq!(Some(target), find(None, "twice", None).as_deref());
+    }
+
+    #[test]
+    fn function_kinds() {
+        let tree = cpp_source_tree();
+        let ra: CppAnalyser = Default::default();
+
+        for (parent, function, kind) in [
+            (None, "greet", FunctionKind::Function),
+            (Some("Greeter"), "greet", FunctionKind::Method),
+            (Some("Greeter"), "shout", FunctionKind::Method),
+            (Some("Greeter"), "Greeter", FunctionKind::Constructor),
+            (Some("Greeter"), "name", FunctionKind::Getter),
+        ] {
+            let function_node = ra
+                .find_correct_node(
+                    CPP_SOURCE,
+                    &tree,
+                    &parent.map(str::to_string),
+                    &Some(function.to_string()),
+                    &None,
+                )
+                .unwrap();
+
+            assert_eq!(kind, ra.function_kind(CPP_SOURCE, function_node))
+        }
+    }
+
+    #[test]
+    fn packages() {
+        let tree = cpp_source_tree();
+        let ra: CppAnalyser = Default::default();
+
+        assert_eq!(vec!["boost"], ra.referenced_packages(CPP_SOURCE, &tree));
+        assert_eq!(
+            vec!["boost", "fmt"],
+            ra.declared_packages(
+                "cmake_minimum_required(VERSION 3.20)\nfind_package(Boost 1.80 REQUIRED)\nfind_package(fmt)\n"
+            )
+        );
+    }
+}
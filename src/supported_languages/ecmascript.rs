@@ -0,0 +1,307 @@
+use tree_sitter::{Node, Tree};
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::helpers::tree_sitter::node_value;
+use crate::supported_languages::supported_language::FunctionKind;
+
+// Node kinds that hold a function when they are the value of a declarator, pair or field
+const FUNCTION_VALUE_KINDS: &[&str] = &["function", "function_expression", "arrow_function"];
+
+const TEST_BLOCK_CALLEES: &[&str] = &["describe", "it", "test"];
+
+const NODE_BUILTIN_MODULES: &[&str] = &[
+    "assert",
+    "buffer",
+    "child_process",
+    "crypto",
+    "events",
+    "fs",
+    "http",
+    "https",
+    "net",
+    "os",
+    "path",
+    "perf_hooks",
+    "process",
+    "readline",
+    "stream",
+    "url",
+    "util",
+    "worker_threads",
+    "zlib",
+];
+
+const ALLOCATING_METHODS: &[&str] = &["concat", "filter", "flatMap", "map", "slice", "split"];
+
+const PACKAGE_JSON_DEPENDENCY_KEYS: &[&str] = &[
+    "dependencies",
+    "devDependencies",
+    "peerDependencies",
+    "optionalDependencies",
+];
+
+/// Resolves a dotted parent path, like `config.handlers`, into the node whose members are the
+/// candidate functions. That is either a class body, an object literal or a namespace body.
+pub fn find_scope<'a>(source_file: &str, root_tree: &'a Tree, path: &str) -> Option<Node<'a>> {
+    let mut segments = path.split('.');
+    let first_segment = segments.next()?;
+
+    let mut scope = traverse(root_tree.walk(), Order::Pre)
+        .find_map(|node| declared_scope(source_file, node, first_segment))?;
+    for segment in segments {
+        scope = member_scope(source_file, scope, segment)?;
+    }
+
+    Some(scope)
+}
+
+/// Finds the functions named `function` amongst the members of a scope returned by `find_scope`.
+/// There may be more than one, as a getter and a setter can share their name.
+pub fn find_functions_in_scope<'a>(
+    source_file: &str,
+    scope: Node<'a>,
+    function: &str,
+) -> Vec<Node<'a>> {
+    // Namespaces contain regular statements
+    if scope.kind() == "statement_block" {
+        return traverse(scope.walk(), Order::Pre)
+            .filter(|node| match node.kind() {
+                "function_declaration" => name_of(source_file, *node, "name") == Some(function),
+                "lexical_declaration" | "variable_declaration" => {
+                    node.named_child(0).is_some_and(|declarator| {
+                        name_of(source_file, declarator, "name") == Some(function)
+                            && holds_function(declarator, "value")
+                    })
+                }
+                _ => false,
+            })
+            .collect();
+    }
+
+    let mut cursor = scope.walk();
+    let found = scope
+        .named_children(&mut cursor)
+        .filter(|member| match member.kind() {
+            "method_definition" => name_of(source_file, *member, "name") == Some(function),
+            "pair" => {
+                key_of(source_file, *member) == Some(function) && holds_function(*member, "value")
+            }
+            "field_definition" | "public_field_definition" => {
+                field_name_of(source_file, *member) == Some(function)
+                    && holds_function(*member, "value")
+            }
+            _ => false,
+        })
+        .collect();
+
+    found
+}
+
+/// The node kinds the javascript and typescript analysers find functions by
+pub const NODE_KINDS: &[&str] = &[
+    "function_declaration",
+    "method_definition",
+    "lexical_declaration",
+    "variable_declarator",
+    "arrow_function",
+    "class_declaration",
+    "pair",
+];
+
+/// Every function that the javascript and typescript analysers can resolve, in source order.
+pub fn find_all_functions<'a>(root_tree: &'a Tree) -> Vec<Node<'a>> {
+    traverse(root_tree.walk(), Order::Pre)
+        .filter(|node| match node.kind() {
+            "function_declaration" | "method_definition" => true,
+            "lexical_declaration" => node
+                .named_child(0)
+                .is_some_and(|declarator| holds_function(declarator, "value")),
+            "pair" | "field_definition" | "public_field_definition" => {
+                holds_function(*node, "value")
+            }
+            _ => false,
+        })
+        .collect()
+}
+
+/// Classifies the nodes that the javascript and typescript analysers can return.
+pub fn function_kind(source_file: &str, function_node: Node) -> FunctionKind {
+    match function_node.kind() {
+        "method_definition" => {
+            let mut cursor = function_node.walk();
+            let is_getter = function_node
+                .children(&mut cursor)
+                .any(|child| child.kind() == "get");
+
+            if name_of(source_file, function_node, "name") == Some("constructor") {
+                FunctionKind::Constructor
+            } else if is_getter {
+                FunctionKind::Getter
+            } else {
+                FunctionKind::Method
+            }
+        }
+        "pair" | "field_definition" | "public_field_definition" => FunctionKind::Method,
+        _ if is_inside_test_block(source_file, function_node) => FunctionKind::Test,
+        _ => FunctionKind::Function,
+    }
+}
+
+/// Whether the node creates an object, an array or a copy of one, like `items.map(...)`
+pub fn is_allocation_suspect(source_file: &str, node: Node) -> bool {
+    match node.kind() {
+        "new_expression" | "array" | "object" => true,
+        "call_expression" => node
+            .child_by_field_name("function")
+            .filter(|function| function.kind() == "member_expression")
+            .and_then(|function| function.child_by_field_name("property"))
+            .is_some_and(|method| ALLOCATING_METHODS.contains(&node_value(source_file, method))),
+        _ => false,
+    }
+}
+
+/// The packages declared in a package.json
+pub fn declared_packages(manifest: &str) -> Vec<String> {
+    let Ok(manifest) = serde_json::from_str::<serde_json::Value>(manifest) else {
+        return vec![];
+    };
+
+    PACKAGE_JSON_DEPENDENCY_KEYS
+        .iter()
+        .filter_map(|key| manifest.get(key)?.as_object())
+        .flat_map(|dependencies| dependencies.keys().cloned())
+        .collect()
+}
+
+/// The packages imported or required by the code, bar relative paths and node's own modules
+pub fn referenced_packages(source: &str, tree: &Tree) -> Vec<String> {
+    let mut packages: Vec<String> = traverse(tree.walk(), Order::Pre)
+        .filter_map(|node| match node.kind() {
+            "import_statement" | "export_statement" => node.child_by_field_name("source"),
+            "call_expression" => {
+                let function = node.child_by_field_name("function")?;
+                if function.kind() != "import" && node_value(source, function) != "require" {
+                    return None;
+                }
+                node.child_by_field_name("arguments")?.named_child(0)
+            }
+            _ => None,
+        })
+        .filter(|specifier| specifier.kind() == "string")
+        .filter_map(|specifier| {
+            let specifier = node_value(source, specifier).trim_matches(['"', '\'', '`']);
+            package_of_specifier(specifier)
+        })
+        .collect();
+    packages.sort();
+    packages.dedup();
+
+    packages
+}
+
+/// The package a specifier imports from, like `lodash` for `lodash/fp`, or `@scope/name` for
+/// `@scope/name/sub`
+pub fn package_of_specifier(specifier: &str) -> Option<String> {
+    if specifier.starts_with(['.', '/']) || specifier.starts_with("node:") {
+        return None;
+    }
+    let segments = if specifier.starts_with('@') { 2 } else { 1 };
+    let package = specifier
+        .splitn(segments + 1, '/')
+        .take(segments)
+        .collect::<Vec<&str>>()
+        .join("/");
+
+    (!NODE_BUILTIN_MODULES.contains(&package.as_str())).then_some(package)
+}
+
+// Test runners like jest and mocha declare tests with calls like `describe("...", () => {...})`
+fn is_inside_test_block(source_file: &str, node: Node) -> bool {
+    let mut ancestor = node.parent();
+    while let Some(node) = ancestor {
+        if node.kind() == "call_expression" {
+            let callee = name_of(source_file, node, "function").unwrap_or_default();
+            let callee = callee.split('.').next().unwrap_or_default();
+            if TEST_BLOCK_CALLEES.contains(&callee) {
+                return true;
+            }
+        }
+        ancestor = node.parent();
+    }
+
+    false
+}
+
+// If `node` declares something named `name` that can hold functions, returns its scope
+fn declared_scope<'a>(source_file: &str, node: Node<'a>, name: &str) -> Option<Node<'a>> {
+    match node.kind() {
+        "class_declaration" | "internal_module" | "module" => {
+            if name_of(source_file, node, "name")? == name {
+                node.child_by_field_name("body")
+            } else {
+                None
+            }
+        }
+        "variable_declarator" => {
+            if name_of(source_file, node, "name")? == name {
+                scope_of_value(node.child_by_field_name("value")?)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+fn member_scope<'a>(source_file: &str, scope: Node<'a>, name: &str) -> Option<Node<'a>> {
+    if scope.kind() == "statement_block" {
+        return traverse(scope.walk(), Order::Pre)
+            .skip(1)
+            .find_map(|node| declared_scope(source_file, node, name));
+    }
+
+    let mut cursor = scope.walk();
+    let found = scope
+        .named_children(&mut cursor)
+        .find_map(|member| match member.kind() {
+            "pair" if key_of(source_file, member)? == name => {
+                scope_of_value(member.child_by_field_name("value")?)
+            }
+            "field_definition" | "public_field_definition"
+                if field_name_of(source_file, member)? == name =>
+            {
+                scope_of_value(member.child_by_field_name("value")?)
+            }
+            _ => None,
+        });
+
+    found
+}
+
+fn scope_of_value(value: Node) -> Option<Node> {
+    match value.kind() {
+        "object" => Some(value),
+        "class" => value.child_by_field_name("body"),
+        _ => None,
+    }
+}
+
+fn holds_function(node: Node, field: &str) -> bool {
+    node.child_by_field_name(field)
+        .is_some_and(|value| FUNCTION_VALUE_KINDS.contains(&value.kind()))
+}
+
+fn name_of<'a>(source_file: &'a str, node: Node<'a>, field: &str) -> Option<&'a str> {
+    node.child_by_field_name(field)
+        .map(|name_node| node_value(source_file, name_node))
+}
+
+// Object keys may be quoted, as in `{ "onMessage": () => {} }`
+fn key_of<'a>(source_file: &'a str, pair: Node<'a>) -> Option<&'a str> {
+    name_of(source_file, pair, "key").map(|key| key.trim_matches(|c| c == '"' || c == '\''))
+}
+
+// The javascript grammar names the field `property`, whereas typescript names it `name`
+fn field_name_of<'a>(source_file: &'a str, field: Node<'a>) -> Option<&'a str> {
+    name_of(source_file, field, "property").or_else(|| name_of(source_file, field, "name"))
+}
@@ -0,0 +1,356 @@
+use std::fmt::{Display, Formatter};
+
+use tree_sitter::{Node, Range, Tree};
+
+use crate::helpers::tree_sitter::node_value;
+use crate::supported_languages::supported_language::{FunctionKind, Language, SupportedLanguage};
+
+/// Comprehensions build a new list, binary or map
+const COMPREHENSION_KINDS: &[&str] = &[
+    "list_comprehension",
+    "binary_comprehension",
+    "map_comprehension",
+];
+
+pub struct ErlangAnalyser {
+    inner: Language,
+}
+
+impl Default for ErlangAnalyser {
+    fn default() -> Self {
+        Self {
+            inner: Language::Erlang,
+        }
+    }
+}
+
+impl Display for ErlangAnalyser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl SupportedLanguage for ErlangAnalyser {
+    /// Functions are identified by their name and arity, like `handle_call/3`, as functions with
+    /// the same name and another arity are other functions. Without an arity, the first function
+    /// of the name is picked. The parent is the module.
+    fn find_correct_node<'a>(
+        &self,
+        source_file: &str,
+        root_tree: &'a Tree,
+        parent_identifier: &Option<String>,
+        function_identifier: &Option<String>,
+        kind: &Option<FunctionKind>,
+    ) -> Result<Node<'a>, &'a str> {
+        let Some(function) = function_identifier else {
+            return Ok(root_tree.root_node());
+        };
+        if parent_identifier
+            .as_ref()
+            .is_some_and(|parent| module_name(source_file, root_tree) != Some(parent))
+        {
+            return Err("parent not found");
+        }
+
+        let (name, arity) = match function.rsplit_once('/') {
+            Some((name, arity)) => match arity.parse::<usize>() {
+                Ok(arity) => (name, Some(arity)),
+                Err(_) => (function.as_str(), None),
+            },
+            None => (function.as_str(), None),
+        };
+
+        self.find_all_functions(root_tree)
+            .into_iter()
+            .filter(|function_node| {
+                name_and_arity(source_file, *function_node).is_some_and(
+                    |(other_name, other_arity)| {
+                        other_name == name && arity.is_none_or(|arity| arity == other_arity)
+                    },
+                )
+            })
+            .find(|function_node| self.is_of_kind(source_file, *function_node, kind))
+            .ok_or("function not found")
+    }
+
+    /// The first clause of every function, which the others follow
+    fn find_all_functions<'a>(&self, root_tree: &'a Tree) -> Vec<Node<'a>> {
+        let mut cursor = root_tree.walk();
+        let functions = root_tree
+            .root_node()
+            .named_children(&mut cursor)
+            .filter(|node| node.kind() == "fun_decl")
+            .filter(|node| previous_form(*node).is_none_or(|previous| !continues(previous)))
+            .collect();
+
+        functions
+    }
+
+    fn node_kinds(&self) -> &'static [&'static str] {
+        &["fun_decl", "function_clause", "module_attribute"]
+    }
+
+    /// EUnit runs the functions whose name ends with `_test`, and the tests that those ending
+    /// with `_test_` generate
+    fn function_kind(&self, source_file: &str, function_node: Node) -> FunctionKind {
+        match name_and_arity(source_file, function_node) {
+            Some((name, 0)) if name.ends_with("_test") || name.ends_with("_test_") => {
+                FunctionKind::Test
+            }
+            _ => FunctionKind::Function,
+        }
+    }
+
+    fn language(&self) -> tree_sitter::Language {
+        tree_sitter_erlang::language()
+    }
+
+    /// Comprehensions, and `++`, which copies the list on its left
+    fn is_allocation_suspect(&self, _source_file: &str, node: Node) -> bool {
+        COMPREHENSION_KINDS.contains(&node.kind())
+            || (node.kind() == "binary_op_expr"
+                && node
+                    .child(1)
+                    .is_some_and(|operator| operator.kind() == "++"))
+    }
+
+    fn default_instructions(&self) -> &'static str {
+        "Prefer tail-recursive functions with accumulators, build iolists rather than concatenating with `++`, keep text in binaries, and avoid copying large terms between processes."
+    }
+
+    /// Erlang code calls modules, which do not name the applications `rebar.config` declares
+    fn manifest_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn declared_packages(&self, _manifest: &str) -> Vec<String> {
+        vec![]
+    }
+
+    fn referenced_packages(&self, _source: &str, _tree: &Tree) -> Vec<String> {
+        vec![]
+    }
+
+    fn line_comment(&self) -> &'static str {
+        "%"
+    }
+
+    /// Every clause of a function is a form of its own, ending with `;` but for the last one
+    fn clauses<'a>(&self, source_file: &str, function_node: Node<'a>) -> Vec<Node<'a>> {
+        let mut clauses = vec![function_node];
+        let mut clause = function_node;
+        while let Some(next) = next_form(clause) {
+            if !continues(clause)
+                || name_and_arity(source_file, next) != name_and_arity(source_file, function_node)
+            {
+                break;
+            }
+            clauses.push(next);
+            clause = next;
+        }
+
+        clauses
+    }
+
+    /// A suggestion replaces every clause of the function
+    fn replaced_range(&self, source_file: &str, node: Node) -> Range {
+        let last_clause = *self.clauses(source_file, node).last().unwrap();
+
+        Range {
+            end_byte: last_clause.end_byte(),
+            end_point: last_clause.end_position(),
+            ..node.range()
+        }
+    }
+}
+
+/// The name of the module, as `-module` declares it
+fn module_name<'a>(source_file: &'a str, root_tree: &'a Tree) -> Option<&'a str> {
+    let mut cursor = root_tree.walk();
+    let module_attribute = root_tree
+        .root_node()
+        .named_children(&mut cursor)
+        .find(|node| node.kind() == "module_attribute")?;
+
+    module_attribute
+        .child_by_field_name("name")
+        .map(|name| node_value(source_file, name))
+}
+
+/// The name and number of arguments of the clause of the form, if it is a function one
+fn name_and_arity<'a>(source_file: &'a str, fun_decl: Node<'a>) -> Option<(&'a str, usize)> {
+    let clause = fun_decl
+        .child_by_field_name("clause")
+        .filter(|clause| clause.kind() == "function_clause")?;
+    let name = clause.child_by_field_name("name")?;
+    let arguments = clause.child_by_field_name("args")?;
+
+    Some((node_value(source_file, name), arguments.named_child_count()))
+}
+
+/// Whether another clause of the function follows the clause
+fn continues(fun_decl: Node) -> bool {
+    fun_decl.kind() == "fun_decl"
+        && fun_decl
+            .child(fun_decl.child_count().saturating_sub(1))
+            .is_some_and(|separator| separator.kind() == ";")
+}
+
+/// The form before the form, past comments
+fn previous_form(form: Node) -> Option<Node> {
+    let mut node = form.prev_named_sibling()?;
+    while node.kind() == "comment" {
+        node = node.prev_named_sibling()?;
+    }
+
+    Some(node)
+}
+
+/// The form after the form, past comments
+fn next_form(form: Node) -> Option<Node> {
+    let mut node = form.next_named_sibling()?;
+    while node.kind() == "comment" {
+        node = node.next_named_sibling()?;
+    }
+
+    Some(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Tree;
+
+    use crate::helpers::tree_sitter::{node_value, parse_source_with_language};
+    use crate::supported_languages::erlang::ErlangAnalyser;
+    use crate::supported_languages::supported_language::{FunctionKind, SupportedLanguage};
+
+    const ERLANG_SOURCE: &str = r#"-module(cart).
+-behaviour(gen_server).
+-export([handle_call/3, total/1, total/2]).
+
+%% Sums the prices of the items
+total([]) -> 0;
+%% Items without a price are free
+total([{_, Price} | Rest]) when Price > 0 ->
+    Price + total(Rest).
+
+total(Items, Discount) ->
+    total(Items) - Discount.
+
+handle_call(total, _From, State) ->
+    {reply, total(State), State};
+handle_call({add, Item}, _From, State) ->
+    {reply, ok, [Item | State]}.
+
+total_test() -> 0 = total([]).
+"#;
+
+    fn erlang_source_tree() -> Tree {
+        parse_source_with_language(ERLANG_SOURCE, tree_sitter_erlang::language())
+    }
+
+    // What a suggestion for the function replaces
+    fn find(parent: Option<&str>, function: &str) -> Option<String> {
+        let tree = erlang_source_tree();
+        let ra: ErlangAnalyser = Default::default();
+
+        ra.find_correct_node(
+            ERLANG_SOURCE,
+            &tree,
+            &parent.map(str::to_string),
+            &Some(function.to_string()),
+            &None,
+        )
+        .ok()
+        .map(|function_node| {
+            let range = ra.replaced_range(ERLANG_SOURCE, function_node);
+            ERLANG_SOURCE[range.start_byte..range.end_byte].to_string()
+        })
+    }
+
+    #[test]
+    fn no_function() {
+        let tree = erlang_source_tree();
+        let ra: ErlangAnalyser = Default::default();
+
+        assert_eq!(
+            ERLANG_SOURCE,
+            node_value(
+                ERLANG_SOURCE,
+                ra.find_correct_node(ERLANG_SOURCE, &tree, &None, &None, &None)
+                    .unwrap(),
+            ),
+        )
+    }
+
+    #[test]
+    fn every_clause() {
+        let target = r#"handle_call(total, _From, State) ->
+    {reply, total(State), State};
+handle_call({add, Item}, _From, State) ->
+    {reply, ok, [Item | State]}."#;
+
+        assert_eq!(Some(target), find(None, "handle_call/3").as_deref());
+        assert_eq!(Some(target), find(Some("cart"), "handle_call").as_deref());
+        assert_eq!(None, find(Some("checkout"), "handle_call/3"));
+    }
+
+    #[test]
+    fn by_arity() {
+        // Comments between the clauses are part of the function
+        let target = r#"total([]) -> 0;
+%% Items without a price are free
+total([{_, Price} | Rest]) when Price > 0 ->
+    Price + total(Rest)."#;
+
+        assert_eq!(Some(target), find(None, "total/1").as_deref());
+        assert_eq!(Some(target), find(None, "total").as_deref());
+        assert_eq!(
+            Some("total(Items, Discount) ->\n    total(Items) - Discount."),
+            find(None, "total/2").as_deref()
+        );
+        assert_eq!(None, find(None, "total/3"));
+    }
+
+    #[test]
+    fn functions() {
+        let tree = erlang_source_tree();
+        let ra: ErlangAnalyser = Default::default();
+        let functions: Vec<String> = ra
+            .find_all_functions(&tree)
+            .into_iter()
+            .map(|function_node| {
+                let (name, arity) = super::name_and_arity(ERLANG_SOURCE, function_node).unwrap();
+                format!("{}/{}", name, arity)
+            })
+            .collect();
+
+        assert_eq!(
+            vec!["total/1", "total/2", "handle_call/3", "total_test/0"],
+            functions
+        );
+    }
+
+    #[test]
+    fn function_kinds() {
+        let tree = erlang_source_tree();
+        let ra: ErlangAnalyser = Default::default();
+
+        for (function, kind) in [
+            ("total/1", FunctionKind::Function),
+            ("total_test/0", FunctionKind::Test),
+        ] {
+            let function_node = ra
+                .find_correct_node(
+                    ERLANG_SOURCE,
+                    &tree,
+                    &None,
+                    &Some(function.to_string()),
+                    &None,
+                )
+                .unwrap();
+
+            assert_eq!(kind, ra.function_kind(ERLANG_SOURCE, function_node))
+        }
+    }
+}
@@ -1,9 +1,22 @@
 use std::fmt::{Display, Formatter};
 
 use tree_sitter::{Node, Tree};
+use tree_sitter_traversal::{traverse, Order};
 
 use crate::helpers::tree_sitter::{find_all_of_kind, node_value};
-use crate::supported_languages::supported_language::{Language, SupportedLanguage};
+use crate::supported_languages::supported_language::{FunctionKind, Language, SupportedLanguage};
+
+const ALLOCATING_FUNCTIONS: &[&str] = &[
+    "append",
+    "make",
+    "new",
+    "fmt.Sprint",
+    "fmt.Sprintf",
+    "fmt.Sprintln",
+    "strings.Join",
+    "strings.Split",
+    "strings.Repeat",
+];
 
 pub struct GoAnalyser {
     inner: Language,
@@ -30,36 +43,19 @@ impl SupportedLanguage for GoAnalyser {
         root_tree: &'a Tree,
         parent_identifier: &Option<String>,
         function_identifier: &Option<String>,
+        kind: &Option<FunctionKind>,
     ) -> Result<Node<'a>, &'a str> {
         if let Some(function) = function_identifier {
             return if let Some(parent) = parent_identifier {
-                let all_method_decls = find_all_of_kind(root_tree.walk(), "method_declaration");
-
-                let candidate_function_node = all_method_decls.into_iter().find(|method_decl| {
-                    let method_name = node_value(
-                        source_file,
-                        method_decl.child_by_field_name("name").unwrap(),
-                    );
-
-                    let receiver_node = method_decl.child_by_field_name("receiver").unwrap();
-
-                    // method receiver nodes only have one argument, a single parameter declaration
-                    let parameter_declaration_node = receiver_node.child(1).unwrap();
-
-                    // It always has a type
-                    let receiver_type = parameter_declaration_node
-                        .child_by_field_name("type")
-                        .unwrap();
-
-                    // That can either be a pointer, or not
-                    return if receiver_type.kind() == "type_identifier" {
-                        node_value(source_file, receiver_type) == parent && method_name == function
-                    } else {
-                        let pointer_receiver_type = receiver_type.child(1).unwrap();
-
-                        node_value(source_file, pointer_receiver_type) == parent
-                            && method_name == function
-                    };
+                let mut all_method_decls = find_all_of_kind(root_tree.walk(), "method_declaration")
+                    .into_iter()
+                    .filter(|method_decl| self.is_of_kind(source_file, *method_decl, kind));
+
+                let candidate_function_node = all_method_decls.find(|method_decl| {
+                    method_decl
+                        .child_by_field_name("name")
+                        .is_some_and(|name| node_value(source_file, name) == function)
+                        && receiver_type_name(source_file, *method_decl) == Some(parent.as_str())
                 });
 
                 if let Some(function_node) = candidate_function_node {
@@ -68,15 +64,14 @@ impl SupportedLanguage for GoAnalyser {
 
                 Err("method not found")
             } else {
-                let all_functions = find_all_of_kind(root_tree.walk(), "function_declaration");
-
-                if let Some(function_node) = all_functions.into_iter().find(|function_decl| {
-                    let function_name = node_value(
-                        source_file,
-                        function_decl.child_by_field_name("name").unwrap(),
-                    );
-
-                    function_name == function
+                let mut all_functions = find_all_of_kind(root_tree.walk(), "function_declaration")
+                    .into_iter()
+                    .filter(|function_decl| self.is_of_kind(source_file, *function_decl, kind));
+
+                if let Some(function_node) = all_functions.find(|function_decl| {
+                    function_decl
+                        .child_by_field_name("name")
+                        .is_some_and(|name| node_value(source_file, name) == function)
                 }) {
                     return Ok(function_node);
                 }
@@ -87,9 +82,198 @@ impl SupportedLanguage for GoAnalyser {
         Ok(root_tree.root_node())
     }
 
+    fn find_all_functions<'a>(&self, root_tree: &'a Tree) -> Vec<Node<'a>> {
+        traverse(root_tree.walk(), Order::Pre)
+            .filter(|node| {
+                node.kind() == "function_declaration" || node.kind() == "method_declaration"
+            })
+            .collect()
+    }
+
+    fn node_kinds(&self) -> &'static [&'static str] {
+        &["function_declaration", "method_declaration"]
+    }
+
+    fn function_kind(&self, source_file: &str, function_node: Node) -> FunctionKind {
+        let name = function_node
+            .child_by_field_name("name")
+            .map_or("", |name| node_value(source_file, name));
+        let parameters = function_node.child_by_field_name("parameters");
+
+        if function_node.kind() == "method_declaration" {
+            return if parameters.is_some_and(|parameters| parameters.named_child_count() == 0)
+                && returns_a_field(function_node)
+            {
+                FunctionKind::Getter
+            } else {
+                FunctionKind::Method
+            };
+        }
+
+        // `go test` only runs these when they take a `*testing.T`, `*testing.B` or `*testing.F`
+        let is_test = ["Test", "Benchmark", "Fuzz"]
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+            && parameters.is_some_and(|parameters| {
+                node_value(source_file, parameters).contains("*testing.")
+            });
+        if is_test {
+            FunctionKind::Test
+        } else if name.starts_with("New") {
+            FunctionKind::Constructor
+        } else {
+            FunctionKind::Function
+        }
+    }
+
     fn language(&self) -> tree_sitter::Language {
         tree_sitter_go::language()
     }
+
+    fn is_allocation_suspect(&self, source_file: &str, node: Node) -> bool {
+        match node.kind() {
+            "composite_literal" => true,
+            "call_expression" => node
+                .child_by_field_name("function")
+                .is_some_and(|function| {
+                    ALLOCATING_FUNCTIONS.contains(&node_value(source_file, function))
+                }),
+            _ => false,
+        }
+    }
+
+    fn default_instructions(&self) -> &'static str {
+        "Preallocate slices and maps whose size is known, avoid fmt in hot paths, and build strings with a strings.Builder."
+    }
+
+    fn manifest_name(&self) -> Option<&'static str> {
+        Some("go.mod")
+    }
+
+    /// Go code refers to modules by the name of their package, which is usually the last element
+    /// of their path, bar the major version, as in `yaml` for `gopkg.in/yaml.v3`
+    fn declared_packages(&self, manifest: &str) -> Vec<String> {
+        let mut in_require_block = false;
+        let mut module_paths = vec![];
+        for line in manifest.lines() {
+            let line = line.split("//").next().unwrap_or_default().trim();
+            if in_require_block {
+                if line == ")" {
+                    in_require_block = false;
+                } else {
+                    module_paths.extend(line.split_whitespace().next());
+                }
+            } else if let Some(directive) = line.strip_prefix("require") {
+                let directive = directive.trim();
+                if directive == "(" {
+                    in_require_block = true;
+                } else {
+                    module_paths.extend(directive.split_whitespace().next());
+                }
+            }
+        }
+
+        module_paths
+            .into_iter()
+            .filter_map(|module_path| {
+                let mut elements = module_path.rsplit('/');
+                let mut last = elements.next()?;
+                let is_major_version = |element: &str| {
+                    element
+                        .strip_prefix('v')
+                        .is_some_and(|version| version.parse::<u64>().is_ok())
+                };
+                if is_major_version(last) {
+                    last = elements.next()?;
+                }
+                let package = match last.rsplit_once('.') {
+                    Some((package, version)) if is_major_version(version) => package,
+                    _ => last,
+                };
+
+                Some(package.to_string())
+            })
+            .collect()
+    }
+
+    /// The packages the code imports, and the ones it uses, like `strings` in `strings.Builder`.
+    /// Operands of selectors that are also declared or used on their own are variables.
+    fn referenced_packages(&self, source: &str, tree: &Tree) -> Vec<String> {
+        let mut packages = vec![];
+        let mut variables = vec![];
+        for node in traverse(tree.walk(), Order::Pre) {
+            match node.kind() {
+                "import_spec" => {
+                    let Some(path) = node.child_by_field_name("path") else {
+                        continue;
+                    };
+                    let package = match node.child_by_field_name("name") {
+                        Some(name) => node_value(source, name),
+                        None => {
+                            let path = node_value(source, path).trim_matches(['"', '`']);
+                            path.rsplit('/').next().unwrap_or_default()
+                        }
+                    };
+                    packages.push(package.to_string());
+                }
+                "qualified_type" => {
+                    if let Some(package) = node.child_by_field_name("package") {
+                        packages.push(node_value(source, package).to_string());
+                    }
+                }
+                "identifier" => {
+                    let is_operand = node.parent().is_some_and(|parent| {
+                        parent.kind() == "selector_expression"
+                            && parent.child_by_field_name("operand") == Some(node)
+                    });
+                    if is_operand {
+                        packages.push(node_value(source, node).to_string());
+                    } else {
+                        variables.push(node_value(source, node));
+                    }
+                }
+                _ => {}
+            }
+        }
+        packages.retain(|package| !variables.contains(&package.as_str()) && package != "_");
+        packages.sort();
+        packages.dedup();
+
+        packages
+    }
+}
+
+// The type of the receiver of the method, like `Greeter` in both `(g Greeter)` and `(g *Greeter)`
+#[allow(clippy::needless_return)]
+fn receiver_type_name<'a>(source_file: &'a str, method_decl: Node<'a>) -> Option<&'a str> {
+    // method receiver nodes only have one argument, a single parameter declaration
+    let parameter_declaration_node = method_decl.child_by_field_name("receiver")?.child(1)?;
+    let receiver_type = parameter_declaration_node.child_by_field_name("type")?;
+
+    // That can either be a pointer, or not
+    return if receiver_type.kind() == "type_identifier" {
+        Some(node_value(source_file, receiver_type))
+    } else {
+        Some(node_value(source_file, receiver_type.child(1)?))
+    };
+}
+
+// Whether the body is a single `return receiver.field`
+fn returns_a_field(method_decl: Node) -> bool {
+    let Some(body) = method_decl.child_by_field_name("body") else {
+        return false;
+    };
+    if body.named_child_count() != 1 {
+        return false;
+    }
+
+    let statement = body.named_child(0).unwrap();
+    statement.kind() == "return_statement"
+        && statement
+            .named_child(0)
+            .filter(|expression_list| expression_list.named_child_count() == 1)
+            .and_then(|expression_list| expression_list.named_child(0))
+            .is_some_and(|expression| expression.kind() == "selector_expression")
 }
 
 #[cfg(test)]
@@ -98,7 +282,7 @@ mod tests {
 
     use crate::helpers::tree_sitter::{node_value, parse_source_with_language};
     use crate::supported_languages::go::GoAnalyser;
-    use crate::supported_languages::supported_language::SupportedLanguage;
+    use crate::supported_languages::supported_language::{FunctionKind, SupportedLanguage};
 
     const GO_SOURCE: &str = r#"package main
 
@@ -114,6 +298,16 @@ type Greeter struct {
     name string
 }
 
+// Constructor of the struct
+func NewGreeter(name string) *Greeter {
+    return &Greeter{name: name}
+}
+
+// Getter of the struct
+func (g Greeter) Name() string {
+    return g.name
+}
+
 // Method in the struct
 func (g Greeter) greet() {
     fmt.Printf("Hello from %s, inside the Greeter struct!\n", g.name)
@@ -145,7 +339,7 @@ func main() {
             GO_SOURCE,
             node_value(
                 GO_SOURCE,
-                ra.find_correct_node(GO_SOURCE, &tree, &None, &None)
+                ra.find_correct_node(GO_SOURCE, &tree, &None, &None, &None)
                     .unwrap(),
             ),
         )
@@ -164,7 +358,7 @@ func main() {
             target,
             node_value(
                 GO_SOURCE,
-                ra.find_correct_node(GO_SOURCE, &tree, &None, &Some("greet".to_string()))
+                ra.find_correct_node(GO_SOURCE, &tree, &None, &Some("greet".to_string()), &None)
                     .unwrap(),
             ),
         )
@@ -187,7 +381,8 @@ func main() {
                     GO_SOURCE,
                     &tree,
                     &Some("Greeter".to_string()),
-                    &Some("greet".to_string())
+                    &Some("greet".to_string()),
+                    &None,
                 )
                 .unwrap(),
             ),
@@ -211,10 +406,71 @@ func main() {
                     GO_SOURCE,
                     &tree,
                     &Some("Greeter".to_string()),
-                    &Some("greetPointer".to_string())
+                    &Some("greetPointer".to_string()),
+                    &None,
                 )
                 .unwrap(),
             ),
         )
     }
+
+    #[test]
+    fn function_kinds() {
+        let tree = go_source_tree();
+        let ra: GoAnalyser = Default::default();
+
+        for (parent, function, kind) in [
+            (None, "greet", FunctionKind::Function),
+            (None, "NewGreeter", FunctionKind::Constructor),
+            (Some("Greeter"), "greet", FunctionKind::Method),
+            (Some("Greeter"), "Name", FunctionKind::Getter),
+        ] {
+            let function_node = ra
+                .find_correct_node(
+                    GO_SOURCE,
+                    &tree,
+                    &parent.map(str::to_string),
+                    &Some(function.to_string()),
+                    &None,
+                )
+                .unwrap();
+
+            assert_eq!(kind, ra.function_kind(GO_SOURCE, function_node))
+        }
+    }
+
+    #[test]
+    fn packages() {
+        let source = r#"func join(names []string) string {
+	var sb strings.Builder
+	for _, name := range names {
+		sb.WriteString(name)
+	}
+	var _ bytes.Buffer
+	return fmt.Sprint(sb.String())
+}"#;
+        let tree = parse_source_with_language(source, tree_sitter_go::language());
+        let ra: GoAnalyser = Default::default();
+
+        assert_eq!(
+            vec!["bytes", "fmt", "strings"],
+            ra.referenced_packages(source, &tree)
+        );
+        assert_eq!(
+            vec!["errors", "yaml", "cmp"],
+            ra.declared_packages(
+                r#"module example.com/greeter
+
+go 1.21
+
+require github.com/pkg/errors v0.9.1
+
+require (
+	gopkg.in/yaml.v3 v3.0.1 // indirect
+	github.com/google/go-cmp/cmp/v2 v2.0.0
+)
+"#
+            )
+        );
+    }
 }
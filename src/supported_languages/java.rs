@@ -0,0 +1,540 @@
+use std::fmt::{Display, Formatter};
+
+use tree_sitter::{Node, Tree};
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::helpers::tree_sitter::node_value;
+use crate::supported_languages::supported_language::{FunctionKind, Language, SupportedLanguage};
+
+const FUNCTION_KINDS: &[&str] = &[
+    "method_declaration",
+    "constructor_declaration",
+    "compact_constructor_declaration",
+];
+
+/// What a method can be declared in, by name. Constants of enums can have bodies of their own.
+const TYPE_KINDS: &[&str] = &[
+    "class_declaration",
+    "interface_declaration",
+    "enum_declaration",
+    "record_declaration",
+    "annotation_type_declaration",
+    "enum_constant",
+];
+
+const TEST_ANNOTATIONS: &[&str] = &["Test", "ParameterizedTest", "RepeatedTest", "TestFactory"];
+
+const ALLOCATING_METHODS: &[&str] = &[
+    "String.format",
+    "String.join",
+    "String.valueOf",
+    "toString",
+    "split",
+    "substring",
+    "stream",
+    "boxed",
+];
+
+pub struct JavaAnalyser {
+    inner: Language,
+}
+
+impl Default for JavaAnalyser {
+    fn default() -> Self {
+        Self {
+            inner: Language::Java,
+        }
+    }
+}
+
+impl Display for JavaAnalyser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl SupportedLanguage for JavaAnalyser {
+    fn find_correct_node<'a>(
+        &self,
+        source_file: &str,
+        root_tree: &'a Tree,
+        parent_identifier: &Option<String>,
+        function_identifier: &Option<String>,
+        kind: &Option<FunctionKind>,
+    ) -> Result<Node<'a>, &'a str> {
+        let Some(function) = function_identifier else {
+            return Ok(root_tree.root_node());
+        };
+
+        // Overloads share a name, so the first one of the kind is picked
+        let mut candidates = self
+            .find_all_functions(root_tree)
+            .into_iter()
+            .filter(|function_node| {
+                function_node
+                    .child_by_field_name("name")
+                    .is_some_and(|name| node_value(source_file, name) == function)
+            })
+            .filter(|function_node| self.is_of_kind(source_file, *function_node, kind));
+
+        let Some(parent) = parent_identifier else {
+            return candidates.next().ok_or("method not found");
+        };
+        // The parent may be a dotted path through nested types, like `Outer.Inner`
+        let path: Vec<&str> = parent.split('.').collect();
+        if let Some(function_node) = candidates
+            .find(|function_node| enclosing_types(source_file, *function_node).ends_with(&path))
+        {
+            return Ok(function_node);
+        }
+
+        let parent_exists = traverse(root_tree.walk(), Order::Pre)
+            .filter(|node| TYPE_KINDS.contains(&node.kind()))
+            .any(|node| {
+                node.child_by_field_name("name")
+                    .is_some_and(|name| Some(&node_value(source_file, name)) == path.last())
+            });
+        if parent_exists {
+            Err("method not found")
+        } else {
+            Err("parent not found")
+        }
+    }
+
+    /// Abstract methods and those of interfaces without a default have no body to improve
+    fn find_all_functions<'a>(&self, root_tree: &'a Tree) -> Vec<Node<'a>> {
+        traverse(root_tree.walk(), Order::Pre)
+            .filter(|node| FUNCTION_KINDS.contains(&node.kind()))
+            .filter(|node| node.child_by_field_name("body").is_some())
+            .collect()
+    }
+
+    fn node_kinds(&self) -> &'static [&'static str] {
+        &[
+            "method_declaration",
+            "constructor_declaration",
+            "class_declaration",
+            "interface_declaration",
+            "enum_declaration",
+        ]
+    }
+
+    fn function_kind(&self, source_file: &str, function_node: Node) -> FunctionKind {
+        if function_node.kind() != "method_declaration" {
+            return FunctionKind::Constructor;
+        }
+
+        let mut cursor = function_node.walk();
+        let modifiers = function_node
+            .children(&mut cursor)
+            .find(|child| child.kind() == "modifiers");
+        let is_test = modifiers.is_some_and(|modifiers| {
+            let mut cursor = modifiers.walk();
+            let is_test = modifiers.named_children(&mut cursor).any(|annotation| {
+                annotation
+                    .child_by_field_name("name")
+                    .is_some_and(|name| TEST_ANNOTATIONS.contains(&node_value(source_file, name)))
+            });
+            is_test
+        });
+        let is_static = modifiers.is_some_and(|modifiers| {
+            let mut cursor = modifiers.walk();
+            let is_static = modifiers
+                .children(&mut cursor)
+                .any(|modifier| modifier.kind() == "static");
+            is_static
+        });
+        let name = function_node
+            .child_by_field_name("name")
+            .map_or("", |name| node_value(source_file, name));
+        let takes_nothing = function_node
+            .child_by_field_name("parameters")
+            .is_some_and(|parameters| parameters.named_child_count() == 0);
+
+        if is_test {
+            FunctionKind::Test
+        } else if is_static {
+            FunctionKind::Function
+        } else if (name.starts_with("get") || name.starts_with("is"))
+            && takes_nothing
+            && returns_a_field(function_node)
+        {
+            FunctionKind::Getter
+        } else {
+            FunctionKind::Method
+        }
+    }
+
+    fn language(&self) -> tree_sitter::Language {
+        tree_sitter_java::language()
+    }
+
+    fn is_allocation_suspect(&self, source_file: &str, node: Node) -> bool {
+        match node.kind() {
+            "object_creation_expression" | "array_creation_expression" => true,
+            "method_invocation" => {
+                let name = node
+                    .child_by_field_name("name")
+                    .map_or("", |name| node_value(source_file, name));
+                let qualified_name = match node.child_by_field_name("object") {
+                    Some(object) => format!("{}.{}", node_value(source_file, object), name),
+                    None => name.to_string(),
+                };
+                ALLOCATING_METHODS.contains(&name)
+                    || ALLOCATING_METHODS.contains(&qualified_name.as_str())
+            }
+            _ => false,
+        }
+    }
+
+    fn default_instructions(&self) -> &'static str {
+        "Presize collections whose size is known, avoid boxing and streams in hot loops, and build strings with a StringBuilder."
+    }
+
+    fn manifest_name(&self) -> Option<&'static str> {
+        Some("pom.xml")
+    }
+
+    /// Java code refers to dependencies by packages that rarely match their coordinates, but
+    /// mostly share the first two elements of their group, like `com.google` for
+    /// `com.google.guava`
+    fn declared_packages(&self, manifest: &str) -> Vec<String> {
+        manifest
+            .split("<dependency>")
+            .skip(1)
+            .filter_map(|dependency| {
+                let group = dependency
+                    .split("<groupId>")
+                    .nth(1)?
+                    .split("</groupId>")
+                    .next()?;
+                Some(package_prefix(group.trim()))
+            })
+            .collect()
+    }
+
+    /// The packages the code imports, by the first two elements of their name, bar the ones of the
+    /// JDK
+    fn referenced_packages(&self, source: &str, tree: &Tree) -> Vec<String> {
+        let mut packages: Vec<String> = traverse(tree.walk(), Order::Pre)
+            .filter(|node| node.kind() == "import_declaration")
+            .filter_map(|import| import.named_child(0))
+            .map(|name| package_prefix(node_value(source, name)))
+            .filter(|package| {
+                !["java.", "javax.", "jdk.", "sun."]
+                    .iter()
+                    .any(|jdk| format!("{}.", package).starts_with(jdk))
+            })
+            .collect();
+        packages.sort();
+        packages.dedup();
+
+        packages
+    }
+}
+
+fn package_prefix(name: &str) -> String {
+    name.split('.').take(2).collect::<Vec<&str>>().join(".")
+}
+
+/// The names of the types the function is declared in, outermost first. Anonymous classes have
+/// none, and are left out.
+fn enclosing_types<'a>(source_file: &'a str, function_node: Node<'a>) -> Vec<&'a str> {
+    let mut types = vec![];
+    let mut node = function_node.parent();
+    while let Some(ancestor) = node {
+        if TYPE_KINDS.contains(&ancestor.kind()) {
+            types.extend(
+                ancestor
+                    .child_by_field_name("name")
+                    .map(|name| node_value(source_file, name)),
+            );
+        }
+        node = ancestor.parent();
+    }
+    types.reverse();
+
+    types
+}
+
+// Whether the body is a single `return field` or `return this.field`
+fn returns_a_field(method_decl: Node) -> bool {
+    let Some(body) = method_decl.child_by_field_name("body") else {
+        return false;
+    };
+    if body.named_child_count() != 1 {
+        return false;
+    }
+
+    let statement = body.named_child(0).unwrap();
+    statement.kind() == "return_statement"
+        && statement.named_child(0).is_some_and(|expression| {
+            expression.kind() == "identifier" || expression.kind() == "field_access"
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Tree;
+
+    use crate::helpers::tree_sitter::{node_value, parse_source_with_language};
+    use crate::supported_languages::java::JavaAnalyser;
+    use crate::supported_languages::supported_language::{FunctionKind, SupportedLanguage};
+
+    const JAVA_SOURCE: &str = r#"package com.example;
+
+import java.util.List;
+import com.google.common.collect.ImmutableList;
+
+// A class with a constructor, a getter and overloaded methods
+public class Greeter {
+    private final String name;
+
+    public Greeter(String name) {
+        this.name = name;
+    }
+
+    public String getName() {
+        return this.name;
+    }
+
+    public void greet() {
+        System.out.println("Hello from " + name);
+    }
+
+    public void greet(String other) {
+        System.out.println("Hello to " + other + " from " + name);
+    }
+
+    public static Greeter anonymous() {
+        return new Greeter("anonymous");
+    }
+
+    // A nested class with a method named in the same way as the one of the outer class
+    static class Loud {
+        void greet() {
+            System.out.println("HELLO!");
+        }
+    }
+
+    @Test
+    void greets() {
+        new Greeter("Bob").greet();
+    }
+}
+
+// An interface with an abstract method and a default one
+interface Polite {
+    void greet();
+
+    default void thank() {
+        System.out.println("Thank you!");
+    }
+}
+
+// An enum whose constants override a method
+enum Operation {
+    PLUS {
+        int apply(int a, int b) {
+            return a + b;
+        }
+    };
+
+    int apply(int a, int b) {
+        throw new UnsupportedOperationException();
+    }
+}"#;
+
+    fn java_source_tree() -> Tree {
+        parse_source_with_language(JAVA_SOURCE, tree_sitter_java::language())
+    }
+
+    fn find(parent: Option<&str>, function: &str, kind: Option<FunctionKind>) -> Option<String> {
+        let tree = java_source_tree();
+        let ra: JavaAnalyser = Default::default();
+
+        ra.find_correct_node(
+            JAVA_SOURCE,
+            &tree,
+            &parent.map(str::to_string),
+            &Some(function.to_string()),
+            &kind,
+        )
+        .ok()
+        .map(|function_node| node_value(JAVA_SOURCE, function_node).to_string())
+    }
+
+    #[test]
+    fn no_function() {
+        let tree = java_source_tree();
+        let ra: JavaAnalyser = Default::default();
+
+        assert_eq!(
+            JAVA_SOURCE,
+            node_value(
+                JAVA_SOURCE,
+                ra.find_correct_node(JAVA_SOURCE, &tree, &None, &None, &None)
+                    .unwrap(),
+            ),
+        )
+    }
+
+    #[test]
+    fn method() {
+        let target = r#"public void greet() {
+        System.out.println("Hello from " + name);
+    }"#;
+
+        assert_eq!(
+            Some(target),
+            find(Some("Greeter"), "greet", None).as_deref()
+        );
+        assert_eq!(Some(target), find(None, "greet", None).as_deref());
+    }
+
+    #[test]
+    fn constructor() {
+        let target = r#"public Greeter(String name) {
+        this.name = name;
+    }"#;
+
+        assert_eq!(
+            Some(target),
+            find(Some("Greeter"), "Greeter", Some(FunctionKind::Constructor)).as_deref()
+        );
+    }
+
+    #[test]
+    fn nested_class_method() {
+        let target = r#"void greet() {
+            System.out.println("HELLO!");
+        }"#;
+
+        assert_eq!(Some(target), find(Some("Loud"), "greet", None).as_deref());
+        assert_eq!(
+            Some(target),
+            find(Some("Greeter.Loud"), "greet", None).as_deref()
+        );
+        assert_eq!(None, find(Some("Polite.Loud"), "greet", None));
+    }
+
+    #[test]
+    fn default_interface_method() {
+        let target = r#"default void thank() {
+        System.out.println("Thank you!");
+    }"#;
+
+        assert_eq!(Some(target), find(Some("Polite"), "thank", None).as_deref());
+        // Abstract methods have no body to improve
+        assert_eq!(None, find(Some("Polite"), "greet", None));
+    }
+
+    #[test]
+    fn enum_methods() {
+        let target = r#"int apply(int a, int b) {
+        throw new UnsupportedOperationException();
+    }"#;
+        let constant_target = r#"int apply(int a, int b) {
+            return a + b;
+        }"#;
+
+        assert_eq!(
+            Some(target),
+            find(Some("Operation"), "apply", None).as_deref()
+        );
+        assert_eq!(
+            Some(constant_target),
+            find(Some("Operation.PLUS"), "apply", None).as_deref()
+        );
+        assert_eq!(
+            Some(constant_target),
+            find(Some("PLUS"), "apply", None).as_deref()
+        );
+    }
+
+    #[test]
+    fn overloaded_methods() {
+        let tree = java_source_tree();
+        let ra: JavaAnalyser = Default::default();
+
+        let overloads: Vec<&str> = ra
+            .find_all_functions(&tree)
+            .into_iter()
+            .filter(|function_node| {
+                function_node
+                    .child_by_field_name("name")
+                    .is_some_and(|name| node_value(JAVA_SOURCE, name) == "greet")
+            })
+            .map(|function_node| {
+                node_value(JAVA_SOURCE, function_node)
+                    .lines()
+                    .next()
+                    .unwrap()
+            })
+            .collect();
+
+        assert_eq!(
+            vec![
+                "public void greet() {",
+                "public void greet(String other) {",
+                "void greet() {"
+            ],
+            overloads
+        );
+    }
+
+    #[test]
+    fn function_kinds() {
+        let tree = java_source_tree();
+        let ra: JavaAnalyser = Default::default();
+
+        for (parent, function, kind) in [
+            (Some("Greeter"), "greet", FunctionKind::Method),
+            (Some("Greeter"), "Greeter", FunctionKind::Constructor),
+            (Some("Greeter"), "getName", FunctionKind::Getter),
+            (Some("Greeter"), "anonymous", FunctionKind::Function),
+            (Some("Greeter"), "greets", FunctionKind::Test),
+        ] {
+            let function_node = ra
+                .find_correct_node(
+                    JAVA_SOURCE,
+                    &tree,
+                    &parent.map(str::to_string),
+                    &Some(function.to_string()),
+                    &None,
+                )
+                .unwrap();
+
+            assert_eq!(kind, ra.function_kind(JAVA_SOURCE, function_node))
+        }
+    }
+
+    #[test]
+    fn packages() {
+        let tree = java_source_tree();
+        let ra: JavaAnalyser = Default::default();
+
+        assert_eq!(
+            vec!["com.google"],
+            ra.referenced_packages(JAVA_SOURCE, &tree)
+        );
+        assert_eq!(
+            vec!["com.google", "org.junit"],
+            ra.declared_packages(
+                r#"<project>
+  <groupId>com.example</groupId>
+  <dependencies>
+    <dependency>
+      <groupId>com.google.guava</groupId>
+      <artifactId>guava</artifactId>
+    </dependency>
+    <dependency>
+      <groupId>org.junit.jupiter</groupId>
+      <artifactId>junit-jupiter</artifactId>
+    </dependency>
+  </dependencies>
+</project>"#
+            )
+        );
+    }
+}
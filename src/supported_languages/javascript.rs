@@ -3,9 +3,13 @@ use std::fmt::{Display, Formatter};
 use tree_sitter::{Node, Tree};
 
 use crate::helpers::tree_sitter::{
-    find_all_of_kind, find_first_of_kind_with_field_value, node_value,
+    find_all_of_kind, find_all_of_kind_with_field_value, node_value,
 };
-use crate::supported_languages::supported_language::{Language, SupportedLanguage};
+use crate::supported_languages::ecmascript::{
+    declared_packages, find_all_functions, find_functions_in_scope, find_scope, function_kind,
+    is_allocation_suspect, referenced_packages, NODE_KINDS,
+};
+use crate::supported_languages::supported_language::{FunctionKind, Language, SupportedLanguage};
 
 pub struct JavascriptAnalyser {
     inner: Language,
@@ -25,6 +29,16 @@ impl Default for JavascriptAnalyser {
     }
 }
 
+impl JavascriptAnalyser {
+    /// The analyser of `.jsx` files. The Javascript grammar parses JSX already, so only the name
+    /// differs.
+    pub fn jsx() -> Self {
+        Self {
+            inner: Language::Jsx,
+        }
+    }
+}
+
 impl SupportedLanguage for JavascriptAnalyser {
     fn find_correct_node<'a>(
         &self,
@@ -32,60 +46,44 @@ impl SupportedLanguage for JavascriptAnalyser {
         root_tree: &'a Tree,
         parent_identifier: &Option<String>,
         function_identifier: &Option<String>,
+        kind: &Option<FunctionKind>,
     ) -> Result<Node<'a>, &'a str> {
         if let Some(function) = function_identifier {
             if let Some(parent) = parent_identifier {
-                let all_class_decls = find_all_of_kind(root_tree.walk(), "class_declaration");
-                let candidate_correct_class_decl = all_class_decls.into_iter().find(|impl_node| {
-                    let class_decl_node = impl_node.child_by_field_name("name").unwrap();
-
-                    node_value(source_file, class_decl_node) == parent
-                });
-
-                if let Some(correct_class_decl_node) = candidate_correct_class_decl {
-                    let class_body = correct_class_decl_node.child_by_field_name("body").unwrap();
-                    let mut cursor = root_tree.walk();
-
-                    return if let Some(function_node) = class_body
-                        .children_by_field_name("member", &mut cursor)
-                        .filter(|member| member.kind() == "method_definition")
-                        .find(|method| {
-                            node_value(source_file, method.child_by_field_name("name").unwrap())
-                                == function
-                        }) {
-                        Ok(function_node)
-                    } else {
-                        Err("method not found")
-                    };
+                // The parent may be a dotted path through classes, objects and namespaces
+                return if let Some(scope) = find_scope(source_file, root_tree, parent) {
+                    find_functions_in_scope(source_file, scope, function)
+                        .into_iter()
+                        .find(|function_node| self.is_of_kind(source_file, *function_node, kind))
+                        .ok_or("method not found")
                 } else {
-                    return Err("class not found");
-                }
+                    Err("parent not found")
+                };
             };
 
             // If it is a function declaration
-            if let Some(function_node) = find_first_of_kind_with_field_value(
+            if let Some(function_node) = find_all_of_kind_with_field_value(
                 source_file,
                 root_tree.walk(),
                 "function_declaration",
                 "name",
                 function,
-            ) {
+            )
+            .into_iter()
+            .find(|function_node| self.is_of_kind(source_file, *function_node, kind))
+            {
                 return Ok(function_node);
             };
             // If it is an arrow function or a expression
             return if let Some(function_node) =
                 find_all_of_kind(root_tree.walk(), "lexical_declaration")
                     .into_iter()
-                    .filter(|lexical_decl| lexical_decl.child(1).is_some())
+                    .filter(|lexical_decl| self.is_of_kind(source_file, *lexical_decl, kind))
                     .find(|lexical_decl| {
-                        node_value(
-                            source_file,
-                            lexical_decl
-                                .child(1)
-                                .unwrap()
-                                .child_by_field_name("name")
-                                .unwrap(),
-                        ) == function
+                        lexical_decl
+                            .child(1)
+                            .and_then(|declarator| declarator.child_by_field_name("name"))
+                            .is_some_and(|name| node_value(source_file, name) == function)
                     })
             {
                 Ok(function_node)
@@ -97,9 +95,49 @@ impl SupportedLanguage for JavascriptAnalyser {
         Ok(root_tree.root_node())
     }
 
+    fn find_all_functions<'a>(&self, root_tree: &'a Tree) -> Vec<Node<'a>> {
+        find_all_functions(root_tree)
+    }
+
+    fn node_kinds(&self) -> &'static [&'static str] {
+        NODE_KINDS
+    }
+
+    fn function_kind(&self, source_file: &str, function_node: Node) -> FunctionKind {
+        function_kind(source_file, function_node)
+    }
+
     fn language(&self) -> tree_sitter::Language {
         tree_sitter_javascript::language()
     }
+
+    fn is_allocation_suspect(&self, source_file: &str, node: Node) -> bool {
+        is_allocation_suspect(source_file, node)
+    }
+
+    fn default_instructions(&self) -> &'static str {
+        "Prefer plain loops over chains of array methods in hot paths, avoid creating closures and objects inside loops, and use a Map or a Set for lookups."
+    }
+
+    fn manifest_name(&self) -> Option<&'static str> {
+        Some("package.json")
+    }
+
+    fn declared_packages(&self, manifest: &str) -> Vec<String> {
+        declared_packages(manifest)
+    }
+
+    fn referenced_packages(&self, source: &str, tree: &Tree) -> Vec<String> {
+        referenced_packages(source, tree)
+    }
+
+    fn add_dependency_command(&self, package: &str) -> Option<Vec<String>> {
+        Some(vec![
+            "npm".to_string(),
+            "install".to_string(),
+            package.to_string(),
+        ])
+    }
 }
 
 #[cfg(test)]
@@ -108,7 +146,7 @@ mod tests {
 
     use crate::helpers::tree_sitter::{node_value, parse_source_with_language};
     use crate::supported_languages::javascript::JavascriptAnalyser;
-    use crate::supported_languages::supported_language::SupportedLanguage;
+    use crate::supported_languages::supported_language::{FunctionKind, SupportedLanguage};
 
     // exhausting
     const JAVASCRIPT_SOURCE: &str = r#"// Function Declaration
@@ -140,6 +178,18 @@ const asyncGreetArrow = async () => {
 
 // Method Definition in a Class
 class Greeter {
+    constructor(name) {
+        this._name = name;
+    }
+
+    get name() {
+        return this._name;
+    }
+
+    set name(value) {
+        this._name = value;
+    }
+
     greet() {
         console.log("Hello from method in a class!");
     }
@@ -149,6 +199,18 @@ class Greeter {
     }
 }
 
+// Functions nested inside object literals
+const config = {
+    handlers: {
+        onMessage(message) {
+            console.log("Hello from a nested object method!", message);
+        },
+        onError: (error) => {
+            console.log("Hello from a nested arrow function!", error);
+        },
+    },
+};
+
 // Call all the functions
 greet();
 asyncGreet();
@@ -175,7 +237,7 @@ greeter.asyncGreet();
             JAVASCRIPT_SOURCE,
             node_value(
                 JAVASCRIPT_SOURCE,
-                ra.find_correct_node(JAVASCRIPT_SOURCE, &tree, &None, &None)
+                ra.find_correct_node(JAVASCRIPT_SOURCE, &tree, &None, &None, &None)
                     .unwrap(),
             ),
         )
@@ -194,8 +256,14 @@ greeter.asyncGreet();
             target,
             node_value(
                 JAVASCRIPT_SOURCE,
-                ra.find_correct_node(JAVASCRIPT_SOURCE, &tree, &None, &Some("greet".to_string()))
-                    .unwrap(),
+                ra.find_correct_node(
+                    JAVASCRIPT_SOURCE,
+                    &tree,
+                    &None,
+                    &Some("greet".to_string()),
+                    &None
+                )
+                .unwrap(),
             ),
         )
     }
@@ -217,7 +285,8 @@ greeter.asyncGreet();
                     JAVASCRIPT_SOURCE,
                     &tree,
                     &None,
-                    &Some("asyncGreet".to_string())
+                    &Some("asyncGreet".to_string()),
+                    &None,
                 )
                 .unwrap(),
             ),
@@ -241,7 +310,8 @@ greeter.asyncGreet();
                     JAVASCRIPT_SOURCE,
                     &tree,
                     &None,
-                    &Some("greetExpression".to_string())
+                    &Some("greetExpression".to_string()),
+                    &None,
                 )
                 .unwrap(),
             ),
@@ -265,7 +335,8 @@ greeter.asyncGreet();
                     JAVASCRIPT_SOURCE,
                     &tree,
                     &None,
-                    &Some("asyncGreetExpression".to_string())
+                    &Some("asyncGreetExpression".to_string()),
+                    &None,
                 )
                 .unwrap(),
             ),
@@ -289,7 +360,8 @@ greeter.asyncGreet();
                     JAVASCRIPT_SOURCE,
                     &tree,
                     &None,
-                    &Some("greetArrow".to_string())
+                    &Some("greetArrow".to_string()),
+                    &None,
                 )
                 .unwrap(),
             ),
@@ -313,7 +385,8 @@ greeter.asyncGreet();
                     JAVASCRIPT_SOURCE,
                     &tree,
                     &None,
-                    &Some("asyncGreetArrow".to_string())
+                    &Some("asyncGreetArrow".to_string()),
+                    &None,
                 )
                 .unwrap(),
             ),
@@ -337,7 +410,8 @@ greeter.asyncGreet();
                     JAVASCRIPT_SOURCE,
                     &tree,
                     &Some("Greeter".to_string()),
-                    &Some("greet".to_string())
+                    &Some("greet".to_string()),
+                    &None,
                 )
                 .unwrap(),
             ),
@@ -362,9 +436,136 @@ greeter.asyncGreet();
                     &tree,
                     &Some("Greeter".to_string()),
                     &Some("asyncGreet".to_string()),
+                    &None,
                 )
                 .unwrap(),
             ),
         )
     }
+
+    #[test]
+    fn nested_object_method() {
+        let target = r#"onMessage(message) {
+            console.log("Hello from a nested object method!", message);
+        }"#;
+
+        let tree = javascript_source_tree();
+        let ra: JavascriptAnalyser = Default::default();
+
+        assert_eq!(
+            target,
+            node_value(
+                JAVASCRIPT_SOURCE,
+                ra.find_correct_node(
+                    JAVASCRIPT_SOURCE,
+                    &tree,
+                    &Some("config.handlers".to_string()),
+                    &Some("onMessage".to_string()),
+                    &None,
+                )
+                .unwrap(),
+            ),
+        )
+    }
+
+    #[test]
+    fn nested_object_arrow_function() {
+        let target = r#"onError: (error) => {
+            console.log("Hello from a nested arrow function!", error);
+        }"#;
+
+        let tree = javascript_source_tree();
+        let ra: JavascriptAnalyser = Default::default();
+
+        assert_eq!(
+            target,
+            node_value(
+                JAVASCRIPT_SOURCE,
+                ra.find_correct_node(
+                    JAVASCRIPT_SOURCE,
+                    &tree,
+                    &Some("config.handlers".to_string()),
+                    &Some("onError".to_string()),
+                    &None,
+                )
+                .unwrap(),
+            ),
+        )
+    }
+
+    #[test]
+    fn getter_and_setter_sharing_a_name() {
+        let target = r#"set name(value) {
+        this._name = value;
+    }"#;
+
+        let tree = javascript_source_tree();
+        let ra: JavascriptAnalyser = Default::default();
+
+        assert_eq!(
+            target,
+            node_value(
+                JAVASCRIPT_SOURCE,
+                ra.find_correct_node(
+                    JAVASCRIPT_SOURCE,
+                    &tree,
+                    &Some("Greeter".to_string()),
+                    &Some("name".to_string()),
+                    &Some(FunctionKind::Method),
+                )
+                .unwrap(),
+            ),
+        )
+    }
+
+    #[test]
+    fn function_kinds() {
+        let tree = javascript_source_tree();
+        let ra: JavascriptAnalyser = Default::default();
+
+        for (parent, function, kind) in [
+            (None, "greet", FunctionKind::Function),
+            (Some("Greeter"), "greet", FunctionKind::Method),
+            (Some("Greeter"), "constructor", FunctionKind::Constructor),
+            (Some("Greeter"), "name", FunctionKind::Getter),
+        ] {
+            let function_node = ra
+                .find_correct_node(
+                    JAVASCRIPT_SOURCE,
+                    &tree,
+                    &parent.map(str::to_string),
+                    &Some(function.to_string()),
+                    &None,
+                )
+                .unwrap();
+
+            assert_eq!(kind, ra.function_kind(JAVASCRIPT_SOURCE, function_node))
+        }
+    }
+
+    #[test]
+    fn packages() {
+        let source = r#"import { debounce } from "lodash/fp";
+const { join } = require("node:path");
+const fs = require("fs");
+const local = require("./local");
+
+async function load() {
+    const { parse } = await import("@babel/parser");
+}
+"#;
+        let tree = parse_source_with_language(source, tree_sitter_javascript::language());
+        let ra: JavascriptAnalyser = Default::default();
+
+        assert_eq!(
+            vec!["@babel/parser", "lodash"],
+            ra.referenced_packages(source, &tree)
+        );
+        assert_eq!(
+            vec!["lodash", "jest"],
+            ra.declared_packages(
+                r#"{"dependencies": {"lodash": "^4"}, "devDependencies": {"jest": "^29"}}"#
+            )
+        );
+    }
 }
@@ -1,11 +1,42 @@
 use std::fmt::{Display, Formatter};
 
 use tree_sitter::{Node, Tree};
+use tree_sitter_traversal::{traverse, Order};
 
 use crate::helpers::tree_sitter::{
-    find_all_of_kind, find_first_of_kind_with_field_value, node_value,
+    find_all_of_kind, find_all_of_kind_with_field_value, node_value,
 };
-use crate::supported_languages::supported_language::{Language, SupportedLanguage};
+use crate::supported_languages::supported_language::{FunctionKind, Language, SupportedLanguage};
+
+const BUILTIN_CRATES: &[&str] = &["alloc", "core", "proc_macro", "std", "test"];
+
+const CARGO_DEPENDENCY_TABLES: &[&str] =
+    &["dependencies", "dev-dependencies", "build-dependencies"];
+
+const ALLOCATING_MACROS: &[&str] = &["format", "vec"];
+
+const ALLOCATING_METHODS: &[&str] = &[
+    "clone",
+    "collect",
+    "to_owned",
+    "to_string",
+    "to_vec",
+    "to_lowercase",
+    "to_uppercase",
+];
+
+const ALLOCATING_CONSTRUCTORS: &[&str] = &[
+    "Arc::new",
+    "Box::new",
+    "BTreeMap::new",
+    "HashMap::new",
+    "HashSet::new",
+    "Rc::new",
+    "String::from",
+    "String::new",
+    "Vec::new",
+    "Vec::with_capacity",
+];
 
 pub struct RustAnalyzer {
     inner: Language,
@@ -32,6 +63,7 @@ impl SupportedLanguage for RustAnalyzer {
         root_tree: &'a Tree,
         parent_identifier: &Option<String>,
         function_identifier: &Option<String>,
+        kind: &Option<FunctionKind>,
     ) -> Result<Node<'a>, &'a str> {
         let cursor = root_tree.walk();
         if let Some(function) = function_identifier {
@@ -39,9 +71,13 @@ impl SupportedLanguage for RustAnalyzer {
             if let Some(parent) = parent_identifier {
                 let all_impls = find_all_of_kind(cursor, "impl_item");
                 for parent_impl in all_impls.into_iter() {
-                    let impl_type_node = parent_impl.child_by_field_name("type").unwrap();
+                    let Some(impl_type_node) = parent_impl.child_by_field_name("type") else {
+                        continue;
+                    };
                     let impl_type_name = if impl_type_node.kind() == "generic_type" {
-                        let type_name_node = impl_type_node.child_by_field_name("type").unwrap();
+                        let type_name_node = impl_type_node
+                            .child_by_field_name("type")
+                            .unwrap_or(impl_type_node);
                         node_value(source_file, type_name_node)
                     } else {
                         node_value(source_file, impl_type_node)
@@ -59,17 +95,17 @@ impl SupportedLanguage for RustAnalyzer {
                 candidate_subtrees.push(cursor)
             };
 
-            return if let Some(function_node) = candidate_subtrees
+            return if let Some(function_node) = candidate_subtrees.into_iter().find_map(|cursor| {
+                find_all_of_kind_with_field_value(
+                    source_file,
+                    cursor,
+                    "function_item",
+                    "name",
+                    function,
+                )
                 .into_iter()
-                .find_map(|cursor| {
-                    find_first_of_kind_with_field_value(
-                        source_file,
-                        cursor,
-                        "function_item",
-                        "name",
-                        function,
-                    )
-                }) {
+                .find(|function_item| self.is_of_kind(source_file, *function_item, kind))
+            }) {
                 Ok(function_node)
             } else {
                 Err("function not found")
@@ -79,9 +115,277 @@ impl SupportedLanguage for RustAnalyzer {
         Ok(root_tree.root_node())
     }
 
+    fn find_all_functions<'a>(&self, root_tree: &'a Tree) -> Vec<Node<'a>> {
+        find_all_of_kind(root_tree.walk(), "function_item")
+    }
+
+    fn node_kinds(&self) -> &'static [&'static str] {
+        &["function_item", "impl_item", "trait_item", "mod_item"]
+    }
+
+    fn function_kind(&self, source_file: &str, function_node: Node) -> FunctionKind {
+        if is_test(source_file, function_node) {
+            return FunctionKind::Test;
+        }
+
+        // Associated functions live in the declaration list of an impl or trait
+        let parent_item = function_node
+            .parent()
+            .and_then(|declaration_list| declaration_list.parent())
+            .filter(|item| item.kind() == "impl_item" || item.kind() == "trait_item");
+        let Some(parent_item) = parent_item else {
+            return FunctionKind::Function;
+        };
+
+        let Some(parameters) = function_node.child_by_field_name("parameters") else {
+            return FunctionKind::Function;
+        };
+        let mut cursor = parameters.walk();
+        let self_parameter = parameters
+            .named_children(&mut cursor)
+            .find(|parameter| parameter.kind() == "self_parameter");
+
+        match self_parameter {
+            None => {
+                let return_type = function_node
+                    .child_by_field_name("return_type")
+                    .map(|return_type| node_value(source_file, return_type));
+                let parent_type = parent_item
+                    .child_by_field_name("type")
+                    .map(|parent_type| node_value(source_file, parent_type));
+
+                if return_type.is_some()
+                    && (return_type == Some("Self") || return_type == parent_type)
+                {
+                    FunctionKind::Constructor
+                } else {
+                    FunctionKind::Function
+                }
+            }
+            Some(self_parameter) => {
+                if node_value(source_file, self_parameter) == "&self"
+                    && parameters.named_child_count() == 1
+                    && returns_a_field(function_node)
+                {
+                    FunctionKind::Getter
+                } else {
+                    FunctionKind::Method
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::needless_return)]
     fn language(&self) -> tree_sitter::Language {
         return tree_sitter_rust::language();
     }
+
+    fn is_allocation_suspect(&self, source_file: &str, node: Node) -> bool {
+        match node.kind() {
+            "macro_invocation" => node
+                .child_by_field_name("macro")
+                .is_some_and(|name| ALLOCATING_MACROS.contains(&node_value(source_file, name))),
+            "call_expression" => {
+                let Some(mut function) = node.child_by_field_name("function") else {
+                    return false;
+                };
+                // Like `collect::<Vec<_>>`
+                if function.kind() == "generic_function" {
+                    let Some(generic_function) = function.child_by_field_name("function") else {
+                        return false;
+                    };
+                    function = generic_function;
+                }
+                match function.kind() {
+                    "field_expression" => {
+                        function.child_by_field_name("field").is_some_and(|method| {
+                            ALLOCATING_METHODS.contains(&node_value(source_file, method))
+                        })
+                    }
+                    "scoped_identifier" => {
+                        let path = node_value(source_file, function);
+                        ALLOCATING_CONSTRUCTORS
+                            .iter()
+                            .any(|constructor| path.ends_with(constructor))
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    fn default_instructions(&self) -> &'static str {
+        "Prefer iterators over index loops, borrow instead of cloning, and avoid allocations that are not needed, like collecting into a Vec only to iterate over it."
+    }
+
+    fn manifest_name(&self) -> Option<&'static str> {
+        Some("Cargo.toml")
+    }
+
+    fn declared_packages(&self, manifest: &str) -> Vec<String> {
+        let Ok(manifest) = manifest.parse::<toml::Table>() else {
+            return vec![];
+        };
+        let mut tables = vec![&manifest];
+        if let Some(targets) = manifest.get("target").and_then(toml::Value::as_table) {
+            tables.extend(targets.values().filter_map(toml::Value::as_table));
+        }
+
+        tables
+            .into_iter()
+            .flat_map(|table| {
+                CARGO_DEPENDENCY_TABLES
+                    .iter()
+                    .filter_map(|key| table.get(*key)?.as_table())
+            })
+            .flat_map(|dependencies| dependencies.keys())
+            .map(|name| name.replace('-', "_"))
+            .collect()
+    }
+
+    /// The first element of the paths of the code, like `rayon` in `rayon::prelude::*`. Those
+    /// starting with an uppercase letter, like `Vec::new`, are types, and those the code declares
+    /// with `mod` or imports from the crate, like `utils` in `use crate::utils;`, are its modules.
+    fn referenced_packages(&self, source: &str, tree: &Tree) -> Vec<String> {
+        let modules = crate_modules(source, tree);
+        let mut packages: Vec<String> = traverse(tree.walk(), Order::Pre)
+            .filter_map(|node| match node.kind() {
+                "scoped_identifier" | "scoped_type_identifier" | "scoped_use_list" => {
+                    node.child_by_field_name("path")
+                }
+                "extern_crate_declaration" => node.child_by_field_name("name"),
+                _ => None,
+            })
+            .filter(|path| path.kind() == "identifier")
+            .map(|path| node_value(source, path))
+            .filter(|package| {
+                package.starts_with(|c: char| c.is_lowercase())
+                    && !BUILTIN_CRATES.contains(package)
+                    && !modules.contains(package)
+            })
+            .map(str::to_string)
+            .collect();
+        packages.sort();
+        packages.dedup();
+
+        packages
+    }
+
+    fn add_dependency_command(&self, package: &str) -> Option<Vec<String>> {
+        Some(vec![
+            "cargo".to_string(),
+            "add".to_string(),
+            package.to_string(),
+        ])
+    }
+}
+
+/// The modules of the crate the code brings into scope, by declaring them with `mod` or by
+/// importing them with a path starting at `crate`, `super` or `self`
+fn crate_modules<'a>(source: &'a str, tree: &'a Tree) -> Vec<&'a str> {
+    traverse(tree.walk(), Order::Pre)
+        .flat_map(|node| match node.kind() {
+            "mod_item" => node
+                .child_by_field_name("name")
+                .map(|name| node_value(source, name))
+                .into_iter()
+                .collect(),
+            "use_declaration" => {
+                let argument = node.child_by_field_name("argument");
+                let local = argument.is_some_and(|argument| {
+                    let root = node_value(source, argument).split("::").next();
+                    matches!(root, Some("crate" | "super" | "self"))
+                });
+                if !local {
+                    return vec![];
+                }
+                traverse(node.walk(), Order::Pre)
+                    .filter(|node| node.kind() == "identifier")
+                    .map(|node| node_value(source, node))
+                    .collect()
+            }
+            _ => vec![],
+        })
+        .collect()
+}
+
+// Either annotated with a test attribute, like `#[test]` or `#[tokio::test]`, or inside a
+// `#[cfg(test)]` module
+fn is_test(source_file: &str, function_node: Node) -> bool {
+    if attributes(source_file, function_node).any(|attribute| {
+        let path = attribute.split('(').next().unwrap_or_default();
+        path == "test" || path.ends_with("::test")
+    }) {
+        return true;
+    }
+
+    let mut ancestor = function_node.parent();
+    while let Some(node) = ancestor {
+        if node.kind() == "mod_item"
+            && attributes(source_file, node).any(|attribute| attribute == "cfg(test)")
+        {
+            return true;
+        }
+        ancestor = node.parent();
+    }
+
+    false
+}
+
+// The contents of the outer attributes of an item, like `test` for `#[test]`
+fn attributes<'a>(source_file: &'a str, item: Node<'a>) -> impl Iterator<Item = &'a str> {
+    std::iter::successors(item.prev_named_sibling(), |sibling| {
+        sibling.prev_named_sibling()
+    })
+    .take_while(|sibling| sibling.kind() == "attribute_item" || sibling.kind() == "line_comment")
+    .filter(|sibling| sibling.kind() == "attribute_item")
+    .map(move |attribute_item| {
+        node_value(source_file, attribute_item)
+            .trim_start_matches("#[")
+            .trim_end_matches(']')
+            .trim()
+    })
+}
+
+// Whether the body is nothing but a, possibly borrowed or cloned, `self.field`
+fn returns_a_field(function_node: Node) -> bool {
+    let Some(body) = function_node.child_by_field_name("body") else {
+        return false;
+    };
+    if body.named_child_count() != 1 {
+        return false;
+    }
+
+    let mut expression = body.named_child(0).unwrap();
+    loop {
+        match expression.kind() {
+            "reference_expression" => {
+                let Some(value) = expression.child_by_field_name("value") else {
+                    return false;
+                };
+                expression = value;
+            }
+            "call_expression" => {
+                let Some(function) = expression.child_by_field_name("function") else {
+                    return false;
+                };
+                if function.kind() != "field_expression" {
+                    return false;
+                }
+                let Some(value) = function.child_by_field_name("value") else {
+                    return false;
+                };
+                expression = value;
+            }
+            "field_expression" => {
+                return expression
+                    .child_by_field_name("value")
+                    .is_some_and(|value| value.kind() == "self");
+            }
+            _ => return false,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -90,7 +394,7 @@ mod tests {
 
     use crate::helpers::tree_sitter::{node_value, parse_source_with_language};
     use crate::supported_languages::rust::RustAnalyzer;
-    use crate::supported_languages::supported_language::SupportedLanguage;
+    use crate::supported_languages::supported_language::{FunctionKind, SupportedLanguage};
 
     const RUST_SOURCE: &str = r#"// This is a top-level function
 fn greet() {
@@ -103,6 +407,16 @@ struct Greeter {
 }
 
 impl Greeter {
+    // This is a constructor
+    fn new(name: String) -> Self {
+        Self { name }
+    }
+
+    // This is a getter
+    fn name(&self) -> &String {
+        &self.name
+    }
+
     // This is a method in the struct with the same name as the top-level function
     fn greet(&self) {
         println!("Hello from {}, inside the Greeter struct!", self.name);
@@ -130,6 +444,15 @@ fn main() {
         name: String::from("Alice"),
     };
     greeter.greet();
+}
+
+#[cfg(test)]
+mod tests {
+    // This is a test with the same name as the top-level function
+    #[test]
+    fn greet() {
+        super::greet();
+    }
 }"#;
 
     fn rust_source_tree() -> Tree {
@@ -145,7 +468,7 @@ fn main() {
             RUST_SOURCE,
             node_value(
                 RUST_SOURCE,
-                ra.find_correct_node(RUST_SOURCE, &tree, &None, &None)
+                ra.find_correct_node(RUST_SOURCE, &tree, &None, &None, &None)
                     .unwrap(),
             ),
         )
@@ -164,7 +487,7 @@ fn main() {
             target,
             node_value(
                 RUST_SOURCE,
-                ra.find_correct_node(RUST_SOURCE, &tree, &None, &Some("greet".to_string()))
+                ra.find_correct_node(RUST_SOURCE, &tree, &None, &Some("greet".to_string()), &None,)
                     .unwrap(),
             ),
         )
@@ -188,8 +511,9 @@ fn main() {
                     &tree,
                     &Some("Greeter".to_string()),
                     &Some("greet".to_string()),
+                    &None,
                 )
-                    .unwrap(),
+                .unwrap(),
             ),
         )
     }
@@ -212,9 +536,60 @@ fn main() {
                     &tree,
                     &Some("GenericGreeter".to_string()),
                     &Some("greet".to_string()),
+                    &None,
                 )
-                    .unwrap(),
+                .unwrap(),
             ),
         )
     }
+
+    #[test]
+    fn test_function() {
+        let target = r#"fn greet() {
+        super::greet();
+    }"#;
+
+        let tree = rust_source_tree();
+        let ra: RustAnalyzer = Default::default();
+
+        assert_eq!(
+            target,
+            node_value(
+                RUST_SOURCE,
+                ra.find_correct_node(
+                    RUST_SOURCE,
+                    &tree,
+                    &None,
+                    &Some("greet".to_string()),
+                    &Some(FunctionKind::Test),
+                )
+                .unwrap(),
+            ),
+        )
+    }
+
+    #[test]
+    fn function_kinds() {
+        let tree = rust_source_tree();
+        let ra: RustAnalyzer = Default::default();
+
+        for (parent, function, kind) in [
+            (None, "greet", FunctionKind::Function),
+            (Some("Greeter"), "greet", FunctionKind::Method),
+            (Some("Greeter"), "new", FunctionKind::Constructor),
+            (Some("Greeter"), "name", FunctionKind::Getter),
+        ] {
+            let function_node = ra
+                .find_correct_node(
+                    RUST_SOURCE,
+                    &tree,
+                    &parent.map(str::to_string),
+                    &Some(function.to_string()),
+                    &None,
+                )
+                .unwrap();
+
+            assert_eq!(kind, ra.function_kind(RUST_SOURCE, function_node))
+        }
+    }
 }
@@ -0,0 +1,439 @@
+use std::fmt::{Display, Formatter};
+
+use tree_sitter::{Node, Point, Range, Tree};
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::helpers::tree_sitter::node_value;
+use crate::supported_languages::supported_language::{FunctionKind, Language, SupportedLanguage};
+
+/// What a function can be defined in, by name. Case classes are classes, with a `case` modifier.
+const TYPE_KINDS: &[&str] = &[
+    "object_definition",
+    "class_definition",
+    "trait_definition",
+    "enum_definition",
+];
+
+const TEST_ANNOTATIONS: &[&str] = &["Test", "ParameterizedTest"];
+
+const ALLOCATING_METHODS: &[&str] = &[
+    "toList", "toVector", "toArray", "toSeq", "toSet", "toMap", "map", "flatMap", "filter", "zip",
+    "mkString", "format",
+];
+
+pub struct ScalaAnalyser {
+    inner: Language,
+}
+
+impl Default for ScalaAnalyser {
+    fn default() -> Self {
+        Self {
+            inner: Language::Scala,
+        }
+    }
+}
+
+impl Display for ScalaAnalyser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl SupportedLanguage for ScalaAnalyser {
+    /// The parent is the object, class or trait the function is defined in, so that `Foo` names
+    /// both a class and its companion object, or a dotted path through nested ones
+    fn find_correct_node<'a>(
+        &self,
+        source_file: &str,
+        root_tree: &'a Tree,
+        parent_identifier: &Option<String>,
+        function_identifier: &Option<String>,
+        kind: &Option<FunctionKind>,
+    ) -> Result<Node<'a>, &'a str> {
+        let Some(function) = function_identifier else {
+            return Ok(root_tree.root_node());
+        };
+
+        // Overloads share a name, so the first one of the kind is picked
+        let mut candidates = self
+            .find_all_functions(root_tree)
+            .into_iter()
+            .filter(|function_node| {
+                function_node
+                    .child_by_field_name("name")
+                    .is_some_and(|name| node_value(source_file, name) == function)
+            })
+            .filter(|function_node| self.is_of_kind(source_file, *function_node, kind));
+
+        let Some(parent) = parent_identifier else {
+            return candidates.next().ok_or("function not found");
+        };
+        let path: Vec<&str> = parent.split('.').collect();
+        if let Some(function_node) = candidates
+            .find(|function_node| enclosing_types(source_file, *function_node).ends_with(&path))
+        {
+            return Ok(function_node);
+        }
+
+        let parent_exists = traverse(root_tree.walk(), Order::Pre)
+            .filter(|node| TYPE_KINDS.contains(&node.kind()))
+            .any(|node| {
+                node.child_by_field_name("name")
+                    .is_some_and(|name| Some(&node_value(source_file, name)) == path.last())
+            });
+        if parent_exists {
+            Err("function not found")
+        } else {
+            Err("parent not found")
+        }
+    }
+
+    /// Abstract functions, declared without a body, have nothing to improve
+    fn find_all_functions<'a>(&self, root_tree: &'a Tree) -> Vec<Node<'a>> {
+        traverse(root_tree.walk(), Order::Pre)
+            .filter(|node| node.kind() == "function_definition")
+            .collect()
+    }
+
+    fn node_kinds(&self) -> &'static [&'static str] {
+        &[
+            "function_definition",
+            "object_definition",
+            "class_definition",
+            "trait_definition",
+            "template_body",
+        ]
+    }
+
+    /// Functions of objects and top-level ones are functions, those of classes and traits
+    /// methods. Auxiliary constructors are named `this`.
+    fn function_kind(&self, source_file: &str, function_node: Node) -> FunctionKind {
+        let mut cursor = function_node.walk();
+        let is_test = function_node
+            .children(&mut cursor)
+            .filter(|child| child.kind() == "annotation")
+            .any(|annotation| {
+                annotation
+                    .child_by_field_name("name")
+                    .is_some_and(|name| TEST_ANNOTATIONS.contains(&node_value(source_file, name)))
+            });
+        let name = function_node
+            .child_by_field_name("name")
+            .map_or("", |name| node_value(source_file, name));
+        let enclosing_type = enclosing_type(function_node);
+
+        if is_test {
+            FunctionKind::Test
+        } else if name == "this" {
+            FunctionKind::Constructor
+        } else if enclosing_type.is_none_or(|node| node.kind() == "object_definition") {
+            FunctionKind::Function
+        } else if function_node.child_by_field_name("parameters").is_none()
+            && function_node
+                .child_by_field_name("body")
+                .is_some_and(|body| {
+                    body.kind() == "identifier" || body.kind() == "field_expression"
+                })
+        {
+            FunctionKind::Getter
+        } else {
+            FunctionKind::Method
+        }
+    }
+
+    fn language(&self) -> tree_sitter::Language {
+        tree_sitter_scala::language()
+    }
+
+    fn is_allocation_suspect(&self, source_file: &str, node: Node) -> bool {
+        match node.kind() {
+            "instance_expression" => true,
+            "field_expression" => node
+                .child_by_field_name("field")
+                .is_some_and(|field| ALLOCATING_METHODS.contains(&node_value(source_file, field))),
+            _ => false,
+        }
+    }
+
+    fn default_instructions(&self) -> &'static str {
+        "Prefer views, iterators and `foldLeft` to chains of intermediate collections, avoid boxing in hot loops, and use tail recursion or `while` loops where the stack could grow."
+    }
+
+    fn manifest_name(&self) -> Option<&'static str> {
+        Some("build.sbt")
+    }
+
+    /// Like Java code, Scala code mostly refers to dependencies by packages sharing the first two
+    /// elements of their organisation, like `org.typelevel` in `"org.typelevel" %% "cats-core"`
+    fn declared_packages(&self, manifest: &str) -> Vec<String> {
+        manifest
+            .lines()
+            .filter(|line| line.contains('%'))
+            .filter_map(|line| line.split('"').nth(1))
+            .map(package_prefix)
+            .collect()
+    }
+
+    /// The packages the code imports, by the first two elements of their name, bar the ones of the
+    /// standard libraries of Scala and Java
+    fn referenced_packages(&self, source: &str, tree: &Tree) -> Vec<String> {
+        let mut packages: Vec<String> = traverse(tree.walk(), Order::Pre)
+            .filter(|node| node.kind() == "import_declaration")
+            .map(|import| {
+                let mut cursor = import.walk();
+                let path: Vec<&str> = import
+                    .children_by_field_name("path", &mut cursor)
+                    .filter(|segment| segment.is_named())
+                    .take(2)
+                    .map(|segment| node_value(source, segment))
+                    .collect();
+                path.join(".")
+            })
+            .filter(|package| {
+                !["scala", "java", "javax"]
+                    .iter()
+                    .any(|standard| package.split('.').next() == Some(standard))
+            })
+            .collect();
+        packages.sort();
+        packages.dedup();
+
+        packages
+    }
+
+    /// Bodies indented on the lines after their `def`, with no braces, take in the blank lines and
+    /// indentation that follow them, which the suggestion must not replace
+    fn replaced_range(&self, source_file: &str, node: Node) -> Range {
+        let range = node.range();
+        let code = &source_file[range.start_byte..range.end_byte];
+        let end_byte = range.start_byte + code.trim_end().len();
+        let end_point = match source_file[..end_byte].rfind('\n') {
+            Some(newline) => Point::new(
+                range.start_point.row + code[..end_byte - range.start_byte].matches('\n').count(),
+                end_byte - newline - 1,
+            ),
+            None => Point::new(range.start_point.row, end_byte),
+        };
+
+        Range {
+            end_byte,
+            end_point,
+            ..range
+        }
+    }
+}
+
+fn package_prefix(name: &str) -> String {
+    name.split('.').take(2).collect::<Vec<&str>>().join(".")
+}
+
+/// The innermost object, class or trait the function is defined in
+fn enclosing_type(function_node: Node) -> Option<Node> {
+    let mut node = function_node.parent();
+    while let Some(ancestor) = node {
+        if TYPE_KINDS.contains(&ancestor.kind()) {
+            return Some(ancestor);
+        }
+        node = ancestor.parent();
+    }
+
+    None
+}
+
+/// The names of the objects, classes and traits the function is defined in, outermost first
+fn enclosing_types<'a>(source_file: &'a str, function_node: Node<'a>) -> Vec<&'a str> {
+    let mut types = vec![];
+    let mut node = enclosing_type(function_node);
+    while let Some(ancestor) = node {
+        types.extend(
+            ancestor
+                .child_by_field_name("name")
+                .map(|name| node_value(source_file, name)),
+        );
+        node = enclosing_type(ancestor);
+    }
+    types.reverse();
+
+    types
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Tree;
+
+    use crate::helpers::tree_sitter::{node_value, parse_source_with_language};
+    use crate::supported_languages::scala::ScalaAnalyser;
+    use crate::supported_languages::supported_language::{FunctionKind, SupportedLanguage};
+
+    const SCALA_SOURCE: &str = r#"package shop
+
+import scala.concurrent.{ExecutionContext, Future}
+import cats.effect.IO
+
+// A case class with a getter, a curried method and an auxiliary constructor
+case class Cart(items: List[Int]) {
+  def total: Int = items.sum
+
+  def fold[A](zero: A)(op: (A, Int) => A): A = items.foldLeft(zero)(op)
+
+  def this() = this(Nil)
+}
+
+// Its companion object, with a function taking an implicit parameter list
+object Cart {
+  def total(prices: List[Int])(implicit ec: ExecutionContext): Future[Int] =
+    Future(prices.sum)
+
+  object Discounts {
+    def total(prices: List[Int]): Int = prices.sum / 2
+  }
+}
+
+trait Priced {
+  def price: Int
+
+  def doubled: Int = price * 2
+}
+
+class CartTest {
+  @Test def emptyCartIsFree(): Unit = assert(new Cart().total == 0)
+}"#;
+
+    fn scala_source_tree() -> Tree {
+        parse_source_with_language(SCALA_SOURCE, tree_sitter_scala::language())
+    }
+
+    // What a suggestion for the function replaces
+    fn find(parent: Option<&str>, function: &str, kind: Option<FunctionKind>) -> Option<String> {
+        let tree = scala_source_tree();
+        let ra: ScalaAnalyser = Default::default();
+
+        ra.find_correct_node(
+            SCALA_SOURCE,
+            &tree,
+            &parent.map(str::to_string),
+            &Some(function.to_string()),
+            &kind,
+        )
+        .ok()
+        .map(|function_node| {
+            let range = ra.replaced_range(SCALA_SOURCE, function_node);
+            SCALA_SOURCE[range.start_byte..range.end_byte].to_string()
+        })
+    }
+
+    #[test]
+    fn no_function() {
+        let tree = scala_source_tree();
+        let ra: ScalaAnalyser = Default::default();
+
+        assert_eq!(
+            SCALA_SOURCE,
+            node_value(
+                SCALA_SOURCE,
+                ra.find_correct_node(SCALA_SOURCE, &tree, &None, &None, &None)
+                    .unwrap(),
+            ),
+        )
+    }
+
+    #[test]
+    fn class_and_companion_object() {
+        let getter = "def total: Int = items.sum";
+        let function = r#"def total(prices: List[Int])(implicit ec: ExecutionContext): Future[Int] =
+    Future(prices.sum)"#;
+
+        assert_eq!(Some(getter), find(Some("Cart"), "total", None).as_deref());
+        assert_eq!(
+            Some(function),
+            find(Some("Cart"), "total", Some(FunctionKind::Function)).as_deref()
+        );
+        assert_eq!(
+            Some("def total(prices: List[Int]): Int = prices.sum / 2"),
+            find(Some("Cart.Discounts"), "total", None).as_deref()
+        );
+    }
+
+    #[test]
+    fn indented_body() {
+        let tree = scala_source_tree();
+        let ra: ScalaAnalyser = Default::default();
+        let function_node = ra
+            .find_correct_node(
+                SCALA_SOURCE,
+                &tree,
+                &None,
+                &Some("total".to_string()),
+                &Some(FunctionKind::Function),
+            )
+            .unwrap();
+
+        // The blank line and the indentation of the next object are left out
+        assert!(node_value(SCALA_SOURCE, function_node).ends_with("\n\n  "));
+        let range = ra.replaced_range(SCALA_SOURCE, function_node);
+        assert_eq!(range.start_point.row + 1, range.end_point.row);
+        assert_eq!("    Future(prices.sum)".len(), range.end_point.column);
+    }
+
+    #[test]
+    fn curried_function() {
+        let target = "def fold[A](zero: A)(op: (A, Int) => A): A = items.foldLeft(zero)(op)";
+
+        assert_eq!(Some(target), find(None, "fold", None).as_deref());
+    }
+
+    #[test]
+    fn trait_method() {
+        assert_eq!(
+            Some("def doubled: Int = price * 2"),
+            find(Some("Priced"), "doubled", None).as_deref()
+        );
+        // Abstract functions have no body
+        assert_eq!(None, find(Some("Priced"), "price", None));
+        assert_eq!(None, find(Some("Unknown"), "doubled", None));
+    }
+
+    #[test]
+    fn function_kinds() {
+        let tree = scala_source_tree();
+        let ra: ScalaAnalyser = Default::default();
+
+        for (parent, function, kind) in [
+            ("Cart", "total", FunctionKind::Getter),
+            ("Cart", "fold", FunctionKind::Method),
+            ("Cart", "this", FunctionKind::Constructor),
+            ("Discounts", "total", FunctionKind::Function),
+            ("Priced", "doubled", FunctionKind::Method),
+            ("CartTest", "emptyCartIsFree", FunctionKind::Test),
+        ] {
+            let function_node = ra
+                .find_correct_node(
+                    SCALA_SOURCE,
+                    &tree,
+                    &Some(parent.to_string()),
+                    &Some(function.to_string()),
+                    &None,
+                )
+                .unwrap();
+
+            assert_eq!(kind, ra.function_kind(SCALA_SOURCE, function_node))
+        }
+    }
+
+    #[test]
+    fn packages() {
+        let tree = scala_source_tree();
+        let ra: ScalaAnalyser = Default::default();
+
+        assert_eq!(
+            vec!["cats.effect".to_string()],
+            ra.referenced_packages(SCALA_SOURCE, &tree)
+        );
+        assert_eq!(
+            vec!["org.typelevel".to_string()],
+            ra.declared_packages(
+                "scalaVersion := \"2.13.12\"\nlibraryDependencies += \"org.typelevel\" %% \"cats-effect\" % \"3.5.2\"\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,365 @@
+use std::fmt::{Display, Formatter};
+
+use tree_sitter::{Node, Tree};
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::context::name_of;
+use crate::helpers::tree_sitter::node_value;
+use crate::supported_languages::ecmascript;
+use crate::supported_languages::supported_language::{FunctionKind, Language, SupportedLanguage};
+
+/// Definitions with a body, found by their name. Constructors and the `receive` and `fallback`
+/// functions are named by their keyword.
+const FUNCTION_KINDS: &[&str] = &[
+    "function_definition",
+    "modifier_definition",
+    "constructor_definition",
+    "fallback_receive_definition",
+];
+
+/// What a function can be defined in, by name
+const CONTRACT_KINDS: &[&str] = &[
+    "contract_declaration",
+    "library_declaration",
+    "interface_declaration",
+];
+
+pub struct SolidityAnalyser {
+    inner: Language,
+}
+
+impl Default for SolidityAnalyser {
+    fn default() -> Self {
+        Self {
+            inner: Language::Solidity,
+        }
+    }
+}
+
+impl Display for SolidityAnalyser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl SupportedLanguage for SolidityAnalyser {
+    /// The parent is the contract, library or interface the function or modifier is defined in.
+    /// Overloads share a name, so the first one of the kind is picked.
+    fn find_correct_node<'a>(
+        &self,
+        source_file: &str,
+        root_tree: &'a Tree,
+        parent_identifier: &Option<String>,
+        function_identifier: &Option<String>,
+        kind: &Option<FunctionKind>,
+    ) -> Result<Node<'a>, &'a str> {
+        let Some(function) = function_identifier else {
+            return Ok(root_tree.root_node());
+        };
+
+        let mut candidates = self
+            .find_all_functions(root_tree)
+            .into_iter()
+            .filter(|function_node| name_of(source_file, *function_node) == Some(function))
+            .filter(|function_node| self.is_of_kind(source_file, *function_node, kind));
+
+        let Some(parent) = parent_identifier else {
+            return candidates.next().ok_or("function not found");
+        };
+        if let Some(function_node) = candidates.find(|function_node| {
+            enclosing_contract(*function_node).and_then(|contract| name_of(source_file, contract))
+                == Some(parent)
+        }) {
+            return Ok(function_node);
+        }
+
+        let parent_exists = traverse(root_tree.walk(), Order::Pre)
+            .filter(|node| CONTRACT_KINDS.contains(&node.kind()))
+            .any(|node| name_of(source_file, node) == Some(parent));
+        if parent_exists {
+            Err("function not found")
+        } else {
+            Err("parent not found")
+        }
+    }
+
+    /// The functions of interfaces, and abstract ones, have no body to improve
+    fn find_all_functions<'a>(&self, root_tree: &'a Tree) -> Vec<Node<'a>> {
+        traverse(root_tree.walk(), Order::Pre)
+            .filter(|node| FUNCTION_KINDS.contains(&node.kind()))
+            .filter(|node| node.child_by_field_name("body").is_some())
+            .collect()
+    }
+
+    fn node_kinds(&self) -> &'static [&'static str] {
+        &[
+            "function_definition",
+            "modifier_definition",
+            "constructor_definition",
+            "contract_declaration",
+            "library_declaration",
+            "contract_body",
+        ]
+    }
+
+    /// Functions of contracts, and their modifiers, are methods, as they work on the state of the
+    /// contract, while those of libraries and free ones are functions. Foundry runs the functions
+    /// of contracts whose name starts with `test` or `invariant`. Public state variables get their
+    /// getter from the compiler, so none are written.
+    fn function_kind(&self, source_file: &str, function_node: Node) -> FunctionKind {
+        let name = name_of(source_file, function_node).unwrap_or_default();
+        let contract = enclosing_contract(function_node)
+            .filter(|contract| contract.kind() == "contract_declaration");
+
+        if function_node.kind() == "constructor_definition" {
+            FunctionKind::Constructor
+        } else if contract.is_none() {
+            FunctionKind::Function
+        } else if name.starts_with("test") || name.starts_with("invariant") {
+            FunctionKind::Test
+        } else {
+            FunctionKind::Method
+        }
+    }
+
+    fn language(&self) -> tree_sitter::Language {
+        tree_sitter_solidity::language()
+    }
+
+    /// Contracts and arrays created with `new`, and the bytes `abi.encode` and its kin build in
+    /// memory
+    fn is_allocation_suspect(&self, source_file: &str, node: Node) -> bool {
+        match node.kind() {
+            "new_expression" => true,
+            "member_expression" => node_value(source_file, node).starts_with("abi.encode"),
+            _ => false,
+        }
+    }
+
+    fn default_instructions(&self) -> &'static str {
+        "Optimise for gas: read storage once into memory, notably the length of arrays in loops, take read-only external arguments as calldata, use `unchecked` arithmetic where it cannot overflow, custom errors rather than revert strings, and pack storage variables."
+    }
+
+    /// Hardhat projects install their libraries, like `@openzeppelin/contracts`, with npm
+    fn manifest_name(&self) -> Option<&'static str> {
+        Some("package.json")
+    }
+
+    fn declared_packages(&self, manifest: &str) -> Vec<String> {
+        ecmascript::declared_packages(manifest)
+    }
+
+    /// The packages the code imports from, bar relative paths
+    fn referenced_packages(&self, source: &str, tree: &Tree) -> Vec<String> {
+        let mut packages: Vec<String> = traverse(tree.walk(), Order::Pre)
+            .filter(|node| node.kind() == "import_directive")
+            .filter_map(|import| import.child_by_field_name("source"))
+            .filter_map(|specifier| {
+                ecmascript::package_of_specifier(
+                    node_value(source, specifier).trim_matches(['"', '\'']),
+                )
+            })
+            .collect();
+        packages.sort();
+        packages.dedup();
+
+        packages
+    }
+}
+
+/// The contract, library or interface the function is defined in, if any
+fn enclosing_contract(function_node: Node) -> Option<Node> {
+    let mut node = function_node.parent();
+    while let Some(ancestor) = node {
+        if CONTRACT_KINDS.contains(&ancestor.kind()) {
+            return Some(ancestor);
+        }
+        node = ancestor.parent();
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Tree;
+
+    use crate::helpers::tree_sitter::{node_value, parse_source_with_language};
+    use crate::supported_languages::solidity::SolidityAnalyser;
+    use crate::supported_languages::supported_language::{FunctionKind, SupportedLanguage};
+
+    const SOLIDITY_SOURCE: &str = r#"// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+import "@openzeppelin/contracts/token/ERC20/IERC20.sol";
+import "./Prices.sol";
+
+/// A cart paid in tokens
+contract Cart {
+    uint256[] private prices;
+    address public owner;
+
+    constructor() {
+        owner = msg.sender;
+    }
+
+    modifier onlyOwner() {
+        require(msg.sender == owner, "not the owner");
+        _;
+    }
+
+    function add(uint256 price) external onlyOwner {
+        prices.push(price);
+    }
+
+    function total() public view returns (uint256 sum) {
+        for (uint256 i = 0; i < prices.length; i++) {
+            sum += prices[i];
+        }
+    }
+
+    receive() external payable {}
+}
+
+library Prices {
+    function total(uint256[] memory prices) internal pure returns (uint256 sum) {
+        for (uint256 i = 0; i < prices.length; i++) sum += prices[i];
+    }
+}
+
+interface Priced {
+    function price() external view returns (uint256);
+}
+
+contract CartTest {
+    function testTotalIsZero() public {
+        assert(new Cart().total() == 0);
+    }
+}
+
+function free() pure returns (uint256) {
+    return 0;
+}
+"#;
+
+    fn solidity_source_tree() -> Tree {
+        parse_source_with_language(SOLIDITY_SOURCE, tree_sitter_solidity::language())
+    }
+
+    fn find(parent: Option<&str>, function: &str) -> Option<String> {
+        let tree = solidity_source_tree();
+        let ra: SolidityAnalyser = Default::default();
+
+        ra.find_correct_node(
+            SOLIDITY_SOURCE,
+            &tree,
+            &parent.map(str::to_string),
+            &Some(function.to_string()),
+            &None,
+        )
+        .ok()
+        .map(|function_node| node_value(SOLIDITY_SOURCE, function_node).to_string())
+    }
+
+    #[test]
+    fn no_function() {
+        let tree = solidity_source_tree();
+        let ra: SolidityAnalyser = Default::default();
+
+        assert_eq!(
+            SOLIDITY_SOURCE,
+            node_value(
+                SOLIDITY_SOURCE,
+                ra.find_correct_node(SOLIDITY_SOURCE, &tree, &None, &None, &None)
+                    .unwrap(),
+            ),
+        )
+    }
+
+    #[test]
+    fn contract_function() {
+        let target = r#"function total() public view returns (uint256 sum) {
+        for (uint256 i = 0; i < prices.length; i++) {
+            sum += prices[i];
+        }
+    }"#;
+
+        assert_eq!(Some(target), find(Some("Cart"), "total").as_deref());
+        assert_eq!(Some(target), find(None, "total").as_deref());
+    }
+
+    #[test]
+    fn library_function() {
+        let target = r#"function total(uint256[] memory prices) internal pure returns (uint256 sum) {
+        for (uint256 i = 0; i < prices.length; i++) sum += prices[i];
+    }"#;
+
+        assert_eq!(Some(target), find(Some("Prices"), "total").as_deref());
+    }
+
+    #[test]
+    fn modifier() {
+        let target = r#"modifier onlyOwner() {
+        require(msg.sender == owner, "not the owner");
+        _;
+    }"#;
+
+        assert_eq!(Some(target), find(Some("Cart"), "onlyOwner").as_deref());
+    }
+
+    #[test]
+    fn keyword_named_functions() {
+        assert_eq!(
+            Some("constructor() {\n        owner = msg.sender;\n    }"),
+            find(Some("Cart"), "constructor").as_deref()
+        );
+        assert_eq!(
+            Some("receive() external payable {}"),
+            find(Some("Cart"), "receive").as_deref()
+        );
+    }
+
+    #[test]
+    fn without_body() {
+        assert_eq!(None, find(Some("Priced"), "price"));
+        assert_eq!(None, find(Some("Unknown"), "total"));
+    }
+
+    #[test]
+    fn function_kinds() {
+        let tree = solidity_source_tree();
+        let ra: SolidityAnalyser = Default::default();
+
+        for (parent, function, kind) in [
+            (Some("Cart"), "add", FunctionKind::Method),
+            (Some("Cart"), "onlyOwner", FunctionKind::Method),
+            (Some("Cart"), "constructor", FunctionKind::Constructor),
+            (Some("Prices"), "total", FunctionKind::Function),
+            (Some("CartTest"), "testTotalIsZero", FunctionKind::Test),
+            (None, "free", FunctionKind::Function),
+        ] {
+            let function_node = ra
+                .find_correct_node(
+                    SOLIDITY_SOURCE,
+                    &tree,
+                    &parent.map(str::to_string),
+                    &Some(function.to_string()),
+                    &None,
+                )
+                .unwrap();
+
+            assert_eq!(kind, ra.function_kind(SOLIDITY_SOURCE, function_node))
+        }
+    }
+
+    #[test]
+    fn packages() {
+        let tree = solidity_source_tree();
+        let ra: SolidityAnalyser = Default::default();
+
+        assert_eq!(
+            vec!["@openzeppelin/contracts".to_string()],
+            ra.referenced_packages(SOLIDITY_SOURCE, &tree)
+        );
+    }
+}
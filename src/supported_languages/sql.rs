@@ -0,0 +1,239 @@
+use std::fmt::{Display, Formatter};
+
+use tree_sitter::{Node, Tree};
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::helpers::tree_sitter::node_value;
+use crate::supported_languages::supported_language::{FunctionKind, Language, SupportedLanguage};
+
+/// Statements creating a routine with a body. Remote functions have theirs elsewhere.
+const ROUTINE_KINDS: &[&str] = &[
+    "create_function_statement",
+    "create_table_function_statement",
+    "create_procedure_statement",
+];
+
+/// Aggregates building an array or a string out of every row of the group
+const ALLOCATING_FUNCTIONS: &[&str] = &["ARRAY_AGG", "ARRAY_CONCAT_AGG", "STRING_AGG"];
+
+pub struct SqlAnalyser {
+    inner: Language,
+}
+
+impl Default for SqlAnalyser {
+    fn default() -> Self {
+        Self {
+            inner: Language::Sql,
+        }
+    }
+}
+
+impl Display for SqlAnalyser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl SupportedLanguage for SqlAnalyser {
+    /// Functions and procedures are found by their name, which may be qualified by their dataset,
+    /// like `shop.refresh_totals`. The parent is that dataset.
+    fn find_correct_node<'a>(
+        &self,
+        source_file: &str,
+        root_tree: &'a Tree,
+        parent_identifier: &Option<String>,
+        function_identifier: &Option<String>,
+        kind: &Option<FunctionKind>,
+    ) -> Result<Node<'a>, &'a str> {
+        let Some(function) = function_identifier else {
+            return Ok(root_tree.root_node());
+        };
+
+        self.find_all_functions(root_tree)
+            .into_iter()
+            .filter(|function_node| {
+                let Some(routine_name) = routine_name(source_file, *function_node) else {
+                    return false;
+                };
+                let (dataset, name) = match routine_name.rsplit_once('.') {
+                    Some((dataset, name)) => (Some(dataset), name),
+                    None => (None, routine_name),
+                };
+
+                match parent_identifier {
+                    Some(parent) => Some(parent.as_str()) == dataset && name == function,
+                    None => routine_name == function || name == function,
+                }
+            })
+            .find(|function_node| self.is_of_kind(source_file, *function_node, kind))
+            .ok_or("function not found")
+    }
+
+    fn find_all_functions<'a>(&self, root_tree: &'a Tree) -> Vec<Node<'a>> {
+        traverse(root_tree.walk(), Order::Pre)
+            .filter(|node| ROUTINE_KINDS.contains(&node.kind()))
+            .collect()
+    }
+
+    fn node_kinds(&self) -> &'static [&'static str] {
+        &[
+            "create_function_statement",
+            "create_table_function_statement",
+            "create_procedure_statement",
+            "create_function_body",
+            "procedure_body",
+        ]
+    }
+
+    /// SQL has no methods, and tests of its routines live outside of them
+    fn function_kind(&self, _source_file: &str, _function_node: Node) -> FunctionKind {
+        FunctionKind::Function
+    }
+
+    fn language(&self) -> tree_sitter::Language {
+        tree_sitter_sql_bigquery::language()
+    }
+
+    fn is_allocation_suspect(&self, source_file: &str, node: Node) -> bool {
+        node.kind() == "function_call"
+            && node
+                .child_by_field_name("function")
+                .is_some_and(|function| {
+                    ALLOCATING_FUNCTIONS
+                        .contains(&node_value(source_file, function).to_uppercase().as_str())
+                })
+    }
+
+    fn default_instructions(&self) -> &'static str {
+        "Filter and aggregate as early as possible, select only the columns that are used, prefer joins to correlated subqueries, and avoid scanning the same table more than once."
+    }
+
+    /// Routines use tables and other routines, which no manifest declares
+    fn manifest_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn declared_packages(&self, _manifest: &str) -> Vec<String> {
+        vec![]
+    }
+
+    fn referenced_packages(&self, _source: &str, _tree: &Tree) -> Vec<String> {
+        vec![]
+    }
+
+    fn line_comment(&self) -> &'static str {
+        "--"
+    }
+}
+
+/// The name the routine is created with, qualified by its dataset if it is
+fn routine_name<'a>(source_file: &'a str, function_node: Node<'a>) -> Option<&'a str> {
+    function_node
+        .child_by_field_name("routine_name")
+        .map(|name| node_value(source_file, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter::Tree;
+
+    use crate::helpers::tree_sitter::{node_value, parse_source_with_language};
+    use crate::supported_languages::sql::SqlAnalyser;
+    use crate::supported_languages::supported_language::SupportedLanguage;
+
+    const SQL_SOURCE: &str = r#"-- Totals of the carts
+CREATE TEMP FUNCTION total(prices ARRAY<INT64>) RETURNS INT64 AS (
+  (SELECT SUM(price) FROM UNNEST(prices) AS price)
+);
+
+CREATE OR REPLACE FUNCTION shop.discounted(price INT64, rate FLOAT64) AS (price * (1 - rate));
+
+CREATE PROCEDURE shop.refresh_totals(IN since DATE)
+BEGIN
+  DELETE FROM shop.totals WHERE day >= since;
+  INSERT INTO shop.totals SELECT day, SUM(price) FROM shop.orders WHERE day >= since GROUP BY day;
+END;
+
+CREATE TABLE FUNCTION shop.orders_of(customer STRING) AS
+  SELECT * FROM shop.orders WHERE customer_id = customer;
+
+SELECT total([1, 2, 3]);
+"#;
+
+    fn sql_source_tree() -> Tree {
+        parse_source_with_language(SQL_SOURCE, tree_sitter_sql_bigquery::language())
+    }
+
+    fn find(parent: Option<&str>, function: &str) -> Option<String> {
+        let tree = sql_source_tree();
+        let ra: SqlAnalyser = Default::default();
+
+        ra.find_correct_node(
+            SQL_SOURCE,
+            &tree,
+            &parent.map(str::to_string),
+            &Some(function.to_string()),
+            &None,
+        )
+        .ok()
+        .map(|function_node| node_value(SQL_SOURCE, function_node).to_string())
+    }
+
+    #[test]
+    fn no_function() {
+        let tree = sql_source_tree();
+        let ra: SqlAnalyser = Default::default();
+
+        assert_eq!(
+            SQL_SOURCE,
+            node_value(
+                SQL_SOURCE,
+                ra.find_correct_node(SQL_SOURCE, &tree, &None, &None, &None)
+                    .unwrap(),
+            ),
+        )
+    }
+
+    #[test]
+    fn function() {
+        let target = r#"CREATE TEMP FUNCTION total(prices ARRAY<INT64>) RETURNS INT64 AS (
+  (SELECT SUM(price) FROM UNNEST(prices) AS price)
+)"#;
+
+        assert_eq!(Some(target), find(None, "total").as_deref());
+        assert_eq!(None, find(Some("shop"), "total"));
+    }
+
+    #[test]
+    fn qualified_names() {
+        let target =
+            "CREATE OR REPLACE FUNCTION shop.discounted(price INT64, rate FLOAT64) AS (price * (1 - rate))";
+
+        assert_eq!(Some(target), find(None, "discounted").as_deref());
+        assert_eq!(Some(target), find(None, "shop.discounted").as_deref());
+        assert_eq!(Some(target), find(Some("shop"), "discounted").as_deref());
+        assert_eq!(None, find(Some("warehouse"), "discounted"));
+    }
+
+    #[test]
+    fn procedure() {
+        let target = r#"CREATE PROCEDURE shop.refresh_totals(IN since DATE)
+BEGIN
+  DELETE FROM shop.totals WHERE day >= since;
+  INSERT INTO shop.totals SELECT day, SUM(price) FROM shop.orders WHERE day >= since GROUP BY day;
+END"#;
+
+        assert_eq!(
+            Some(target),
+            find(Some("shop"), "refresh_totals").as_deref()
+        );
+    }
+
+    #[test]
+    fn table_function() {
+        let target = r#"CREATE TABLE FUNCTION shop.orders_of(customer STRING) AS
+  SELECT * FROM shop.orders WHERE customer_id = customer"#;
+
+        assert_eq!(Some(target), find(None, "orders_of").as_deref());
+    }
+}
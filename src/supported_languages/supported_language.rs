@@ -1,14 +1,32 @@
 use std::fmt::{Display, Formatter};
 
-use tree_sitter::{Node, Tree};
+use tree_sitter::{Node, Range, Tree};
+use tree_sitter_traversal::{traverse, Order};
 
 use Language::*;
 
+use crate::supported_languages::bash::BashAnalyser;
+use crate::supported_languages::cpp::CppAnalyser;
+use crate::supported_languages::erlang::ErlangAnalyser;
 use crate::supported_languages::go::GoAnalyser;
+use crate::supported_languages::java::JavaAnalyser;
 use crate::supported_languages::javascript::JavascriptAnalyser;
 use crate::supported_languages::rust::RustAnalyzer;
+use crate::supported_languages::scala::ScalaAnalyser;
+use crate::supported_languages::solidity::SolidityAnalyser;
+use crate::supported_languages::sql::SqlAnalyser;
 use crate::supported_languages::typescript::TypescriptAnalyser;
 
+/// What role a function plays in its language. Used to disambiguate functions that share a name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum FunctionKind {
+    Function,
+    Method,
+    Getter,
+    Constructor,
+    Test,
+}
+
 pub trait SupportedLanguage: Display {
     fn find_correct_node<'a>(
         &self,
@@ -16,23 +34,85 @@ pub trait SupportedLanguage: Display {
         root_tree: &'a Tree,
         parent_identifier: &Option<String>,
         function_identifier: &Option<String>,
+        kind: &Option<FunctionKind>,
     ) -> Result<Node<'a>, &'a str>;
+    /// Every node that `find_correct_node` could return for some identifiers, in source order
+    fn find_all_functions<'a>(&self, root_tree: &'a Tree) -> Vec<Node<'a>>;
+    /// The node kinds functions are found by, which an update of the grammar may rename
+    fn node_kinds(&self) -> &'static [&'static str];
+    /// Classifies a node that `find_correct_node` can return
+    fn function_kind(&self, source_file: &str, function_node: Node) -> FunctionKind;
     fn language(&self) -> tree_sitter::Language;
+    /// Whether the node is an expression that likely allocates on the heap, like a `clone()`
+    fn is_allocation_suspect(&self, source_file: &str, node: Node) -> bool;
+    /// Idiomatic optimisation advice added to every prompt, unless `.senior.toml` overrides it
+    fn default_instructions(&self) -> &'static str;
+    /// The file declaring the dependencies of a package, like `Cargo.toml`, if the language has one
+    fn manifest_name(&self) -> Option<&'static str>;
+    /// The names code refers to the dependencies declared in the manifest by
+    fn declared_packages(&self, manifest: &str) -> Vec<String>;
+    /// The crates, packages or modules the code refers to, bar the ones built into the language
+    fn referenced_packages(&self, source: &str, tree: &Tree) -> Vec<String>;
+    /// The command adding a dependency to the manifest, when there is one
+    fn add_dependency_command(&self, _package: &str) -> Option<Vec<String>> {
+        None
+    }
+    /// What starts a comment running to the end of the line
+    fn line_comment(&self) -> &'static str {
+        "//"
+    }
+    /// The nodes the function is made of, which is the node itself unless the grammar splits
+    /// the function in several
+    fn clauses<'a>(&self, _source_file: &str, function_node: Node<'a>) -> Vec<Node<'a>> {
+        vec![function_node]
+    }
+    /// The range a suggestion for the node replaces, which is the one of the node unless the
+    /// grammar takes in what follows it, or splits the function in several nodes
+    fn replaced_range(&self, _source_file: &str, node: Node) -> Range {
+        node.range()
+    }
+
+    fn is_of_kind(
+        &self,
+        source_file: &str,
+        function_node: Node,
+        kind: &Option<FunctionKind>,
+    ) -> bool {
+        kind.is_none_or(|kind| self.function_kind(source_file, function_node) == kind)
+    }
 }
 
 pub enum Language {
+    Bash,
+    Cpp,
+    Erlang,
     Go,
+    Java,
     JavaScript,
+    Jsx,
     Rust,
+    Scala,
+    Solidity,
+    Sql,
+    Tsx,
     TypeScript,
 }
 
 impl Display for Language {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let stringified_value = match self {
+            Bash => "bash",
+            Cpp => "cpp",
+            Erlang => "erlang",
             Go => "go",
+            Java => "java",
             JavaScript => "javascript",
+            Jsx => "jsx",
             Rust => "rust",
+            Scala => "scala",
+            Solidity => "solidity",
+            Sql => "sql",
+            Tsx => "tsx",
             TypeScript => "typescript",
         };
 
@@ -40,12 +120,74 @@ impl Display for Language {
     }
 }
 
+// The versions of the grammar crates in Cargo.toml, which a test keeps these in sync with
+const TREE_SITTER_BASH_VERSION: &str = "0.20.5";
+const TREE_SITTER_CPP_VERSION: &str = "0.20.5";
+const TREE_SITTER_ERLANG_VERSION: &str = "0.4.0";
+const TREE_SITTER_GO_VERSION: &str = "0.20.0";
+const TREE_SITTER_JAVA_VERSION: &str = "0.20.2";
+const TREE_SITTER_JAVASCRIPT_VERSION: &str = "0.20.0";
+const TREE_SITTER_RUST_VERSION: &str = "0.20.3";
+const TREE_SITTER_SCALA_VERSION: &str = "0.20.3";
+const TREE_SITTER_SOLIDITY_VERSION: &str = "1.2.9";
+const TREE_SITTER_SQL_BIGQUERY_VERSION: &str = "0.8.0";
+const TREE_SITTER_TYPESCRIPT_VERSION: &str = "0.20.2";
+
+/// Every language senior has an analyser for
+pub const LANGUAGES: [Language; 13] = [
+    Rust, Go, JavaScript, Jsx, TypeScript, Tsx, Java, Cpp, Bash, Scala, Erlang, Sql, Solidity,
+];
+
+impl Language {
+    /// The crate of the tree-sitter grammar the language is parsed with
+    pub fn grammar_crate(&self) -> &'static str {
+        match self {
+            Bash => "tree-sitter-bash",
+            Cpp => "tree-sitter-cpp",
+            Erlang => "tree-sitter-erlang",
+            Go => "tree-sitter-go",
+            Java => "tree-sitter-java",
+            JavaScript | Jsx => "tree-sitter-javascript",
+            Rust => "tree-sitter-rust",
+            Scala => "tree-sitter-scala",
+            Solidity => "tree-sitter-solidity",
+            Sql => "tree-sitter-sql-bigquery",
+            Tsx | TypeScript => "tree-sitter-typescript",
+        }
+    }
+    /// The version of the grammar crate, as Cargo.toml asks for it
+    pub fn grammar_version(&self) -> &'static str {
+        match self {
+            Bash => TREE_SITTER_BASH_VERSION,
+            Cpp => TREE_SITTER_CPP_VERSION,
+            Erlang => TREE_SITTER_ERLANG_VERSION,
+            Go => TREE_SITTER_GO_VERSION,
+            Java => TREE_SITTER_JAVA_VERSION,
+            JavaScript | Jsx => TREE_SITTER_JAVASCRIPT_VERSION,
+            Rust => TREE_SITTER_RUST_VERSION,
+            Scala => TREE_SITTER_SCALA_VERSION,
+            Solidity => TREE_SITTER_SOLIDITY_VERSION,
+            Sql => TREE_SITTER_SQL_BIGQUERY_VERSION,
+            Tsx | TypeScript => TREE_SITTER_TYPESCRIPT_VERSION,
+        }
+    }
+}
+
 /// File globs that identify languages based on the file path.
 pub fn language_globs(language: &Language) -> Vec<glob::Pattern> {
     let glob_strs: &'static [&'static str] = match language {
+        Bash => &["*.sh", "*.bash"],
+        Cpp => &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx", "*.h"],
+        Erlang => &["*.erl", "*.hrl"],
         Go => &["*.go"],
+        Java => &["*.java"],
         JavaScript => &["*.cjs", "*.js", "*.mjs"],
+        Jsx => &["*.jsx"],
         Rust => &["*.rs"],
+        Scala => &["*.scala", "*.sc"],
+        Solidity => &["*.sol"],
+        Sql => &["*.sql"],
+        Tsx => &["*.tsx"],
         TypeScript => &["*.ts"],
     };
 
@@ -55,23 +197,381 @@ pub fn language_globs(language: &Language) -> Vec<glob::Pattern> {
         .collect()
 }
 
-pub fn detect_language(file_name: &str) -> Result<Box<dyn SupportedLanguage>, &str> {
-    let candidate_language = vec![Rust, Go, JavaScript, TypeScript]
-        .into_iter()
-        .find(|language| {
+/// File globs that identify test files, which only contain test code.
+pub fn test_globs(language: &Language) -> Vec<glob::Pattern> {
+    let glob_strs: &'static [&'static str] = match language {
+        Bash => &["*_test.sh", "test_*.sh", "**/test/*.sh", "**/tests/*.sh"],
+        Cpp => &["*_test.cpp", "*_test.cc", "**/test/*.cpp", "**/tests/*.cpp"],
+        Erlang => &["*_tests.erl", "*_SUITE.erl", "**/test/*.erl"],
+        Go => &["*_test.go"],
+        Java => &["**/src/test/**/*.java", "*Test.java", "*Tests.java"],
+        JavaScript => &["*.test.*js", "*.spec.*js", "**/__tests__/*"],
+        Jsx => &["*.test.jsx", "*.spec.jsx", "**/__tests__/*"],
+        Rust => &["**/tests/*.rs", "**/benches/*.rs"],
+        Scala => &[
+            "**/src/test/**/*.scala",
+            "*Test.scala",
+            "*Spec.scala",
+            "*Suite.scala",
+        ],
+        Solidity => &["*.t.sol", "**/test/*.sol"],
+        Sql => &["*_test.sql", "**/tests/*.sql"],
+        Tsx => &["*.test.tsx", "*.spec.tsx", "**/__tests__/*"],
+        TypeScript => &["*.test.ts", "*.spec.ts", "**/__tests__/*"],
+    };
+
+    glob_strs
+        .iter()
+        .map(|name| glob::Pattern::new(name).unwrap())
+        .collect()
+}
+
+/// The path as globs see it, with forward slashes and the extension in lower case, so that
+/// `C:\\repo\\src\\MAIN.RS` is a Rust file on every OS
+pub fn glob_path(file_name: &str) -> String {
+    let file_name = file_name.replace('\\', "/");
+    let name_start = file_name.rfind('/').map_or(0, |slash| slash + 1);
+    match file_name[name_start..].rfind('.') {
+        Some(dot) => {
+            let extension_start = name_start + dot;
+            format!(
+                "{}{}",
+                &file_name[..extension_start],
+                file_name[extension_start..].to_lowercase()
+            )
+        }
+        None => file_name,
+    }
+}
+
+/// Whether the glob matches the path, whichever separators and extension case it has
+pub fn matches_glob(pattern: &glob::Pattern, file_name: &str) -> bool {
+    pattern.matches(&glob_path(file_name))
+}
+
+pub fn is_test_file(file_name: &str) -> bool {
+    LANGUAGES
+        .iter()
+        .filter(|language| {
             language_globs(language)
                 .into_iter()
-                .any(|patt| patt.matches(file_name))
+                .any(|patt| matches_glob(&patt, file_name))
+        })
+        .any(|language| {
+            test_globs(language)
+                .into_iter()
+                .any(|patt| matches_glob(&patt, file_name))
+        })
+}
+
+/// The node kinds the analyser finds functions by that its grammar does not have
+pub fn unknown_node_kinds(supported_language: &dyn SupportedLanguage) -> Vec<&'static str> {
+    let grammar = supported_language.language();
+    supported_language
+        .node_kinds()
+        .iter()
+        .filter(|kind| grammar.id_for_node_kind(kind, true) == 0)
+        .copied()
+        .collect()
+}
+
+/// Why no function was found, blaming the grammar rather than the identifiers when an update of it
+/// renamed the node kinds the analyser relies on
+pub fn explain_not_found(supported_language: &dyn SupportedLanguage, error: &str) -> String {
+    let unknown = unknown_node_kinds(supported_language);
+    if unknown.is_empty() {
+        return error.to_string();
+    }
+
+    let grammar = LANGUAGES
+        .iter()
+        .find(|language| language.to_string() == supported_language.to_string())
+        .map_or("".to_string(), |language| {
+            format!(
+                "{} {}, ",
+                language.grammar_crate(),
+                language.grammar_version()
+            )
         });
 
-    if let Some(language) = candidate_language {
-        match language {
-            Rust => Ok(Box::<RustAnalyzer>::default()),
-            Go => Ok(Box::<GoAnalyser>::default()),
-            JavaScript => Ok(Box::<JavascriptAnalyser>::default()),
-            TypeScript => Ok(Box::<TypescriptAnalyser>::default()),
+    format!(
+        "the {} grammar ({}ABI version {}) changed node kinds, it has no `{}`; please update senior",
+        supported_language,
+        grammar,
+        supported_language.language().version(),
+        unknown.join("`, `")
+    )
+}
+
+// What a node of the tree dump is called, like the name of a function or the type of an impl
+fn dumped_name<'a>(source_file: &'a str, node: Node<'a>) -> Option<&'a str> {
+    let name_node = ["name", "type", "declarator"]
+        .iter()
+        .find_map(|field| node.child_by_field_name(field))
+        .or_else(|| node.named_child(0)?.child_by_field_name("name"))?;
+
+    source_file[name_node.byte_range()].lines().next()
+}
+
+/// The nodes the analyser finds functions by, and the syntax errors among them, indented by how
+/// they nest, with their names and lines. Shows why a function was not found.
+pub fn dump_tree(
+    supported_language: &dyn SupportedLanguage,
+    source_file: &str,
+    tree: &Tree,
+) -> String {
+    let kinds = supported_language.node_kinds();
+    let is_dumped = |node: &Node| kinds.contains(&node.kind()) || node.is_error();
+    let mut dump = vec![];
+    for node in traverse(tree.walk(), Order::Pre).filter(is_dumped) {
+        let mut depth = 0;
+        let mut ancestor = node.parent();
+        while let Some(node) = ancestor {
+            depth += usize::from(is_dumped(&node));
+            ancestor = node.parent();
+        }
+        let name = dumped_name(source_file, node)
+            .filter(|_| !node.is_error())
+            .map(|name| format!(" `{}`", name))
+            .unwrap_or_default();
+        dump.push(format!(
+            "{}{}{} lines {}-{}",
+            "  ".repeat(depth),
+            node.kind(),
+            name,
+            node.start_position().row + 1,
+            node.end_position().row + 1
+        ));
+    }
+
+    dump.join("\n")
+}
+
+/// The analyser of the language
+pub fn analyser(language: &Language) -> Box<dyn SupportedLanguage> {
+    match language {
+        Rust => Box::<RustAnalyzer>::default(),
+        Go => Box::<GoAnalyser>::default(),
+        JavaScript => Box::<JavascriptAnalyser>::default(),
+        TypeScript => Box::<TypescriptAnalyser>::default(),
+        Jsx => Box::new(JavascriptAnalyser::jsx()),
+        Tsx => Box::new(TypescriptAnalyser::tsx()),
+        Java => Box::<JavaAnalyser>::default(),
+        Cpp => Box::<CppAnalyser>::default(),
+        Bash => Box::<BashAnalyser>::default(),
+        Scala => Box::<ScalaAnalyser>::default(),
+        Erlang => Box::<ErlangAnalyser>::default(),
+        Sql => Box::<SqlAnalyser>::default(),
+        Solidity => Box::<SolidityAnalyser>::default(),
+    }
+}
+
+pub fn detect_language(file_name: &str) -> Result<Box<dyn SupportedLanguage>, &str> {
+    let candidate_language = LANGUAGES.into_iter().find(|language| {
+        language_globs(language)
+            .into_iter()
+            .any(|patt| matches_glob(&patt, file_name))
+    });
+
+    match candidate_language {
+        Some(language) => Ok(analyser(&language)),
+        None => Err("not a supported file"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tree_sitter_traversal::{traverse, Order};
+
+    use crate::helpers::tree_sitter::{node_value, parse_source_with_language};
+    use crate::supported_languages::supported_language::{
+        analyser, detect_language, dump_tree, is_test_file, unknown_node_kinds, FunctionKind,
+        Language, LANGUAGES,
+    };
+
+    const ROUNDS: usize = 200;
+
+    // Legal code that is easy to overlook, such as anonymous classes and blocks without statements
+    const SOURCES: &[(&str, &str)] = &[
+        ("rust.rs", include_str!("../../example_sources/rust.rs")),
+        (
+            "rust.rs",
+            "impl<T> Trait for Box<dyn Fn(T)> { fn f(&self) {} }\nimpl Default for () {}\nfn g() -> impl Fn() { || {} }\nmacro_rules! m { () => {} }",
+        ),
+        ("go.go", include_str!("../../example_sources/go.go")),
+        (
+            "go.go",
+            "package p\nfunc (Greeter) f() {}\nfunc (g *Greeter[T]) h() int { return g.x }\nfunc () broken() {}\nvar f = func() {}",
+        ),
+        ("javascript.js", include_str!("../../example_sources/javascript.js")),
+        (
+            "javascript.js",
+            "const A = class { f() {} };\nlet [a, b] = [() => {}, function () {}];\nlet c;\nnew (class { g() {} })();\nexport default function () {}",
+        ),
+        ("typescript.ts", include_str!("../../example_sources/typescript.ts")),
+        (
+            "typescript.ts",
+            "const A = class<T> { f(): T {} };\nlet { a } = { a: () => 1 };\ndeclare function g(): void;\nnamespace N { export const h = () => {} }\nabstract class B { abstract i(): void }",
+        ),
+        (
+            "component.tsx",
+            "export const List = <T,>({ items }: { items: T[] }) => <ul>{items.map((i) => <li>{String(i)}</li>)}</ul>;\nfunction f() { return <><A b={() => 1} /></>; }",
+        ),
+        (
+            "component.jsx",
+            "export default function App() { return <div onClick={() => go()}>{a ? <B /> : null}</div>; }",
+        ),
+        ("java.java", include_str!("../../example_sources/java.java")),
+        ("cpp.cpp", include_str!("../../example_sources/cpp.cpp")),
+        (
+            "cpp.cpp",
+            "template <> void A<int>::f() {}\nauto g = [](int x) { return x; };\nstruct B { B() = default; ~B() {} operator bool() const { return true; } };\nint (*h(int))(int) { return 0; }\nvoid ::i() {}",
+        ),
+        (
+            "java.java",
+            "record R(int a) { R { } }\nenum E { A { void f() {} }; abstract void f(); }\nclass C { Runnable r = new Runnable() { public void run() {} }; { } }\n@interface N { int v() default 0; }",
+        ),
+    ];
+
+    /// A xorshift generator, seeded so that failures can be replayed
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+    }
+
+    /// Deletes, duplicates and moves around pieces of the source, keeping it valid-ish
+    fn mutate(random: &mut Random, source: &str) -> String {
+        let mut pieces: Vec<&str> = source
+            .split_inclusive(|c: char| c.is_whitespace() || "(){}[]<>;,.:".contains(c))
+            .collect();
+        for _ in 0..random.below(8) + 1 {
+            let index = random.below(pieces.len());
+            match random.below(3) {
+                0 => {
+                    pieces.remove(index);
+                }
+                1 => pieces.insert(index, pieces[index]),
+                _ => {
+                    let other = random.below(pieces.len());
+                    pieces.swap(index, other);
+                }
+            }
+            if pieces.is_empty() {
+                break;
+            }
+        }
+
+        pieces.concat()
+    }
+
+    #[test]
+    fn fuzzed_sources_do_not_panic() {
+        let kinds = [
+            None,
+            Some(FunctionKind::Function),
+            Some(FunctionKind::Method),
+            Some(FunctionKind::Getter),
+            Some(FunctionKind::Test),
+        ];
+        let mut random = Random(0x5eed);
+        for (file_name, source) in SOURCES {
+            let supported_language = detect_language(file_name).unwrap();
+            for _ in 0..ROUNDS {
+                let source = mutate(&mut random, source);
+                let tree = parse_source_with_language(&source, supported_language.language());
+                let identifiers: Vec<String> = traverse(tree.walk(), Order::Pre)
+                    .filter(|node| node.kind().contains("identifier"))
+                    .map(|node| node_value(&source, node).to_string())
+                    .collect();
+                let pick = |random: &mut Random| {
+                    (!identifiers.is_empty() && random.below(4) != 0)
+                        .then(|| identifiers[random.below(identifiers.len())].clone())
+                };
+                let parent = pick(&mut random);
+                let function = pick(&mut random);
+                let kind = &kinds[random.below(kinds.len())];
+
+                let _ =
+                    supported_language.find_correct_node(&source, &tree, &parent, &function, kind);
+                for function_node in supported_language.find_all_functions(&tree) {
+                    supported_language.function_kind(&source, function_node);
+                }
+                for node in traverse(tree.walk(), Order::Pre) {
+                    supported_language.is_allocation_suspect(&source, node);
+                }
+                supported_language.referenced_packages(&source, &tree);
+            }
         }
-    } else {
-        Err("not a supported file")
+    }
+
+    #[test]
+    fn grammars_have_the_node_kinds_analysers_rely_on() {
+        for language in LANGUAGES {
+            assert_eq!(
+                Vec::<&str>::new(),
+                unknown_node_kinds(analyser(&language).as_ref()),
+                "{}",
+                language
+            );
+        }
+        // Kinds a grammar does not have, like the interfaces of Javascript, are told apart by id 0
+        let javascript = analyser(&Language::JavaScript);
+        assert_eq!(
+            0,
+            javascript
+                .language()
+                .id_for_node_kind("interface_declaration", true)
+        );
+    }
+
+    #[test]
+    fn grammar_versions_match_the_manifest() {
+        let manifest: toml::Table = include_str!("../../Cargo.toml").parse().unwrap();
+        for language in LANGUAGES {
+            assert_eq!(
+                Some(language.grammar_version()),
+                manifest["dependencies"][language.grammar_crate()].as_str(),
+                "{}",
+                language
+            );
+        }
+    }
+
+    #[test]
+    fn tree_dumps() {
+        let source = "struct Cart;\n\nimpl Cart {\n    fn total(&self) -> u64 {\n        0\n    }\n}\n\nfn checkout() {}\n";
+        let supported_language = detect_language("src/cart.rs").unwrap();
+        let tree = parse_source_with_language(source, supported_language.language());
+
+        assert_eq!(
+            "impl_item `Cart` lines 3-7\n  function_item `total` lines 4-6\nfunction_item `checkout` lines 9-9",
+            dump_tree(supported_language.as_ref(), source, &tree)
+        );
+    }
+
+    #[test]
+    fn windows_paths() {
+        assert_eq!(
+            "rust",
+            detect_language("C:\\repo\\src\\MAIN.RS")
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(
+            "tsx",
+            detect_language("\\\\?\\C:\\repo\\src\\Cart.TSX")
+                .unwrap()
+                .to_string()
+        );
+        assert!(is_test_file("C:\\repo\\tests\\cart.rs"));
+        assert!(is_test_file("\\\\server\\share\\repo\\cart_test.Go"));
+        assert!(!is_test_file("C:\\repo\\src\\Latest.java"));
+        assert!(detect_language("C:\\repo\\README.md").is_err());
     }
 }
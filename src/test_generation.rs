@@ -0,0 +1,399 @@
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::{Path, PathBuf};
+
+use colored::*;
+use inquire::Confirm;
+use tree_sitter::{Node, Tree};
+use tree_sitter_traversal::{traverse, Order};
+
+use crate::color::Palette;
+use crate::diff::render_text_diff;
+use crate::extract::{imports, insert_after, root_child, Selection};
+use crate::helpers::tree_sitter::{node_value, parse_with_thread_parser, syntax_errors};
+use crate::openai::{complete, Prompt};
+use crate::suggestion::parse_suggestion;
+use crate::supported_languages::supported_language::{
+    detect_language, explain_not_found, SupportedLanguage,
+};
+
+/// Where Rust tests of a file live, within the file itself
+const RUST_TEST_MODULE: &str = "tests";
+
+pub struct TestSettings<'a> {
+    pub selection: Selection,
+    pub model: &'a str,
+    pub skip_prompt: bool,
+    pub palette: Palette,
+}
+
+/// The file the tests of a source file conventionally go to: the file itself in Rust, where they
+/// go in its `mod tests`, the `_test.go` file next to it in Go, and in Javascript and Typescript
+/// the `.test` or `.spec` file next to it, or else the one in `__tests__/`
+pub fn test_file_path(file_path: &Path, language: &str) -> Result<PathBuf, String> {
+    let directory = file_path.parent().unwrap_or(Path::new(""));
+    let stem = file_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = file_path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    match language {
+        "rust" => Ok(file_path.to_path_buf()),
+        "go" => Ok(directory.join(format!("{}_test.go", stem))),
+        "javascript" | "jsx" | "typescript" | "tsx" => {
+            let candidates = [
+                directory.join(format!("{}.test.{}", stem, extension)),
+                directory.join(format!("{}.spec.{}", stem, extension)),
+                directory
+                    .join("__tests__")
+                    .join(format!("{}.test.{}", stem, extension)),
+                directory
+                    .join("__tests__")
+                    .join(format!("{}.spec.{}", stem, extension)),
+            ];
+            Ok(candidates
+                .iter()
+                .find(|candidate| candidate.exists())
+                .unwrap_or(&candidates[2])
+                .clone())
+        }
+        _ => Err(format!(
+            "tests are only written for rust, go, javascript and typescript, not for {}",
+            language
+        )),
+    }
+}
+
+// The `#[cfg(test)] mod tests` of the file, if it has one
+fn rust_test_module<'a>(source_file: &str, tree: &'a Tree) -> Option<Node<'a>> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let module = root.named_children(&mut cursor).find(|node| {
+        node.kind() == "mod_item"
+            && node
+                .child_by_field_name("name")
+                .is_some_and(|name| node_value(source_file, name) == RUST_TEST_MODULE)
+            && node.child_by_field_name("body").is_some()
+    });
+
+    module
+}
+
+/// The tests the test file already has, which the new ones should not repeat
+pub fn existing_tests(test_file: &str, tree: &Tree, language: &str) -> String {
+    if language != "rust" {
+        return test_file.to_string();
+    }
+
+    rust_test_module(test_file, tree)
+        .map(|module| node_value(test_file, module).to_string())
+        .unwrap_or_default()
+}
+
+// The quoted paths an import names, which tell whether two imports are the same
+fn imported_paths<'a>(source_file: &'a str, import: Node<'a>) -> Vec<&'a str> {
+    traverse(import.walk(), Order::Pre)
+        .filter(|node| node.kind().contains("string") && node.named_child_count() <= 1)
+        .map(|node| node_value(source_file, node).trim_matches(['"', '\'', '`']))
+        .filter(|path| !path.is_empty())
+        .collect()
+}
+
+fn indent(code: &str, indentation: &str) -> String {
+    code.lines()
+        .map(|line| {
+            if line.trim().is_empty() {
+                "".to_string()
+            } else {
+                format!("{}{}", indentation, line)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// The test file with the new tests added where they belong: at the end of the `mod tests` of a
+/// Rust file, which is created if need be, or at the end of the other test files, with the
+/// imports they lack added after theirs
+pub fn add_tests(
+    supported_language: &dyn SupportedLanguage,
+    test_file: &str,
+    tests: &str,
+    package_clause: Option<&str>,
+) -> Result<String, String> {
+    let language = supported_language.to_string();
+    let tree = parse_with_thread_parser(test_file, supported_language.language())
+        .ok_or("failed to parse the test file")?;
+    let tests_tree = parse_with_thread_parser(tests, supported_language.language())
+        .ok_or("failed to parse the tests")?;
+
+    if language == "rust" {
+        return Ok(match rust_test_module(test_file, &tree) {
+            Some(module) => {
+                let body = module.child_by_field_name("body").unwrap();
+                let closing_brace = body.end_byte() - 1;
+                let existing = &test_file[body.start_byte() + 1..closing_brace];
+                // Importing the same item twice into a module does not compile
+                let tests: Vec<&str> = tests
+                    .lines()
+                    .filter(|line| {
+                        !(line.starts_with("use ")
+                            && existing.lines().any(|existing| existing.trim() == *line))
+                    })
+                    .collect();
+                format!(
+                    "{}\n\n{}\n{}",
+                    test_file[..closing_brace].trim_end(),
+                    indent(tests.join("\n").trim(), "    "),
+                    &test_file[closing_brace..]
+                )
+            }
+            None => format!(
+                "{}\n\n#[cfg(test)]\nmod {} {{\n{}\n}}\n",
+                test_file.trim_end(),
+                RUST_TEST_MODULE,
+                indent(tests.trim(), "    ")
+            ),
+        });
+    }
+
+    let existing_paths: Vec<&str> = imports(&tree)
+        .iter()
+        .flat_map(|import| imported_paths(test_file, *import))
+        .collect();
+    let new_imports = imports(&tests_tree);
+    // Go groups imports, of which only the missing ones are added, one by one
+    let missing_imports: Vec<String> = new_imports
+        .iter()
+        .flat_map(|import| {
+            let specs: Vec<Node> = traverse(import.walk(), Order::Pre)
+                .filter(|node| node.kind() == "import_spec")
+                .collect();
+            if specs.is_empty() {
+                vec![(*import, node_value(tests, *import).to_string())]
+            } else {
+                specs
+                    .into_iter()
+                    .map(|spec| (spec, format!("import {}", node_value(tests, spec))))
+                    .collect()
+            }
+        })
+        .filter(|(import, _)| {
+            !imported_paths(tests, *import)
+                .iter()
+                .all(|path| existing_paths.contains(path))
+        })
+        .map(|(_, import)| import)
+        .collect();
+    let rest = new_imports.iter().fold(tests.to_string(), |rest, import| {
+        rest.replace(node_value(tests, *import), "")
+    });
+
+    let mut test_file = match (test_file.trim().is_empty(), package_clause) {
+        (true, Some(package_clause)) => format!("{}\n", package_clause),
+        _ => test_file.to_string(),
+    };
+    if !missing_imports.is_empty() {
+        let tree = parse_with_thread_parser(&test_file, supported_language.language())
+            .ok_or("failed to parse the test file")?;
+        let missing_imports = missing_imports.join("\n");
+        test_file = match imports(&tree).last() {
+            Some(import) => insert_after(&test_file, Some(*import), &missing_imports),
+            None => match root_child(&tree, "package_clause") {
+                Some(clause) => {
+                    insert_after(&test_file, Some(clause), &format!("\n{}", missing_imports))
+                }
+                None => insert_after(&test_file, None, &missing_imports),
+            },
+        };
+    }
+
+    Ok(match test_file.trim_end() {
+        "" => format!("{}\n", rest.trim()),
+        test_file => format!("{}\n\n{}\n", test_file, rest.trim()),
+    })
+}
+
+fn draft_test_instructions(
+    language: &str,
+    function: &str,
+    module: &str,
+    existing_tests: &str,
+) -> Prompt {
+    let existing_tests = if existing_tests.trim().is_empty() {
+        "There are no tests yet.".to_string()
+    } else {
+        format!(
+            "These tests exist already, so do not repeat what they cover:\n{}",
+            existing_tests
+        )
+    };
+
+    Prompt {
+        system: format!(
+            r#"You are a senior {} engineer writing unit tests.
+
+Strictly adhere to the following instructions:
+1. Write tests for the function you are given, covering its edge cases.
+2. Follow the conventions of the existing tests, if there are any.
+3. Respond only with the new tests and the imports they need, and no commentary or explanations. {}"#,
+            language, module
+        ),
+        user: format!("{}\n\n{}", function, existing_tests),
+    }
+}
+
+/// Has the model write unit tests for the selected function, and adds them to its conventional test
+/// file, next to the tests that exist already
+pub async fn run_test_generation(
+    file_path: &str,
+    settings: &TestSettings<'_>,
+    secret: &str,
+) -> Result<(), String> {
+    let source_file =
+        read_to_string(file_path).map_err(|e| format!("failed to read {}: {}", file_path, e))?;
+    let supported_language = detect_language(file_path)?;
+    let language = supported_language.to_string();
+    let tree = parse_with_thread_parser(&source_file, supported_language.language())
+        .ok_or("failed to parse the file")?;
+    let selection = &settings.selection;
+    let function_node = supported_language
+        .find_correct_node(
+            &source_file,
+            &tree,
+            &selection.parent_identifier,
+            &selection.function_identifier,
+            &selection.kind,
+        )
+        .map_err(|e| explain_not_found(supported_language.as_ref(), e))?;
+
+    let test_path = test_file_path(Path::new(file_path), &language)?;
+    let test_file = read_to_string(&test_path).unwrap_or_default();
+    let test_tree = parse_with_thread_parser(&test_file, supported_language.language())
+        .ok_or("failed to parse the test file")?;
+    let module = match language.as_str() {
+        "rust" => "They go in the `mod tests` of the file, so leave the module out.".to_string(),
+        "go" => "Leave out the package clause.".to_string(),
+        _ => format!(
+            "They go in {}, so import the function relative to it.",
+            test_path.display()
+        ),
+    };
+    let prompt = draft_test_instructions(
+        &language,
+        node_value(&source_file, function_node),
+        &module,
+        &existing_tests(&test_file, &test_tree, &language),
+    );
+    let tests = parse_suggestion(&complete(settings.model, &prompt, secret).await?).code;
+
+    let package_clause =
+        root_child(&tree, "package_clause").map(|clause| node_value(&source_file, clause));
+    let updated = add_tests(
+        supported_language.as_ref(),
+        &test_file,
+        &tests,
+        package_clause,
+    )?;
+    let updated_tree = parse_with_thread_parser(&updated, supported_language.language())
+        .ok_or("failed to parse the test file once updated")?;
+    let errors = syntax_errors(&updated, &updated_tree);
+    if !errors.is_empty() {
+        return Err(format!(
+            "the test file would not parse with the new tests, so it was left as it was: {}",
+            errors.join(", ")
+        ));
+    }
+
+    println!("{}", test_path.display().to_string().bold());
+    println!(
+        "{}",
+        render_text_diff(&test_file, &updated, settings.palette)
+    );
+    let apply = settings.skip_prompt
+        || Confirm::new(&format!("Add the tests to {}?", test_path.display()))
+            .with_default(false)
+            .prompt()
+            .unwrap();
+    if apply {
+        if let Some(directory) = test_path.parent().filter(|directory| !directory.exists()) {
+            create_dir_all(directory)
+                .map_err(|e| format!("failed to create {}: {}", directory.display(), e))?;
+        }
+        write(&test_path, updated)
+            .map_err(|e| format!("failed to write {}: {}", test_path.display(), e))?;
+        println!(
+            "{}",
+            format!("added the tests to {}", test_path.display()).green()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::supported_languages::supported_language::detect_language;
+    use crate::test_generation::{add_tests, test_file_path};
+
+    #[test]
+    fn test_file_paths() {
+        assert_eq!(
+            PathBuf::from("src/lib.rs"),
+            test_file_path(Path::new("src/lib.rs"), "rust").unwrap()
+        );
+        assert_eq!(
+            PathBuf::from("pkg/cart_test.go"),
+            test_file_path(Path::new("pkg/cart.go"), "go").unwrap()
+        );
+        assert_eq!(
+            PathBuf::from("src/__tests__/cart.test.ts"),
+            test_file_path(Path::new("src/cart.ts"), "typescript").unwrap()
+        );
+        assert!(test_file_path(Path::new("Cart.java"), "java").is_err());
+    }
+
+    #[test]
+    fn rust_test_module() {
+        let rust = detect_language("src/lib.rs").unwrap();
+        let tests = "use super::*;\n\n#[test]\nfn doubles() {\n    assert_eq!(4, double(2));\n}";
+
+        assert_eq!(
+            "fn double(x: u32) -> u32 {\n    x * 2\n}\n\n#[cfg(test)]\nmod tests {\n    use super::*;\n\n    #[test]\n    fn doubles() {\n        assert_eq!(4, double(2));\n    }\n}\n",
+            add_tests(rust.as_ref(), "fn double(x: u32) -> u32 {\n    x * 2\n}\n", tests, None).unwrap()
+        );
+
+        let source = "fn double(x: u32) -> u32 {\n    x * 2\n}\n\n#[cfg(test)]\nmod tests {\n    use super::*;\n\n    #[test]\n    fn zero() {\n        assert_eq!(0, double(0));\n    }\n}\n";
+        assert_eq!(
+            "fn double(x: u32) -> u32 {\n    x * 2\n}\n\n#[cfg(test)]\nmod tests {\n    use super::*;\n\n    #[test]\n    fn zero() {\n        assert_eq!(0, double(0));\n    }\n\n    #[test]\n    fn doubles() {\n        assert_eq!(4, double(2));\n    }\n}\n",
+            add_tests(rust.as_ref(), source, tests, None).unwrap()
+        );
+    }
+
+    #[test]
+    fn go_test_file() {
+        let go = detect_language("cart.go").unwrap();
+        let tests =
+            "import (\n\t\"testing\"\n\t\"strings\"\n)\n\nfunc TestTotal(t *testing.T) {\n}";
+
+        assert_eq!(
+            "package cart\n\nimport \"testing\"\nimport \"strings\"\n\nfunc TestTotal(t *testing.T) {\n}\n",
+            add_tests(go.as_ref(), "", tests, Some("package cart")).unwrap()
+        );
+        let existing = "package cart\n\nimport \"testing\"\n\nfunc TestEmpty(t *testing.T) {\n}\n";
+        assert_eq!(
+            "package cart\n\nimport \"testing\"\nimport \"strings\"\n\nfunc TestEmpty(t *testing.T) {\n}\n\nfunc TestTotal(t *testing.T) {\n}\n",
+            add_tests(go.as_ref(), existing, tests, Some("package cart")).unwrap()
+        );
+        let tests = "import \"testing\"\n\nfunc TestTotal(t *testing.T) {\n}";
+        assert_eq!(
+            "package cart\n\nimport \"testing\"\n\nfunc TestEmpty(t *testing.T) {\n}\n\nfunc TestTotal(t *testing.T) {\n}\n",
+            add_tests(go.as_ref(), existing, tests, Some("package cart")).unwrap()
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+use tree_sitter::{Range, Tree};
+
+use crate::coverage::same_file;
+use crate::helpers::tree_sitter::parse_with_thread_parser;
+use crate::supported_languages::supported_language::{detect_language, SupportedLanguage};
+
+/// The system prompt of `senior fix --trace`, which fixes a crash one function of its stack trace
+/// at a time
+pub const TRACE_SYSTEM_PROMPT: &str = r#"You are a senior software engineer. Your task is to fix the crash shown by a stack trace, in the code you are given, which is one of its frames.
+
+Strictly adhere to the following instructions:
+1. If the crash starts in this code, fix its cause. Otherwise, only handle what this code passes on that leads to the crash.
+2. Do not change the type signature, nor anything unrelated to the crash.
+3. Respond only with code, and no commentary or explanations.
+"#;
+
+/// Directories of dependencies and toolchains, whose frames are not the repository's
+const THIRD_PARTY_DIRECTORIES: &[&str] = &["node_modules", ".cargo", ".rustup", "rustc", "vendor"];
+
+/// How many lines of the trace are passed on to the model
+const TRACE_LINES: usize = 40;
+
+/// A location in a stack trace
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub file: String,
+    /// Counted from 1
+    pub line: usize,
+}
+
+/// A function of the repository that the trace passes through
+#[derive(Debug, PartialEq)]
+pub struct TracedFunction {
+    pub file: String,
+    /// The line of its deepest frame, counted from 1
+    pub line: usize,
+    /// The lines it spans before any fix, counted from 1
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+// A location like `src/cart.rs:12:5`, `/home/ci/shop/cart.go:12 +0x1d` or `Cart.java:12`
+fn location(text: &str) -> Option<Frame> {
+    let text = text.trim().trim_start_matches("file://");
+    let text = text.split_whitespace().next()?;
+    let mut parts = text.split(':');
+    let file = parts.next()?;
+    let line = parts.next()?.parse().ok()?;
+    detect_language(file).ok()?;
+
+    Some(Frame {
+        file: file.trim_start_matches("./").to_string(),
+        line,
+    })
+}
+
+// Where a line of a trace points to, in the formats of Rust panics and backtraces, Go goroutine
+// dumps, Node.js and the JVM
+fn frame(line: &str) -> Option<Frame> {
+    let line = line.trim();
+    // `thread 'main' panicked at src/cart.rs:12:5:`, or before Rust 1.73
+    // `thread 'main' panicked at 'boom', src/cart.rs:12:5`
+    if let Some((_, rest)) = line.split_once("panicked at ") {
+        return location(rest.split_whitespace().last()?.trim_end_matches(':'));
+    }
+    let line = line.strip_prefix("at ").unwrap_or(line);
+    // `at total (/home/ci/shop/src/cart.js:12:5)` or `at com.shop.Cart.total(Cart.java:12)`
+    if let Some((_, inside)) = line
+        .strip_suffix(')')
+        .and_then(|line| line.rsplit_once('('))
+    {
+        if let Some(frame) = location(inside) {
+            return Some(frame);
+        }
+    }
+
+    location(line)
+}
+
+/// The frames of a stack trace, deepest first, as every supported format lists them
+pub fn parse_trace(trace: &str) -> Vec<Frame> {
+    let mut frames: Vec<Frame> = vec![];
+    for frame in trace.lines().filter_map(frame) {
+        // A Rust panic and the first frame of its backtrace point to the same line
+        if frames.last() != Some(&frame) {
+            frames.push(frame);
+        }
+    }
+
+    frames
+}
+
+/// The frames in the files of the repository, with their paths in it. The paths of a trace may be
+/// absolute, or relative to another directory.
+pub fn repository_frames(frames: &[Frame], repository_files: &[String]) -> Vec<Frame> {
+    frames
+        .iter()
+        .filter(|frame| {
+            !Path::new(&frame.file).components().any(|component| {
+                THIRD_PARTY_DIRECTORIES.contains(&component.as_os_str().to_string_lossy().as_ref())
+            })
+        })
+        .filter_map(|frame| {
+            let file = repository_files
+                .iter()
+                .find(|file_path| same_file(&frame.file, file_path))?;
+            Some(Frame {
+                file: file.clone(),
+                line: frame.line,
+            })
+        })
+        .collect()
+}
+
+/// The outermost function of the tree around the line, counted from 1
+pub fn enclosing_function(
+    supported_language: &dyn SupportedLanguage,
+    tree: &Tree,
+    line: usize,
+) -> Option<Range> {
+    supported_language
+        .find_all_functions(tree)
+        .into_iter()
+        .find(|function_node| {
+            function_node.start_position().row < line
+                && line <= function_node.end_position().row + 1
+        })
+        .map(|function_node| function_node.range())
+}
+
+/// The functions the frames are in, deepest first, each once. Frames outside of any function are
+/// left out.
+pub fn traced_functions(frames: &[Frame]) -> Result<Vec<TracedFunction>, String> {
+    let mut functions: Vec<TracedFunction> = vec![];
+    for frame in frames {
+        let supported_language = detect_language(&frame.file)?;
+        let source_file = read_to_string(&frame.file)
+            .map_err(|e| format!("failed to read {}: {}", frame.file, e))?;
+        let tree = parse_with_thread_parser(&source_file, supported_language.language())
+            .ok_or_else(|| format!("failed to parse {}", frame.file))?;
+        let Some(range) = enclosing_function(supported_language.as_ref(), &tree, frame.line) else {
+            continue;
+        };
+        let start_line = range.start_point.row + 1;
+        let traced = |function: &TracedFunction| {
+            function.file == frame.file && function.start_line == start_line
+        };
+        if !functions.iter().any(traced) {
+            functions.push(TracedFunction {
+                file: frame.file.clone(),
+                line: frame.line,
+                start_line,
+                end_line: range.end_point.row + 1,
+            });
+        }
+    }
+
+    Ok(functions)
+}
+
+/// What the model is told about the trace, given the line the function is at in it
+pub fn trace_context(trace: &str, line: &str) -> String {
+    format!(
+        "It is part of this stack trace, at the line `{}`:\n```\n{}\n```",
+        line.trim(),
+        trace
+            .lines()
+            .take(TRACE_LINES)
+            .collect::<Vec<&str>>()
+            .join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::trace::{parse_trace, repository_frames, Frame};
+
+    fn frame(file: &str, line: usize) -> Frame {
+        Frame {
+            file: file.to_string(),
+            line,
+        }
+    }
+
+    #[test]
+    fn frames_of_each_runtime() {
+        let rust = r#"thread 'main' panicked at src/cart.rs:12:5:
+called `Option::unwrap()` on a `None` value
+stack backtrace:
+   0: rust_begin_unwind
+             at /rustc/90b35a6239c3d8bdabc530a6a0816f7ff89a0aaf/library/std/src/panicking.rs:645:5
+   1: shop::cart::total
+             at ./src/cart.rs:12:5
+   2: shop::main
+             at ./src/main.rs:4:13
+"#;
+        assert_eq!(
+            vec![
+                frame("src/cart.rs", 12),
+                frame(
+                    "/rustc/90b35a6239c3d8bdabc530a6a0816f7ff89a0aaf/library/std/src/panicking.rs",
+                    645
+                ),
+                frame("src/cart.rs", 12),
+                frame("src/main.rs", 4),
+            ],
+            parse_trace(rust)
+        );
+        let repository_files = vec![
+            "./src/cart.rs".to_string(),
+            "./src/main.rs".to_string(),
+            "./src/panicking.rs".to_string(),
+        ];
+        assert_eq!(
+            vec![
+                frame("./src/cart.rs", 12),
+                frame("./src/cart.rs", 12),
+                frame("./src/main.rs", 4),
+            ],
+            repository_frames(&parse_trace(rust), &repository_files)
+        );
+
+        let go = "panic: runtime error: index out of range [3] with length 3\n\ngoroutine 1 [running]:\nmain.total(...)\n\t/home/ci/shop/cart.go:7 +0x1d\nmain.main()\n\t/home/ci/shop/main.go:5 +0x18\nexit status 2\n";
+        assert_eq!(
+            vec![
+                frame("/home/ci/shop/cart.go", 7),
+                frame("/home/ci/shop/main.go", 5)
+            ],
+            parse_trace(go)
+        );
+
+        let node = "TypeError: Cannot read properties of undefined (reading 'price')\n    at total (/home/ci/shop/src/cart.js:3:24)\n    at /home/ci/shop/src/index.js:9:1\n    at node:internal/main/run_main_module:28:49\n";
+        assert_eq!(
+            vec![
+                frame("/home/ci/shop/src/cart.js", 3),
+                frame("/home/ci/shop/src/index.js", 9)
+            ],
+            parse_trace(node)
+        );
+
+        let java = "Exception in thread \"main\" java.lang.NullPointerException\n\tat com.shop.Cart.total(Cart.java:12)\n\tat com.shop.Main.main(Main.java:5)\n";
+        assert_eq!(
+            vec![frame("Cart.java", 12), frame("Main.java", 5)],
+            parse_trace(java)
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::fs::{metadata, read_to_string};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use colored::*;
+use tree_sitter::{Range, Tree};
+
+use crate::batch::source_files;
+use crate::helpers::tree_sitter::{parse_with_thread_parser, reparse_with_thread_parser};
+use crate::outcome::Outcome;
+use crate::supported_languages::supported_language::detect_language;
+use crate::{suggest, Optimizer, DEFAULT_THEME};
+
+/// What the watcher remembers of a file between two polls
+struct WatchedFile {
+    modified: SystemTime,
+    source_file: Arc<str>,
+    tree: Tree,
+}
+
+/// The settings of `senior watch`
+pub struct WatchSettings<'a> {
+    pub no_ignore: bool,
+    pub interval: Duration,
+    /// Ask for suggestions for the changed functions, with this model and API key
+    pub suggest_with: Option<(&'a str, &'a str)>,
+}
+
+/// The outermost functions of the tree that overlap the bytes from `start_byte` to `end_byte`
+fn functions_overlapping(
+    file_path: &str,
+    tree: &Tree,
+    start_byte: usize,
+    end_byte: usize,
+) -> Vec<Range> {
+    let supported_language = detect_language(file_path).unwrap();
+    let mut changed_functions: Vec<Range> = vec![];
+    for function_node in supported_language.find_all_functions(tree) {
+        let is_nested = changed_functions
+            .last()
+            .is_some_and(|outer| function_node.end_byte() <= outer.end_byte);
+        if !is_nested
+            && function_node.start_byte() <= end_byte
+            && start_byte <= function_node.end_byte()
+        {
+            changed_functions.push(function_node.range());
+        }
+    }
+
+    changed_functions
+}
+
+/// Keeps the trees of every supported file under the path in memory, and reports the functions
+/// that change, reparsing the changed files incrementally. Runs until interrupted.
+pub async fn run_watch(path: &Path, settings: &WatchSettings<'_>) -> Result<(), String> {
+    let mut watched_files: HashMap<String, WatchedFile> = HashMap::new();
+    let mut first_poll = true;
+
+    loop {
+        let file_paths = source_files(path, settings.no_ignore);
+        watched_files.retain(|file_path, _| file_paths.contains(file_path));
+
+        for file_path in file_paths {
+            let Ok(modified) = metadata(&file_path).and_then(|metadata| metadata.modified()) else {
+                continue;
+            };
+            if watched_files
+                .get(&file_path)
+                .is_some_and(|watched_file| watched_file.modified == modified)
+            {
+                continue;
+            }
+            let Ok(source_file) = read_to_string(&file_path) else {
+                continue;
+            };
+            let source_file: Arc<str> = source_file.into();
+            let language = detect_language(&file_path)?.language();
+
+            let (tree, changed_bytes) = match watched_files.get(&file_path) {
+                Some(watched_file) => {
+                    let Some((tree, edit)) = reparse_with_thread_parser(
+                        &watched_file.source_file,
+                        &watched_file.tree,
+                        &source_file,
+                        language,
+                    ) else {
+                        continue;
+                    };
+                    (tree, Some((edit.start_byte, edit.new_end_byte)))
+                }
+                None => {
+                    let Some(tree) = parse_with_thread_parser(&source_file, language) else {
+                        continue;
+                    };
+                    // Files seen for the first time are new, unless the watch just started
+                    (tree, (!first_poll).then_some((0, source_file.len())))
+                }
+            };
+
+            if let Some((start_byte, end_byte)) = changed_bytes {
+                for function in functions_overlapping(&file_path, &tree, start_byte, end_byte) {
+                    println!(
+                        "{}",
+                        format!("{}:{} changed", file_path, function.start_point.row + 1).bold()
+                    );
+                    if let Some((model, secret)) = settings.suggest_with {
+                        let opt = Optimizer::new(
+                            &file_path,
+                            None,
+                            None,
+                            None,
+                            None,
+                            DEFAULT_THEME,
+                            model,
+                            true,
+                            false,
+                            true,
+                        );
+                        let mut opt = match opt {
+                            Ok(opt) => opt,
+                            Err(e) => {
+                                eprintln!("{}", e.red());
+                                continue;
+                            }
+                        };
+                        opt.source_file = Arc::clone(&source_file);
+                        opt.tree = Some(tree.clone());
+                        opt.select_function_at(&function);
+                        if let Outcome::ApiError(e) = suggest(&mut opt, secret, None).await {
+                            eprintln!("{}", e.red());
+                        }
+                    }
+                }
+            }
+
+            watched_files.insert(
+                file_path,
+                WatchedFile {
+                    modified,
+                    source_file,
+                    tree,
+                },
+            );
+        }
+
+        if first_poll {
+            println!(
+                "{}",
+                format!("watching {} files", watched_files.len()).green()
+            );
+            first_poll = false;
+        }
+        tokio::time::sleep(settings.interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::helpers::tree_sitter::{parse_source_with_language, reparse_with_thread_parser};
+    use crate::watch::functions_overlapping;
+
+    #[test]
+    fn changed_functions() {
+        let language = tree_sitter_rust::language();
+        let old_source = "fn greet() {\n    hello();\n}\n\nfn shout() {}\n";
+        let new_source = "fn greet() {\n    hello();\n}\n\nfn shout() { loud(); }\n";
+        let old_tree = parse_source_with_language(old_source, language);
+        let (tree, edit) =
+            reparse_with_thread_parser(old_source, &old_tree, new_source, language).unwrap();
+
+        let changed_rows: Vec<usize> =
+            functions_overlapping("src/lib.rs", &tree, edit.start_byte, edit.new_end_byte)
+                .iter()
+                .map(|function| function.start_point.row)
+                .collect();
+        assert_eq!(vec![4], changed_rows);
+    }
+}
@@ -0,0 +1,274 @@
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+/// A member of a Cargo workspace, of a go.work or of a pnpm workspace
+#[derive(Debug, PartialEq)]
+pub struct Package {
+    pub name: String,
+    pub root: PathBuf,
+}
+
+/// The members globs of the root Cargo.toml, minus the excluded ones
+fn cargo_members(root: &Path) -> Option<(Vec<String>, Vec<String>)> {
+    let manifest: toml::Table = read_to_string(root.join("Cargo.toml")).ok()?.parse().ok()?;
+    let workspace = manifest.get("workspace")?;
+    let strings = |key: &str| -> Vec<String> {
+        workspace
+            .get(key)
+            .and_then(|value| value.as_array())
+            .map(|values| {
+                values
+                    .iter()
+                    .filter_map(|value| value.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    Some((strings("members"), strings("exclude")))
+}
+
+/// The directories of the `use` directives of a go.work
+fn go_work_members(go_work: &str) -> Vec<String> {
+    let mut members = vec![];
+    let mut in_use_block = false;
+    for line in go_work.lines() {
+        let line = line.split("//").next().unwrap_or_default().trim();
+        if in_use_block {
+            if line == ")" {
+                in_use_block = false;
+            } else if !line.is_empty() {
+                members.push(line.to_string());
+            }
+        } else if let Some(directive) = line.strip_prefix("use") {
+            let directive = directive.trim();
+            if directive == "(" {
+                in_use_block = true;
+            } else if !directive.is_empty() {
+                members.push(directive.to_string());
+            }
+        }
+    }
+
+    members
+}
+
+/// The `packages` globs of a pnpm-workspace.yaml, and the ones negated with `!`
+fn pnpm_members(pnpm_workspace: &str) -> (Vec<String>, Vec<String>) {
+    let mut members = vec![];
+    let mut excluded = vec![];
+    let mut in_packages = false;
+    for line in pnpm_workspace.lines() {
+        let trimmed = line.trim();
+        if !line.starts_with([' ', '\t', '-']) {
+            in_packages = trimmed == "packages:";
+        } else if let Some(item) = trimmed.strip_prefix('-').filter(|_| in_packages) {
+            let item = item.trim().trim_matches(|c| c == '\'' || c == '"');
+            match item.strip_prefix('!') {
+                Some(negated) => excluded.push(negated.to_string()),
+                None => members.push(item.to_string()),
+            }
+        }
+    }
+
+    (members, excluded)
+}
+
+fn package_name(member_root: &Path, manifest: &str) -> Option<String> {
+    let contents = read_to_string(member_root.join(manifest)).ok()?;
+    match manifest {
+        "Cargo.toml" => {
+            let manifest: toml::Table = contents.parse().ok()?;
+            Some(manifest.get("package")?.get("name")?.as_str()?.to_string())
+        }
+        "go.mod" => contents
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("module "))
+            .map(|module| module.trim().to_string()),
+        _ => {
+            let manifest: serde_json::Value = serde_json::from_str(&contents).ok()?;
+            Some(manifest.get("name")?.as_str()?.to_string())
+        }
+    }
+}
+
+/// Expands the globs into the directories under the root that hold the given manifest
+fn expand_members(
+    root: &Path,
+    members: &[String],
+    excluded: &[String],
+    manifest: &str,
+) -> Vec<Package> {
+    let excluded: Vec<glob::Pattern> = excluded
+        .iter()
+        .filter_map(|pattern| glob::Pattern::new(pattern.trim_start_matches("./")).ok())
+        .collect();
+
+    let mut packages = vec![];
+    for member in members {
+        let pattern = root.join(member.trim_start_matches("./"));
+        let Ok(paths) = glob::glob(&pattern.to_string_lossy()) else {
+            continue;
+        };
+        for member_root in paths.flatten() {
+            let relative_root = member_root.strip_prefix(root).unwrap_or(&member_root);
+            if !member_root.join(manifest).is_file()
+                || excluded
+                    .iter()
+                    .any(|pattern| pattern.matches_path(relative_root))
+            {
+                continue;
+            }
+            let name = package_name(&member_root, manifest)
+                .unwrap_or_else(|| relative_root.to_string_lossy().to_string());
+            packages.push(Package {
+                name,
+                root: member_root.canonicalize().unwrap_or(member_root),
+            });
+        }
+    }
+
+    packages
+}
+
+/// The packages of the workspaces declared at the root, if any
+pub fn find_packages(root: &Path) -> Vec<Package> {
+    let mut packages: Vec<Package> = vec![];
+    if let Some((members, excluded)) = cargo_members(root) {
+        packages.extend(expand_members(root, &members, &excluded, "Cargo.toml"));
+    }
+    if let Ok(go_work) = read_to_string(root.join("go.work")) {
+        packages.extend(expand_members(
+            root,
+            &go_work_members(&go_work),
+            &[],
+            "go.mod",
+        ));
+    }
+    if let Ok(pnpm_workspace) = read_to_string(root.join("pnpm-workspace.yaml")) {
+        let (members, excluded) = pnpm_members(&pnpm_workspace);
+        packages.extend(expand_members(root, &members, &excluded, "package.json"));
+    }
+    packages.sort_by(|package, other| package.root.cmp(&other.root));
+    packages.dedup_by(|package, other| package.root == other.root);
+
+    packages
+}
+
+/// The innermost package holding the file
+pub fn package_of<'a>(packages: &'a [Package], file_path: &Path) -> Option<&'a Package> {
+    let file_path = file_path
+        .canonicalize()
+        .unwrap_or_else(|_| file_path.to_path_buf());
+
+    packages
+        .iter()
+        .filter(|package| file_path.starts_with(&package.root))
+        .max_by_key(|package| package.root.components().count())
+}
+
+/// Groups the files by the package holding them, keeping their order. Files outside of every
+/// package end up in a group without a name.
+pub fn group_by_package(
+    packages: &[Package],
+    files: Vec<String>,
+) -> Vec<(Option<String>, Vec<String>)> {
+    let mut groups: Vec<(Option<String>, Vec<String>)> = vec![];
+    for file_path in files {
+        let package =
+            package_of(packages, Path::new(&file_path)).map(|package| package.name.clone());
+        match groups.iter_mut().find(|(name, _)| *name == package) {
+            Some((_, group)) => group.push(file_path),
+            None => groups.push((package, vec![file_path])),
+        }
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+    use std::path::Path;
+
+    use crate::workspace::{find_packages, go_work_members, group_by_package, pnpm_members};
+
+    #[test]
+    fn go_work() {
+        let go_work = r#"go 1.21
+
+use ./tools // the linters
+
+use (
+    ./api
+    ./web
+)
+"#;
+
+        assert_eq!(vec!["./tools", "./api", "./web"], go_work_members(go_work));
+    }
+
+    #[test]
+    fn pnpm_workspace() {
+        let pnpm_workspace = r#"packages:
+  - 'packages/*'
+  - "apps/**"
+  - '!**/test/**'
+catalog:
+  - react
+"#;
+
+        assert_eq!(
+            (
+                vec!["packages/*".to_string(), "apps/**".to_string()],
+                vec!["**/test/**".to_string()]
+            ),
+            pnpm_members(pnpm_workspace)
+        );
+    }
+
+    #[test]
+    fn cargo_workspace() {
+        let root = std::env::temp_dir().join("senior-workspace-test");
+        for (member, name) in [
+            ("crates/core", "core"),
+            ("crates/cli", "cli"),
+            ("crates/old", "old"),
+        ] {
+            create_dir_all(root.join(member).join("src")).unwrap();
+            write(
+                root.join(member).join("Cargo.toml"),
+                format!("[package]\nname = \"{}\"\n", name),
+            )
+            .unwrap();
+        }
+        write(
+            root.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/old\"]\n",
+        )
+        .unwrap();
+
+        let packages = find_packages(&root);
+        let mut names: Vec<&str> = packages
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect();
+        names.sort();
+        assert_eq!(vec!["cli", "core"], names);
+
+        write(root.join("crates/core/src/lib.rs"), "").unwrap();
+        let core_file = root
+            .join("crates/core/src/lib.rs")
+            .to_string_lossy()
+            .to_string();
+        let build_file = root.join("build.rs").to_string_lossy().to_string();
+        assert_eq!(
+            vec![
+                (Some("core".to_string()), vec![core_file.clone()]),
+                (None, vec![build_file.clone()])
+            ],
+            group_by_package(&packages, vec![core_file, build_file])
+        );
+        assert!(find_packages(Path::new("/nonexistent")).is_empty());
+    }
+}
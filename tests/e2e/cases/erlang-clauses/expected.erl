@@ -0,0 +1,14 @@
+-module(cart).
+-behaviour(gen_server).
+-export([init/1, handle_call/3, handle_cast/2]).
+
+init(Items) -> {ok, Items}.
+
+%% Answers with the total of the cart, or adds an item to it
+handle_call(total, _From, Items) ->
+    Total = lists:foldl(fun({_, Price}, Sum) -> Sum + Price end, 0, Items),
+    {reply, Total, Items};
+handle_call({add, Item}, _From, Items) ->
+    {reply, ok, [Item | Items]}.
+
+handle_cast(_Message, Items) -> {noreply, Items}.
@@ -0,0 +1,32 @@
+fn greet() {
+    print!("Hello from top-level function!\n");
+}
+
+struct Greeter {
+    name: String,
+}
+
+impl Greeter {
+    fn greet(&self) {
+        println!("Hello from {}, inside the Greeter struct!", self.name);
+    }
+}
+
+struct GenericGreeter<T> {
+    name: T,
+}
+
+impl<T: std::fmt::Display> GenericGreeter<T> {
+    fn greet(&self) {
+        println!("Hello from {}, inside the GenericGreeter struct!", self.name);
+    }
+}
+
+fn main() {
+    greet();
+
+    let greeter = Greeter {
+        name: String::from("Alice"),
+    };
+    greeter.greet();
+}
\ No newline at end of file
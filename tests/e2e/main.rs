@@ -0,0 +1,127 @@
+//! Runs senior against the files of `example_sources/`, with the answers of the model read from
+//! the `answer-<n>` files of each case instead of asked for, and compares the rewritten file with
+//! the `expected` file of the case. Setting `SENIOR_UPDATE_GOLDEN` writes the rewritten files
+//! down as the expected ones instead.
+
+use std::env;
+use std::fs::{copy, create_dir_all, read_dir, read_to_string, remove_dir_all, write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::Deserialize;
+
+const UPDATE_GOLDEN_ENV_VAR_KEY: &str = "SENIOR_UPDATE_GOLDEN";
+
+/// The `case.json` file of a case
+#[derive(Deserialize)]
+struct Case {
+    /// The file of `example_sources/` to rewrite
+    source: String,
+    /// What senior is run with, after the file
+    args: Vec<String>,
+}
+
+/// The answers of the case, ordered by their number
+fn answers(case_dir: &Path) -> Result<Vec<String>, String> {
+    let mut answers: Vec<(usize, PathBuf)> = read_dir(case_dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let number = path
+                .file_stem()?
+                .to_str()?
+                .strip_prefix("answer-")?
+                .parse()
+                .ok()?;
+
+            Some((number, path))
+        })
+        .collect();
+    answers.sort();
+
+    answers
+        .iter()
+        .map(|(_, path)| {
+            read_to_string(path)
+                .map(|answer| answer.trim_end_matches('\n').to_string())
+                .map_err(|e| format!("failed to read {}: {}", path.display(), e))
+        })
+        .collect()
+}
+
+fn run_case(case_dir: &Path) -> Result<(), String> {
+    let name = case_dir.file_name().unwrap().to_string_lossy();
+    let case: Case = serde_json::from_str(
+        &read_to_string(case_dir.join("case.json")).map_err(|e| format!("{}: {}", name, e))?,
+    )
+    .map_err(|e| format!("{}: {}", name, e))?;
+
+    // Every case runs in a directory of its own, which also keeps its cache and history apart
+    let work_dir = env::temp_dir().join("senior-e2e").join(name.as_ref());
+    let _ = remove_dir_all(&work_dir);
+    create_dir_all(&work_dir).map_err(|e| e.to_string())?;
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    copy(
+        manifest_dir.join("example_sources").join(&case.source),
+        work_dir.join(&case.source),
+    )
+    .map_err(|e| format!("{}: {}", name, e))?;
+    let answers_path = work_dir.join("answers.json");
+    write(
+        &answers_path,
+        serde_json::to_string(&answers(case_dir)?).unwrap(),
+    )
+    .map_err(|e| e.to_string())?;
+
+    let output = Command::new(env!("CARGO_BIN_EXE_senior"))
+        .arg(&case.source)
+        .args(&case.args)
+        .current_dir(&work_dir)
+        .env("OPENAI_API_KEY", "mock")
+        .env("SENIOR_MOCK_ANSWERS", &answers_path)
+        .env("NO_COLOR", "1")
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "{}: senior failed:\n{}",
+            name,
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let rewritten = read_to_string(work_dir.join(&case.source)).map_err(|e| e.to_string())?;
+    let extension = Path::new(&case.source).extension().unwrap();
+    let expected_path = case_dir.join("expected").with_extension(extension);
+    if env::var_os(UPDATE_GOLDEN_ENV_VAR_KEY).is_some() {
+        return write(&expected_path, rewritten).map_err(|e| e.to_string());
+    }
+    let expected = read_to_string(&expected_path)
+        .map_err(|e| format!("failed to read {}: {}", expected_path.display(), e))?;
+    if rewritten != expected {
+        return Err(format!(
+            "{}: the rewritten file differs from {}:\n{}",
+            name,
+            expected_path.display(),
+            rewritten
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn golden_files() {
+    let cases_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/e2e/cases");
+    let mut case_dirs: Vec<PathBuf> = read_dir(cases_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    case_dirs.sort();
+
+    let failures: Vec<String> = case_dirs
+        .iter()
+        .filter_map(|case_dir| run_case(case_dir).err())
+        .collect();
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}